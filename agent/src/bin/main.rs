@@ -1,19 +1,33 @@
-use std::{process::ExitCode, str::FromStr, time::Duration};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    rc::Rc,
+    str::FromStr,
+    time::Duration,
+};
 
 use alumet::{
     agent::{
         self,
-        config::{AutoDefaultConfigProvider, DefaultConfigProvider, NoDefaultConfigProvider, merge_override},
+        config::{
+            AutoDefaultConfigProvider, ConfigSource, ConfigSources, DefaultConfigProvider, NoDefaultConfigProvider,
+            merge_override,
+        },
         exec,
-        plugin::{PluginFilter, PluginSet, UnknownPluginInConfigPolicy},
-        watch,
+        plugin::{PluginFilter, PluginInfo, PluginSet, UnknownPluginInConfigPolicy},
+        reload, watch,
     },
-    pipeline,
-    plugin::PluginMetadata,
+    pipeline::{self, naming::PluginName},
+    plugin::{ConfigTable, PluginMetadata},
     static_plugins,
 };
-use alumet_agent::{exec_hints, init_logger};
-use anyhow::Context;
+use alumet_agent::{
+    config_check::{ConfigReport, Severity, suggest},
+    exec_hints, init_logger,
+    plugins_dir::load_plugins_dir,
+};
+use anyhow::{Context, anyhow};
 use clap::{Args, FromArgMatches};
 use cli::{ConfigArgs, ConfigCommand, PluginsArgs, PluginsCommand};
 use config::GeneralConfig;
@@ -38,7 +52,12 @@ fn load_plugins_metadata() -> Vec<PluginMetadata> {
         plugin_kwollect_input::KwollectPluginInput,
         plugin_kwollect_output::KwollectPlugin,
         plugin_filter::FilterPlugin,
+        plugin_attributes::AttributesPlugin,
+        plugin_unit_conversion::UnitConversionPlugin,
+        plugin_energy_power::EnergyPowerPlugin,
         plugin_energy_to_carbon::EnergyToCarbonPlugin,
+        plugin_http_control::HttpControlPlugin,
+        plugin_control_rules::ControlRulesPlugin,
     ];
 
     // plugins that only work on Linux
@@ -65,6 +84,27 @@ fn load_plugins_metadata() -> Vec<PluginMetadata> {
     plugins
 }
 
+/// Loads the dynamic plugins of the directory `dir` and adds them to the set of plugins.
+///
+/// If `enabled_plugins` is set, the dynamic plugins that are not in the list are disabled.
+fn add_dynamic_plugins(plugins: &mut PluginSet, dir: &Path, enabled_plugins: Option<&[String]>) -> anyhow::Result<()> {
+    for metadata in load_plugins_dir(dir)? {
+        if plugins.get_plugin(&metadata.name).is_some() {
+            return Err(anyhow!(
+                "dynamic plugin '{}' in {dir:?} has the same name as another plugin",
+                metadata.name
+            ));
+        }
+        let enabled = enabled_plugins.is_none_or(|names| names.contains(&metadata.name));
+        plugins.add_plugin(PluginInfo {
+            metadata,
+            enabled,
+            config: None,
+        });
+    }
+    Ok(())
+}
+
 /// Main agent function.
 ///
 /// The steps are:
@@ -94,12 +134,31 @@ fn main() -> anyhow::Result<ExitCode> {
     // Special flags like --help will exit. In other cases, we continue.
     print_welcome();
 
+    // Load the dynamic plugins, if a directory is given on the command line.
+    if let Some(dir) = &args.common.plugins_dir {
+        add_dynamic_plugins(&mut plugins, dir, None).context("could not load dynamic plugins")?;
+    }
+
     // If the CLI args override the list of enabled plugins, we need to know it now,
     // because that will change how some "no config" commands work (such as config regen).
     if let Some(enabled_plugins) = &args.common.plugins {
         plugins.enable_only(enabled_plugins);
     }
 
+    // The commands that run before the config is loaded still need the dynamic plugins
+    // of the directory given in the config file, if there is one.
+    if args.common.plugins_dir.is_none() && is_no_config_command(&args) && Path::new(&args.common.config).exists() {
+        let loaded = parse_config_overrides(&args)
+            .and_then(|o| Ok(config_loader(&args.common, o).load_with_sources()?))
+            .and_then(|(config, sources)| config_plugins_dir(&config, &sources));
+        match loaded {
+            Ok(Some(dir)) => add_dynamic_plugins(&mut plugins, &dir, args.common.plugins.as_deref())
+                .context("could not load dynamic plugins")?,
+            Ok(None) => (),
+            Err(e) => log::warn!("Could not read plugins_dir from the config file, dynamic plugins are ignored: {e:#}"),
+        }
+    }
+
     // Run CLI commands that run before the config is loaded.
     if run_command_no_config(&args, &plugins)? {
         return Ok(ExitCode::SUCCESS);
@@ -107,18 +166,46 @@ fn main() -> anyhow::Result<ExitCode> {
 
     // parse config file
     let config_override = parse_config_overrides(&args).context("invalid config overrides")?;
-    let default_config_provider: Box<dyn DefaultConfigProvider> = if args.common.no_default_config {
+    let config_command = match &args.command {
+        Some(cli::Command::Config(ConfigArgs { command })) => Some(command.clone()),
+        _ => None,
+    };
+    let check_config = matches!(config_command, Some(ConfigCommand::Check { .. }));
+    let show_config = matches!(config_command, Some(ConfigCommand::Show { .. }));
+    let default_config_provider: Box<dyn DefaultConfigProvider> = if args.common.no_default_config || check_config {
+        // don't check (or write!) a default config when asked to check the config file
         Box::new(NoDefaultConfigProvider)
     } else {
         Box::new(AutoDefaultConfigProvider::new(&plugins, config::GeneralConfig::default))
     };
-    let mut config = agent::config::Loader::parse_file(&args.common.config)
-        .or_default_boxed(default_config_provider, true)
-        .substitute_env_variables(true)
-        .with_override(config_override)
-        .load()
+    let (mut config, config_sources) = config_loader(&args.common, config_override.clone())
+        .or_default_boxed(default_config_provider, !show_config)
+        .load_with_sources()
         .context("could not load config file")?;
 
+    // Load the dynamic plugins of the directory given in the config, unless overridden by the CLI.
+    if args.common.plugins_dir.is_none()
+        && let Some(dir) = config_plugins_dir(&config, &config_sources)?
+    {
+        add_dynamic_plugins(&mut plugins, &dir, args.common.plugins.as_deref())
+            .context("could not load dynamic plugins")?;
+    }
+
+    // Keep the full config, to compare it with the new one when it is reloaded.
+    let applied_config = config.clone();
+
+    // Check or show the config, if requested, before anything fails because of it.
+    match config_command {
+        Some(ConfigCommand::Check { strict }) => {
+            return run_config_check(&args, plugins, config, &config_sources, strict);
+        }
+        Some(ConfigCommand::Show { effective }) => {
+            run_config_show(&args, plugins, config, config_sources, effective)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        _ => (),
+    }
+
     // Extract the config of each plugin.
     // If not set by CLI args, use the config to determine which plugins are enabled.
     let plugins_config_order = plugins
@@ -142,13 +229,38 @@ fn main() -> anyhow::Result<ExitCode> {
 
     // begin the creation of the pipeline (we have some settings to apply to it)
     let mut pipeline = pipeline::Builder::new();
-    apply_pipeline_settings(&args, &config, &mut pipeline);
+    apply_pipeline_settings(&args, &config, &mut pipeline).context("invalid pipeline settings")?;
 
     // start Alumet with the pipeline and plugins
+    let output_plugins = Rc::new(RefCell::new(Vec::new()));
     let agent = agent::Builder::from_pipeline(plugins, pipeline)
+        .after_plugins_start({
+            let output_plugins = output_plugins.clone();
+            move |pipeline| *output_plugins.borrow_mut() = find_output_plugins(pipeline)
+        })
         .build_and_start()
         .context("startup failure")?;
 
+    // Reload the config on SIGHUP, and apply the changes that do not require a restart.
+    #[cfg(unix)]
+    {
+        let running_plugins = agent.initialized_plugins.iter().map(|p| p.name().to_owned()).collect();
+        let applied = reload::AppliedConfig::new(applied_config, running_plugins, args.common.plugins.is_none())
+            .context("invalid plugins config")?
+            .recreate_outputs(
+                output_plugins.take(),
+                load_plugins_metadata,
+                agent.pipeline.metrics_reader(),
+            );
+        let common_args = args.common.clone();
+        let load_config = move || {
+            config_loader(&common_args, config_override.clone())
+                .load()
+                .context("could not load config file")
+        };
+        reload::reload_on_sighup(&agent.pipeline, applied, load_config)?;
+    }
+
     // run the provided command, the default is Run
     match args.command.take().unwrap_or(cli::Command::Run) {
         cli::Command::Run => {
@@ -230,6 +342,57 @@ fn print_welcome() {
     }
 }
 
+/// Returns the builtin plugins that only have outputs, whose outputs can be recreated when the config is reloaded.
+fn find_output_plugins(pipeline: &pipeline::Builder) -> Vec<String> {
+    let inspector = pipeline.inspect();
+    let others: Vec<PluginName> = inspector
+        .sources_by_plugin()
+        .map(|(plugin, _)| plugin)
+        .chain(inspector.transforms_by_plugin().map(|(plugin, _)| plugin))
+        .collect();
+    let builtin: Vec<String> = load_plugins_metadata().into_iter().map(|p| p.name).collect();
+    inspector
+        .outputs_by_plugin()
+        .map(|(plugin, _)| plugin)
+        .filter(|plugin| !others.contains(plugin) && builtin.contains(&plugin.0))
+        .map(|plugin| plugin.0)
+        .collect()
+}
+
+/// Returns the `plugins_dir` option of the config, if any.
+///
+/// A relative path is resolved against the directory of the config file that sets it.
+/// If the option comes from an override, it is relative to the current directory.
+fn config_plugins_dir(config: &toml::Table, sources: &ConfigSources) -> anyhow::Result<Option<PathBuf>> {
+    let Some(dir) = config.get("plugins_dir") else {
+        return Ok(None);
+    };
+    let dir = Path::new(
+        dir.as_str()
+            .context("invalid general config: plugins_dir must be a string")?,
+    );
+    let dir = match sources.get("plugins_dir") {
+        Some(ConfigSource::File(file)) => file.parent().unwrap_or(Path::new("")).join(dir),
+        _ => dir.to_owned(),
+    };
+    Ok(Some(dir))
+}
+
+/// Returns `true` if the command selected by the CLI user is handled by [`run_command_no_config`].
+fn is_no_config_command(args: &cli::Cli) -> bool {
+    use cli::Command;
+
+    matches!(
+        args.command,
+        Some(Command::Config(ConfigArgs {
+            command: ConfigCommand::Regen,
+        })) | Some(Command::Plugins(PluginsArgs {
+            status: false,
+            command: PluginsCommand::List,
+        }))
+    )
+}
+
 /// If selected by the CLI user, runs a command that does not need the config file.
 ///
 /// Returns `true` if a command was run (in which case you probably should stop here).
@@ -264,6 +427,132 @@ fn run_command_no_config(args: &cli::Cli, plugins: &PluginSet) -> anyhow::Result
     }
 }
 
+/// Returns a config loader for the config file, overlays and overrides given on the command line.
+fn config_loader<'d>(args: &cli::CommonArgs, config_override: toml::Table) -> agent::config::Loader<'d> {
+    let loader = agent::config::Loader::parse_file(&args.config)
+        .substitute_env_variables(true)
+        .with_override(config_override);
+    args.config_overlay
+        .iter()
+        .fold(loader, |loader, overlay| loader.with_overlay(overlay))
+}
+
+/// Prints the configuration, after merging the includes, the overlays and the overrides.
+///
+/// If `effective` is true, the default config of the enabled plugins that are not configured
+/// is added, and each value is annotated with its source.
+fn run_config_show(
+    args: &cli::Cli,
+    mut plugins: PluginSet,
+    mut config: toml::Table,
+    mut sources: ConfigSources,
+    effective: bool,
+) -> anyhow::Result<()> {
+    if !effective {
+        print!("{}", toml::to_string_pretty(&config)?);
+        return Ok(());
+    }
+
+    // Find the enabled plugins, without modifying the config that we print.
+    plugins
+        .extract_config(
+            &mut config.clone(),
+            args.common.plugins.is_none(),
+            UnknownPluginInConfigPolicy::LogWarn,
+        )
+        .context("invalid plugins config")?;
+    let plugins_config = config
+        .entry("plugins")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .context("invalid plugins config: plugins must be a table")?;
+    for p in plugins.metadata(PluginFilter::Enabled) {
+        if plugins_config.contains_key(&p.name) {
+            continue;
+        }
+        let default_config =
+            (p.default_config)().with_context(|| format!("failed to generate default config of plugin {}", p.name))?;
+        if let Some(ConfigTable(default_config)) = default_config {
+            sources.record(&format!("plugins.{}", p.name), &default_config, &ConfigSource::Default);
+            plugins_config.insert(p.name.clone(), toml::Value::Table(default_config));
+        }
+    }
+    print!("{}", sources.annotate(&config));
+    Ok(())
+}
+
+/// Checks the configuration without starting the plugins, and reports every issue found.
+fn run_config_check(
+    args: &cli::Cli,
+    mut plugins: PluginSet,
+    mut config: toml::Table,
+    sources: &ConfigSources,
+    strict: bool,
+) -> anyhow::Result<ExitCode> {
+    let file = &args.common.config;
+    let files = sources
+        .files()
+        .iter()
+        .filter_map(|f| Some((f.clone(), std::fs::read_to_string(f).ok()?)));
+    let mut report = ConfigReport::new(files);
+
+    // Unknown plugins
+    if let Some(toml::Value::Table(plugins_config)) = config.get("plugins") {
+        let known: Vec<String> = plugins.metadata(PluginFilter::Any).map(|p| p.name.clone()).collect();
+        for name in plugins_config.keys().filter(|name| !known.contains(name)) {
+            let message = match suggest(name, known.iter()) {
+                Some(similar) => format!("unknown plugin `{name}`, did you mean `{similar}`?"),
+                None => format!("unknown plugin `{name}`"),
+            };
+            report.push(Severity::Error, format!("plugins.{name}"), message);
+        }
+    }
+    if let Err(e) = plugins.extract_config(
+        &mut config,
+        args.common.plugins.is_none(),
+        UnknownPluginInConfigPolicy::Ignore,
+    ) {
+        report.push_error("plugins", &e);
+    }
+
+    // General options: the deserializer reveals the unknown keys.
+    report.deserialize::<GeneralConfig>("", &config);
+
+    // Plugins: compare with the default config (which only gives hints, since the default config does not
+    // contain every possible key), then run the initialization (without starting anything).
+    let (enabled_plugins, _) = plugins.into_partition();
+    for p in enabled_plugins {
+        let name = p.metadata.name;
+        let prefix = format!("plugins.{name}");
+        let plugin_config = match p.config {
+            Some(c) => c,
+            None => continue, // the default config will be used
+        };
+        match (p.metadata.default_config)() {
+            Ok(Some(reference)) => report.check_keys(&prefix, &plugin_config, &reference.0),
+            Ok(None) => (),
+            Err(e) => log::warn!("Failed to generate the default config of plugin {name}: {e:#}"),
+        }
+        if let Err(e) = (p.metadata.init)(ConfigTable(plugin_config)) {
+            report.push_error(&prefix, &e);
+        }
+    }
+
+    for issue in report.issues() {
+        println!("{issue}");
+    }
+    let n_errors = report.count(Severity::Error);
+    let n_warnings = report.count(Severity::Warning);
+    let n_hints = report.count(Severity::Hint);
+    if n_errors > 0 || (strict && n_warnings > 0) {
+        println!("\nConfiguration {file} is invalid: {n_errors} error(s), {n_warnings} warning(s), {n_hints} hint(s).");
+        Ok(ExitCode::FAILURE)
+    } else {
+        println!("\nConfiguration {file} is valid ({n_warnings} warning(s), {n_hints} hint(s)).");
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
 /// If selected by the CLI user, runs a command that does not need the measurement pipeline.
 ///
 /// Returns `true` if a command was run (in which case you probably should stop here).
@@ -292,7 +581,11 @@ fn run_command_no_measurement(args: &cli::Cli, _config: &GeneralConfig, plugins:
 }
 
 /// Setup the measurement pipeline according to CLI args and config file.
-fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &mut pipeline::Builder) {
+fn apply_pipeline_settings(
+    args: &cli::Cli,
+    config: &GeneralConfig,
+    pipeline: &mut pipeline::Builder,
+) -> anyhow::Result<()> {
     // config file
     if let Some(max_update_interval) = config.max_update_interval {
         pipeline.trigger_constraints_mut().max_update_interval = max_update_interval.into_inner();
@@ -300,6 +593,27 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     if let Some(source_channel_size) = config.source_channel_size {
         *pipeline.source_channel_size() = source_channel_size;
     }
+    for (name, transform_config) in &config.transforms {
+        let name = config::parse_transform_name(name)?;
+        let inputs = transform_config
+            .to_inputs()
+            .with_context(|| format!("invalid inputs for transform {name}"))?;
+        pipeline.transform_inputs(name, inputs);
+    }
+    for (i, route_config) in config.output_routes.iter().enumerate() {
+        let route = route_config
+            .to_route()
+            .with_context(|| format!("invalid output route #{i}"))?;
+        pipeline.route_outputs(route);
+    }
+    if let Some(spool_config) = &config.output_spool {
+        for spool in spool_config.to_spools().context("invalid output spool")? {
+            pipeline.spool_outputs(spool);
+        }
+    }
+    if let Some(self_monitoring) = &config.self_monitoring {
+        pipeline.self_monitoring(self_monitoring.poll_interval);
+    }
 
     // cli arguments
     if let Some(max_update_interval) = args.common.max_update_interval {
@@ -318,6 +632,7 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     if let Some(n) = args.common.priority_worker_threads {
         pipeline.high_priority_threads(n);
     }
+    Ok(())
 }
 
 /// Parses the config overrides provided on the command line, and merges them into a single table.
@@ -400,7 +715,7 @@ fn agent_version() -> String {
 /// See https://docs.rs/clap/latest/clap/_derive/index.html#mixing-builder-and-derive-apis
 mod cli {
     use clap::{Args, Parser, Subcommand};
-    use std::time::Duration;
+    use std::{path::PathBuf, time::Duration};
 
     // NOTE: the doc comment attached to `Cli` is used by clap as the description of
     // the application. It is displayed at the start of the help message.
@@ -467,12 +782,28 @@ mod cli {
         pub command: ConfigCommand,
     }
 
-    #[derive(Subcommand)]
+    #[derive(Subcommand, Clone)]
     pub enum ConfigCommand {
         /// Regenerate the configuration file and stop.
         ///
         /// If the file exists, it will be overwritten.
         Regen,
+        /// Check the configuration file and stop.
+        ///
+        /// Every enabled plugin is initialized with its configuration, but nothing is started.
+        /// Invalid values are reported as errors, unknown general options as warnings.
+        /// The differences with the default config of the plugins are reported as hints, which are never fatal.
+        Check {
+            /// Treat the warnings as errors (but not the hints).
+            #[arg(long)]
+            strict: bool,
+        },
+        /// Print the configuration, after merging the included files, the overlays and the overrides, and stop.
+        Show {
+            /// Add the default config of the enabled plugins, and indicate where each value comes from.
+            #[arg(long)]
+            effective: bool,
+        },
     }
 
     #[derive(Args)]
@@ -529,12 +860,24 @@ mod cli {
         #[arg(long)]
         pub config_override: Option<Vec<String>>,
 
+        /// Additional config files, merged in order on top of the config file (and of the files it includes).
+        ///
+        /// Useful for per-host settings, ex. `--config-overlay /etc/alumet/$(hostname).toml`.
+        #[arg(long, env = "ALUMET_CONFIG_OVERLAY", value_delimiter = ',')]
+        pub config_overlay: Vec<PathBuf>,
+
         /// List of plugins to enable, separated by commas, ex. `csv,rapl`.
         ///
         /// All the other plugins will be disabled.
         #[arg(long, value_delimiter = ',')]
         pub plugins: Option<Vec<String>>,
 
+        /// Directory that contains dynamic plugins (shared libraries) to load in addition to the builtin plugins.
+        ///
+        /// Overrides the `plugins_dir` option of the config file.
+        #[arg(long, env = "ALUMET_PLUGINS_DIR")]
+        pub plugins_dir: Option<PathBuf>,
+
         /// Maximum amount of time between two updates of the sources' commands.
         ///
         /// A lower value means that the latency of source commands will be lower,
@@ -577,8 +920,20 @@ mod cli {
 /// and to write the default configuration to the TOML config file,
 /// therefore the structs derive [`serde::Deserialize`] and [`serde::Serialize`].
 mod config {
-    use std::time::Duration;
-
+    use std::{collections::BTreeMap, path::PathBuf, str::FromStr, time::Duration};
+
+    use alumet::pipeline::{
+        elements::{
+            output::{
+                routing::{AttributePattern, MeasurementSelector, OutputRoute, ResourcePattern},
+                spool::{OutputSpool, SpoolConfig},
+            },
+            transform::graph::{TransformInputs, TransformUpstream},
+        },
+        matching::{OutputNamePattern, StringPattern},
+        naming::TransformName,
+    };
+    use anyhow::{Context, anyhow};
     use serde::{Deserialize, Serialize};
 
     /// General config options, which are not specific to a particular plugin.
@@ -587,5 +942,245 @@ mod config {
         // TODO move these to an "advanced" table
         pub max_update_interval: Option<humantime_serde::Serde<Duration>>,
         pub source_channel_size: Option<usize>,
+
+        /// Directory that contains dynamic plugins (shared libraries) to load in addition to the builtin plugins.
+        ///
+        /// A relative path is resolved against the directory of the config file.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub plugins_dir: Option<PathBuf>,
+
+        /// Inputs of the transforms, by transform name (`"plugin/transform"`).
+        ///
+        /// The transforms that are not listed here are chained, as in a simple pipeline.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub transforms: BTreeMap<String, TransformConfig>,
+
+        /// Restrictions on the measurements that the outputs receive.
+        ///
+        /// The outputs that are not targeted by any route receive every measurement.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub output_routes: Vec<OutputRouteConfig>,
+
+        /// On-disk spool for the outputs that must not lose measurements.
+        pub output_spool: Option<OutputSpoolConfig>,
+
+        /// Measures the health of the pipeline itself.
+        pub self_monitoring: Option<SelfMonitoringConfig>,
+    }
+
+    /// Declares the inputs of a transform.
+    ///
+    /// # Example
+    /// ```toml
+    /// [transforms."aggregation/aggregation"]
+    /// inputs = ["sources"]
+    /// metrics = ["rapl_*"]
+    /// ```
+    #[derive(Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct TransformConfig {
+        /// `"sources"` or the name of another transform (`"plugin/transform"`).
+        pub inputs: Vec<String>,
+        /// Patterns of the metrics to receive. If unset, receive every metric that is not
+        /// claimed by another transform.
+        pub metrics: Option<Vec<String>>,
+    }
+
+    impl TransformConfig {
+        pub fn to_inputs(&self) -> anyhow::Result<TransformInputs> {
+            let mut upstreams = self.inputs.iter().map(|input| match input.as_str() {
+                "sources" => Ok(TransformUpstream::Sources),
+                name => parse_transform_name(name).map(TransformUpstream::Transform),
+            });
+            let first = upstreams.next().ok_or_else(|| anyhow!("inputs must not be empty"))??;
+            let mut inputs = TransformInputs::from(first);
+            for upstream in upstreams {
+                inputs = inputs.and(upstream?);
+            }
+            if let Some(metrics) = &self.metrics {
+                let patterns = metrics
+                    .iter()
+                    .map(|m| parse_pattern(m))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                inputs = inputs.with_metrics(patterns);
+            }
+            Ok(inputs)
+        }
+    }
+
+    /// Restricts the measurements that some outputs receive.
+    ///
+    /// # Example
+    /// ```toml
+    /// [[output_routes]]
+    /// outputs = "csv/*"
+    /// metrics = ["perf_*"]
+    /// resources = [{ kind = "cpu_core" }]
+    /// attributes = { domain = "package" }
+    /// ```
+    #[derive(Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct OutputRouteConfig {
+        /// Pattern of the outputs (`"plugin/output"`), which can contain wildcards.
+        pub outputs: String,
+        /// Patterns of the metrics to send to the outputs.
+        pub metrics: Option<Vec<String>>,
+        /// Patterns of the resources to send to the outputs.
+        pub resources: Option<Vec<ResourcePatternConfig>>,
+        /// Patterns of the resource consumers to send to the outputs.
+        pub consumers: Option<Vec<ResourcePatternConfig>>,
+        /// Patterns of the attributes that the measurements must have.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub attributes: BTreeMap<String, String>,
+    }
+
+    #[derive(Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct ResourcePatternConfig {
+        pub kind: String,
+        #[serde(default = "wildcard")]
+        pub id: String,
+    }
+
+    /// Enables an on-disk spool for some outputs.
+    ///
+    /// # Example
+    /// ```toml
+    /// [output_spool]
+    /// directory = "/var/lib/alumet/spool"
+    /// outputs = ["influxdb/*"]
+    /// max_size = 1073741824
+    /// retry_interval = "5s"
+    /// ```
+    #[derive(Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct OutputSpoolConfig {
+        /// Directory where the spools are stored.
+        pub directory: PathBuf,
+        /// Patterns of the outputs (`"plugin/output"`) that use the spool.
+        #[serde(default = "all_outputs")]
+        pub outputs: Vec<String>,
+        /// Maximum size of the spool of each output, in bytes.
+        #[serde(default = "default_spool_size")]
+        pub max_size: u64,
+        /// How long to wait before retrying to write, after a failure.
+        #[serde(with = "humantime_serde", default = "default_retry_interval")]
+        pub retry_interval: Duration,
+    }
+
+    fn all_outputs() -> Vec<String> {
+        vec![String::from("*/*")]
+    }
+
+    fn default_spool_size() -> u64 {
+        1024 * 1024 * 1024 // 1 GiB
+    }
+
+    fn default_retry_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    impl OutputSpoolConfig {
+        pub fn to_spools(&self) -> anyhow::Result<Vec<OutputSpool>> {
+            let config = SpoolConfig {
+                directory: self.directory.clone(),
+                max_size: self.max_size,
+                retry_interval: self.retry_interval,
+            };
+            self.outputs
+                .iter()
+                .map(|pattern| {
+                    Ok(OutputSpool {
+                        outputs: parse_output_pattern(pattern)?,
+                        config: config.clone(),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Enables the self-monitoring of the pipeline.
+    ///
+    /// # Example
+    /// ```toml
+    /// [self_monitoring]
+    /// poll_interval = "10s"
+    /// ```
+    #[derive(Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct SelfMonitoringConfig {
+        /// How often to measure the health of the pipeline.
+        #[serde(with = "humantime_serde", default = "default_self_monitoring_interval")]
+        pub poll_interval: Duration,
+    }
+
+    fn default_self_monitoring_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn wildcard() -> String {
+        String::from("*")
+    }
+
+    impl OutputRouteConfig {
+        pub fn to_route(&self) -> anyhow::Result<OutputRoute> {
+            let outputs = parse_output_pattern(&self.outputs)?;
+            let parse_resources = |patterns: &Vec<ResourcePatternConfig>| {
+                patterns
+                    .iter()
+                    .map(|p| {
+                        Ok(ResourcePattern {
+                            kind: parse_pattern(&p.kind)?,
+                            id: parse_pattern(&p.id)?,
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            };
+            let selector = MeasurementSelector {
+                metrics: self
+                    .metrics
+                    .as_ref()
+                    .map(|m| m.iter().map(|p| parse_pattern(p)).collect())
+                    .transpose()?,
+                resources: self.resources.as_ref().map(parse_resources).transpose()?,
+                consumers: self.consumers.as_ref().map(parse_resources).transpose()?,
+                attributes: self
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok(AttributePattern {
+                            key: key.to_owned(),
+                            value: parse_pattern(value)?,
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            };
+            Ok(OutputRoute { outputs, selector })
+        }
+    }
+
+    fn parse_output_pattern(pattern: &str) -> anyhow::Result<OutputNamePattern> {
+        match pattern.split_once('/') {
+            Some((plugin, output)) => Ok(OutputNamePattern::new(parse_pattern(plugin)?, parse_pattern(output)?)),
+            None => Err(anyhow!(
+                "invalid output pattern {pattern:?}, expected \"plugin/output\""
+            )),
+        }
+    }
+
+    fn parse_pattern(pattern: &str) -> anyhow::Result<StringPattern> {
+        StringPattern::from_str(pattern).with_context(|| format!("invalid pattern {pattern:?}"))
+    }
+
+    /// Parses a transform name of the form `"plugin/transform"`.
+    pub fn parse_transform_name(name: &str) -> anyhow::Result<TransformName> {
+        match name.split_once('/') {
+            Some((plugin, transform)) if !plugin.is_empty() && !transform.is_empty() => {
+                Ok(TransformName::from_str(plugin, transform))
+            }
+            _ => Err(anyhow!(
+                "invalid transform name {name:?}, expected \"plugin/transform\""
+            )),
+        }
     }
 }
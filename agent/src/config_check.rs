@@ -0,0 +1,533 @@
+//! Validation of the configuration, without starting the measurement pipeline.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::word_distance::distance_with_adjacent_transposition;
+
+/// Maximum distance between an unknown key and a known key for the latter to be suggested.
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+/// Issues found in a configuration.
+pub struct ConfigReport {
+    /// The configuration files, in merge order, with the location of their keys.
+    files: Vec<(PathBuf, KeyLocations)>,
+    issues: Vec<ConfigIssue>,
+}
+
+/// A problem in the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// Path of the faulty key, for instance `plugins.csv.output_path`.
+    pub path: String,
+    pub message: String,
+    /// Position of the key in the configuration file, if known.
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// A possible problem, which is never fatal: the check is not precise enough to be sure.
+    Hint,
+}
+
+/// A position in a file (starting at line 1, column 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Location of each key in the configuration file.
+struct KeyLocations {
+    /// Key path -> position of the key. The elements of the arrays of tables are not distinguished.
+    spans: HashMap<String, Range<usize>>,
+    /// Byte offset of the beginning of each line.
+    line_starts: Vec<usize>,
+}
+
+impl ConfigReport {
+    /// Creates an empty report.
+    ///
+    /// `config_files` contains the path and content of the configuration files, in the order in which
+    /// they have been merged. It is used to locate the issues: a key is located in the last file that defines it.
+    /// If a file cannot be parsed, the issues are reported without their location in this file.
+    pub fn new(config_files: impl IntoIterator<Item = (PathBuf, String)>) -> Self {
+        let files = config_files
+            .into_iter()
+            .filter_map(|(path, content)| Some((path, KeyLocations::parse(&content)?)))
+            .collect();
+        Self {
+            files,
+            issues: Vec::new(),
+        }
+    }
+
+    pub fn issues(&self) -> &[ConfigIssue] {
+        &self.issues
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|i| i.severity == severity).count()
+    }
+
+    /// Reports an issue with the key at `path`.
+    ///
+    /// A key can have several issues, but the same issue is only reported once.
+    /// A hint is replaced by an error or a warning about the same key, which is more precise.
+    pub fn push(&mut self, severity: Severity, path: String, message: String) {
+        // Several checks can detect the same problem, report it only once.
+        if self.issues.iter().any(|i| {
+            i.path == path && (i.message == message || (severity == Severity::Hint && i.severity != Severity::Hint))
+        }) {
+            return;
+        }
+        if severity != Severity::Hint
+            && let Some(hint) = self
+                .issues
+                .iter_mut()
+                .find(|i| i.path == path && i.severity == Severity::Hint)
+        {
+            hint.severity = severity;
+            hint.message = message;
+            return;
+        }
+        let location = self
+            .files
+            .iter()
+            .rev()
+            .find_map(|(file, locations)| locations.locate(file, &path));
+        self.issues.push(ConfigIssue {
+            severity,
+            path,
+            message,
+            location,
+        });
+    }
+
+    /// Reports an error that occurred while deserializing the table at `prefix`.
+    ///
+    /// If the error comes from the TOML deserializer, the path of the faulty key is extracted from it.
+    pub fn push_error(&mut self, prefix: &str, error: &anyhow::Error) {
+        let toml_error = error.chain().find_map(|e| e.downcast_ref::<toml::de::Error>());
+        match toml_error {
+            Some(e) => {
+                // The Display impl of the error ends with "in `key.path`", which is not available otherwise.
+                let key = e.to_string().lines().find_map(|l| {
+                    l.strip_prefix("in `")
+                        .and_then(|l| l.strip_suffix('`'))
+                        .map(ToOwned::to_owned)
+                });
+                let mut path = join_path(prefix, key.as_deref().unwrap_or_default());
+                // Point to the unknown field itself, not to its parent table.
+                if let Some(field) = e
+                    .message()
+                    .strip_prefix("unknown field `")
+                    .and_then(|m| m.split_once('`'))
+                    .map(|(field, _)| field)
+                {
+                    path = join_path(&path, field);
+                }
+                self.push(Severity::Error, path, e.message().to_owned());
+            }
+            None => self.push(Severity::Error, prefix.to_owned(), format!("{error:#}")),
+        }
+    }
+
+    /// Deserializes the table at `prefix` and reports the keys that the deserializer has ignored as warnings.
+    ///
+    /// Unlike [`check_keys`](Self::check_keys), this is exact: the ignored keys are unknown.
+    /// If the deserialization fails, the error is reported and `None` is returned.
+    pub fn deserialize<T: DeserializeOwned + Serialize>(&mut self, prefix: &str, config: &toml::Table) -> Option<T> {
+        let mut ignored = Vec::new();
+        let res: Result<T, _> = serde_ignored::deserialize(toml::Value::Table(config.clone()), |path| {
+            ignored.push(path.to_string())
+        });
+        let value = match res {
+            Ok(value) => value,
+            Err(e) => {
+                self.push_error(prefix, &e.into());
+                return None;
+            }
+        };
+        // The known keys, to suggest a replacement. Some of them may be missing (skipped when serializing).
+        let reference = toml::Table::try_from(&value).unwrap_or_default();
+        for ignored_path in ignored {
+            // `?` stands for the content of an `Option` and the numbers for the elements of an array:
+            // they do not appear in the paths of the keys.
+            let segments: Vec<&str> = ignored_path
+                .split('.')
+                .filter(|s| *s != "?" && s.parse::<usize>().is_err())
+                .collect();
+            let Some((key, parents)) = segments.split_last() else {
+                continue;
+            };
+            let known = parents
+                .iter()
+                .try_fold(&reference, |table, parent| match table.get(*parent)? {
+                    toml::Value::Table(t) => Some(t),
+                    toml::Value::Array(a) => a.first()?.as_table(),
+                    _ => None,
+                });
+            let message = match known.and_then(|t| suggest(key, t.keys())) {
+                Some(known) => format!("unknown key `{key}`, did you mean `{known}`?"),
+                None => format!("unknown key `{key}`"),
+            };
+            self.push(Severity::Warning, join_path(prefix, &segments.join(".")), message);
+        }
+        Some(value)
+    }
+
+    /// Compares `config` with a `reference` config, usually the default config of a plugin.
+    ///
+    /// The reference may lack some valid keys (optional values, aliases, etc.) and its values may have
+    /// another type than what is accepted (e.g. a single value instead of a list). Therefore, the differences
+    /// are only reported as hints, with a suggestion if a known key is similar. The actual errors come
+    /// from the deserialization of the config.
+    pub fn check_keys(&mut self, prefix: &str, config: &toml::Table, reference: &toml::Table) {
+        for (key, value) in config {
+            let path = join_path(prefix, key);
+            let Some(expected) = reference.get(key) else {
+                let message = match suggest(key, reference.keys()) {
+                    Some(known) => format!("key `{key}` is not in the default config, did you mean `{known}`?"),
+                    None => format!("key `{key}` is not in the default config"),
+                };
+                self.push(Severity::Hint, path, message);
+                continue;
+            };
+            match (value, expected) {
+                // An empty table is probably a map with arbitrary keys, such as a list of named items.
+                (toml::Value::Table(t), toml::Value::Table(ref_t)) if !ref_t.is_empty() => {
+                    self.check_keys(&path, t, ref_t)
+                }
+                (toml::Value::Table(_), toml::Value::Table(_)) => (),
+                (toml::Value::Array(a), toml::Value::Array(ref_a)) => {
+                    if let Some(toml::Value::Table(ref_t)) = ref_a.first()
+                        && !ref_t.is_empty()
+                    {
+                        for t in a.iter().filter_map(|v| v.as_table()) {
+                            self.check_keys(&path, t, ref_t);
+                        }
+                    }
+                }
+                // Integers are accepted where floats are expected.
+                (toml::Value::Integer(_), toml::Value::Float(_)) => (),
+                (v, expected) if v.type_str() != expected.type_str() => {
+                    let message = format!(
+                        "the default config has a value of type {} here, not {}",
+                        expected.type_str(),
+                        v.type_str()
+                    );
+                    self.push(Severity::Hint, path, message);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Hint => "hint",
+        };
+        write!(f, "{severity}: ")?;
+        if !self.path.is_empty() {
+            write!(f, "{}", self.path)?;
+            if let Some(Location { file, line, column }) = &self.location {
+                write!(f, " ({}, line {line}, column {column})", file.display())?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl KeyLocations {
+    fn parse(content: &str) -> Option<Self> {
+        fn visit(table: &toml::de::DeTable, prefix: &str, spans: &mut HashMap<String, Range<usize>>) {
+            for (key, value) in table {
+                let path = join_path(prefix, key.get_ref());
+                visit_value(value.get_ref(), &path, spans);
+                spans.entry(path).or_insert_with(|| key.span());
+            }
+        }
+        fn visit_value(value: &toml::de::DeValue, path: &str, spans: &mut HashMap<String, Range<usize>>) {
+            match value {
+                toml::de::DeValue::Table(t) => visit(t, path, spans),
+                toml::de::DeValue::Array(a) => {
+                    for v in a.iter() {
+                        visit_value(v.get_ref(), path, spans);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let table = toml::de::DeTable::parse(content).ok()?;
+        let mut spans = HashMap::new();
+        visit(table.get_ref(), "", &mut spans);
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Some(Self { spans, line_starts })
+    }
+
+    fn locate(&self, file: &Path, path: &str) -> Option<Location> {
+        let offset = self.spans.get(path)?.start;
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let column = offset - self.line_starts[line - 1] + 1;
+        Some(Location {
+            file: file.to_owned(),
+            line,
+            column,
+        })
+    }
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    match (prefix, key) {
+        ("", key) => key.to_owned(),
+        (prefix, "") => prefix.to_owned(),
+        (prefix, key) => format!("{prefix}.{key}"),
+    }
+}
+
+/// Returns the candidate that is the closest to `key`, if it is close enough.
+pub fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    candidates
+        .map(|c| (distance_with_adjacent_transposition(key, c), c))
+        .filter(|(d, _)| *d < MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{ConfigReport, Location, Severity};
+
+    const CONFIG: &str = r#"
+max_update_interval = "500ms"
+
+[plugins.csv]
+output_pth = "alumet-output.csv"
+force_flush = "yes"
+
+[[plugins.procfs.groups]]
+exe_regex = ""
+poll_interval = "2s"
+"#;
+
+    const REFERENCE: &str = r#"
+max_update_interval = "500ms"
+
+[plugins.csv]
+output_path = "alumet-output.csv"
+force_flush = true
+
+[plugins.procfs]
+[[plugins.procfs.groups]]
+exe_regex = ""
+poll_interval = "2s"
+flush_interval = "4s"
+"#;
+
+    fn config_files() -> [(PathBuf, String); 1] {
+        [(PathBuf::from("alumet-config.toml"), CONFIG.to_owned())]
+    }
+
+    fn location(line: usize) -> Location {
+        Location {
+            file: PathBuf::from("alumet-config.toml"),
+            line,
+            column: 1,
+        }
+    }
+
+    #[test]
+    fn unknown_keys_and_types() {
+        let config: toml::Table = toml::from_str(CONFIG).unwrap();
+        let reference: toml::Table = toml::from_str(REFERENCE).unwrap();
+        let mut report = ConfigReport::new(config_files());
+        report.check_keys("", &config, &reference);
+
+        // the comparison with the default config is not precise enough to report errors
+        let issues = report.issues();
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(issues[0].severity, Severity::Hint);
+        assert_eq!(issues[0].path, "plugins.csv.output_pth");
+        assert_eq!(issues[0].location, Some(location(5)));
+        assert_eq!(
+            issues[0].to_string(),
+            "hint: plugins.csv.output_pth (alumet-config.toml, line 5, column 1): key `output_pth` is not in the default config, did you mean `output_path`?"
+        );
+        assert_eq!(issues[1].severity, Severity::Hint);
+        assert_eq!(issues[1].path, "plugins.csv.force_flush");
+        assert_eq!(issues[1].location, Some(location(6)));
+        assert_eq!(report.count(Severity::Error), 0);
+        assert_eq!(report.count(Severity::Warning), 0);
+    }
+
+    #[test]
+    fn ignored_keys() {
+        #[derive(Debug, serde::Deserialize, serde::Serialize)]
+        struct General {
+            max_update_interval: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            plugins_dir: Option<String>,
+            spool: Option<Spool>,
+        }
+        #[derive(Debug, serde::Deserialize, serde::Serialize)]
+        struct Spool {
+            directory: String,
+        }
+
+        let config: toml::Table = toml::from_str(
+            "max_update_intervl = \"1s\"\nplugins_dir = \"/plugins\"\n[spool]\ndirectory = \"/tmp\"\ndirctory = \"/var\"",
+        )
+        .unwrap();
+        let mut report = ConfigReport::new([]);
+        let general: Option<General> = report.deserialize("", &config);
+        assert_eq!(general.unwrap().plugins_dir.as_deref(), Some("/plugins"));
+
+        // the skipped field is not reported, the unknown keys are, even in nested tables
+        let issues = report.issues();
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert_eq!(issues[0].path, "max_update_intervl");
+        // no suggestion: the missing optional value is not in the serialized config
+        assert_eq!(issues[0].message, "unknown key `max_update_intervl`");
+        assert_eq!(issues[1].path, "spool.dirctory");
+        assert_eq!(issues[1].message, "unknown key `dirctory`, did you mean `directory`?");
+
+        // a deserialization error is reported as an error
+        let config: toml::Table = toml::from_str("max_update_interval = 1").unwrap();
+        let mut report = ConfigReport::new([]);
+        assert!(report.deserialize::<General>("", &config).is_none());
+        assert_eq!(report.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn several_issues_per_key() {
+        let mut report = ConfigReport::new([]);
+        report.push(Severity::Hint, String::from("a"), String::from("hint"));
+        report.push(Severity::Error, String::from("a"), String::from("first error"));
+        report.push(Severity::Error, String::from("a"), String::from("second error"));
+        report.push(Severity::Error, String::from("a"), String::from("second error"));
+        report.push(Severity::Hint, String::from("a"), String::from("another hint"));
+        report.push(Severity::Warning, String::from("b"), String::from("warning"));
+
+        let issues: Vec<_> = report
+            .issues()
+            .iter()
+            .map(|i| (i.path.as_str(), i.message.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![("a", "first error"), ("a", "second error"), ("b", "warning")]
+        );
+    }
+
+    #[test]
+    fn deserialization_error() {
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(unused)]
+        struct Groups {
+            groups: Vec<Group>,
+        }
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(unused)]
+        struct Group {
+            exe_regex: String,
+            poll_interval: u64,
+        }
+
+        let config: toml::Table = toml::from_str(CONFIG).unwrap();
+        let procfs = config["plugins"]["procfs"].clone();
+        let error = anyhow::Error::from(procfs.try_into::<Groups>().unwrap_err()).context("invalid config");
+
+        let mut report = ConfigReport::new(config_files());
+        report.push_error("plugins.procfs", &error);
+        let issues = report.issues();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "plugins.procfs.groups.poll_interval");
+        assert_eq!(issues[0].location, Some(location(10)));
+
+        // the same issue is only reported once
+        report.push_error("plugins.procfs", &error);
+        assert_eq!(report.issues().len(), 1);
+    }
+
+    #[test]
+    fn unknown_field_error() {
+        #[derive(Debug, serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[allow(unused)]
+        struct Csv {
+            output_path: Option<String>,
+        }
+
+        let config: toml::Table = toml::from_str(CONFIG).unwrap();
+        let reference: toml::Table = toml::from_str(REFERENCE).unwrap();
+        let mut report = ConfigReport::new(config_files());
+        report.check_keys("", &config, &reference);
+
+        // the hint about the unknown key becomes an error
+        let csv = config["plugins"]["csv"].clone();
+        let error = anyhow::Error::from(csv.try_into::<Csv>().unwrap_err());
+        report.push_error("plugins.csv", &error);
+        let issues = report.issues();
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].path, "plugins.csv.output_pth");
+        assert!(issues[0].message.starts_with("unknown field `output_pth`"));
+    }
+
+    #[test]
+    fn location_in_last_file() {
+        let overlay = "[plugins.csv]\nforce_flush = 1";
+        let files = [
+            (PathBuf::from("alumet-config.toml"), CONFIG.to_owned()),
+            (PathBuf::from("overlay.toml"), overlay.to_owned()),
+        ];
+        let mut config: toml::Table = toml::from_str(CONFIG).unwrap();
+        alumet::agent::config::merge_override(&mut config, toml::from_str(overlay).unwrap());
+        let reference: toml::Table = toml::from_str(REFERENCE).unwrap();
+        let mut report = ConfigReport::new(files);
+        report.check_keys("", &config, &reference);
+
+        let issues = report.issues();
+        assert_eq!(issues[0].location, Some(location(5)));
+        assert_eq!(
+            issues[1].location,
+            Some(Location {
+                file: PathBuf::from("overlay.toml"),
+                line: 2,
+                column: 1
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_general_key() {
+        let config: toml::Table = toml::from_str("max_update_intervl = \"1s\"").unwrap();
+        let mut report = ConfigReport::new([]);
+        report.check_keys("", &config, &toml::Table::new());
+        assert_eq!(report.count(Severity::Hint), 1);
+        assert_eq!(report.issues()[0].location, None);
+    }
+}
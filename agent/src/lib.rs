@@ -2,7 +2,9 @@ use std::path::PathBuf;
 
 use env_logger::Env;
 
+pub mod config_check;
 pub mod exec_hints;
+pub mod plugins_dir;
 pub mod word_distance;
 
 /// Returns the absolute path of the currently running executable.
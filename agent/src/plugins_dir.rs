@@ -0,0 +1,54 @@
+//! Dynamic plugins, loaded from shared libraries at runtime.
+
+use std::path::Path;
+
+use alumet::plugin::PluginMetadata;
+use alumet_ffi::dynload::{LoadError, load_cdylib};
+use anyhow::Context;
+
+/// Loads the dynamic plugins that are stored in the directory `dir`.
+///
+/// Every file with the extension of shared libraries on this platform (`.so` on Linux) is loaded
+/// as a plugin, in alphabetical order. The subdirectories are not explored.
+///
+/// The libraries that cannot be loaded, for instance because they are not Alumet plugins or
+/// because they have been compiled for an incompatible version of Alumet, are skipped with a warning.
+///
+/// # Errors
+/// Returns an error if the directory cannot be read.
+pub fn load_plugins_dir(dir: &Path) -> anyhow::Result<Vec<PluginMetadata>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("could not read plugins directory {dir:?}"))? {
+        let path = entry?.path();
+        if path.is_file() && is_shared_library(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut plugins = Vec::with_capacity(files.len());
+    for file in files {
+        match load_cdylib(&file) {
+            Ok(metadata) => {
+                log::info!(
+                    "Loaded dynamic plugin {} v{} from {file:?}",
+                    metadata.name,
+                    metadata.version
+                );
+                plugins.push(metadata);
+            }
+            Err(e @ LoadError::IncompatiblePlugin { .. }) => {
+                log::warn!("Skipping incompatible dynamic plugin {file:?}: {e}");
+            }
+            Err(e) => {
+                log::warn!("Skipping {file:?}, which is not a valid dynamic plugin: {e}");
+            }
+        }
+    }
+    Ok(plugins)
+}
+
+fn is_shared_library(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION)
+}
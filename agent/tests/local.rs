@@ -145,3 +145,167 @@ fn plugin_enabled_with_bad_config_should_fail() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn plugins_dir_skips_invalid_libraries() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let plugins_dir = tmp_dir.path().join("plugins");
+    std::fs::create_dir(&plugins_dir)?;
+    let fake_lib = plugins_dir.join(format!("libfake.{}", std::env::consts::DLL_EXTENSION));
+    std::fs::write(&fake_lib, "not a shared library")?;
+
+    let plugins_dir_str = plugins_dir.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--plugins-dir", plugins_dir_str, "plugins", "list"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("- csv v"), "builtin plugins should be listed");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("libfake"), "the invalid library should be reported");
+    Ok(())
+}
+
+#[test]
+fn plugins_dir_from_config_is_relative_to_config_file() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf_dir = tmp_dir.path().join("conf");
+    let plugins_dir = conf_dir.join("plugins");
+    std::fs::create_dir_all(&plugins_dir)?;
+    let fake_lib = plugins_dir.join(format!("libfake.{}", std::env::consts::DLL_EXTENSION));
+    std::fs::write(&fake_lib, "not a shared library")?;
+    let conf = conf_dir.join("config.toml");
+    std::fs::write(&conf, "plugins_dir = \"plugins\"\n")?;
+
+    // run from another directory, and before the rest of the config is loaded
+    let conf_path_str = conf.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "plugins", "list"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("libfake"),
+        "the plugins_dir of the config should be loaded"
+    );
+    Ok(())
+}
+
+#[test]
+fn plugins_dir_missing_should_fail() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let plugins_dir = tmp_dir.path().join("does-not-exist");
+
+    let plugins_dir_str = plugins_dir.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--plugins-dir", plugins_dir_str, "plugins", "list"],
+        tmp_dir.path(),
+    )?;
+    assert!(!output.status.success(), "command should fail");
+    Ok(())
+}
+
+#[test]
+fn config_check_reports_all_errors() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    std::fs::write(
+        &conf,
+        indoc! {r#"
+            [plugins.csv]
+            output_pth = "out.csv"
+            force_flush = "yes"
+
+            [plugins.procfss]
+        "#},
+    )?;
+
+    let conf_path_str = conf.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "check"],
+        tmp_dir.path(),
+    )?;
+    assert!(!output.status.success(), "command should fail");
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        stdout.contains("plugins.csv.output_pth (")
+            && stdout.contains("config.toml, line 2, column 1): unknown field `output_pth`")
+    );
+    assert!(stdout.contains("config.toml, line 3, column 1): the default config has a value of type boolean here"));
+    assert!(stdout.contains("unknown plugin `procfss`, did you mean `procfs`?"));
+    Ok(())
+}
+
+#[test]
+fn config_check_valid() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    let conf_path_str = conf.to_str().unwrap();
+    let args = ["--plugins", "csv", "--config", conf_path_str];
+
+    // the default config must be valid
+    let status = run_agent(AGENT_BIN, &[&args[..], &["config", "regen"]].concat(), tmp_dir.path())?;
+    assert!(status.success(), "config regen should succeed");
+    let output = run_agent_tee(AGENT_BIN, &[&args[..], &["config", "check"]].concat(), tmp_dir.path())?;
+    assert!(output.status.success(), "command should succeed");
+    assert!(
+        !tmp_dir.path().join("alumet-output.csv").exists(),
+        "the plugins should not be started"
+    );
+    Ok(())
+}
+
+#[test]
+fn config_show_effective_with_include_and_overlay() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf_d = tmp_dir.path().join("conf.d");
+    std::fs::create_dir(&conf_d)?;
+    let conf = tmp_dir.path().join("config.toml");
+    std::fs::write(
+        &conf,
+        "include = [\"conf.d/*.toml\"]\n\n[plugins.csv]\noutput_path = \"base.csv\"\n",
+    )?;
+    std::fs::write(
+        conf_d.join("10-csv.toml"),
+        "[plugins.csv]\noutput_path = \"included.csv\"\n",
+    )?;
+    let overlay = tmp_dir.path().join("host.toml");
+    std::fs::write(&overlay, "[plugins.csv]\nforce_flush = false\n")?;
+
+    let conf_path_str = conf.to_str().unwrap();
+    let overlay_path_str = overlay.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--plugins",
+            "csv",
+            "--config",
+            conf_path_str,
+            "--config-overlay",
+            overlay_path_str,
+            "config",
+            "show",
+            "--effective",
+        ],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains(&format!(
+        "output_path = \"included.csv\" # {}",
+        conf_d.join("10-csv.toml").display()
+    )));
+    assert!(stdout.contains(&format!("force_flush = false # {overlay_path_str}")));
+    assert!(!stdout.contains("include ="));
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Control of the measurement pipeline while it is running.
+
+use std::str::FromStr;
+
+use alumet::pipeline::control::request::{self, any::AnyAnonymousControlRequest};
+use alumet::pipeline::control::{PluginControlHandle, handle::OnBackgroundError};
+use alumet::pipeline::matching::{OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern};
+use alumet::plugin::AlumetPostStart;
+
+use super::string::AStr;
+
+/// A handle to control the measurement pipeline, obtained with [`alumet_pipeline_control`].
+///
+/// It can be used from any thread, and must be freed with [`control_handle_free`].
+pub struct FfiControlHandle {
+    handle: PluginControlHandle,
+    runtime: tokio::runtime::Handle,
+}
+
+/// An action to apply to some elements of the pipeline.
+#[repr(C)]
+pub enum FfiControlAction {
+    Enable,
+    Disable,
+    /// Triggers the sources now. Only applies to sources.
+    TriggerNow,
+}
+
+/// Returns a new handle to control the measurement pipeline.
+#[unsafe(no_mangle)]
+pub extern "C" fn alumet_pipeline_control(alumet: &AlumetPostStart) -> *mut FfiControlHandle {
+    let handle = FfiControlHandle {
+        handle: alumet.pipeline_control(),
+        runtime: alumet.async_runtime(),
+    };
+    Box::into_raw(Box::new(handle))
+}
+
+/// Frees a control handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn control_handle_free(handle: *mut FfiControlHandle) {
+    let boxed = unsafe { Box::from_raw(handle) };
+    drop(boxed);
+}
+
+/// Applies an action to the sources that match the given patterns, for instance `"rapl"` and `"*"`.
+///
+/// The request is sent in the background: this function does not wait for it to be processed.
+/// Returns `false` if the request is invalid or if the pipeline has been shut down.
+#[unsafe(no_mangle)]
+pub extern "C" fn control_sources(
+    handle: &FfiControlHandle,
+    plugin: AStr,
+    source: AStr,
+    action: FfiControlAction,
+) -> bool {
+    let Some((plugin, source)) = parse_patterns(plugin, source, "control_sources") else {
+        return false;
+    };
+    let builder = request::source(SourceNamePattern::new(plugin, source));
+    let request = match action {
+        FfiControlAction::Enable => builder.enable(),
+        FfiControlAction::Disable => builder.disable(),
+        FfiControlAction::TriggerNow => builder.trigger_now(),
+    };
+    handle.dispatch(request.into(), "control_sources")
+}
+
+/// Applies an action to the transforms that match the given patterns, like [`control_sources`].
+#[unsafe(no_mangle)]
+pub extern "C" fn control_transforms(
+    handle: &FfiControlHandle,
+    plugin: AStr,
+    transform: AStr,
+    action: FfiControlAction,
+) -> bool {
+    let Some((plugin, transform)) = parse_patterns(plugin, transform, "control_transforms") else {
+        return false;
+    };
+    let builder = request::transform(TransformNamePattern::new(plugin, transform));
+    let request = match action {
+        FfiControlAction::Enable => builder.enable(),
+        FfiControlAction::Disable => builder.disable(),
+        FfiControlAction::TriggerNow => {
+            log::error!("control_transforms: transforms cannot be triggered");
+            return false;
+        }
+    };
+    handle.dispatch(request.into(), "control_transforms")
+}
+
+/// Applies an action to the outputs that match the given patterns, like [`control_sources`].
+#[unsafe(no_mangle)]
+pub extern "C" fn control_outputs(
+    handle: &FfiControlHandle,
+    plugin: AStr,
+    output: AStr,
+    action: FfiControlAction,
+) -> bool {
+    let Some((plugin, output)) = parse_patterns(plugin, output, "control_outputs") else {
+        return false;
+    };
+    let builder = request::output(OutputNamePattern::new(plugin, output));
+    let request = match action {
+        FfiControlAction::Enable => builder.enable(),
+        FfiControlAction::Disable => builder.disable(),
+        FfiControlAction::TriggerNow => {
+            log::error!("control_outputs: outputs cannot be triggered");
+            return false;
+        }
+    };
+    handle.dispatch(request.into(), "control_outputs")
+}
+
+/// Shuts the measurement pipeline down.
+#[unsafe(no_mangle)]
+pub extern "C" fn control_shutdown(handle: &FfiControlHandle) {
+    handle.handle.shutdown();
+}
+
+impl FfiControlHandle {
+    fn dispatch(&self, request: AnyAnonymousControlRequest, fn_name: &str) -> bool {
+        // The C code may not run in the context of the async runtime: enter it.
+        let _guard = self.runtime.enter();
+        match self
+            .handle
+            .dispatch_in_current_runtime(request, None, OnBackgroundError::Log)
+        {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("{fn_name}: {e}");
+                false
+            }
+        }
+    }
+}
+
+fn parse_patterns(plugin: AStr, element: AStr, fn_name: &str) -> Option<(StringPattern, StringPattern)> {
+    let parse = |pat: AStr| match StringPattern::from_str(pat.as_str()) {
+        Ok(p) => Some(p),
+        Err(e) => {
+            log::error!("{fn_name}: invalid pattern '{}': {e}", pat.as_str());
+            None
+        }
+    };
+    Some((parse(plugin)?, parse(element)?))
+}
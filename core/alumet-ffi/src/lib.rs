@@ -19,7 +19,7 @@
 use alumet::measurement::{MeasurementAccumulator, MeasurementBuffer};
 use alumet::pipeline::elements::output::OutputContext;
 use alumet::pipeline::elements::transform::TransformContext;
-use alumet::plugin::AlumetPluginStart;
+use alumet::plugin::{AlumetPluginStart, AlumetPostStart, AlumetPreStart};
 use libc::c_void;
 use time::Timestamp;
 
@@ -27,6 +27,7 @@ use time::Timestamp;
 pub mod dynload;
 
 pub mod config;
+pub mod control;
 pub mod metrics;
 pub mod pipeline;
 pub mod plugin;
@@ -34,6 +35,7 @@ pub mod resources;
 pub mod string;
 pub mod time;
 pub mod units;
+pub mod util;
 
 // ====== Function types ======
 pub type PluginInitFn = extern "C" fn(config: *const toml::Table) -> *mut c_void;
@@ -50,6 +52,9 @@ pub type TransformApplyFn =
 pub type OutputWriteFn =
     extern "C" fn(instance: *mut c_void, buffer: *const MeasurementBuffer, ctx: *const FfiOutputContext);
 
+pub type PreStartFn = extern "C" fn(data: *mut c_void, alumet: *mut AlumetPreStart);
+pub type PostStartFn = extern "C" fn(data: *mut c_void, alumet: *mut AlumetPostStart);
+
 // ====== OutputContext ======
 
 #[repr(C)]
@@ -84,11 +89,16 @@ mod cbindgen_workaround {
     opaque_type!(TransformContext, __workaround_6);
     opaque_type!(AlumetPluginStart, __workaround_7);
     opaque_type!(WrappedMeasurementValue, __workaround_8);
+    opaque_type!(AlumetPreStart, __workaround_9);
+    opaque_type!(AlumetPostStart, __workaround_10);
+    opaque_type!(CounterDiff, __workaround_11);
 
     #[repr(C)]
     pub enum WrappedMeasurementType {
         F64,
         U64,
+        I64,
+        Histogram,
     }
 
     #[repr(C)]
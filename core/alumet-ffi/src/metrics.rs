@@ -4,14 +4,14 @@ use libc::c_void;
 
 use alumet::{
     measurement::{
-        AttributeValue, MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue,
+        AttributeValue, Histogram, MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue,
     },
     metrics::{def::RawMetricId, registry::MetricRegistry},
     resources::{Resource, ResourceConsumer},
 };
 
 use super::{
-    FfiOutputContext,
+    FfiOutputContext, FfiTransformContext,
     resources::{FfiConsumerId, FfiResourceId},
     string::{AStr, AString},
     time::Timestamp,
@@ -25,6 +25,42 @@ pub extern "C" fn metric_name<'a>(metric: RawMetricId, ctx: &'a FfiOutputContext
     AStr::from(name)
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn transform_ctx_metric_name<'a>(metric: RawMetricId, ctx: &'a FfiTransformContext) -> AStr<'a> {
+    let metrics: &MetricRegistry = unsafe { &*ctx.inner }.metrics;
+    let name: &str = &metrics.by_id(&metric).unwrap().name;
+    AStr::from(name)
+}
+
+/// Looks up a metric by its name. If it exists, writes its id to `metric` and returns `true`.
+#[unsafe(no_mangle)]
+pub extern "C" fn output_ctx_metric_by_name(ctx: &FfiOutputContext, name: AStr, metric: &mut RawMetricId) -> bool {
+    let metrics: &MetricRegistry = unsafe { &*ctx.inner }.metrics;
+    metric_by_name(metrics, name, metric)
+}
+
+/// Looks up a metric by its name. If it exists, writes its id to `metric` and returns `true`.
+#[unsafe(no_mangle)]
+pub extern "C" fn transform_ctx_metric_by_name(
+    ctx: &FfiTransformContext,
+    name: AStr,
+    metric: &mut RawMetricId,
+) -> bool {
+    let metrics: &MetricRegistry = unsafe { &*ctx.inner }.metrics;
+    metric_by_name(metrics, name, metric)
+}
+
+/// Internal: C binding to [`MetricRegistry::by_name`].
+pub(crate) fn metric_by_name(metrics: &MetricRegistry, name: AStr, metric: &mut RawMetricId) -> bool {
+    match metrics.by_name(name.as_str()) {
+        Some((id, _)) => {
+            *metric = id;
+            true
+        }
+        None => false,
+    }
+}
+
 // ====== MeasurementPoint ffi ======
 
 #[unsafe(no_mangle)]
@@ -81,6 +117,52 @@ pub extern "C" fn mpoint_new_f64(
     )
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn mpoint_new_i64(
+    timestamp: Timestamp,
+    metric: RawMetricId,
+    resource: FfiResourceId,
+    consumer: FfiConsumerId,
+    value: i64,
+) -> *mut MeasurementPoint {
+    mpoint_new(
+        timestamp,
+        metric,
+        resource,
+        consumer,
+        WrappedMeasurementValue::I64(value),
+    )
+}
+
+/// Creates a MeasurementPoint whose value is a histogram.
+///
+/// The bounds must be increasing, and the last count is the one of the `+Inf` bucket (see [`FfiHistogram`]).
+/// The arrays are copied. Returns a null pointer if the histogram is invalid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mpoint_new_histogram(
+    timestamp: Timestamp,
+    metric: RawMetricId,
+    resource: FfiResourceId,
+    consumer: FfiConsumerId,
+    value: FfiHistogram,
+) -> *mut MeasurementPoint {
+    let bounds = unsafe { std::slice::from_raw_parts(value.bounds, value.n_bounds) }.to_vec();
+    let counts = unsafe { std::slice::from_raw_parts(value.counts, value.n_bounds + 1) }.to_vec();
+    match Histogram::from_parts(bounds, counts, value.sum) {
+        Ok(h) => mpoint_new(
+            timestamp,
+            metric,
+            resource,
+            consumer,
+            WrappedMeasurementValue::Histogram(h),
+        ),
+        Err(e) => {
+            log::error!("mpoint_new_histogram: {e}");
+            std::ptr::null_mut()
+        }
+    }
+}
+
 /// Free a MeasurementPoint.
 /// Do **not** call this function after pushing a point with [`mbuffer_push`] or [`maccumulator_push`].
 #[unsafe(no_mangle)]
@@ -158,17 +240,109 @@ pub extern "C" fn mpoint_consumer_id(point: &MeasurementPoint) -> AString {
     point.consumer.id_display().to_string().into()
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn mpoint_attr_len(point: &MeasurementPoint) -> usize {
+    point.attributes_len()
+}
+
+/// Looks up an attribute of the point. If it exists, writes its value to `value` and returns `true`.
+///
+/// The value is only valid as long as the point exists and is not modified (see [`FfiAttributeValue`]).
+#[unsafe(no_mangle)]
+pub extern "C" fn mpoint_attr_get<'a>(
+    point: &'a MeasurementPoint,
+    key: AStr,
+    value: &mut FfiAttributeValue<'a>,
+) -> bool {
+    match point.attributes().find(|(k, _)| *k == key.as_str()) {
+        Some((_, v)) => {
+            *value = v.into();
+            true
+        }
+        None => false,
+    }
+}
+
+pub type ForeachAttributeFn = unsafe extern "C" fn(*mut c_void, AStr, FfiAttributeValue);
+
+/// Iterates on the attributes of a point by calling `f(data, key, value)` for each attribute.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mpoint_attr_foreach(point: &MeasurementPoint, data: *mut c_void, f: ForeachAttributeFn) {
+    for (key, value) in point.attributes() {
+        unsafe { f(data, AStr::from(key), value.into()) };
+    }
+}
+
 #[repr(C)]
 #[allow(unused)]
 pub enum FfiMeasurementValue {
     U64(u64),
     F64(f64),
+    I64(i64),
+    Histogram(FfiHistogram),
+}
+
+/// A view of the value of an attribute.
+///
+/// When returned by [`mpoint_attr_get`] or [`mpoint_attr_foreach`], the strings and lists
+/// are only valid as long as the point exists and is not modified.
+#[repr(C)]
+#[allow(unused)]
+pub enum FfiAttributeValue<'a> {
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    Str(AStr<'a>),
+    ListU64(FfiListU64),
 }
+
+/// A view of a list of `u64`, which contains `len` values.
+#[repr(C)]
+pub struct FfiListU64 {
+    pub values: *const u64,
+    pub len: usize,
+}
+
+/// A view of a histogram.
+///
+/// `bounds` contains `n_bounds` values and `counts` contains `n_bounds + 1` values.
+/// When returned by [`mpoint_value`], the pointers are only valid as long as the point exists.
+#[repr(C)]
+pub struct FfiHistogram {
+    pub bounds: *const f64,
+    pub n_bounds: usize,
+    pub counts: *const u64,
+    pub sum: f64,
+}
+
 impl From<&WrappedMeasurementValue> for FfiMeasurementValue {
     fn from(value: &WrappedMeasurementValue) -> Self {
         match value {
             WrappedMeasurementValue::F64(x) => FfiMeasurementValue::F64(*x),
             WrappedMeasurementValue::U64(x) => FfiMeasurementValue::U64(*x),
+            WrappedMeasurementValue::I64(x) => FfiMeasurementValue::I64(*x),
+            WrappedMeasurementValue::Histogram(h) => FfiMeasurementValue::Histogram(FfiHistogram {
+                bounds: h.bounds().as_ptr(),
+                n_bounds: h.bounds().len(),
+                counts: h.counts().as_ptr(),
+                sum: h.sum(),
+            }),
+        }
+    }
+}
+
+impl<'a> From<&'a AttributeValue> for FfiAttributeValue<'a> {
+    fn from(value: &'a AttributeValue) -> Self {
+        match value {
+            AttributeValue::U64(x) => FfiAttributeValue::U64(*x),
+            AttributeValue::F64(x) => FfiAttributeValue::F64(*x),
+            AttributeValue::Bool(b) => FfiAttributeValue::Bool(*b),
+            AttributeValue::Str(s) => FfiAttributeValue::Str(AStr::from(*s)),
+            AttributeValue::String(s) => FfiAttributeValue::Str(AStr::from(s.as_str())),
+            AttributeValue::ListU64(l) => FfiAttributeValue::ListU64(FfiListU64 {
+                values: l.as_ptr(),
+                len: l.len(),
+            }),
         }
     }
 }
@@ -193,6 +367,33 @@ pub unsafe extern "C" fn mbuffer_foreach(buf: &MeasurementBuffer, data: *mut c_v
     }
 }
 
+pub type ForeachPointMutFn = unsafe extern "C" fn(*mut c_void, *mut MeasurementPoint);
+
+/// Iterates on a [`MeasurementBuffer`] by calling `f(data, point)` for each point of the buffer.
+///
+/// Unlike [`mbuffer_foreach`], the points can be modified, for instance with [`mpoint_attr_str`].
+/// They must **not** be freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mbuffer_foreach_mut(buf: &mut MeasurementBuffer, data: *mut c_void, f: ForeachPointMutFn) {
+    for point in buf.iter_mut() {
+        unsafe { f(data, point) };
+    }
+}
+
+pub type RetainPointFn = unsafe extern "C" fn(*mut c_void, *const MeasurementPoint) -> bool;
+
+/// Removes the points for which `f(data, point)` returns `false`, and keeps the others.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mbuffer_retain(buf: &mut MeasurementBuffer, data: *mut c_void, f: RetainPointFn) {
+    buf.retain(|point| unsafe { f(data, point) });
+}
+
+/// Removes all the points of the buffer.
+#[unsafe(no_mangle)]
+pub extern "C" fn mbuffer_clear(buf: &mut MeasurementBuffer) {
+    buf.clear();
+}
+
 /// Adds a measurement to the buffer.
 /// The point is consumed in the operation, you must **not** use it afterwards.
 #[unsafe(no_mangle)]
@@ -207,3 +408,47 @@ pub unsafe extern "C" fn maccumulator_push(buf: &mut MeasurementAccumulator, poi
     let boxed = unsafe { Box::from_raw(point) };
     buf.push(*boxed);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(metric: u64) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            alumet::measurement::Timestamp::now(),
+            RawMetricId::from_u64(metric),
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(0),
+        )
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut p = point(0)
+            .with_attr("str", "abc")
+            .with_attr("list", AttributeValue::ListU64(vec![1, 2]));
+        mpoint_attr_u64(&mut p, AStr::from("u64"), 42);
+        assert_eq!(mpoint_attr_len(&p), 3);
+
+        let mut value = FfiAttributeValue::Bool(false);
+        assert!(mpoint_attr_get(&p, AStr::from("str"), &mut value));
+        assert!(matches!(value, FfiAttributeValue::Str(s) if s.as_str() == "abc"));
+        assert!(mpoint_attr_get(&p, AStr::from("u64"), &mut value));
+        assert!(matches!(value, FfiAttributeValue::U64(42)));
+        assert!(mpoint_attr_get(&p, AStr::from("list"), &mut value));
+        assert!(matches!(value, FfiAttributeValue::ListU64(FfiListU64 { len: 2, .. })));
+        assert!(!mpoint_attr_get(&p, AStr::from("missing"), &mut value));
+    }
+
+    #[test]
+    fn test_retain() {
+        unsafe extern "C" fn keep_metric_1(_data: *mut c_void, point: *const MeasurementPoint) -> bool {
+            unsafe { &*point }.metric == RawMetricId::from_u64(1)
+        }
+        let mut buf = MeasurementBuffer::from(vec![point(0), point(1), point(2), point(1)]);
+        unsafe { mbuffer_retain(&mut buf, std::ptr::null_mut(), keep_metric_1) };
+        assert_eq!(mbuffer_len(&buf), 2);
+        assert!(buf.iter().all(|p| p.metric == RawMetricId::from_u64(1)));
+    }
+}
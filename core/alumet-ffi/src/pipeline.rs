@@ -3,15 +3,17 @@ use libc::c_void;
 use super::{DropFn, FfiOutputContext, FfiTransformContext, OutputWriteFn, SourcePollFn, TransformApplyFn};
 use alumet::{
     measurement::{MeasurementAccumulator, MeasurementBuffer},
+    metrics::online::MetricReader,
     pipeline::{
         self,
         elements::{
             error,
-            output::{self, error::WriteError},
+            output::{self, AsyncOutputStream, OutputContext, error::WriteError, interface::StreamRecvError},
             transform::{self, TransformError},
         },
     },
 };
+use futures::StreamExt;
 
 pub(crate) struct FfiSource {
     pub data: *mut c_void,
@@ -28,12 +30,18 @@ pub(crate) struct FfiOutput {
     pub write_fn: OutputWriteFn,
     pub drop_fn: Option<DropFn>,
 }
+/// Data of a hook registered with `alumet_on_pipeline_start` or `alumet_on_pre_pipeline_start`.
+pub(crate) struct FfiHook {
+    pub data: *mut c_void,
+    pub drop_fn: Option<DropFn>,
+}
 // To be safely `Send`, sources/transforms/outputs may not use any thread-local storage,
 // and the `data` pointer must not be shared with other threads.
 // When implementing a non-Rust plugin, this has to be checked manually.
 unsafe impl Send for FfiSource {}
 unsafe impl Send for FfiTransform {}
 unsafe impl Send for FfiOutput {}
+unsafe impl Send for FfiHook {}
 
 impl pipeline::Source for FfiSource {
     fn poll(
@@ -64,6 +72,31 @@ impl pipeline::Output for FfiOutput {
     }
 }
 
+/// Runs an async output that calls the write function of `output` for each buffer.
+pub(crate) async fn run_async_output(
+    output: FfiOutput,
+    mut stream: AsyncOutputStream,
+    metrics: MetricReader,
+) -> anyhow::Result<()> {
+    while let Some(measurements) = stream.0.next().await {
+        match measurements {
+            Ok(buf) => {
+                let metrics = metrics.read().await;
+                let ctx = OutputContext { metrics: &metrics };
+                let ffi_ctx = FfiOutputContext { inner: &ctx };
+                (output.write_fn)(output.data, &buf, &ffi_ctx);
+            }
+            Err(StreamRecvError::Lagged(n)) => {
+                log::warn!("{n} measurement buffers were lost because this output was too slow!");
+            }
+            Err(e) => {
+                log::error!("unexpected error in async output: {e:?}");
+            }
+        }
+    }
+    Ok(())
+}
+
 impl Drop for FfiSource {
     fn drop(&mut self) {
         if let Some(drop) = self.drop_fn {
@@ -85,3 +118,10 @@ impl Drop for FfiOutput {
         }
     }
 }
+impl Drop for FfiHook {
+    fn drop(&mut self) {
+        if let Some(drop) = self.drop_fn {
+            unsafe { drop(self.data) };
+        }
+    }
+}
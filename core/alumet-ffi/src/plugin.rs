@@ -5,13 +5,15 @@ use libc::c_void;
 use alumet::measurement::WrappedMeasurementType;
 use alumet::metrics::def::RawMetricId;
 use alumet::pipeline::elements::source::trigger;
-use alumet::{plugin::AlumetPluginStart, units::Unit};
+use alumet::plugin::{AlumetPluginStart, AlumetPreStart};
+use alumet::units::Unit;
 
-use super::pipeline::{FfiOutput, FfiTransform};
+use super::metrics::metric_by_name;
+use super::pipeline::{FfiHook, FfiOutput, FfiTransform, run_async_output};
 use super::time::TimeDuration;
 use super::units::FfiUnit;
 use super::{NullableDropFn, SourcePollFn, pipeline::FfiSource, string::AStr};
-use super::{OutputWriteFn, TransformApplyFn};
+use super::{OutputWriteFn, PostStartFn, PreStartFn, TransformApplyFn};
 
 #[unsafe(no_mangle)]
 pub extern "C" fn alumet_create_metric(
@@ -106,3 +108,86 @@ pub extern "C" fn alumet_add_output(
         .add_blocking_output("fixme", output)
         .expect("FIXME: the C API only supports one output per plugin for the moment");
 }
+
+/// Adds an _async_ output to the pipeline.
+///
+/// Unlike the outputs added with [`alumet_add_output`], async outputs do not have their own thread:
+/// `output_write_fn` is called on the async runtime of the pipeline, and it must **not** block.
+/// Use it for outputs that only do fast operations, such as pushing the measurements to a queue.
+#[unsafe(no_mangle)]
+pub extern "C" fn alumet_add_async_output(
+    alumet: &mut AlumetPluginStart,
+    name: AStr,
+    output_data: *mut c_void,
+    output_write_fn: OutputWriteFn,
+    output_drop_fn: NullableDropFn,
+) -> bool {
+    let output = FfiOutput {
+        data: output_data,
+        write_fn: output_write_fn,
+        drop_fn: output_drop_fn,
+    };
+    let res = alumet.add_async_output_builder(name.as_str(), move |ctx, stream| {
+        Ok(Box::pin(run_async_output(output, stream, ctx.metrics_reader())))
+    });
+    match res {
+        Ok(_) => true,
+        Err(e) => {
+            log::error!("alumet_add_async_output: {e}");
+            false
+        }
+    }
+}
+
+/// Registers a function that will be called just before the pipeline startup, with `hook_fn(hook_data, alumet)`.
+///
+/// After that, the data is dropped with `hook_drop_fn`, if it is not null.
+#[unsafe(no_mangle)]
+pub extern "C" fn alumet_on_pre_pipeline_start(
+    alumet: &mut AlumetPluginStart,
+    hook_data: *mut c_void,
+    hook_fn: PreStartFn,
+    hook_drop_fn: NullableDropFn,
+) {
+    let hook = FfiHook {
+        data: hook_data,
+        drop_fn: hook_drop_fn,
+    };
+    alumet.on_pre_pipeline_start(move |alumet| {
+        hook_fn(hook.data, alumet);
+        Ok(())
+    });
+}
+
+/// Registers a function that will be called just after the pipeline startup, with `hook_fn(hook_data, alumet)`.
+///
+/// This is where you can obtain a control handle with [`alumet_pipeline_control`](crate::control::alumet_pipeline_control).
+/// After that, the data is dropped with `hook_drop_fn`, if it is not null.
+#[unsafe(no_mangle)]
+pub extern "C" fn alumet_on_pipeline_start(
+    alumet: &mut AlumetPluginStart,
+    hook_data: *mut c_void,
+    hook_fn: PostStartFn,
+    hook_drop_fn: NullableDropFn,
+) {
+    let hook = FfiHook {
+        data: hook_data,
+        drop_fn: hook_drop_fn,
+    };
+    alumet.on_pipeline_start(move |alumet| {
+        hook_fn(hook.data, alumet);
+        Ok(())
+    });
+}
+
+/// Looks up a metric by its name. If it exists, writes its id to `metric` and returns `true`.
+///
+/// Unlike the plugin start-up phase, every plugin has registered its metrics at this point.
+#[unsafe(no_mangle)]
+pub extern "C" fn alumet_pre_start_metric_by_name(
+    alumet: &AlumetPreStart,
+    name: AStr,
+    metric: &mut RawMetricId,
+) -> bool {
+    metric_by_name(alumet.metrics(), name, metric)
+}
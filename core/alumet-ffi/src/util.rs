@@ -0,0 +1,43 @@
+use alumet::plugin::util::{CounterDiff, CounterDiffUpdate};
+
+/// Result of [`counter_diff_update`].
+#[repr(C)]
+pub enum FfiCounterDiffUpdate {
+    /// This is the first counter update, its value is not meaningful.
+    FirstTime,
+    /// Normal counter update, gives the difference between the current and the previous value.
+    Difference(u64),
+    /// Counter update with overflow correction, gives the corrected difference.
+    CorrectedDifference(u64),
+}
+
+/// Creates a new `CounterDiff` with a maximum value.
+///
+/// To free the `CounterDiff`, use [`counter_diff_free`].
+#[unsafe(no_mangle)]
+pub extern "C" fn counter_diff_new(max_value: u64) -> *mut CounterDiff {
+    Box::into_raw(Box::new(CounterDiff::with_max_value(max_value)))
+}
+
+/// Frees a `CounterDiff`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn counter_diff_free(counter: *mut CounterDiff) {
+    let boxed = unsafe { Box::from_raw(counter) };
+    drop(boxed);
+}
+
+/// Provides a new value and computes the difference with the previous value, if there is one.
+#[unsafe(no_mangle)]
+pub extern "C" fn counter_diff_update(counter: &mut CounterDiff, new_value: u64) -> FfiCounterDiffUpdate {
+    match counter.update(new_value) {
+        CounterDiffUpdate::FirstTime => FfiCounterDiffUpdate::FirstTime,
+        CounterDiffUpdate::Difference(d) => FfiCounterDiffUpdate::Difference(d),
+        CounterDiffUpdate::CorrectedDifference(d) => FfiCounterDiffUpdate::CorrectedDifference(d),
+    }
+}
+
+/// Resets the state of a `CounterDiff`.
+#[unsafe(no_mangle)]
+pub extern "C" fn counter_diff_reset(counter: &mut CounterDiff) {
+    counter.reset();
+}
@@ -42,8 +42,23 @@
 //!
 //! // TODO use the config
 //! ```
+//!
+//! # Includes and overlays
+//!
+//! A configuration file can include other files with the top-level `include` directive.
+//! The paths are relative to the directory of the including file, and the file names can contain
+//! the wildcards `*` and `?`. The included files are merged, in alphabetical order, on top of
+//! the including file (see [`merge_override`]). They can include other files.
+//!
+//! ```toml
+//! include = ["conf.d/*.toml", "hosts/${HOSTNAME}.toml"]
+//! ```
+//!
+//! Additional files can be merged on top of the configuration with [`Loader::with_overlay`].
+//! [`Loader::load_with_sources`] returns where each value comes from.
+use std::fmt::{Display, Write};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::{borrow::Cow, env::VarError};
 
@@ -65,10 +80,32 @@ pub struct Loader<'d> {
     save_default: bool,
     /// Additional values that override the content of the config.
     overrides: Option<toml::Table>,
+    /// Additional files that are merged on top of the config, in order.
+    overlays: Vec<PathBuf>,
     /// Should environment variable substitution be applied before deserializing?
     substitute_env: bool,
 }
 
+/// The origin of each value of a configuration loaded by [`Loader::load_with_sources`].
+#[derive(Debug, Default, Clone)]
+pub struct ConfigSources {
+    /// Key path (ex. `plugins.csv.output_path`) -> source of the value.
+    values: IndexMap<String, ConfigSource>,
+    /// The files that have been loaded, in merge order.
+    files: Vec<PathBuf>,
+}
+
+/// Where a configuration value comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// A configuration file, included or not.
+    File(PathBuf),
+    /// A default configuration, generated by a [`DefaultConfigProvider`] or by a plugin.
+    Default,
+    /// An override, see [`Loader::with_override`].
+    Override,
+}
+
 /// Generates default configurations.
 ///
 /// See [`AutoDefaultConfigProvider`] for the "standard" implementation.
@@ -109,6 +146,7 @@ impl<'d> Loader<'d> {
             default_provider: None,
             save_default: false,
             overrides: None,
+            overlays: Vec::new(),
             substitute_env: false,
         }
     }
@@ -149,6 +187,15 @@ impl<'d> Loader<'d> {
         self
     }
 
+    /// Merges an additional file on top of the configuration, after the includes and before the overrides.
+    ///
+    /// Multiple overlays can be set. The overlays are applied in order.
+    /// Like the main file, an overlay can include other files.
+    pub fn with_overlay<P: Into<PathBuf>>(mut self, overlay_file: P) -> Self {
+        self.overlays.push(overlay_file.into());
+        self
+    }
+
     /// Enables or disables the substitution of environment variables.
     ///
     /// Variable substitution is performed _before_ passing the content of the config
@@ -166,14 +213,50 @@ impl<'d> Loader<'d> {
         })
     }
 
+    /// Loads the configuration with the provided settings, and returns the source of each value.
+    pub fn load_with_sources(mut self) -> Result<(toml::Table, ConfigSources), LoadError> {
+        self.load_impl_with_sources().map_err(|e| LoadError {
+            config_file: self.file,
+            kind: e,
+        })
+    }
+
     fn load_impl(&mut self) -> Result<toml::Table, LoadErrorCause> {
+        self.load_impl_with_sources().map(|(config, _)| config)
+    }
+
+    fn load_impl_with_sources(&mut self) -> Result<(toml::Table, ConfigSources), LoadErrorCause> {
+        let mut config = toml::Table::new();
+        let mut sources = ConfigSources::default();
+
+        // main file (or default config)
+        let source = if self.file.exists() {
+            ConfigSource::File(self.file.clone())
+        } else {
+            ConfigSource::Default
+        };
         let config_content = self.read_config_or_default()?;
-        let config_content = substitute_env(&config_content)?;
-        let mut parsed_config = toml::Table::from_str(&config_content)?;
+        let mut stack: Vec<PathBuf> = self.file.canonicalize().into_iter().collect();
+        let file = self.file.clone();
+        merge_file(&file, &config_content, source, &mut config, &mut sources, &mut stack)?;
+
+        // overlays
+        for overlay in std::mem::take(&mut self.overlays) {
+            let load_overlay = |config: &mut toml::Table, sources: &mut ConfigSources| {
+                let content = std::fs::read_to_string(&overlay).map_err(LoadErrorCause::Read)?;
+                let mut stack = vec![overlay.canonicalize().map_err(LoadErrorCause::Read)?];
+                let source = ConfigSource::File(overlay.clone());
+                merge_file(&overlay, &content, source, config, sources, &mut stack)
+            };
+            load_overlay(&mut config, &mut sources).map_err(|e| LoadErrorCause::Overlay(overlay, Box::new(e)))?;
+        }
+
+        // overrides
         if let Some(overrides) = self.overrides.take() {
-            merge_override(&mut parsed_config, overrides);
+            sources.record("", &overrides, &ConfigSource::Override);
+            merge_override(&mut config, overrides);
         }
-        Ok(parsed_config)
+        Ok((config, sources))
     }
 
     fn read_config_or_default(&mut self) -> Result<String, LoadErrorCause> {
@@ -203,6 +286,221 @@ impl<'d> Loader<'d> {
     }
 }
 
+/// Parses the content of a configuration `file` and merges it into `config`, followed by the files that it includes.
+///
+/// `stack` contains the canonical path of the files that are being loaded, to detect include cycles.
+fn merge_file(
+    file: &Path,
+    content: &str,
+    source: ConfigSource,
+    config: &mut toml::Table,
+    sources: &mut ConfigSources,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), LoadErrorCause> {
+    let content = substitute_env(content)?;
+    let mut table = toml::Table::from_str(&content)?;
+    let includes = match table.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::String(pattern)) => vec![pattern],
+        Some(toml::Value::Array(patterns)) => patterns
+            .into_iter()
+            .map(|p| match p {
+                toml::Value::String(pattern) => Ok(pattern),
+                _ => Err(LoadErrorCause::InvalidInclude(String::from(
+                    "`include` must be a string or an array of strings",
+                ))),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => {
+            return Err(LoadErrorCause::InvalidInclude(String::from(
+                "`include` must be a string or an array of strings",
+            )));
+        }
+    };
+
+    if let ConfigSource::File(path) = &source {
+        sources.files.push(path.clone());
+    }
+    sources.record("", &table, &source);
+    merge_override(config, table);
+
+    // The included files override the content of the including file.
+    let base_dir = file.parent().unwrap_or(Path::new(""));
+    for pattern in includes {
+        for included in resolve_include(base_dir, &pattern)? {
+            let mut load_included = || {
+                let canonical = included.canonicalize().map_err(LoadErrorCause::Read)?;
+                if stack.contains(&canonical) {
+                    return Err(LoadErrorCause::InvalidInclude(format!(
+                        "include cycle: {included:?} is already being loaded"
+                    )));
+                }
+                let content = std::fs::read_to_string(&included).map_err(LoadErrorCause::Read)?;
+                stack.push(canonical);
+                let source = ConfigSource::File(included.clone());
+                merge_file(&included, &content, source, config, sources, stack)?;
+                stack.pop();
+                Ok(())
+            };
+            load_included().map_err(|e| LoadErrorCause::Include(included.clone(), Box::new(e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the files that match an include `pattern`, in alphabetical order.
+///
+/// Only the file name can contain wildcards. If the directory does not exist, no file matches.
+fn resolve_include(base_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, LoadErrorCause> {
+    let path = base_dir.join(pattern);
+    let file_pattern = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains(['*', '?']) => name,
+        _ => return Ok(vec![path]),
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let entries = match std::fs::read_dir(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    }) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(LoadErrorCause::Read(e)),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(LoadErrorCause::Read)?;
+        let matches = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| matches_wildcard(file_pattern, name));
+        if matches && entry.path().is_file() {
+            files.push(dir.join(entry.file_name()));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Checks whether a file `name` matches a `pattern` that can contain the wildcards `*` and `?`.
+///
+/// Like in shells, hidden files only match patterns that start with a dot.
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` in the pattern, and of the name character that it matched last
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the `*` match one more character
+            backtrack = Some((star, matched + 1));
+            p = star + 1;
+            n = matched + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl ConfigSources {
+    /// Returns the source of the value at the given key path, for instance `plugins.csv.output_path`.
+    pub fn get(&self, path: &str) -> Option<&ConfigSource> {
+        self.values.get(path)
+    }
+
+    /// Returns the configuration files that have been loaded, in the order in which they have been merged.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Records that the values of `table`, located at the key path `prefix`, come from `source`.
+    pub fn record(&mut self, prefix: &str, table: &toml::Table, source: &ConfigSource) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{prefix}.{key}")
+            };
+            match value {
+                toml::Value::Table(t) if !t.is_empty() => self.record(&path, t, source),
+                _ => {
+                    self.values.insert(path, source.clone());
+                }
+            }
+        }
+    }
+
+    /// Serializes `config` to TOML, with a comment that indicates the source of each value.
+    pub fn annotate(&self, config: &toml::Table) -> String {
+        fn format_key(key: &str) -> String {
+            let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if bare {
+                key.to_owned()
+            } else {
+                toml::Value::String(key.to_owned()).to_string()
+            }
+        }
+
+        fn write_table(sources: &ConfigSources, out: &mut String, path: &mut Vec<String>, table: &toml::Table) {
+            // values first, then sub-tables, as required by the TOML syntax
+            for (key, value) in table.iter().filter(|(_, v)| !v.is_table()) {
+                path.push(key.to_owned());
+                let _ = write!(out, "{} = {value}", format_key(key));
+                if let Some(source) = sources.get(&path.join(".")) {
+                    let _ = write!(out, " # {source}");
+                }
+                out.push('\n');
+                path.pop();
+            }
+            for (key, value) in table {
+                if let toml::Value::Table(t) = value {
+                    path.push(key.to_owned());
+                    if t.is_empty() || t.values().any(|v| !v.is_table()) {
+                        let header: Vec<String> = path.iter().map(|k| format_key(k)).collect();
+                        if !out.is_empty() {
+                            out.push('\n');
+                        }
+                        let _ = write!(out, "[{}]", header.join("."));
+                        match sources.get(&path.join(".")) {
+                            Some(source) if t.is_empty() => {
+                                let _ = writeln!(out, " # {source}");
+                            }
+                            _ => out.push('\n'),
+                        }
+                    }
+                    write_table(sources, out, path, t);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut out = String::new();
+        write_table(self, &mut out, &mut Vec::new(), config);
+        out
+    }
+}
+
+impl Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::Override => write!(f, "override"),
+        }
+    }
+}
+
 impl<'f, F: Fn() -> anyhow::Result<toml::Table> + 'f> DefaultConfigProvider for F {
     fn default_config(&self) -> anyhow::Result<toml::Table> {
         let table = self()?;
@@ -525,6 +823,18 @@ pub mod error {
         /// (after environment variable substitution).
         #[error("invalid TOML config")]
         InvalidToml(#[from] toml::de::Error),
+
+        /// The `include` directive is invalid.
+        #[error("invalid include: {0}")]
+        InvalidInclude(String),
+
+        /// An included file could not be loaded.
+        #[error("could not load included file {0:?}")]
+        Include(PathBuf, #[source] Box<LoadErrorCause>),
+
+        /// An overlay file could not be loaded.
+        #[error("could not load overlay file {0:?}")]
+        Overlay(PathBuf, #[source] Box<LoadErrorCause>),
     }
 
     /// Environment variable substitution failed.
@@ -1219,4 +1529,158 @@ mod tests {
         // When types differ, the overrider value should replace the original
         assert_eq!(original.get("forty"), Some(&toml::Value::Integer(40)));
     }
+
+    #[test]
+    fn test_matches_wildcard() {
+        assert!(matches_wildcard("*.toml", "a.toml"));
+        assert!(!matches_wildcard("*.toml", ".toml"));
+        assert!(matches_wildcard("10-*.toml", "10-node.toml"));
+        assert!(matches_wildcard("node?.toml", "node1.toml"));
+        assert!(matches_wildcard("*a*b*", "xxaybbz"));
+        assert!(matches_wildcard("*", "file"));
+        assert!(!matches_wildcard("*.toml", "a.toml.bak"));
+        assert!(!matches_wildcard("*.toml", ".hidden.toml"));
+        assert!(!matches_wildcard("node?.toml", "node12.toml"));
+    }
+
+    #[test]
+    fn test_load_with_includes_and_overlays() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("conf.d");
+        std::fs::create_dir(&conf_d).unwrap();
+        let main = dir.path().join("alumet-config.toml");
+        std::fs::write(
+            &main,
+            r#"
+            include = ["conf.d/*.toml", "missing.d/*.toml"]
+            poll = "1s"
+            [plugins.a]
+            x = 1
+            y = 1
+            "#,
+        )
+        .unwrap();
+        std::fs::write(conf_d.join("10-first.toml"), "plugins.a.x = 2\nplugins.b.z = 2").unwrap();
+        std::fs::write(
+            conf_d.join("20-second.toml"),
+            "plugins.a.x = 3\ninclude = \"../nested.toml\"",
+        )
+        .unwrap();
+        std::fs::write(conf_d.join("ignored.txt"), "plugins.a.x = 100").unwrap();
+        std::fs::write(dir.path().join("nested.toml"), "plugins.b.w = 4").unwrap();
+        let overlay = dir.path().join("host.toml");
+        std::fs::write(&overlay, "poll = \"2s\"").unwrap();
+
+        let mut overrides = Table::new();
+        overrides.insert(String::from("extra"), toml::Value::Boolean(true));
+        let (config, sources) = Loader::parse_file(&main)
+            .with_overlay(&overlay)
+            .with_override(overrides)
+            .load_with_sources()
+            .unwrap();
+
+        let expected: Table = toml::from_str(
+            r#"
+            poll = "2s"
+            extra = true
+            [plugins.a]
+            x = 3
+            y = 1
+            [plugins.b]
+            z = 2
+            w = 4
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config, expected);
+        assert_eq!(
+            sources.files(),
+            &[
+                main.clone(),
+                conf_d.join("10-first.toml"),
+                conf_d.join("20-second.toml"),
+                conf_d.join("../nested.toml"),
+                overlay.clone(),
+            ]
+        );
+        assert_eq!(
+            sources.get("plugins.a.x"),
+            Some(&ConfigSource::File(conf_d.join("20-second.toml")))
+        );
+        assert_eq!(sources.get("plugins.a.y"), Some(&ConfigSource::File(main.clone())));
+        assert_eq!(sources.get("poll"), Some(&ConfigSource::File(overlay.clone())));
+        assert_eq!(sources.get("extra"), Some(&ConfigSource::Override));
+        assert_eq!(sources.get("include"), None);
+    }
+
+    #[test]
+    fn test_load_include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.toml");
+        std::fs::write(&main, "include = \"other.toml\"").unwrap();
+        std::fs::write(dir.path().join("other.toml"), "include = \"main.toml\"").unwrap();
+        let err = Loader::parse_file(&main).load().unwrap_err();
+        // main.toml -> other.toml -> main.toml
+        let LoadErrorCause::Include(_, cause) = err.kind else {
+            panic!("unexpected error {err:?}");
+        };
+        let LoadErrorCause::Include(_, cause) = *cause else {
+            panic!("unexpected error {cause:?}");
+        };
+        assert!(matches!(*cause, LoadErrorCause::InvalidInclude(_)), "{cause:?}");
+    }
+
+    #[test]
+    fn test_load_missing_include_and_overlay() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.toml");
+        std::fs::write(&main, "include = \"missing.toml\"").unwrap();
+        let err = Loader::parse_file(&main).load().unwrap_err();
+        assert!(matches!(err.kind, LoadErrorCause::Include(_, _)), "{err:?}");
+
+        std::fs::write(&main, "").unwrap();
+        let err = Loader::parse_file(&main)
+            .with_overlay("missing.toml")
+            .load()
+            .unwrap_err();
+        assert!(matches!(err.kind, LoadErrorCause::Overlay(_, _)), "{err:?}");
+    }
+
+    #[test]
+    fn test_annotate() {
+        let config: Table = toml::from_str(
+            r#"
+            poll = "1s"
+            [plugins.a]
+            x = [1, 2]
+            [plugins."b/c"]
+            [plugins.d.e]
+            y = true
+            "#,
+        )
+        .unwrap();
+        let mut sources = ConfigSources::default();
+        sources.record("", &config, &ConfigSource::File(PathBuf::from("main.toml")));
+        let mut overrides = Table::new();
+        overrides.insert(String::from("y"), toml::Value::Boolean(true));
+        sources.record("plugins.d.e", &overrides, &ConfigSource::Override);
+
+        let annotated = sources.annotate(&config);
+        assert_eq!(
+            annotated,
+            indoc::indoc! {r#"
+                poll = "1s" # main.toml
+
+                [plugins.a]
+                x = [1, 2] # main.toml
+
+                [plugins."b/c"] # main.toml
+
+                [plugins.d.e]
+                y = true # override
+            "#}
+        );
+        // the output is valid TOML
+        assert_eq!(toml::from_str::<Table>(&annotated).unwrap(), config);
+    }
 }
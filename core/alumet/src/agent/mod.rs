@@ -70,6 +70,7 @@ pub mod builder;
 pub mod config;
 pub mod exec;
 pub mod plugin;
+pub mod reload;
 pub mod watch;
 
 pub use builder::{Builder, RunningAgent};
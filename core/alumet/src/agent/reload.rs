@@ -0,0 +1,664 @@
+//! Hot reload of the agent configuration.
+//!
+//! The configuration of a running agent can be reloaded without restarting the measurement pipeline.
+//! [`AppliedConfig`] keeps track of the configuration that is currently applied, and computes a
+//! [`ReloadPlan`] when a new configuration is loaded.
+//!
+//! Some changes can be applied through the [control API](crate::pipeline::control):
+//! - disabling a plugin disables its sources, transforms and outputs;
+//! - enabling a plugin that is running (i.e. that has been disabled by a previous reload) enables its elements again;
+//! - changing the top-level `poll_interval` or `flush_interval` of a plugin updates the trigger of its sources
+//!   that are polled at regular intervals (the other sources are left unchanged);
+//! - changing any option of a plugin that only has outputs recreates its outputs, if enabled
+//!   with [`AppliedConfig::recreate_outputs`].
+//!
+//! The other changes, such as the options of a plugin that has sources or a change to the general options,
+//! require a restart of the agent. They are listed in [`ReloadPlan::restart_required`].
+//!
+//! On Unix, [`reload_on_sighup`] reloads the configuration when the agent receives the `SIGHUP` signal.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use alumet::agent::{self, reload::{AppliedConfig, ReloadPlan}};
+//!
+//! # fn example(agent: agent::RunningAgent, old_config: toml::Table, new_config: toml::Table) -> anyhow::Result<()> {
+//! let running_plugins = agent.initialized_plugins.iter().map(|p| p.name().to_owned()).collect();
+//! let mut applied = AppliedConfig::new(old_config, running_plugins, true)?;
+//!
+//! // later
+//! let plan: ReloadPlan = applied.reload(new_config)?;
+//! for change in &plan.restart_required {
+//!     log::warn!("{change} cannot be changed without a restart");
+//! }
+//! let control = agent.pipeline.control_handle();
+//! agent.pipeline.async_runtime().block_on(applied.apply(&plan, &control))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, anyhow};
+use indexmap::IndexMap;
+
+use crate::{
+    metrics::{def::Metric, def::RawMetricId, online::MetricReader, registry::MetricRegistry},
+    pipeline::{
+        self, MeasurementPipeline, Output,
+        control::{AnonymousControlHandle, request},
+        elements::{
+            output::builder::{BlockingOutputBuildContext, OutputBuilder},
+            source::trigger::TriggerSpec,
+        },
+        matching::{OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern},
+        naming::PluginName,
+    },
+    plugin::{AlumetPluginStart, ConfigTable, PluginMetadata},
+};
+
+use super::config::{error::BadTypeError, extract_plugins_config};
+
+/// Key of the plugin options that set the poll interval of its sources.
+const POLL_INTERVAL_KEY: &str = "poll_interval";
+/// Key of the plugin options that set the flush interval of its sources.
+const FLUSH_INTERVAL_KEY: &str = "flush_interval";
+
+/// Maximum amount of time to wait for the pipeline to process each request of a reload.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The configuration that is currently applied to a running agent.
+pub struct AppliedConfig {
+    /// General options (everything but the `plugins` table).
+    general: toml::Table,
+    /// Status and configuration of each plugin.
+    plugins: IndexMap<String, (bool, toml::Table)>,
+    /// The plugins that have been started with the agent.
+    running_plugins: Vec<String>,
+    /// If true, the `enabled` key of the plugins determines whether they are enabled or not.
+    update_status: bool,
+    /// Recreates the outputs of the plugins that only have outputs, if enabled.
+    outputs: Option<OutputsRecreation>,
+}
+
+/// Recreates the outputs of some plugins, see [`AppliedConfig::recreate_outputs`].
+#[derive(Clone)]
+struct OutputsRecreation {
+    /// The plugins whose outputs can be recreated.
+    plugins: Vec<String>,
+    /// Returns the metadata of the available plugins, to initialize them again.
+    metadata: Arc<dyn Fn() -> Vec<PluginMetadata> + Send + Sync>,
+    /// Read access to the metrics, to build the outputs.
+    metrics: MetricReader,
+}
+
+/// A change that can be applied to the running pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReloadAction {
+    /// Disables the sources, transforms and outputs of a plugin.
+    DisablePlugin(String),
+    /// Enables the sources, transforms and outputs of a plugin.
+    EnablePlugin(String),
+    /// Replaces the outputs of a plugin by new outputs, created with a new configuration.
+    RecreateOutputs { plugin: String, config: toml::Table },
+    /// Changes the trigger of the sources of a plugin that are polled at regular intervals.
+    SetTrigger {
+        plugin: String,
+        poll_interval: Duration,
+        flush_interval: Option<Duration>,
+    },
+}
+
+/// The result of a configuration reload.
+#[derive(Debug, Default)]
+pub struct ReloadPlan {
+    /// Changes that can be applied to the running pipeline with [`ReloadPlan::apply`].
+    pub actions: Vec<ReloadAction>,
+    /// Changes that require a restart of the agent, as paths in the configuration (ex. `plugins.rapl.no_perf_events`).
+    pub restart_required: Vec<String>,
+    /// Used to apply [`ReloadAction::RecreateOutputs`].
+    outputs: Option<OutputsRecreation>,
+    /// Status and configuration of each plugin in the new configuration.
+    new_plugins: IndexMap<String, (bool, toml::Table)>,
+}
+
+impl AppliedConfig {
+    /// Creates a new `AppliedConfig` from the configuration that the agent has been started with.
+    ///
+    /// `running_plugins` contains the name of the plugins that have been started.
+    /// If `update_status` is false, the plugins are never enabled nor disabled by a reload, like in
+    /// [`PluginSet::extract_config`](super::plugin::PluginSet::extract_config).
+    pub fn new(
+        mut config: toml::Table,
+        running_plugins: Vec<String>,
+        update_status: bool,
+    ) -> Result<Self, BadTypeError> {
+        let plugins = extract_plugins_config(&mut config)?;
+        Ok(Self {
+            general: config,
+            plugins,
+            running_plugins,
+            update_status,
+            outputs: None,
+        })
+    }
+
+    /// Recreates the outputs of `plugins` when their options change, instead of requiring a restart.
+    ///
+    /// The plugins must only create blocking outputs when they start: on reload, a new instance of the plugin
+    /// is initialized with the new options and started, and the outputs that it creates replace the
+    /// running ones. The new instance of the plugin is then dropped, without being stopped.
+    /// The new outputs must have the same names as the old ones. They take their place between two writes
+    /// and keep their input channel, therefore no measurement is lost.
+    ///
+    /// `metadata` returns the metadata of the available plugins, such as the one used to start the agent.
+    /// `metrics` gives access to the metrics of the running pipeline, see [`MeasurementPipeline::metrics_reader`].
+    pub fn recreate_outputs<F>(mut self, plugins: Vec<String>, metadata: F, metrics: MetricReader) -> Self
+    where
+        F: Fn() -> Vec<PluginMetadata> + Send + Sync + 'static,
+    {
+        self.outputs = Some(OutputsRecreation {
+            plugins,
+            metadata: Arc::new(metadata),
+            metrics,
+        });
+        self
+    }
+
+    /// Computes the differences between the applied configuration and `new_config`.
+    ///
+    /// The applied configuration is only updated by [`AppliedConfig::apply`], when the actions succeed.
+    /// The changes that require a restart are never recorded: they will be reported again by the next reload.
+    pub fn reload(&self, mut new_config: toml::Table) -> Result<ReloadPlan, BadTypeError> {
+        let new_plugins = extract_plugins_config(&mut new_config)?;
+        let mut plan = ReloadPlan {
+            outputs: self.outputs.clone(),
+            ..Default::default()
+        };
+
+        // General options: they are used to build the pipeline, changing them requires a restart.
+        diff_keys(&self.general, &new_config, |key| {
+            plan.restart_required.push(key.to_owned())
+        });
+
+        // Plugins
+        let mut names: Vec<&String> = self.plugins.keys().collect();
+        names.extend(new_plugins.keys().filter(|k| !self.plugins.contains_key(*k)));
+        let started = (true, toml::Table::new());
+        for name in names {
+            let (new_enabled, new_table) = new_plugins.get(name).cloned().unwrap_or_default();
+            if !self.running_plugins.contains(name) {
+                if new_enabled && self.update_status {
+                    plan.restart_required.push(format!("plugins.{name}"));
+                }
+                continue;
+            }
+
+            // The plugin has been started with the agent, therefore it was enabled at that time.
+            let (enabled, table) = self.plugins.get(name).unwrap_or(&started);
+            let mut enabled = *enabled;
+            if self.update_status && enabled != new_enabled {
+                enabled = new_enabled;
+                if new_enabled {
+                    plan.actions.push(ReloadAction::EnablePlugin(name.clone()));
+                } else {
+                    plan.actions.push(ReloadAction::DisablePlugin(name.clone()));
+                    continue;
+                }
+            }
+            if !enabled {
+                continue;
+            }
+
+            // The outputs of the plugin can be recreated with any new option.
+            if self.outputs.as_ref().is_some_and(|o| o.plugins.contains(name)) {
+                if *table != new_table {
+                    plan.actions.push(ReloadAction::RecreateOutputs {
+                        plugin: name.clone(),
+                        config: new_table,
+                    });
+                }
+                continue;
+            }
+
+            // Compare the options of the plugin.
+            let mut trigger_changed = false;
+            diff_keys(table, &new_table, |key| match key {
+                POLL_INTERVAL_KEY | FLUSH_INTERVAL_KEY => trigger_changed = true,
+                _ => plan.restart_required.push(format!("plugins.{name}.{key}")),
+            });
+            if trigger_changed {
+                match parse_trigger(&new_table) {
+                    Some((poll_interval, flush_interval)) => {
+                        plan.actions.push(ReloadAction::SetTrigger {
+                            plugin: name.clone(),
+                            poll_interval,
+                            flush_interval,
+                        });
+                    }
+                    None => plan
+                        .restart_required
+                        .push(format!("plugins.{name}.{POLL_INTERVAL_KEY}")),
+                }
+            }
+        }
+        plan.new_plugins = new_plugins;
+        Ok(plan)
+    }
+
+    /// Applies the actions of `plan` to the running pipeline, by sending requests through the `control` handle.
+    ///
+    /// Each action is recorded in the applied configuration once it has succeeded. If an action fails,
+    /// the remaining ones are not applied, and the next reload will try to apply them again.
+    pub async fn apply(&mut self, plan: &ReloadPlan, control: &AnonymousControlHandle) -> anyhow::Result<()> {
+        for action in &plan.actions {
+            plan.apply_action(action, control).await?;
+            self.commit(plan, action);
+            log::info!("Configuration reloaded: {action}");
+        }
+        Ok(())
+    }
+
+    /// Records the effect of an action that has been applied.
+    fn commit(&mut self, plan: &ReloadPlan, action: &ReloadAction) {
+        let plugin = match action {
+            ReloadAction::DisablePlugin(plugin)
+            | ReloadAction::EnablePlugin(plugin)
+            | ReloadAction::RecreateOutputs { plugin, .. }
+            | ReloadAction::SetTrigger { plugin, .. } => plugin,
+        };
+        let (enabled, table) = self
+            .plugins
+            .entry(plugin.clone())
+            .or_insert_with(|| (true, toml::Table::new()));
+        match action {
+            ReloadAction::DisablePlugin(_) => *enabled = false,
+            ReloadAction::EnablePlugin(_) => *enabled = true,
+            ReloadAction::RecreateOutputs { config, .. } => *table = config.clone(),
+            ReloadAction::SetTrigger { .. } => {
+                if let Some((_, new_table)) = plan.new_plugins.get(plugin) {
+                    copy_key(new_table, table, POLL_INTERVAL_KEY);
+                    copy_key(new_table, table, FLUSH_INTERVAL_KEY);
+                }
+            }
+        }
+    }
+}
+
+impl ReloadPlan {
+    /// Returns `true` if the new configuration is identical to the applied one.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty() && self.restart_required.is_empty()
+    }
+
+    /// Applies one action to the running pipeline.
+    async fn apply_action(&self, action: &ReloadAction, control: &AnonymousControlHandle) -> anyhow::Result<()> {
+        match action {
+            ReloadAction::DisablePlugin(plugin) | ReloadAction::EnablePlugin(plugin) => {
+                let enable = matches!(action, ReloadAction::EnablePlugin(_));
+                let sources = request::source(SourceNamePattern::new(plugin_pattern(plugin), StringPattern::Any));
+                let transforms =
+                    request::transform(TransformNamePattern::new(plugin_pattern(plugin), StringPattern::Any));
+                let outputs = request::output(OutputNamePattern::new(plugin_pattern(plugin), StringPattern::Any));
+                let (sources, transforms, outputs) = if enable {
+                    (sources.enable(), transforms.enable(), outputs.enable())
+                } else {
+                    (sources.disable(), transforms.disable(), outputs.disable())
+                };
+                control.send_wait(sources, REQUEST_TIMEOUT).await?;
+                control.send_wait(transforms, REQUEST_TIMEOUT).await?;
+                control.send_wait(outputs, REQUEST_TIMEOUT).await?;
+            }
+            ReloadAction::RecreateOutputs { plugin, config } => {
+                let recreation = self
+                    .outputs
+                    .as_ref()
+                    .context("the recreation of the outputs is not enabled")?;
+                let outputs = {
+                    let metrics = recreation.metrics.read().await;
+                    recreation
+                        .build_outputs(plugin, config, &metrics)
+                        .with_context(|| format!("could not recreate the outputs of plugin {plugin}"))?
+                };
+                // The running outputs are replaced in place: no measurement is lost, and they keep
+                // running if the replacement fails.
+                let req = request::replace_outputs(PluginName(plugin.clone()), outputs);
+                control
+                    .send_wait(req, REQUEST_TIMEOUT)
+                    .await
+                    .with_context(|| format!("could not replace the outputs of plugin {plugin}"))?;
+            }
+            ReloadAction::SetTrigger {
+                plugin,
+                poll_interval,
+                flush_interval,
+            } => {
+                // Check the intervals: the sources cannot report an error.
+                let mut builder = TriggerSpec::builder(*poll_interval);
+                if let Some(flush_interval) = flush_interval {
+                    builder.flush_interval(*flush_interval);
+                }
+                builder
+                    .build()
+                    .with_context(|| format!("invalid trigger for the sources of plugin {plugin}"))?;
+                let req = request::source(SourceNamePattern::new(plugin_pattern(plugin), StringPattern::Any))
+                    .set_interval(*poll_interval, *flush_interval);
+                control.send_wait(req, REQUEST_TIMEOUT).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ReloadAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReloadAction::DisablePlugin(plugin) => write!(f, "plugin {plugin} disabled"),
+            ReloadAction::EnablePlugin(plugin) => write!(f, "plugin {plugin} enabled"),
+            ReloadAction::RecreateOutputs { plugin, .. } => write!(f, "outputs of plugin {plugin} recreated"),
+            ReloadAction::SetTrigger {
+                plugin,
+                poll_interval,
+                flush_interval: Some(flush_interval),
+            } => write!(
+                f,
+                "sources of plugin {plugin} now polled every {poll_interval:?} and flushed every {flush_interval:?}"
+            ),
+            ReloadAction::SetTrigger {
+                plugin,
+                poll_interval,
+                flush_interval: None,
+            } => write!(f, "sources of plugin {plugin} now polled every {poll_interval:?}"),
+        }
+    }
+}
+
+impl OutputsRecreation {
+    /// Initializes and starts a new instance of `plugin` with `config`, and builds the outputs that it creates.
+    fn build_outputs(
+        &self,
+        plugin: &str,
+        config: &toml::Table,
+        metrics: &MetricRegistry,
+    ) -> anyhow::Result<Vec<(String, Box<dyn Output>)>> {
+        let metadata = (self.metadata)()
+            .into_iter()
+            .find(|p| p.name == plugin)
+            .context("plugin not available")?;
+        let mut instance = (metadata.init)(ConfigTable(config.clone())).context("plugin failed to initialize")?;
+
+        let mut builder = pipeline::Builder::new();
+        let mut pre_start_actions = Vec::new();
+        let mut post_start_actions = Vec::new();
+        let mut ctx = AlumetPluginStart {
+            current_plugin: PluginName(plugin.to_owned()),
+            pipeline_builder: &mut builder,
+            pre_start_actions: &mut pre_start_actions,
+            post_start_actions: &mut post_start_actions,
+        };
+        instance.start(&mut ctx).context("plugin failed to start")?;
+
+        let stats = builder.inspect().stats();
+        if stats.sources + stats.transforms + stats.metrics + stats.metric_listeners > 0
+            || !pre_start_actions.is_empty()
+            || !post_start_actions.is_empty()
+        {
+            return Err(anyhow!(
+                "the plugin does not only create outputs, restart the agent to apply its new configuration"
+            ));
+        }
+        let mut ctx = RegistryContext(metrics);
+        builder
+            .take_outputs()
+            .into_iter()
+            .map(|(name, builder)| match builder {
+                OutputBuilder::Blocking(build) => {
+                    let output = build(&mut ctx).with_context(|| format!("failed to build output {name}"))?;
+                    Ok((name.output().to_owned(), output))
+                }
+                OutputBuilder::Async(_) => Err(anyhow!("output {name} is async, it cannot be recreated")),
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for OutputsRecreation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputsRecreation")
+            .field("plugins", &self.plugins)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builds outputs with the metrics of the running pipeline.
+struct RegistryContext<'a>(&'a MetricRegistry);
+
+impl BlockingOutputBuildContext for RegistryContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.0.by_name(name)
+    }
+}
+
+/// Reloads the configuration every time the agent receives the `SIGHUP` signal.
+///
+/// `load_config` is called to load the new configuration, usually from the file that the agent has been started with.
+/// The changes are applied to the running `pipeline` when possible, the other changes are reported in the logs.
+#[cfg(unix)]
+pub fn reload_on_sighup<F>(
+    pipeline: &MeasurementPipeline,
+    mut applied: AppliedConfig,
+    load_config: F,
+) -> anyhow::Result<()>
+where
+    F: Fn() -> anyhow::Result<toml::Table> + Send + Sync + 'static,
+{
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let rt = pipeline.async_runtime();
+    let mut hangup = {
+        let _guard = rt.enter();
+        signal(SignalKind::hangup()).context("could not listen to SIGHUP")?
+    };
+    let control = pipeline.control_handle();
+    rt.spawn(async move {
+        while hangup.recv().await.is_some() {
+            log::info!("SIGHUP received, reloading the configuration...");
+            if let Err(e) = reload_config(&mut applied, &load_config, &control).await {
+                log::error!("Failed to reload the configuration: {e:#}");
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn reload_config(
+    applied: &mut AppliedConfig,
+    load_config: impl Fn() -> anyhow::Result<toml::Table>,
+    control: &AnonymousControlHandle,
+) -> anyhow::Result<()> {
+    let new_config = load_config()?;
+    let plan = applied.reload(new_config)?;
+    if plan.is_empty() {
+        log::info!("The configuration has not changed.");
+        return Ok(());
+    }
+    for change in &plan.restart_required {
+        log::warn!("The change of {change} cannot be applied without restarting the agent.");
+    }
+    applied.apply(&plan, control).await
+}
+
+fn plugin_pattern(plugin: &str) -> StringPattern {
+    StringPattern::Exact(plugin.to_owned())
+}
+
+/// Calls `on_diff` for each key that has been added, removed or modified between `old` and `new`.
+fn diff_keys(old: &toml::Table, new: &toml::Table, mut on_diff: impl FnMut(&str)) {
+    for (key, old_value) in old {
+        if new.get(key) != Some(old_value) {
+            on_diff(key);
+        }
+    }
+    for key in new.keys() {
+        if !old.contains_key(key) {
+            on_diff(key);
+        }
+    }
+}
+
+fn copy_key(from: &toml::Table, to: &mut toml::Table, key: &str) {
+    match from.get(key) {
+        Some(value) => to.insert(key.to_owned(), value.clone()),
+        None => to.remove(key),
+    };
+}
+
+/// Parses the poll and flush intervals of the sources of a plugin.
+///
+/// Returns `None` if the poll interval is missing or if an interval is invalid.
+fn parse_trigger(config: &toml::Table) -> Option<(Duration, Option<Duration>)> {
+    fn parse_duration(value: &toml::Value) -> Option<Duration> {
+        humantime::parse_duration(value.as_str()?).ok()
+    }
+    let poll_interval = parse_duration(config.get(POLL_INTERVAL_KEY)?)?;
+    let flush_interval = match config.get(FLUSH_INTERVAL_KEY) {
+        Some(value) => Some(parse_duration(value)?),
+        None => None,
+    };
+    Some((poll_interval, flush_interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use indoc::indoc;
+
+    use super::{AppliedConfig, ReloadAction};
+
+    const CONFIG: &str = indoc! {r#"
+        max_update_interval = "500ms"
+
+        [plugins.rapl]
+        poll_interval = "1s"
+        flush_interval = "5s"
+
+        [plugins.csv]
+        output_path = "alumet-output.csv"
+
+        [plugins.procfs]
+        enabled = false
+    "#};
+
+    fn applied() -> AppliedConfig {
+        let config = toml::from_str(CONFIG).unwrap();
+        AppliedConfig::new(config, vec![String::from("rapl"), String::from("csv")], true).unwrap()
+    }
+
+    /// Computes the reload plan, and records its actions as if they had been applied successfully.
+    fn reload(applied: &mut AppliedConfig, new_config: &str) -> super::ReloadPlan {
+        let plan = applied.reload(toml::from_str(new_config).unwrap()).unwrap();
+        for action in &plan.actions {
+            applied.commit(&plan, action);
+        }
+        plan
+    }
+
+    #[test]
+    fn no_change() {
+        let mut applied = applied();
+        assert!(reload(&mut applied, CONFIG).is_empty());
+    }
+
+    #[test]
+    fn trigger_change() {
+        let mut applied = applied();
+        let new_config = CONFIG.replace(r#"poll_interval = "1s""#, r#"poll_interval = "100ms""#);
+        let plan = reload(&mut applied, &new_config);
+        assert_eq!(
+            plan.actions,
+            vec![ReloadAction::SetTrigger {
+                plugin: String::from("rapl"),
+                poll_interval: Duration::from_millis(100),
+                flush_interval: Some(Duration::from_secs(5)),
+            }]
+        );
+        assert!(plan.restart_required.is_empty());
+
+        // the change has been applied
+        assert!(reload(&mut applied, &new_config).is_empty());
+    }
+
+    #[test]
+    fn failed_actions_are_retried() {
+        let mut applied = applied();
+        let new_config: toml::Table = toml::from_str(
+            &CONFIG
+                .replace(r#"poll_interval = "1s""#, r#"poll_interval = "100ms""#)
+                .replace("[plugins.csv]", "[plugins.csv]\nenabled = false"),
+        )
+        .unwrap();
+        let plan = applied.reload(new_config.clone()).unwrap();
+        assert_eq!(plan.actions.len(), 2);
+
+        // nothing has been applied, for instance because the pipeline did not answer in time
+        assert_eq!(applied.reload(new_config.clone()).unwrap().actions, plan.actions);
+
+        // only the first action has succeeded
+        applied.commit(&plan, &plan.actions[0]);
+        assert_eq!(applied.reload(new_config).unwrap().actions, plan.actions[1..]);
+    }
+
+    #[test]
+    fn invalid_trigger() {
+        let mut applied = applied();
+        let new_config = CONFIG.replace(r#"poll_interval = "1s""#, r#"poll_interval = "often""#);
+        let plan = reload(&mut applied, &new_config);
+        assert!(plan.actions.is_empty());
+        assert_eq!(plan.restart_required, vec!["plugins.rapl.poll_interval"]);
+    }
+
+    #[test]
+    fn restart_required() {
+        let mut applied = applied();
+        let new_config = CONFIG
+            .replace("alumet-output.csv", "other.csv")
+            .replace(r#""500ms""#, r#""1s""#)
+            .replace("enabled = false", "enabled = true");
+        let plan = reload(&mut applied, &new_config);
+        assert!(plan.actions.is_empty());
+        assert_eq!(
+            plan.restart_required,
+            vec!["max_update_interval", "plugins.csv.output_path", "plugins.procfs"]
+        );
+
+        // the changes have not been applied, they are reported again
+        assert_eq!(reload(&mut applied, &new_config).restart_required.len(), 3);
+    }
+
+    #[test]
+    fn disable_and_enable() {
+        let mut applied = applied();
+        let disabled = CONFIG.replace("[plugins.csv]", "[plugins.csv]\nenabled = false");
+        let plan = reload(&mut applied, &disabled);
+        assert_eq!(plan.actions, vec![ReloadAction::DisablePlugin(String::from("csv"))]);
+        assert!(plan.restart_required.is_empty());
+
+        // removing the plugin from the config also disables it, but it is already disabled
+        let removed = CONFIG.replace("[plugins.csv]\noutput_path = \"alumet-output.csv\"\n", "");
+        assert!(reload(&mut applied, &removed).is_empty());
+
+        let plan = reload(&mut applied, CONFIG);
+        assert_eq!(plan.actions, vec![ReloadAction::EnablePlugin(String::from("csv"))]);
+        assert!(plan.restart_required.is_empty());
+    }
+
+    #[test]
+    fn status_not_updated() {
+        let config = toml::from_str(CONFIG).unwrap();
+        let mut applied = AppliedConfig::new(config, vec![String::from("csv")], false).unwrap();
+        let new_config = CONFIG.replace("[plugins.csv]", "[plugins.csv]\nenabled = false");
+        assert!(reload(&mut applied, &new_config).is_empty());
+    }
+}
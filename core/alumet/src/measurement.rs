@@ -284,6 +284,28 @@ impl MeasurementType for f64 {
         WrappedMeasurementType::F64
     }
 }
+impl MeasurementType for i64 {
+    type T = i64;
+
+    fn wrapped_value(v: Self::T) -> WrappedMeasurementValue {
+        WrappedMeasurementValue::I64(v)
+    }
+
+    fn wrapped_type() -> WrappedMeasurementType {
+        WrappedMeasurementType::I64
+    }
+}
+impl MeasurementType for Histogram {
+    type T = Histogram;
+
+    fn wrapped_value(v: Self::T) -> WrappedMeasurementValue {
+        WrappedMeasurementValue::Histogram(v)
+    }
+
+    fn wrapped_type() -> WrappedMeasurementType {
+        WrappedMeasurementType::Histogram
+    }
+}
 
 /// Enum of the possible measurement types.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -291,6 +313,8 @@ impl MeasurementType for f64 {
 pub enum WrappedMeasurementType {
     F64,
     U64,
+    I64,
+    Histogram,
 }
 impl fmt::Display for WrappedMeasurementType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -303,6 +327,8 @@ impl fmt::Display for WrappedMeasurementType {
 pub enum WrappedMeasurementValue {
     F64(f64),
     U64(u64),
+    I64(i64),
+    Histogram(Histogram),
 }
 
 impl WrappedMeasurementValue {
@@ -310,21 +336,179 @@ impl WrappedMeasurementValue {
         match self {
             WrappedMeasurementValue::F64(_) => WrappedMeasurementType::F64,
             WrappedMeasurementValue::U64(_) => WrappedMeasurementType::U64,
+            WrappedMeasurementValue::I64(_) => WrappedMeasurementType::I64,
+            WrappedMeasurementValue::Histogram(_) => WrappedMeasurementType::Histogram,
         }
     }
 
+    /// Converts the value to a `f64`.
+    ///
+    /// A histogram is converted to the mean of its values (`NaN` if it is empty).
     pub fn as_f64(&self) -> f64 {
         match self {
             WrappedMeasurementValue::F64(x) => *x,
             WrappedMeasurementValue::U64(x) => *x as f64,
+            WrappedMeasurementValue::I64(x) => *x as f64,
+            WrappedMeasurementValue::Histogram(h) => h.mean(),
         }
     }
 
+    /// Converts the value to a `u64`.
+    ///
+    /// Negative values are converted to zero.
+    /// A histogram is converted to the mean of its values (zero if it is empty).
     pub fn as_u64(&self) -> u64 {
         match self {
             WrappedMeasurementValue::F64(x) => *x as u64,
             WrappedMeasurementValue::U64(x) => *x,
+            WrappedMeasurementValue::I64(x) => (*x).max(0) as u64,
+            WrappedMeasurementValue::Histogram(h) => h.mean() as u64,
+        }
+    }
+}
+
+impl Display for WrappedMeasurementValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WrappedMeasurementValue::F64(x) => write!(f, "{x}"),
+            WrappedMeasurementValue::U64(x) => write!(f, "{x}"),
+            WrappedMeasurementValue::I64(x) => write!(f, "{x}"),
+            WrappedMeasurementValue::Histogram(h) => write!(f, "{h}"),
+        }
+    }
+}
+
+/// A distribution of values, summarized by counting how many values fall in each bucket.
+///
+/// The buckets are delimited by increasing upper bounds (inclusive). An additional bucket,
+/// whose upper bound is `+Inf`, counts the values that are greater than the last bound.
+/// This is the kind of histogram supported by Prometheus and OpenTelemetry ("explicit buckets").
+///
+/// # Example
+/// ```
+/// use alumet::measurement::Histogram;
+///
+/// let mut latency = Histogram::new(vec![0.001, 0.01, 0.1]).unwrap();
+/// latency.record(0.005);
+/// latency.record(0.5);
+/// assert_eq!(latency.counts(), &[0, 1, 0, 1]);
+/// assert_eq!(latency.count(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+/// Error returned when creating an invalid [`Histogram`].
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum HistogramError {
+    #[error("histogram bounds must be finite and strictly increasing")]
+    InvalidBounds,
+    #[error("histogram must have exactly one count per bound, plus one (expected {expected}, got {actual})")]
+    InvalidCounts { expected: usize, actual: usize },
+    #[error("histograms with different bounds cannot be merged")]
+    IncompatibleBounds,
+}
+
+impl Histogram {
+    /// Creates an empty histogram with the given bucket bounds.
+    pub fn new(bounds: Vec<f64>) -> Result<Self, HistogramError> {
+        let n = bounds.len();
+        Self::from_parts(bounds, vec![0; n + 1], 0.0)
+    }
+
+    /// Creates a histogram from existing data.
+    ///
+    /// `counts` must contain one count per bound, plus the count of the `+Inf` bucket.
+    pub fn from_parts(bounds: Vec<f64>, counts: Vec<u64>, sum: f64) -> Result<Self, HistogramError> {
+        let valid_bounds = bounds.iter().all(|b| b.is_finite()) && bounds.windows(2).all(|w| w[0] < w[1]);
+        if !valid_bounds {
+            return Err(HistogramError::InvalidBounds);
+        }
+        if counts.len() != bounds.len() + 1 {
+            return Err(HistogramError::InvalidCounts {
+                expected: bounds.len() + 1,
+                actual: counts.len(),
+            });
+        }
+        Ok(Self { bounds, counts, sum })
+    }
+
+    /// Adds a value to the histogram.
+    pub fn record(&mut self, value: f64) {
+        let bucket = self.bounds.partition_point(|b| *b < value);
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    /// Adds all the values of `other` to this histogram.
+    ///
+    /// Both histograms must have the same bounds.
+    pub fn merge(&mut self, other: &Histogram) -> Result<(), HistogramError> {
+        if self.bounds != other.bounds {
+            return Err(HistogramError::IncompatibleBounds);
         }
+        for (c, other_c) in self.counts.iter_mut().zip(&other.counts) {
+            *c += other_c;
+        }
+        self.sum += other.sum;
+        Ok(())
+    }
+
+    /// Returns the upper bounds of the buckets, without the last `+Inf` bound.
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+
+    /// Returns the number of values in each bucket (not cumulative).
+    /// The last count is the one of the `+Inf` bucket.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the total number of values.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the sum of the values.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Returns the mean of the values, or `NaN` if the histogram is empty.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count() as f64
+    }
+
+    /// Iterates on the buckets, as pairs `(upper_bound, count)`.
+    /// The last upper bound is `f64::INFINITY`.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.bounds
+            .iter()
+            .copied()
+            .chain(std::iter::once(f64::INFINITY))
+            .zip(self.counts.iter().copied())
+    }
+}
+
+impl Display for Histogram {
+    /// Formats the histogram like `count=3 sum=1.5 buckets=[0.1:1 1:2 +Inf:0]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "count={} sum={} buckets=[", self.count(), self.sum)?;
+        for (i, (bound, count)) in self.buckets().enumerate() {
+            if i > 0 {
+                f.write_char(' ')?;
+            }
+            if bound.is_infinite() {
+                write!(f, "+Inf:{count}")?;
+            } else {
+                write!(f, "{bound}:{count}")?;
+            }
+        }
+        f.write_char(']')
     }
 }
 
@@ -576,6 +760,47 @@ mod tests {
         fn as_u64() {
             assert_eq!(WrappedMeasurementValue::U64(69).as_u64(), 69);
             assert_eq!(WrappedMeasurementValue::F64(18.38).as_u64(), 18);
+            assert_eq!(WrappedMeasurementValue::I64(-5).as_u64(), 0);
+            assert_eq!(WrappedMeasurementValue::I64(5).as_u64(), 5);
+        }
+    }
+
+    mod histogram {
+        use super::*;
+
+        #[test]
+        fn record() {
+            let mut h = Histogram::new(vec![1.0, 10.0]).unwrap();
+            for v in [0.5, 1.0, 2.0, 10.0, 11.0, 100.0] {
+                h.record(v);
+            }
+            assert_eq!(h.counts(), &[2, 2, 2]);
+            assert_eq!(h.count(), 6);
+            assert_eq!(h.sum(), 124.5);
+            assert_eq!(WrappedMeasurementValue::Histogram(h.clone()).as_f64(), 124.5 / 6.0);
+            assert_eq!(h.to_string(), "count=6 sum=124.5 buckets=[1:2 10:2 +Inf:2]");
+        }
+
+        #[test]
+        fn invalid() {
+            assert_eq!(Histogram::new(vec![1.0, 1.0]), Err(HistogramError::InvalidBounds));
+            assert_eq!(Histogram::new(vec![f64::NAN]), Err(HistogramError::InvalidBounds));
+            assert_eq!(
+                Histogram::from_parts(vec![1.0], vec![1], 0.0),
+                Err(HistogramError::InvalidCounts { expected: 2, actual: 1 })
+            );
+            assert!(Histogram::new(vec![]).unwrap().mean().is_nan());
+        }
+
+        #[test]
+        fn merge() {
+            let mut a = Histogram::from_parts(vec![1.0], vec![1, 2], 10.0).unwrap();
+            let b = Histogram::from_parts(vec![1.0], vec![3, 0], 1.5).unwrap();
+            a.merge(&b).unwrap();
+            assert_eq!(a, Histogram::from_parts(vec![1.0], vec![4, 2], 11.5).unwrap());
+
+            let c = Histogram::new(vec![2.0]).unwrap();
+            assert_eq!(a.merge(&c), Err(HistogramError::IncompatibleBounds));
         }
     }
 
@@ -309,6 +309,11 @@ impl MetricReader {
     pub(crate) fn blocking_read(&'_ self) -> RwLockReadGuard<'_, MetricRegistry> {
         self.0.inner.blocking_read()
     }
+
+    /// Provides shared read access to the metric registry, if it is not currently locked for writing.
+    pub(crate) fn try_read(&'_ self) -> Option<RwLockReadGuard<'_, MetricRegistry>> {
+        self.0.inner.try_read().ok()
+    }
 }
 
 impl MetricSender {
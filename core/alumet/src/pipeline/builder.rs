@@ -1,4 +1,5 @@
 //! Construction of measurement pipelines.
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, anyhow};
@@ -22,10 +23,15 @@ use crate::pipeline::elements::transform::control::TransformControl;
 use crate::pipeline::util::channel;
 
 use super::elements::output::builder::OutputBuilder;
-use super::elements::source::builder::SourceBuilder;
-use super::elements::source::trigger::TriggerConstraints;
+use super::elements::output::routing::OutputRoute;
+use super::elements::output::spool::OutputSpool;
+use super::elements::source::builder::{ManagedSource, SourceBuilder, SourcePace};
+use super::elements::source::control::TaskState;
+use super::elements::source::trigger::{TriggerConstraints, TriggerSpec};
 use super::elements::transform::builder::TransformBuilder;
+use super::elements::transform::graph::{TransformGraph, TransformInputs};
 use super::error::PipelineError;
+use super::health::{HealthMetrics, HealthSource, PipelineHealth};
 use super::naming::{
     OutputName, PluginName, SourceName, TransformName,
     namespace::{DuplicateNameError, Namespace2},
@@ -60,6 +66,13 @@ pub struct Builder {
     transforms_order: Option<Vec<TransformName>>,
     /// Order in which the transforms have been added, to use if `transforms_order` is `None`.
     default_transforms_order: Vec<TransformName>,
+    /// Inputs of the transforms that are not part of the default chain.
+    transform_inputs: FxHashMap<TransformName, TransformInputs>,
+
+    /// Restrictions on the measurements that the outputs receive.
+    output_routes: Vec<OutputRoute>,
+    /// Spools of the outputs that must not lose measurements.
+    output_spools: Vec<OutputSpool>,
 
     /// Constraints to apply to the TriggerSpec of managed sources.
     trigger_constraints: TriggerConstraints,
@@ -71,6 +84,9 @@ pub struct Builder {
     /// Set this to `false` if you plan to add more outputs at runtime, while there is only one output at the beginning.
     allow_simplified_pipeline: bool,
 
+    /// Poll interval of the self-monitoring source, if enabled.
+    self_monitoring: Option<Duration>,
+
     /// Metrics
     pub(crate) metrics: MetricRegistry,
     metric_listeners: Namespace2<Box<dyn MetricListenerBuilder>>,
@@ -95,9 +111,13 @@ impl Builder {
             outputs: Namespace2::new(),
             transforms_order: None,
             default_transforms_order: Vec::new(),
+            transform_inputs: FxHashMap::default(),
+            output_routes: Vec::new(),
+            output_spools: Vec::new(),
             trigger_constraints: TriggerConstraints::default(),
             source_channel_size: DEFAULT_CHAN_BUF_SIZE,
             allow_simplified_pipeline: true,
+            self_monitoring: None,
             metrics: MetricRegistry::new(),
             metric_listeners: Namespace2::new(),
             threads_normal: None, // default to the number of cores
@@ -124,6 +144,15 @@ impl Builder {
         &mut self.allow_simplified_pipeline
     }
 
+    /// Enables the self-monitoring of the pipeline.
+    ///
+    /// A builtin source, `alumet/self_monitoring`, will measure the health of the pipeline
+    /// (poll durations, write errors, lost measurements, channel usage, etc.) every `poll_interval`.
+    /// See the [`health`](super::health) module for the list of metrics.
+    pub fn self_monitoring(&mut self, poll_interval: Duration) {
+        self.self_monitoring = Some(poll_interval);
+    }
+
     /// Registers a listener that will be notified of the metrics that are created while the pipeline is running,
     /// with a dedicated builder.
     pub fn add_metric_listener_builder(
@@ -202,6 +231,34 @@ impl Builder {
         self.transforms_order = Some(order);
     }
 
+    /// Sets the inputs of a transform, i.e. which measurements it receives.
+    ///
+    /// The transforms that have no explicit inputs form a chain, in the order
+    /// given by [`transforms_order`](Self::transforms_order). The other ones are
+    /// organized as a graph, see [`TransformInputs`].
+    pub fn transform_inputs(&mut self, name: TransformName, inputs: TransformInputs) {
+        self.transform_inputs.insert(name, inputs);
+    }
+
+    /// Restricts the measurements that some outputs receive.
+    ///
+    /// An output that is not targeted by any route receives every measurement.
+    /// An output that is targeted by several routes receives the measurements
+    /// that are selected by at least one of them.
+    pub fn route_outputs(&mut self, route: OutputRoute) {
+        self.output_routes.push(route);
+    }
+
+    /// Enables an on-disk spool for some outputs.
+    ///
+    /// The measurements that these outputs cannot write immediately, because they are too slow or because
+    /// their backend is unavailable, are stored on disk and written later, instead of being lost.
+    /// If several spools match an output, the first one is used.
+    /// See the [`spool`](super::elements::output::spool) module.
+    pub fn spool_outputs(&mut self, spool: OutputSpool) {
+        self.output_spools.push(spool);
+    }
+
     /// Replaces each source builder with the result of the closure `f`.
     pub fn replace_sources(&mut self, mut f: impl FnMut(SourceName, SourceBuilder) -> SourceBuilder) {
         self.sources.replace_each(|(plugin, source), builder| {
@@ -229,6 +286,16 @@ impl Builder {
         });
     }
 
+    /// Removes the output builders from the pipeline builder and returns them.
+    ///
+    /// This is used to build the outputs of a plugin outside of the pipeline, see [`crate::agent::reload`].
+    pub(crate) fn take_outputs(&mut self) -> Vec<(OutputName, OutputBuilder)> {
+        std::mem::replace(&mut self.outputs, Namespace2::new())
+            .into_iter()
+            .map(|((plugin, output), builder)| (OutputName::new(plugin, output), builder))
+            .collect()
+    }
+
     /// Builds the measurement pipeline.
     ///
     /// The new pipeline is immediately started.
@@ -303,6 +370,15 @@ impl Builder {
         // Token to shutdown the remaining parts of the pipeline, after the elements have been stopped.
         let pipeline_shutdown_finalize = CancellationToken::new();
 
+        // Statistics about the pipeline elements, which are always collected.
+        let health = Arc::new(PipelineHealth::default());
+        let health_metrics = match self.self_monitoring {
+            Some(_) => Some(
+                HealthMetrics::register(&mut self.metrics).context("could not create the self-monitoring metrics")?,
+            ),
+            None => None,
+        };
+
         // --- Metric registry (one for the entire pipeline) ---
         // Note: We can modify it without sending a message thanks to MetricAccess::write().
         let mut registry_control = MetricRegistryControl::new(self.metrics);
@@ -322,6 +398,7 @@ impl Builder {
 
         // Channel: sources -> transforms (or sources -> output in case of optimization).
         let (in_tx, in_rx) = mpsc::channel::<MeasurementBuffer>(self.source_channel_size);
+        health.watch_sources_channel(&in_tx);
 
         let mut output_control;
         let transform_control;
@@ -333,14 +410,30 @@ impl Builder {
             add_dummy_output(&mut self.outputs);
         }
 
-        if self.outputs.total_count() == 1 && self.transforms.is_empty() && self.allow_simplified_pipeline {
+        // Organize the transforms as a graph.
+        let order = self.transforms_order.unwrap_or(self.default_transforms_order);
+        let transforms = take_transforms_in_order(self.transforms, order)?;
+        let graph = TransformGraph::new(
+            transforms.iter().map(|(name, _)| name.to_owned()).collect(),
+            self.transform_inputs,
+        )
+        .context("invalid transform graph")?;
+
+        if self.outputs.total_count() == 1 && transforms.is_empty() && self.allow_simplified_pipeline {
             // OPTIMIZATION: there is only one output and no transform,
             // we can connect the inputs directly to the output.
             log::info!("Only one output and no transform, using a simplified and optimized measurement pipeline.");
 
             // Outputs
             let out_rx_provider = channel::ReceiverProvider::from(in_rx);
-            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone());
+            output_control = OutputControl::new(
+                out_rx_provider,
+                self.output_routes,
+                self.output_spools,
+                rt_handle.clone(),
+                metrics_r.clone(),
+                health.clone(),
+            );
             output_control
                 .blocking_create_outputs(self.outputs)
                 .context("output creation failed")?;
@@ -350,21 +443,61 @@ impl Builder {
         } else {
             // Broadcast queue: transforms -> outputs
             let out_tx = broadcast::Sender::<MeasurementBuffer>::new(self.source_channel_size);
+            health.watch_outputs_channel(&out_tx);
 
             // Outputs
             let out_rx_provider = channel::ReceiverProvider::from(out_tx.clone());
-            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone());
+            output_control = OutputControl::new(
+                out_rx_provider,
+                self.output_routes,
+                self.output_spools,
+                rt_handle.clone(),
+                metrics_r.clone(),
+                health.clone(),
+            );
             output_control
                 .blocking_create_outputs(self.outputs)
                 .context("output creation failed")?;
 
-            // Transforms
-            let order = self.transforms_order.unwrap_or(self.default_transforms_order);
-            let transforms = take_transforms_in_order(self.transforms, order)?;
-            transform_control =
-                TransformControl::with_transforms(transforms, metrics_r.clone(), in_rx, out_tx, rt_handle)?;
+            // Transforms, sorted like the nodes of the graph
+            let mut transforms: FxHashMap<TransformName, Box<dyn TransformBuilder>> = transforms.into_iter().collect();
+            let transforms = graph
+                .names()
+                .map(|name| {
+                    let builder = transforms.remove(name).expect("every transform should be in the graph");
+                    (name.to_owned(), builder)
+                })
+                .collect();
+            transform_control = TransformControl::with_transforms(
+                transforms,
+                graph,
+                metrics_r.clone(),
+                (in_rx, out_tx),
+                self.source_channel_size,
+                rt_handle,
+                &health,
+            )?;
         };
 
+        // Self-monitoring source, which is built like the other sources.
+        if let (Some(poll_interval), Some(metrics)) = (self.self_monitoring, health_metrics) {
+            let health = health.clone();
+            let metrics_reader = metrics_r.clone();
+            let builder = SourceBuilder::Managed(
+                Box::new(move |_| {
+                    Ok(ManagedSource {
+                        initial_state: TaskState::Run,
+                        trigger_spec: TriggerSpec::at_interval(poll_interval),
+                        source: Box::new(HealthSource::new(health, metrics, metrics_reader)),
+                    })
+                }),
+                SourcePace::Fast,
+            );
+            self.sources
+                .add(String::from("alumet"), String::from("self_monitoring"), builder)
+                .context("could not add the self-monitoring source")?;
+        }
+
         // Sources, last in order not to loose any measurement if they start measuring right away.
         let mut source_control = SourceControl::new(
             self.trigger_constraints,
@@ -373,13 +506,14 @@ impl Builder {
             rt_handle.clone(),
             rt_priority.as_ref().unwrap_or(&rt_normal).handle().clone(),
             (metrics_r.clone(), metrics_tx.clone()),
+            health.clone(),
         );
         source_control
             .blocking_create_sources(self.sources)
             .context("source creation failed")?;
 
         // Pipeline control
-        let control = PipelineControl::new(source_control, transform_control, output_control);
+        let control = PipelineControl::new(source_control, transform_control, output_control, health);
         let (control_handle, control_join) = control.start(pipeline_shutdown, pipeline_shutdown_finalize, rt_handle);
 
         // Done!
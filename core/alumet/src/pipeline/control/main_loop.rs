@@ -3,6 +3,9 @@ use crate::pipeline::control::messages::RequestMessage;
 use crate::pipeline::error::PipelineError;
 
 use crate::pipeline::elements::{output, source, transform};
+use crate::pipeline::health::PipelineHealth;
+
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use tokio::runtime;
@@ -18,6 +21,7 @@ pub(crate) struct PipelineControl {
     sources: source::control::SourceControl,
     transforms: transform::control::TransformControl,
     outputs: output::control::OutputControl,
+    health: Arc<PipelineHealth>,
 }
 
 impl PipelineControl {
@@ -25,11 +29,13 @@ impl PipelineControl {
         sources: source::control::SourceControl,
         transforms: transform::control::TransformControl,
         outputs: output::control::OutputControl,
+        health: Arc<PipelineHealth>,
     ) -> Self {
         Self {
             sources,
             transforms,
             outputs,
+            health,
         }
     }
 
@@ -102,6 +108,16 @@ impl PipelineControl {
                 };
                 send_response(result, response_tx)
             }
+            messages::ControlRequest::Status(RequestMessage { response_tx, body }) => {
+                let mut buf = Vec::new();
+                self.sources.element_status(&mut buf, &body);
+                self.transforms.element_status(&mut buf, &body);
+                self.outputs.element_status(&mut buf, &body);
+                send_response(Ok(buf), response_tx)
+            }
+            messages::ControlRequest::Stats(RequestMessage { response_tx, body: () }) => {
+                send_response(Ok(self.health.snapshot()), response_tx)
+            }
         }
     }
 
@@ -1,8 +1,10 @@
 use tokio::sync::{mpsc, oneshot};
 
 use crate::pipeline::{
+    control::request::ElementStatus,
     elements::{output, source, transform},
     error::PipelineError,
+    health::PipelineStats,
     matching::ElementNamePattern,
     naming::ElementName,
 };
@@ -14,6 +16,8 @@ pub type Sender = mpsc::Sender<ControlRequest>;
 pub enum ControlRequest {
     NoResult(RequestMessage<EmptyResponseBody, ()>),
     Introspect(RequestMessage<IntrospectionBody, IntrospectionResponse>),
+    Status(RequestMessage<ElementNamePattern, StatusResponse>),
+    Stats(RequestMessage<(), PipelineStats>),
 }
 
 pub type ResponseSender<R> = oneshot::Sender<Result<R, PipelineError>>;
@@ -43,3 +47,5 @@ pub enum IntrospectionBody {
 }
 
 pub type IntrospectionResponse = Vec<ElementName>;
+
+pub type StatusResponse = Vec<ElementStatus>;
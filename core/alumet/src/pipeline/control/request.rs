@@ -18,6 +18,7 @@
 
 use crate::pipeline::{error::PipelineError, naming::PluginName};
 
+mod action;
 pub mod any;
 mod create;
 pub(super) mod introspect;
@@ -25,10 +26,14 @@ mod output;
 pub mod source;
 mod transform;
 
+pub use action::{Action, ActionError};
 pub use create::{CreationRequest, MultiCreationRequestBuilder, SingleCreationRequestBuilder, create_many, create_one};
-pub use introspect::{ElementListFilter, IntrospectionRequest, list_elements};
-pub use output::{OutputRequest, OutputRequestBuilder, RemainingDataStrategy, output};
-pub use source::{SourceRequest, SourceRequestBuilder, source};
+pub use introspect::{
+    ElementListFilter, ElementState, ElementStatus, IntrospectionRequest, StatsRequest, StatusRequest, element_status,
+    list_elements, pipeline_stats,
+};
+pub use output::{OutputRequest, OutputRequestBuilder, RemainingDataStrategy, output, replace_outputs};
+pub use source::{SourceRequest, SourceRequestBuilder, set_group_period, source};
 use tokio::sync::oneshot;
 pub use transform::{TransformRequest, TransformRequestBuilder, transform};
 
@@ -0,0 +1,173 @@
+//! Simple actions that can be applied to the elements selected by a pattern.
+//!
+//! The control plugins (socket, HTTP, rules) let their users pause, resume or stop elements
+//! by giving a pattern and an action. [`Action::requests`] turns such a pair into control requests.
+
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::pipeline::{
+    elements::source::trigger::TriggerSpec,
+    naming::{
+        ElementKind,
+        matching::{ElementNamePattern, OutputNamePattern, SourceNamePattern, TransformNamePattern},
+    },
+};
+
+use super::{RemainingDataStrategy, any::AnyAnonymousControlRequest, output, source, transform};
+
+/// What to do with the selected elements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Action {
+    /// Pauses sources, transforms or outputs.
+    Pause,
+    /// Resumes sources, transforms or outputs.
+    Resume,
+    /// Stops sources or outputs. Outputs write the remaining data before stopping.
+    Stop,
+    /// Changes the poll interval of sources.
+    SetPeriod {
+        #[serde(with = "humantime_serde")]
+        period: Duration,
+    },
+    /// Polls sources now, if they enable manual triggers.
+    TriggerNow,
+}
+
+/// Error returned when an [`Action`] cannot be applied to the selected elements.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ActionError {
+    #[error("action '{0}' requires a pattern with an explicit element kind")]
+    KindRequired(&'static str),
+    #[error("action '{action}' cannot be applied to {kind}s")]
+    Unsupported { action: &'static str, kind: ElementKind },
+}
+
+impl Action {
+    /// The name of the action, as written in the configuration and commands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Pause => "pause",
+            Action::Resume => "resume",
+            Action::Stop => "stop",
+            Action::SetPeriod { .. } => "set-period",
+            Action::TriggerNow => "trigger-now",
+        }
+    }
+
+    /// Builds the control requests that apply this action to the elements selected by `pat`.
+    ///
+    /// Pausing and resuming accept a pattern without kind, which selects sources, transforms and outputs.
+    /// The other actions require an explicit kind.
+    pub fn requests(&self, pat: &ElementNamePattern) -> Result<Vec<AnyAnonymousControlRequest>, ActionError> {
+        let kinds = match (pat.kind, self) {
+            (Some(kind), _) => vec![kind],
+            (None, Action::Pause | Action::Resume) => {
+                vec![ElementKind::Source, ElementKind::Transform, ElementKind::Output]
+            }
+            (None, _) => return Err(ActionError::KindRequired(self.name())),
+        };
+        kinds
+            .into_iter()
+            .map(|kind| {
+                let pat = ElementNamePattern {
+                    kind: Some(kind),
+                    ..pat.clone()
+                };
+                self.request(kind, pat)
+            })
+            .collect()
+    }
+
+    fn request(&self, kind: ElementKind, pat: ElementNamePattern) -> Result<AnyAnonymousControlRequest, ActionError> {
+        // The patterns below cannot fail to convert, because their kind has been checked.
+        let req = match (self, kind) {
+            (Action::Pause, ElementKind::Source) => source(SourceNamePattern::try_from(pat).unwrap()).disable().into(),
+            (Action::Pause, ElementKind::Transform) => {
+                transform(TransformNamePattern::try_from(pat).unwrap()).disable().into()
+            }
+            (Action::Pause, ElementKind::Output) => output(OutputNamePattern::try_from(pat).unwrap()).disable().into(),
+            (Action::Resume, ElementKind::Source) => source(SourceNamePattern::try_from(pat).unwrap()).enable().into(),
+            (Action::Resume, ElementKind::Transform) => {
+                transform(TransformNamePattern::try_from(pat).unwrap()).enable().into()
+            }
+            (Action::Resume, ElementKind::Output) => output(OutputNamePattern::try_from(pat).unwrap()).enable().into(),
+            (Action::Stop, ElementKind::Source) => source(SourceNamePattern::try_from(pat).unwrap()).stop().into(),
+            (Action::Stop, ElementKind::Output) => output(OutputNamePattern::try_from(pat).unwrap())
+                .stop(RemainingDataStrategy::Write)
+                .into(),
+            (Action::SetPeriod { period }, ElementKind::Source) => source(SourceNamePattern::try_from(pat).unwrap())
+                .set_trigger(TriggerSpec::at_interval(*period))
+                .into(),
+            (Action::TriggerNow, ElementKind::Source) => {
+                source(SourceNamePattern::try_from(pat).unwrap()).trigger_now().into()
+            }
+            (action, kind) => {
+                return Err(ActionError::Unsupported {
+                    action: action.name(),
+                    kind,
+                });
+            }
+        };
+        Ok(req)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::SetPeriod { period } => write!(f, "set-period {}", humantime::format_duration(*period)),
+            other => f.write_str(other.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::pipeline::naming::{ElementKind, parsing::parse_pattern};
+
+    use super::{Action, ActionError};
+
+    #[test]
+    fn requests_per_kind() {
+        let any = parse_pattern("*").unwrap();
+        assert_eq!(Action::Pause.requests(&any).unwrap().len(), 3);
+        assert_eq!(Action::Resume.requests(&any).unwrap().len(), 3);
+        assert_eq!(
+            Action::Stop.requests(&any).unwrap_err(),
+            ActionError::KindRequired("stop")
+        );
+        assert_eq!(
+            Action::TriggerNow.requests(&any).unwrap_err().to_string(),
+            "action 'trigger-now' requires a pattern with an explicit element kind"
+        );
+
+        let transforms = parse_pattern("transform/*/*").unwrap();
+        assert_eq!(Action::Pause.requests(&transforms).unwrap().len(), 1);
+        assert_eq!(
+            Action::Stop.requests(&transforms).unwrap_err().to_string(),
+            "action 'stop' cannot be applied to transforms"
+        );
+        let period = Action::SetPeriod {
+            period: Duration::from_secs(1),
+        };
+        assert_eq!(
+            period.requests(&transforms).unwrap_err(),
+            ActionError::Unsupported {
+                action: "set-period",
+                kind: ElementKind::Transform
+            }
+        );
+
+        let sources = parse_pattern("source/*/*").unwrap();
+        assert_eq!(period.requests(&sources).unwrap().len(), 1);
+        assert_eq!(Action::TriggerNow.requests(&sources).unwrap().len(), 1);
+        assert_eq!(Action::Stop.requests(&sources).unwrap().len(), 1);
+        assert_eq!(period.to_string(), "set-period 1s");
+    }
+}
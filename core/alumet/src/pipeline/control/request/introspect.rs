@@ -1,9 +1,13 @@
+use std::time::Duration;
+
 use tokio::sync::oneshot;
 
+use crate::measurement::Timestamp;
 use crate::pipeline::{
     control::messages,
+    health::PipelineStats,
     matching::{ElementNamePattern, StringPattern},
-    naming::ElementKind,
+    naming::{ElementKind, ElementName},
 };
 
 use super::{AnonymousControlRequest, DirectResponseReceiver};
@@ -13,11 +17,53 @@ pub fn list_elements(filter: ElementListFilter) -> IntrospectionRequest {
     IntrospectionRequest { list_filter: filter }
 }
 
+/// Creates a request that returns the status of the elements of the pipeline that match the given filter.
+pub fn element_status(filter: ElementListFilter) -> StatusRequest {
+    StatusRequest { filter }
+}
+
+/// Creates a request that returns a snapshot of the statistics of the pipeline elements and channels.
+///
+/// See the [`health`](crate::pipeline::health) module.
+pub fn pipeline_stats() -> StatsRequest {
+    StatsRequest
+}
+
 #[derive(Debug)]
 pub struct IntrospectionRequest {
     list_filter: ElementListFilter,
 }
 
+#[derive(Debug)]
+pub struct StatusRequest {
+    filter: ElementListFilter,
+}
+
+#[derive(Debug)]
+pub struct StatsRequest;
+
+/// The current status of a pipeline element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementStatus {
+    pub name: ElementName,
+    pub state: ElementState,
+    /// Time between two polls, for the managed sources that are polled at regular intervals.
+    ///
+    /// This is the interval of the trigger that the source task currently uses:
+    /// after a `set_trigger` request, it changes when the task applies the new trigger.
+    pub poll_interval: Option<Duration>,
+    /// Time of the last poll, for the managed sources.
+    pub last_poll: Option<Timestamp>,
+}
+
+/// State of a pipeline element, as requested by the control requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementState {
+    Running,
+    Paused,
+    Stopped,
+}
+
 #[derive(Debug)]
 pub struct ElementListFilter {
     pub(crate) pattern: ElementNamePattern,
@@ -89,3 +135,51 @@ impl AnonymousControlRequest for IntrospectionRequest {
         (req, DirectResponseReceiver(rx))
     }
 }
+
+impl AnonymousControlRequest for StatusRequest {
+    type OkResponse = messages::StatusResponse;
+    type Receiver = DirectResponseReceiver<Self::OkResponse>;
+
+    fn serialize(self) -> messages::ControlRequest {
+        messages::ControlRequest::Status(messages::RequestMessage {
+            response_tx: None,
+            body: self.filter.pattern,
+        })
+    }
+
+    fn serialize_with_response(self) -> (messages::ControlRequest, Self::Receiver) {
+        let (tx, rx) = oneshot::channel();
+        let req = messages::ControlRequest::Status(messages::RequestMessage {
+            response_tx: Some(tx),
+            body: self.filter.pattern,
+        });
+        (req, DirectResponseReceiver(rx))
+    }
+}
+
+impl AnonymousControlRequest for StatsRequest {
+    type OkResponse = PipelineStats;
+    type Receiver = DirectResponseReceiver<Self::OkResponse>;
+
+    fn serialize(self) -> messages::ControlRequest {
+        messages::ControlRequest::Stats(messages::RequestMessage {
+            response_tx: None,
+            body: (),
+        })
+    }
+
+    fn serialize_with_response(self) -> (messages::ControlRequest, Self::Receiver) {
+        let (tx, rx) = oneshot::channel();
+        let req = messages::ControlRequest::Stats(messages::RequestMessage {
+            response_tx: Some(tx),
+            body: (),
+        });
+        (req, DirectResponseReceiver(rx))
+    }
+}
+
+impl ElementState {
+    pub fn is_enabled(&self) -> bool {
+        *self == ElementState::Running
+    }
+}
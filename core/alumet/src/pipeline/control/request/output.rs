@@ -1,8 +1,10 @@
 use tokio::sync::oneshot;
 
 use crate::pipeline::{
+    Output,
     control::{matching::OutputMatcher, messages},
-    elements::output::control::{ConfigureMessage, ControlMessage, TaskState},
+    elements::output::control::{ConfigureMessage, ControlMessage, ReplaceMessage, TaskState},
+    naming::PluginName,
 };
 
 use super::DirectResponseReceiver;
@@ -23,6 +25,16 @@ pub fn output(matcher: impl Into<OutputMatcher>) -> OutputRequestBuilder {
     }
 }
 
+/// Returns a request that replaces the running blocking outputs of `plugin` by new ones.
+///
+/// The new outputs must have the same names as the running outputs of the plugin, which are replaced
+/// between two writes, without losing any measurement. Async outputs cannot be replaced.
+pub fn replace_outputs(plugin: PluginName, outputs: Vec<(String, Box<dyn Output>)>) -> OutputRequest {
+    OutputRequest {
+        msg: ControlMessage::Replace(ReplaceMessage { plugin, outputs }),
+    }
+}
+
 pub enum RemainingDataStrategy {
     Write,
     Ignore,
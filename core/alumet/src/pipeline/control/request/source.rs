@@ -1,9 +1,11 @@
+use std::time::Duration;
+
 use tokio::sync::oneshot;
 
 use crate::pipeline::{
     control::{matching::SourceMatcher, messages},
     elements::source::{
-        control::{ConfigureCommand, ConfigureMessage, ControlMessage},
+        control::{ConfigureCommand, ConfigureMessage, ControlMessage, GroupPeriodMessage},
         trigger::TriggerSpec,
     },
 };
@@ -26,6 +28,20 @@ pub fn source(matcher: impl Into<SourceMatcher>) -> SourceRequestBuilder {
     }
 }
 
+/// Returns a request that changes the period of a trigger group.
+///
+/// All the sources of the group are polled with the new period, from the next wall-clock boundary.
+/// The request fails if there is no group with this name.
+/// See [`TimeTriggerBuilder::group`](crate::pipeline::elements::source::trigger::builder::TimeTriggerBuilder::group).
+pub fn set_group_period(group: impl Into<String>, period: Duration) -> SourceRequest {
+    SourceRequest {
+        msg: ControlMessage::SetGroupPeriod(GroupPeriodMessage {
+            group: group.into(),
+            period,
+        }),
+    }
+}
+
 impl SourceRequestBuilder {
     pub fn set_trigger(self, spec: TriggerSpec) -> SourceRequest {
         SourceRequest {
@@ -36,6 +52,25 @@ impl SourceRequestBuilder {
         }
     }
 
+    /// Changes the poll and flush intervals of the sources that are triggered at regular intervals.
+    ///
+    /// Unlike [`set_trigger`](Self::set_trigger), this does not modify the other sources, and the
+    /// sources keep the other settings of their trigger. In particular, they stay in their
+    /// [trigger group](crate::pipeline::elements::source::trigger::builder::TimeTriggerBuilder::group),
+    /// whose period is changed for all its sources.
+    /// If `flush_interval` is `None`, the measurements are flushed after each poll.
+    pub fn set_interval(self, poll_interval: Duration, flush_interval: Option<Duration>) -> SourceRequest {
+        SourceRequest {
+            msg: ControlMessage::Configure(ConfigureMessage {
+                matcher: self.matcher,
+                command: ConfigureCommand::SetInterval {
+                    poll_interval,
+                    flush_interval,
+                },
+            }),
+        }
+    }
+
     pub fn trigger_now(self) -> SourceRequest {
         SourceRequest {
             msg: ControlMessage::TriggerManually(crate::pipeline::elements::source::control::TriggerMessage {
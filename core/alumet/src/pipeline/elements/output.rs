@@ -7,8 +7,12 @@ pub(crate) mod control;
 pub mod error;
 /// Public interface for implementing outputs.
 pub mod interface;
+/// Restriction of the measurements that the outputs receive.
+pub mod routing;
 /// Functions that run outputs.
 pub mod run;
+/// Persistent buffer for the measurements that could not be written yet.
+pub mod spool;
 
 pub use error::WriteError;
 pub use interface::{AsyncOutputStream, BoxedAsyncOutput, Output, OutputContext};
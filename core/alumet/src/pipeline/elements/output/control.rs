@@ -10,9 +10,11 @@ use tokio::{
     task::{JoinError, JoinSet},
 };
 
+use crate::pipeline::control::request::{ElementState, ElementStatus};
 use crate::pipeline::elements::output::{AsyncOutputStream, run::run_async_output};
+use crate::pipeline::health::{OutputStats, PipelineHealth};
 use crate::pipeline::matching::OutputNamePattern;
-use crate::pipeline::naming::{OutputName, namespace::Namespace2};
+use crate::pipeline::naming::{OutputName, PluginName, namespace::Namespace2};
 use crate::pipeline::util::{
     channel,
     stream::{ControlledStream, SharedStreamState, StreamState},
@@ -22,8 +24,11 @@ use crate::{measurement::MeasurementBuffer, pipeline::error::PipelineError};
 use crate::{metrics::online::MetricReader, pipeline::naming::ElementName};
 
 use super::{
+    Output,
     builder::{self, OutputBuilder},
+    routing::{OutputFilter, OutputRoute},
     run::run_blocking_output,
+    spool::{OutputSpool, Spool},
 };
 
 /// A control messages for outputs.
@@ -31,6 +36,7 @@ use super::{
 pub enum ControlMessage {
     Configure(ConfigureMessage),
     CreateMany(CreateManyMessage),
+    Replace(ReplaceMessage),
 }
 
 #[derive(Debug)]
@@ -46,6 +52,26 @@ pub struct CreateManyMessage {
     pub builders: Vec<(OutputName, builder::SendOutputBuilder)>,
 }
 
+/// Replaces the running blocking outputs of a plugin by new ones.
+///
+/// The new outputs take the place of the old ones in their tasks, between two writes: they keep the same
+/// channel, spool and statistics, therefore no measurement is lost.
+pub struct ReplaceMessage {
+    pub plugin: PluginName,
+    /// The new outputs, with the same names as the old ones (without the plugin name).
+    pub outputs: Vec<(String, Box<dyn Output>)>,
+}
+
+impl std::fmt::Debug for ReplaceMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = self.outputs.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("ReplaceMessage")
+            .field("plugin", &self.plugin)
+            .field("outputs", &names)
+            .finish()
+    }
+}
+
 /// State of a (managed) output task.
 #[derive(Clone, Debug, PartialEq, Eq, Copy, IntoPrimitive, FromPrimitive)]
 #[repr(u8)]
@@ -58,21 +84,27 @@ pub enum TaskState {
     StopNow,
 }
 
+/// A blocking output, shared between its task and its controller, which can replace it.
+pub type GuardedOutput = Arc<Mutex<Box<dyn Output>>>;
+
 pub enum SingleOutputController {
-    Blocking(Arc<SharedOutputConfig>),
+    Blocking(Arc<SharedOutputConfig>, GuardedOutput),
     Async(Arc<SharedStreamState>),
 }
 
 pub struct SharedOutputConfig {
     pub change_notifier: Notify,
     pub atomic_state: AtomicU8,
+    /// Statistics about the output, updated by the output task.
+    pub(crate) stats: Arc<OutputStats>,
 }
 
 impl SharedOutputConfig {
-    pub fn new() -> Self {
+    pub(crate) fn new(stats: Arc<OutputStats>) -> Self {
         Self {
             change_notifier: Notify::new(),
             atomic_state: AtomicU8::new(TaskState::Run as u8),
+            stats,
         }
     }
 
@@ -85,10 +117,27 @@ impl SharedOutputConfig {
 impl SingleOutputController {
     pub fn set_state(&mut self, state: TaskState) {
         match self {
-            SingleOutputController::Blocking(shared) => shared.set_state(state),
+            SingleOutputController::Blocking(shared, _) => shared.set_state(state),
             SingleOutputController::Async(arc) => arc.set(StreamState::from(state as u8)),
         }
     }
+
+    pub fn state(&self) -> ElementState {
+        match self {
+            SingleOutputController::Blocking(shared, _) => {
+                match TaskState::from(shared.atomic_state.load(Ordering::Relaxed)) {
+                    TaskState::Run | TaskState::RunDiscard => ElementState::Running,
+                    TaskState::Pause => ElementState::Paused,
+                    TaskState::StopFinish | TaskState::StopNow => ElementState::Stopped,
+                }
+            }
+            SingleOutputController::Async(arc) => match arc.get() {
+                StreamState::Run => ElementState::Running,
+                StreamState::Pause => ElementState::Paused,
+                StreamState::Stop => ElementState::Stopped,
+            },
+        }
+    }
 }
 
 pub(crate) struct OutputControl {
@@ -103,21 +152,40 @@ struct TaskManager {
 
     rx_provider: channel::ReceiverProvider,
 
+    /// Restrictions on the measurements that the outputs receive.
+    routes: Vec<OutputRoute>,
+
+    /// Spools of the outputs that must not lose measurements.
+    spools: Vec<OutputSpool>,
+
     /// Handle of the "normal" async runtime. Used for creating new outputs.
     rt_normal: runtime::Handle,
 
     metrics: MetricReader,
+
+    /// Statistics about the pipeline, to which the new outputs are added.
+    health: Arc<PipelineHealth>,
 }
 
 impl OutputControl {
-    pub fn new(rx_provider: channel::ReceiverProvider, rt_normal: runtime::Handle, metrics: MetricReader) -> Self {
+    pub fn new(
+        rx_provider: channel::ReceiverProvider,
+        routes: Vec<OutputRoute>,
+        spools: Vec<OutputSpool>,
+        rt_normal: runtime::Handle,
+        metrics: MetricReader,
+        health: Arc<PipelineHealth>,
+    ) -> Self {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
                 controllers: Vec::new(),
                 rx_provider,
+                routes,
+                spools,
                 rt_normal,
                 metrics: metrics.clone(),
+                health,
             },
             metrics,
         }
@@ -174,6 +242,7 @@ impl OutputControl {
         match msg {
             ControlMessage::Configure(msg) => self.tasks.reconfigure(msg),
             ControlMessage::CreateMany(msg) => self.create_outputs(msg.builders).await?,
+            ControlMessage::Replace(msg) => self.tasks.replace_outputs(msg)?,
         }
         Ok(())
     }
@@ -219,6 +288,23 @@ impl OutputControl {
             }))
         }
     }
+
+    pub fn element_status(&self, buf: &mut Vec<ElementStatus>, pat: &ElementNamePattern) {
+        if pat.kind.is_none() || pat.kind == Some(ElementKind::Output) {
+            buf.extend(
+                self.tasks
+                    .controllers
+                    .iter()
+                    .filter(|(name, _)| pat.matches(name))
+                    .map(|(name, controller)| ElementStatus {
+                        name: name.to_owned().into(),
+                        state: controller.state(),
+                        poll_interval: None,
+                        last_poll: None,
+                    }),
+            )
+        }
+    }
 }
 
 impl TaskManager {
@@ -246,25 +332,31 @@ impl TaskManager {
         // Create the necessary context.
         let rx = self.rx_provider.get(); // to receive measurements
         let metrics = self.metrics.clone(); // to read metric definitions
+        let filter = OutputFilter::for_output(&name, &self.routes).map(Arc::new);
+        let spool = match self.spools.iter().find(|s| s.outputs.matches(&name)) {
+            Some(s) => Some(Spool::open(&s.config, &name).context("could not open the output spool")?),
+            None => None,
+        };
 
         // Create and store the task controller.
-        let config = Arc::new(SharedOutputConfig::new());
+        let stats = self.health.register_output(name.clone());
+        let config = Arc::new(SharedOutputConfig::new(stats));
         let shared_config = config.clone();
-        let control = SingleOutputController::Blocking(config);
-        self.controllers.push((name.clone(), control));
 
         // Put the output in a Mutex to overcome the lack of tokio::spawn_scoped.
         let guarded_output = Arc::new(Mutex::new(output));
+        let control = SingleOutputController::Blocking(config, guarded_output.clone());
+        self.controllers.push((name.clone(), control));
 
         // Spawn the task on the runtime.
         match rx {
             // Specialize on the kind of receiver at compile-time (for performance).
             channel::ReceiverEnum::Broadcast(rx) => {
-                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config);
+                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config, filter, spool);
                 self.spawned_tasks.spawn_on(task, &self.rt_normal);
             }
             channel::ReceiverEnum::Single(rx) => {
-                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config);
+                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config, filter, spool);
                 self.spawned_tasks.spawn_on(task, &self.rt_normal);
             }
         }
@@ -290,11 +382,64 @@ impl TaskManager {
             (AsyncOutputStream(stream), state)
         }
 
+        /// Counts the buffers that the output loses because it is too slow.
+        fn count_lagged<S: futures::Stream<Item = Result<MeasurementBuffer, channel::StreamRecvError>>>(
+            stream: S,
+            stats: Arc<OutputStats>,
+        ) -> impl futures::Stream<Item = Result<MeasurementBuffer, channel::StreamRecvError>> {
+            use futures::StreamExt;
+
+            stream.inspect(move |item| {
+                if let Err(channel::StreamRecvError::Lagged(n)) = item {
+                    stats.record_dropped(*n);
+                }
+            })
+        }
+
+        /// Removes the measurements that are not routed to the output from the stream.
+        fn filter_stream<S: futures::Stream<Item = Result<MeasurementBuffer, channel::StreamRecvError>>>(
+            stream: S,
+            filter: Arc<OutputFilter>,
+            metrics: MetricReader,
+        ) -> impl futures::Stream<Item = Result<MeasurementBuffer, channel::StreamRecvError>> {
+            use futures::StreamExt;
+
+            stream.filter_map(move |item| {
+                let filter = filter.clone();
+                let metrics = metrics.clone();
+                async move {
+                    match item {
+                        Ok(mut measurements) => {
+                            filter.apply(&mut measurements, &*metrics.read().await);
+                            (!measurements.is_empty()).then_some(Ok(measurements))
+                        }
+                        Err(e) => Some(Err(e)),
+                    }
+                }
+            })
+        }
+
+        if self.spools.iter().any(|s| s.outputs.matches(&name)) {
+            log::warn!("Output {name} is asynchronous, it does not support spooling.");
+        }
+
         // For async outputs, we need to build the stream first
         let rx = self.rx_provider.get();
-        let (stream, state) = match rx {
-            channel::ReceiverEnum::Broadcast(receiver) => box_controlled_stream(receiver.into_stream()),
-            channel::ReceiverEnum::Single(receiver) => box_controlled_stream(receiver.into_stream()),
+        let filter = OutputFilter::for_output(&name, &self.routes).map(Arc::new);
+        let metrics = self.metrics.clone();
+        let stats = self.health.register_output(name.clone());
+        let (stream, state) = match (rx, filter) {
+            (channel::ReceiverEnum::Broadcast(receiver), None) => {
+                box_controlled_stream(count_lagged(receiver.into_stream(), stats))
+            }
+            (channel::ReceiverEnum::Single(receiver), None) => box_controlled_stream(receiver.into_stream()),
+            (channel::ReceiverEnum::Broadcast(receiver), Some(filter)) => {
+                let stream = count_lagged(receiver.into_stream(), stats);
+                box_controlled_stream(filter_stream(stream, filter, metrics))
+            }
+            (channel::ReceiverEnum::Single(receiver), Some(filter)) => {
+                box_controlled_stream(filter_stream(receiver.into_stream(), filter, metrics))
+            }
         };
 
         // Create the output, in the context of the tokio runtime
@@ -313,6 +458,43 @@ impl TaskManager {
         Ok(())
     }
 
+    fn replace_outputs(&mut self, msg: ReplaceMessage) -> anyhow::Result<()> {
+        // Check everything before replacing anything, so that the outputs are either all replaced, or not at all.
+        let running: Vec<(&str, &GuardedOutput)> = self
+            .controllers
+            .iter()
+            .filter(|(name, controller)| name.plugin() == msg.plugin.0 && controller.state() != ElementState::Stopped)
+            .map(|(name, controller)| match controller {
+                SingleOutputController::Blocking(_, output) => Ok((name.output(), output)),
+                SingleOutputController::Async(_) => {
+                    Err(anyhow::anyhow!("output {name} is async, it cannot be replaced"))
+                }
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let mut old_names: Vec<&str> = running.iter().map(|(name, _)| *name).collect();
+        let mut new_names: Vec<&str> = msg.outputs.iter().map(|(name, _)| name.as_str()).collect();
+        old_names.sort_unstable();
+        new_names.sort_unstable();
+        if old_names != new_names {
+            return Err(anyhow::anyhow!(
+                "plugin {} now creates the outputs {new_names:?} instead of {old_names:?}",
+                msg.plugin.0
+            ));
+        }
+
+        for (name, new_output) in msg.outputs {
+            let (_, guarded_output) = running.iter().find(|(n, _)| *n == name).unwrap();
+            let guarded_output = Arc::clone(guarded_output);
+            // Wait for the write in progress in a blocking thread, and drop the old output there,
+            // because it may flush its data.
+            self.rt_normal.spawn_blocking(move || {
+                let old_output = std::mem::replace(&mut *guarded_output.lock().unwrap(), new_output);
+                drop(old_output);
+            });
+        }
+        Ok(())
+    }
+
     fn reconfigure(&mut self, msg: ConfigureMessage) {
         for (name, output_config) in &mut self.controllers {
             if msg.matcher.matches(name) {
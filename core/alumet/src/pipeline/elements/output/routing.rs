@@ -0,0 +1,313 @@
+//! Routing of the measurements to the outputs.
+//!
+//! By default, every output receives all the measurements produced by the transforms.
+//! With [`OutputRoute`]s, an output can be restricted to the measurements that match
+//! a [`MeasurementSelector`]. The filtering is done by the pipeline, before the measurements
+//! are given to the output, so that output plugins don't have to reimplement it.
+//!
+//! # Example
+//! ```
+//! use alumet::pipeline::elements::output::routing::{MeasurementSelector, OutputRoute};
+//! use alumet::pipeline::matching::{OutputNamePattern, StringPattern};
+//!
+//! // Only send the perf counters to the CSV output.
+//! let route = OutputRoute {
+//!     outputs: OutputNamePattern::new(StringPattern::Exact(String::from("csv")), StringPattern::Any),
+//!     selector: MeasurementSelector::metrics(vec![StringPattern::StartWith(String::from("perf_"))]),
+//! };
+//! ```
+
+use crate::{
+    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint},
+    metrics::registry::MetricRegistry,
+    pipeline::{
+        matching::{OutputNamePattern, StringPattern},
+        naming::OutputName,
+    },
+};
+
+/// Restricts the measurements that some outputs receive.
+#[derive(Debug, Clone)]
+pub struct OutputRoute {
+    /// The outputs that this route applies to.
+    pub outputs: OutputNamePattern,
+    /// The measurements that the outputs receive.
+    pub selector: MeasurementSelector,
+}
+
+/// Selects measurement points based on their metric, resource, consumer and attributes.
+///
+/// Each criterion that is set must be satisfied for a point to be selected.
+/// A list of patterns is satisfied if at least one of its patterns matches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeasurementSelector {
+    /// Patterns of the metric names. `None` means any metric.
+    pub metrics: Option<Vec<StringPattern>>,
+    /// Patterns of the resources. `None` means any resource.
+    pub resources: Option<Vec<ResourcePattern>>,
+    /// Patterns of the resource consumers. `None` means any consumer.
+    pub consumers: Option<Vec<ResourcePattern>>,
+    /// Patterns of attributes that the points must have.
+    pub attributes: Vec<AttributePattern>,
+}
+
+/// Matches a resource or a resource consumer by its kind and id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourcePattern {
+    pub kind: StringPattern,
+    /// Pattern of the id. Resources without an id are represented by an empty string.
+    pub id: StringPattern,
+}
+
+/// Matches an attribute by its key and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributePattern {
+    pub key: String,
+    /// Pattern of the value, converted to a string.
+    pub value: StringPattern,
+}
+
+/// The combination of all the routes that apply to an output.
+#[derive(Debug)]
+pub struct OutputFilter {
+    /// A point is kept if it matches at least one of these selectors.
+    selectors: Vec<MeasurementSelector>,
+}
+
+impl MeasurementSelector {
+    /// Creates a selector that accepts every point.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Creates a selector that accepts the points whose metric name matches one of the `patterns`.
+    pub fn metrics(patterns: Vec<StringPattern>) -> Self {
+        Self {
+            metrics: Some(patterns),
+            ..Default::default()
+        }
+    }
+
+    /// Checks whether this selector accepts the measurement `point`.
+    ///
+    /// The `metrics` registry is used to obtain the name of the metric.
+    /// If the metric is unknown, it does not match any metric pattern.
+    pub fn matches(&self, point: &MeasurementPoint, metrics: &MetricRegistry) -> bool {
+        if let Some(patterns) = &self.metrics {
+            let Some(metric) = metrics.by_id(&point.metric) else {
+                return false;
+            };
+            if !patterns.iter().any(|pat| pat.matches(&metric.name)) {
+                return false;
+            }
+        }
+        if let Some(patterns) = &self.resources {
+            let id = point.resource.id_string().unwrap_or_default();
+            if !patterns.iter().any(|pat| pat.matches(point.resource.kind(), &id)) {
+                return false;
+            }
+        }
+        if let Some(patterns) = &self.consumers {
+            let id = point.consumer.id_string().unwrap_or_default();
+            if !patterns.iter().any(|pat| pat.matches(point.consumer.kind(), &id)) {
+                return false;
+            }
+        }
+        self.attributes.iter().all(|pat| pat.matches(point))
+    }
+}
+
+impl ResourcePattern {
+    /// Creates a pattern that matches every resource of the given kind.
+    pub fn kind(kind: StringPattern) -> Self {
+        Self {
+            kind,
+            id: StringPattern::Any,
+        }
+    }
+
+    pub fn matches(&self, kind: &str, id: &str) -> bool {
+        self.kind.matches(kind) && self.id.matches(id)
+    }
+}
+
+impl AttributePattern {
+    pub fn matches(&self, point: &MeasurementPoint) -> bool {
+        point
+            .attributes()
+            .any(|(key, value)| key == self.key && self.value_matches(value))
+    }
+
+    /// Checks the value of an attribute against the pattern, without allocating for the string values.
+    fn value_matches(&self, value: &AttributeValue) -> bool {
+        match (&self.value, value) {
+            (StringPattern::Any, _) => true,
+            (pat, AttributeValue::Str(s)) => pat.matches(s),
+            (pat, AttributeValue::String(s)) => pat.matches(s),
+            (pat, _) => pat.matches(&value.to_string()),
+        }
+    }
+}
+
+impl OutputFilter {
+    /// Builds the filter of the output `name` from the routes that apply to it.
+    ///
+    /// Returns `None` if no route applies to the output, i.e. if it must receive every measurement.
+    pub fn for_output(name: &OutputName, routes: &[OutputRoute]) -> Option<Self> {
+        let selectors: Vec<MeasurementSelector> = routes
+            .iter()
+            .filter(|route| route.outputs.matches(name))
+            .map(|route| route.selector.clone())
+            .collect();
+        if selectors.is_empty() {
+            None
+        } else {
+            Some(Self { selectors })
+        }
+    }
+
+    /// Removes the measurements that are not selected by this filter.
+    pub fn apply(&self, measurements: &mut MeasurementBuffer, metrics: &MetricRegistry) {
+        measurements.retain(|point| self.selectors.iter().any(|s| s.matches(point, metrics)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::{
+            Metric, RawMetricId,
+            duplicate::{DuplicateCriteria, DuplicateReaction},
+            registry::MetricRegistry,
+        },
+        pipeline::{
+            matching::{OutputNamePattern, StringPattern},
+            naming::OutputName,
+        },
+        resources::{Resource, ResourceConsumer},
+        units::Unit,
+    };
+
+    use super::{AttributePattern, MeasurementSelector, OutputFilter, OutputRoute, ResourcePattern};
+
+    fn registry() -> (MetricRegistry, RawMetricId, RawMetricId) {
+        let mut metrics = MetricRegistry::new();
+        let mut register = |name: &str| {
+            metrics
+                .register(
+                    Metric {
+                        name: name.to_owned(),
+                        description: String::new(),
+                        value_type: crate::measurement::WrappedMeasurementType::U64,
+                        unit: Unit::Unity.into(),
+                    },
+                    DuplicateCriteria::Strict,
+                    DuplicateReaction::Error,
+                )
+                .unwrap()
+        };
+        let perf = register("perf_instructions");
+        let energy = register("energy");
+        (metrics, perf, energy)
+    }
+
+    fn point(metric: RawMetricId, resource: Resource) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            metric,
+            resource,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(1),
+        )
+    }
+
+    #[test]
+    fn selector() {
+        let (metrics, perf, energy) = registry();
+        let p_perf = point(perf, Resource::CpuCore { id: 3 });
+        let p_energy = point(energy, Resource::CpuPackage { id: 0 }).with_attr("domain", "package");
+
+        assert!(MeasurementSelector::any().matches(&p_perf, &metrics));
+
+        let by_metric = MeasurementSelector::metrics(vec![StringPattern::StartWith(String::from("perf_"))]);
+        assert!(by_metric.matches(&p_perf, &metrics));
+        assert!(!by_metric.matches(&p_energy, &metrics));
+
+        let by_resource = MeasurementSelector {
+            resources: Some(vec![ResourcePattern {
+                kind: StringPattern::Exact(String::from("cpu_core")),
+                id: StringPattern::Exact(String::from("3")),
+            }]),
+            ..Default::default()
+        };
+        assert!(by_resource.matches(&p_perf, &metrics));
+        assert!(!by_resource.matches(&p_energy, &metrics));
+
+        let by_consumer = MeasurementSelector {
+            consumers: Some(vec![ResourcePattern::kind(StringPattern::Exact(String::from(
+                "process",
+            )))]),
+            ..Default::default()
+        };
+        assert!(!by_consumer.matches(&p_perf, &metrics));
+
+        let by_attr = MeasurementSelector {
+            attributes: vec![AttributePattern {
+                key: String::from("domain"),
+                value: StringPattern::Exact(String::from("package")),
+            }],
+            ..Default::default()
+        };
+        assert!(!by_attr.matches(&p_perf, &metrics));
+        assert!(by_attr.matches(&p_energy, &metrics));
+    }
+
+    #[test]
+    fn typed_attributes() {
+        let (_, perf, _) = registry();
+        let p = point(perf, Resource::LocalMachine)
+            .with_attr("core", 42_u64)
+            .with_attr("turbo", true)
+            .with_attr("ratio", 0.5)
+            .with_attr("huge", 1e100);
+        let pattern = |key: &str, value: StringPattern| AttributePattern {
+            key: key.to_owned(),
+            value,
+        };
+        assert!(pattern("core", StringPattern::Exact(String::from("42"))).matches(&p));
+        assert!(pattern("core", StringPattern::StartWith(String::from("4"))).matches(&p));
+        assert!(!pattern("core", StringPattern::Exact(String::from("4"))).matches(&p));
+        assert!(pattern("turbo", StringPattern::Exact(String::from("true"))).matches(&p));
+        assert!(pattern("ratio", StringPattern::Exact(String::from("0.5"))).matches(&p));
+        // too long for the stack buffer
+        assert!(pattern("huge", StringPattern::StartWith(String::from("10000"))).matches(&p));
+        assert!(pattern("huge", StringPattern::Any).matches(&p));
+        assert!(!pattern("missing", StringPattern::Any).matches(&p));
+    }
+
+    #[test]
+    fn filter() {
+        let (metrics, perf, energy) = registry();
+        let routes = vec![
+            OutputRoute {
+                outputs: OutputNamePattern::new(StringPattern::Exact(String::from("csv")), StringPattern::Any),
+                selector: MeasurementSelector::metrics(vec![StringPattern::Exact(String::from("perf_instructions"))]),
+            },
+            OutputRoute {
+                outputs: OutputNamePattern::new(StringPattern::Exact(String::from("csv")), StringPattern::Any),
+                selector: MeasurementSelector::metrics(vec![StringPattern::Exact(String::from("other"))]),
+            },
+        ];
+        assert!(OutputFilter::for_output(&OutputName::from_str("influxdb", "out"), &routes).is_none());
+
+        let filter = OutputFilter::for_output(&OutputName::from_str("csv", "out"), &routes).unwrap();
+        let mut buf = MeasurementBuffer::new();
+        buf.push(point(perf, Resource::LocalMachine));
+        buf.push(point(energy, Resource::LocalMachine));
+        buf.push(point(perf, Resource::LocalMachine));
+        filter.apply(&mut buf, &metrics);
+        assert_eq!(buf.len(), 2);
+        assert!(buf.iter().all(|p| p.metric == perf));
+    }
+}
@@ -3,17 +3,23 @@ use std::{
     sync::{Arc, Mutex, atomic::Ordering},
 };
 
+use tokio::{
+    task::{JoinError, JoinHandle},
+    time::Instant,
+};
+
 use crate::{
     measurement::MeasurementBuffer,
     metrics::online::MetricReader,
     pipeline::{
         error::PipelineError,
+        health::OutputStats,
         naming::OutputName,
         util::channel::{self, RecvError},
     },
 };
 
-use super::{BoxedAsyncOutput, Output, OutputContext, control, error::WriteError};
+use super::{BoxedAsyncOutput, Output, OutputContext, control, error::WriteError, routing::OutputFilter, spool::Spool};
 
 pub async fn run_async_output(name: OutputName, output: BoxedAsyncOutput) -> Result<(), PipelineError> {
     output.await.map_err(|e| {
@@ -28,23 +34,40 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
     mut rx: Rx,
     metrics_reader: MetricReader,
     config: Arc<control::SharedOutputConfig>,
+    filter: Option<Arc<OutputFilter>>,
+    spool: Option<Spool>,
 ) -> Result<(), PipelineError> {
+    if let Some(spool) = spool {
+        return run_spooled_output(name, guarded_output, rx, metrics_reader, config, filter, spool).await;
+    }
+
     /// If `measurements` is an `Ok`, build an [`OutputContext`] and call `output.write(&measurements, &ctx)`.
     /// Otherwise, handle the error.
     async fn write_measurements(
         name: &OutputName,
         output: Arc<Mutex<Box<dyn Output>>>,
         metrics_r: MetricReader,
+        filter: Option<Arc<OutputFilter>>,
+        stats: Arc<OutputStats>,
         maybe_measurements: Result<MeasurementBuffer, channel::RecvError>,
     ) -> anyhow::Result<ControlFlow<()>> {
         match maybe_measurements {
-            Ok(measurements) => {
+            Ok(mut measurements) => {
                 log::trace!("writing {} measurements to {name}", measurements.len());
                 let res = tokio::task::spawn_blocking(move || {
-                    let ctx = OutputContext {
-                        metrics: &metrics_r.blocking_read(),
-                    };
-                    output.lock().unwrap().write(&measurements, &ctx)
+                    let metrics = metrics_r.blocking_read();
+                    if let Some(filter) = filter {
+                        // Only keep the measurements that are routed to this output.
+                        filter.apply(&mut measurements, &metrics);
+                        if measurements.is_empty() {
+                            return Ok(());
+                        }
+                    }
+                    let ctx = OutputContext { metrics: &metrics };
+                    let t0 = Instant::now();
+                    let res = output.lock().unwrap().write(&measurements, &ctx);
+                    stats.record_write(t0.elapsed(), res.is_ok());
+                    res
                 })
                 .await?;
                 match res {
@@ -61,6 +84,7 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
             }
             Err(channel::RecvError::Lagged(n)) => {
                 log::warn!("Output {name} is too slow, it lost the oldest {n} messages.");
+                stats.record_dropped(n);
                 Ok(ControlFlow::Continue(()))
             }
             Err(channel::RecvError::Closed) => {
@@ -100,7 +124,9 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                 }
             },
             measurements = rx.recv(), if receive => {
-                let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), measurements)
+                let output = guarded_output.clone();
+                let stats = config.stats.clone();
+                let res = write_measurements(&name, output, metrics_reader.clone(), filter.clone(), stats, measurements)
                     .await
                     .map_err(|e| PipelineError::for_element(name.clone(), e))?;
                 if res.is_break() {
@@ -125,7 +151,9 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                     Err(RecvError::Lagged(n)) => format!("Err(Lagged({n}))"),
                 }
             );
-            let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), received)
+            let output = guarded_output.clone();
+            let stats = config.stats.clone();
+            let res = write_measurements(&name, output, metrics_reader.clone(), filter.clone(), stats, received)
                 .await
                 .map_err(|e| PipelineError::for_element(name.clone(), e))?;
             if res.is_break() {
@@ -136,3 +164,219 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
 
     Ok(())
 }
+
+/// Runs a blocking output whose measurements go through a [`Spool`] when they cannot be written immediately.
+///
+/// New measurements are written directly to the output when possible. While a write is in progress,
+/// or after a write has failed, they are appended to the spool, which is emptied in order as soon as the
+/// output works again. Since the channel is read during the writes, a slow output does not lose data.
+async fn run_spooled_output<Rx: channel::MeasurementReceiver>(
+    name: OutputName,
+    guarded_output: Arc<Mutex<Box<dyn Output>>>,
+    mut rx: Rx,
+    metrics_reader: MetricReader,
+    config: Arc<control::SharedOutputConfig>,
+    filter: Option<Arc<OutputFilter>>,
+    mut spool: Spool,
+) -> Result<(), PipelineError> {
+    type WriteTask = JoinHandle<Result<(), WriteError>>;
+
+    /// A write in progress.
+    struct Writing {
+        task: WriteTask,
+        measurements: Arc<MeasurementBuffer>,
+        /// Sequence number of the measurements in the spool, if they are in it.
+        /// The spooled measurements are pinned, and must be removed from the spool when the write succeeds.
+        spooled: Option<u64>,
+    }
+
+    /// Writes the measurements in a blocking task.
+    fn spawn_write(
+        output: Arc<Mutex<Box<dyn Output>>>,
+        metrics_r: MetricReader,
+        stats: Arc<OutputStats>,
+        measurements: MeasurementBuffer,
+        spooled: Option<u64>,
+    ) -> Writing {
+        let measurements = Arc::new(measurements);
+        let m = measurements.clone();
+        let task = tokio::task::spawn_blocking(move || {
+            let ctx = OutputContext {
+                metrics: &metrics_r.blocking_read(),
+            };
+            let t0 = Instant::now();
+            let res = output.lock().unwrap().write(&m, &ctx);
+            stats.record_write(t0.elapsed(), res.is_ok());
+            res
+        });
+        Writing {
+            task,
+            measurements,
+            spooled,
+        }
+    }
+
+    let spool_err = |e: anyhow::Error| PipelineError::for_element(name.clone(), e.context("output spool failure"));
+
+    /// Handles the result of a finished write. Returns `true` if the write must be retried later.
+    async fn finish_write(
+        name: &OutputName,
+        writing: Writing,
+        joined: Result<Result<(), WriteError>, JoinError>,
+        spool: &mut Spool,
+        metrics_reader: &MetricReader,
+    ) -> Result<bool, PipelineError> {
+        let res = joined.map_err(|e| PipelineError::for_element(name.clone(), e.into()))?;
+        spool.pin(None);
+        match res {
+            Ok(()) => {
+                if let Some(seq) = writing.spooled {
+                    spool.remove(seq).map_err(|e| {
+                        PipelineError::for_element(name.clone(), anyhow::Error::from(e).context("output spool failure"))
+                    })?;
+                }
+                Ok(false)
+            }
+            Err(WriteError::CanRetry(e)) => {
+                log::error!(
+                    "Non-fatal error when writing to {name} (will retry in {:?}): {e:#}",
+                    spool.retry_interval()
+                );
+                if writing.spooled.is_none() {
+                    // The spool is empty, otherwise the write would have been spooled (see below).
+                    let metrics = metrics_reader.read().await;
+                    if let Err(e) = spool.push(&writing.measurements, &metrics) {
+                        log::error!(
+                            "Output {name} could not spool {} measurements, they are lost: {e:#}",
+                            writing.measurements.len()
+                        );
+                    }
+                }
+                Ok(true)
+            }
+            Err(WriteError::Fatal(e)) => {
+                log::error!("Fatal error when writing to {name} (will stop running): {e:?}");
+                let e = e.context(format!("fatal error when writing to {name}"));
+                Err(PipelineError::for_element(name.clone(), e))
+            }
+        }
+    }
+
+    if !spool.is_empty() {
+        log::info!(
+            "Output {name} has {} buffers of measurements in its spool.",
+            spool.len()
+        );
+    }
+
+    let config_change = &config.change_notifier;
+    let retry_interval = spool.retry_interval();
+    let mut writing: Option<Writing> = None;
+    let mut retry_at: Option<Instant> = None;
+    let mut receive = true;
+    let mut closed = false;
+    loop {
+        // Write the oldest measurements of the spool, if possible.
+        if writing.is_none() && retry_at.is_none() && (receive || closed) {
+            let metrics = metrics_reader.read().await;
+            if let Some((seq, measurements)) = spool.front(&metrics).map_err(spool_err)? {
+                spool.pin(Some(seq));
+                writing = Some(spawn_write(
+                    guarded_output.clone(),
+                    metrics_reader.clone(),
+                    config.stats.clone(),
+                    measurements,
+                    Some(seq),
+                ));
+            }
+        }
+        if closed && writing.is_none() {
+            if !spool.is_empty() {
+                log::warn!(
+                    "Output {name} stops with {} buffers of measurements in its spool, they will be written on the next start.",
+                    spool.len()
+                );
+            }
+            break;
+        }
+
+        tokio::select! {
+            _ = config_change.notified() => {
+                let new_state = config.atomic_state.load(Ordering::Relaxed);
+                match new_state.into() {
+                    control::TaskState::Run => {
+                        receive = true;
+                    }
+                    control::TaskState::RunDiscard => {
+                        rx = rx.discard_pending();
+                        receive = true;
+                    }
+                    control::TaskState::Pause => {
+                        receive = false;
+                    }
+                    control::TaskState::StopNow => {
+                        // Wait for the write in progress, so that its measurements are spooled if it fails.
+                        // The rest of the spool will be written on the next start.
+                        if let Some(mut w) = writing.take() {
+                            let joined = (&mut w.task).await;
+                            finish_write(&name, w, joined, &mut spool, &metrics_reader).await?;
+                        }
+                        break;
+                    }
+                    control::TaskState::StopFinish => {
+                        receive = true; // empty the channel before stopping
+                    }
+                }
+            },
+            received = rx.recv(), if receive && !closed => {
+                match received {
+                    Ok(mut measurements) => {
+                        let metrics = metrics_reader.read().await;
+                        if let Some(filter) = &filter {
+                            filter.apply(&mut measurements, &metrics);
+                        }
+                        if measurements.is_empty() {
+                            continue;
+                        }
+                        if writing.is_none() && retry_at.is_none() && spool.is_empty() {
+                            writing = Some(spawn_write(guarded_output.clone(), metrics_reader.clone(), config.stats.clone(), measurements, None));
+                            continue;
+                        }
+                        // The measurements written directly are older than the new ones: they must be spooled first,
+                        // so that they are replayed before the new ones if the write fails.
+                        if let Some(w) = writing.as_mut().filter(|w| w.spooled.is_none()) {
+                            match spool.push(&w.measurements, &metrics) {
+                                Ok(seq) => {
+                                    spool.pin(Some(seq));
+                                    w.spooled = Some(seq);
+                                }
+                                Err(e) => log::error!("Output {name} could not spool {} measurements, they may be lost: {e:#}", w.measurements.len()),
+                            }
+                        }
+                        if let Err(e) = spool.push(&measurements, &metrics) {
+                            log::error!("Output {name} could not spool {} measurements, they are lost: {e:#}", measurements.len());
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("Output {name} is too slow, it lost the oldest {n} messages.");
+                        config.stats.record_dropped(n);
+                    }
+                    Err(RecvError::Closed) => {
+                        log::debug!("The channel connected to output {name} was closed, it will now stop.");
+                        closed = true;
+                    }
+                }
+            },
+            joined = async { (&mut writing.as_mut().unwrap().task).await }, if writing.is_some() => {
+                let w = writing.take().unwrap();
+                if finish_write(&name, w, joined, &mut spool, &metrics_reader).await? {
+                    retry_at = Some(Instant::now() + retry_interval);
+                }
+            },
+            _ = tokio::time::sleep_until(retry_at.unwrap_or_else(Instant::now)), if retry_at.is_some() => {
+                retry_at = None;
+            },
+        }
+    }
+    Ok(())
+}
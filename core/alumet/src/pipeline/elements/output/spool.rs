@@ -0,0 +1,509 @@
+//! Persistent on-disk buffer for the measurements that an output could not write yet.
+//!
+//! When an output is slow, or when its backend is unavailable (e.g. the database is restarting),
+//! the measurements back up in the channel between the transforms and the outputs, and the oldest
+//! ones are eventually lost. An output that is associated with a spool avoids this: the measurements
+//! that cannot be written immediately are serialized to disk, and replayed in order as soon as
+//! the output accepts them again.
+//!
+//! The spool is persistent: measurements that remain in the spool when Alumet stops are
+//! replayed the next time the output starts.
+//!
+//! Spooling is only available for blocking outputs, because async outputs consume their stream
+//! of measurements by themselves.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::Context;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    measurement::{AttributeValue, Histogram, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::{RawMetricId, registry::MetricRegistry},
+    pipeline::{matching::OutputNamePattern, naming::OutputName},
+    resources::{Resource, ResourceConsumer},
+};
+
+/// Version of the on-disk format. Increase it when [`SpoolFile`] changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// Extension of the files of the spool.
+const FILE_EXTENSION: &str = "spool";
+
+/// Enables spooling for some outputs.
+#[derive(Debug, Clone)]
+pub struct OutputSpool {
+    /// The outputs that this spool configuration applies to.
+    pub outputs: OutputNamePattern,
+    pub config: SpoolConfig,
+}
+
+/// Configuration of the spool of an output.
+#[derive(Debug, Clone)]
+pub struct SpoolConfig {
+    /// The directory where the spools are stored.
+    ///
+    /// Each output gets its own subdirectory `<plugin>/<output>`.
+    pub directory: PathBuf,
+    /// Maximum size of the spool of one output, in bytes.
+    ///
+    /// When the spool is full, its oldest measurements are discarded,
+    /// except the ones that are being written to the output.
+    pub max_size: u64,
+    /// How long to wait before retrying to write, after a failure.
+    pub retry_interval: Duration,
+}
+
+/// A persistent queue of measurement buffers.
+pub struct Spool {
+    directory: PathBuf,
+    max_size: u64,
+    retry_interval: Duration,
+    /// Sequence number and size of each file, from the oldest to the most recent.
+    files: VecDeque<(u64, u64)>,
+    total_size: u64,
+    next_seq: u64,
+    /// Sequence number of the file that is being written to the output, which must not be discarded.
+    pinned: Option<u64>,
+}
+
+/// The content of a spool file.
+///
+/// Metrics are stored by name, because metric ids are only valid in the process that created them.
+#[derive(Serialize, Deserialize)]
+struct SpoolFile {
+    version: u32,
+    metrics: Vec<String>,
+    points: Vec<SpooledPoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpooledPoint {
+    /// Index in [`SpoolFile::metrics`].
+    metric: u32,
+    timestamp: (u64, u32),
+    value: SpooledValue,
+    resource: (String, String),
+    consumer: (String, String),
+    attributes: Vec<(String, SpooledValue)>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SpooledValue {
+    F64(f64),
+    U64(u64),
+    Bool(bool),
+    String(String),
+    ListU64(Vec<u64>),
+    I64(i64),
+    Histogram {
+        bounds: Vec<f64>,
+        counts: Vec<u64>,
+        sum: f64,
+    },
+}
+
+impl Spool {
+    /// Opens the spool of the output `name`, creating its directory if necessary.
+    ///
+    /// The files that have been left by a previous run are loaded in the queue.
+    pub fn open(config: &SpoolConfig, name: &OutputName) -> io::Result<Self> {
+        let directory = config.directory.join(name.plugin()).join(name.output());
+        fs::create_dir_all(&directory)?;
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == FILE_EXTENSION) {
+                let seq = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok());
+                match seq {
+                    Some(seq) => files.push((seq, fs::metadata(&path)?.len())),
+                    None => log::warn!("Ignoring unexpected file in the output spool: {}", path.display()),
+                }
+            }
+        }
+        files.sort_unstable();
+        let total_size = files.iter().map(|(_, size)| size).sum();
+        let next_seq = files.last().map(|(seq, _)| seq + 1).unwrap_or(0);
+        Ok(Self {
+            directory,
+            max_size: config.max_size,
+            retry_interval: config.retry_interval,
+            files: VecDeque::from(files),
+            total_size,
+            next_seq,
+            pinned: None,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns how long to wait before retrying to write, after a failure.
+    pub fn retry_interval(&self) -> Duration {
+        self.retry_interval
+    }
+
+    /// Returns the number of buffers in the spool.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Appends a buffer at the end of the queue, and returns its sequence number.
+    ///
+    /// If the spool becomes too large, the oldest buffers are discarded, except the pinned one.
+    pub fn push(&mut self, measurements: &MeasurementBuffer, metrics: &MetricRegistry) -> anyhow::Result<u64> {
+        let bytes = postcard::to_allocvec(&SpoolFile::new(measurements, metrics)).context("serialization failed")?;
+        let size = bytes.len() as u64;
+
+        // Make room for the new buffer.
+        let mut n_discarded = 0;
+        while self.total_size + size > self.max_size {
+            let Some(i) = self.files.iter().position(|(seq, _)| Some(*seq) != self.pinned) else {
+                break;
+            };
+            self.remove_at(i)?;
+            n_discarded += 1;
+        }
+        if n_discarded > 0 {
+            log::warn!(
+                "The output spool {} is full, {n_discarded} buffers of measurements have been discarded.",
+                self.directory.display()
+            );
+        }
+
+        // Write to a temporary file, then rename it, so that a crash never leaves a partial file in the queue.
+        let seq = self.next_seq;
+        let tmp_path = self.directory.join(format!("{seq}.tmp"));
+        fs::write(&tmp_path, bytes).with_context(|| format!("could not write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, self.file_path(seq))?;
+        self.files.push_back((seq, size));
+        self.total_size += size;
+        self.next_seq += 1;
+        Ok(seq)
+    }
+
+    /// Reads the oldest buffer of the queue, without removing it, and returns it with its sequence number.
+    ///
+    /// Files that cannot be read are discarded.
+    pub fn front(&mut self, metrics: &MetricRegistry) -> anyhow::Result<Option<(u64, MeasurementBuffer)>> {
+        while let Some((seq, _)) = self.files.front() {
+            let seq = *seq;
+            let path = self.file_path(seq);
+            let res = fs::read(&path)
+                .context("read failed")
+                .and_then(|bytes| postcard::from_bytes::<SpoolFile>(&bytes).context("deserialization failed"))
+                .and_then(|file| file.into_buffer(metrics));
+            match res {
+                Ok(buf) => return Ok(Some((seq, buf))),
+                Err(e) => {
+                    log::error!("Discarding invalid spool file {}: {e:#}", path.display());
+                    self.pop_front()?;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pins a buffer, or unpins it with `None`.
+    ///
+    /// The pinned buffer is not discarded when the spool is full, because it is being written to the output.
+    pub fn pin(&mut self, seq: Option<u64>) {
+        self.pinned = seq;
+    }
+
+    /// Removes the buffer with the given sequence number, if it is still in the queue.
+    pub fn remove(&mut self, seq: u64) -> io::Result<()> {
+        match self.files.iter().position(|(s, _)| *s == seq) {
+            Some(i) => self.remove_at(i),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes the oldest buffer of the queue.
+    pub fn pop_front(&mut self) -> io::Result<()> {
+        self.remove_at(0)
+    }
+
+    fn remove_at(&mut self, i: usize) -> io::Result<()> {
+        if let Some((seq, size)) = self.files.remove(i) {
+            if self.pinned == Some(seq) {
+                self.pinned = None;
+            }
+            self.total_size -= size;
+            match fs::remove_file(self.file_path(seq)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn file_path(&self, seq: u64) -> PathBuf {
+        // Pad the sequence number to make the files easy to sort by hand.
+        self.directory.join(format!("{seq:020}.{FILE_EXTENSION}"))
+    }
+}
+
+impl SpoolFile {
+    fn new(measurements: &MeasurementBuffer, metrics: &MetricRegistry) -> Self {
+        let mut metric_indices: FxHashMap<RawMetricId, u32> = FxHashMap::default();
+        let mut metric_names = Vec::new();
+        let points = measurements
+            .iter()
+            .map(|p| {
+                let metric = *metric_indices.entry(p.metric).or_insert_with(|| {
+                    let name = metrics.by_id(&p.metric).map(|m| m.name.clone()).unwrap_or_default();
+                    metric_names.push(name);
+                    (metric_names.len() - 1) as u32
+                });
+                SpooledPoint {
+                    metric,
+                    timestamp: p.timestamp.to_unix_timestamp(),
+                    value: match &p.value {
+                        WrappedMeasurementValue::F64(v) => SpooledValue::F64(*v),
+                        WrappedMeasurementValue::U64(v) => SpooledValue::U64(*v),
+                        WrappedMeasurementValue::I64(v) => SpooledValue::I64(*v),
+                        WrappedMeasurementValue::Histogram(h) => SpooledValue::Histogram {
+                            bounds: h.bounds().to_vec(),
+                            counts: h.counts().to_vec(),
+                            sum: h.sum(),
+                        },
+                    },
+                    resource: (p.resource.kind().to_owned(), p.resource.id_string().unwrap_or_default()),
+                    consumer: (p.consumer.kind().to_owned(), p.consumer.id_string().unwrap_or_default()),
+                    attributes: p
+                        .attributes()
+                        .map(|(k, v)| (k.to_owned(), SpooledValue::from(v)))
+                        .collect(),
+                }
+            })
+            .collect();
+        Self {
+            version: FORMAT_VERSION,
+            metrics: metric_names,
+            points,
+        }
+    }
+
+    fn into_buffer(self, metrics: &MetricRegistry) -> anyhow::Result<MeasurementBuffer> {
+        anyhow::ensure!(
+            self.version == FORMAT_VERSION,
+            "unsupported format version {}",
+            self.version
+        );
+        // The metrics that are not registered anymore are ignored.
+        let ids: Vec<Option<RawMetricId>> = self
+            .metrics
+            .iter()
+            .map(|name| {
+                let id = metrics.by_name(name).map(|(id, _)| id);
+                if id.is_none() {
+                    log::warn!("Spooled measurements of unknown metric '{name}' will be ignored.");
+                }
+                id
+            })
+            .collect();
+
+        let mut buf = MeasurementBuffer::with_capacity(self.points.len());
+        for p in self.points {
+            let Some(Some(metric)) = ids.get(p.metric as usize) else {
+                continue;
+            };
+            let value = match p.value {
+                SpooledValue::F64(v) => WrappedMeasurementValue::F64(v),
+                SpooledValue::U64(v) => WrappedMeasurementValue::U64(v),
+                SpooledValue::I64(v) => WrappedMeasurementValue::I64(v),
+                SpooledValue::Histogram { bounds, counts, sum } => {
+                    WrappedMeasurementValue::Histogram(Histogram::from_parts(bounds, counts, sum)?)
+                }
+                _ => anyhow::bail!("invalid measurement value"),
+            };
+            let resource = Resource::parse(p.resource.0, p.resource.1)?;
+            let consumer = ResourceConsumer::parse(p.consumer.0, p.consumer.1)?;
+            let timestamp = Timestamp::from_unix_timestamp(p.timestamp.0, p.timestamp.1);
+            let attributes = p
+                .attributes
+                .into_iter()
+                .map(|(k, v)| Ok((k, AttributeValue::try_from(v)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let point = MeasurementPoint::new_untyped(timestamp, *metric, resource, consumer, value);
+            buf.push(point.with_attr_vec(attributes));
+        }
+        Ok(buf)
+    }
+}
+
+impl From<&AttributeValue> for SpooledValue {
+    fn from(value: &AttributeValue) -> Self {
+        match value {
+            AttributeValue::F64(v) => SpooledValue::F64(*v),
+            AttributeValue::U64(v) => SpooledValue::U64(*v),
+            AttributeValue::Bool(v) => SpooledValue::Bool(*v),
+            AttributeValue::Str(v) => SpooledValue::String(v.to_string()),
+            AttributeValue::String(v) => SpooledValue::String(v.clone()),
+            AttributeValue::ListU64(v) => SpooledValue::ListU64(v.clone()),
+        }
+    }
+}
+
+impl TryFrom<SpooledValue> for AttributeValue {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SpooledValue) -> anyhow::Result<Self> {
+        match value {
+            SpooledValue::F64(v) => Ok(AttributeValue::F64(v)),
+            SpooledValue::U64(v) => Ok(AttributeValue::U64(v)),
+            SpooledValue::Bool(v) => Ok(AttributeValue::Bool(v)),
+            SpooledValue::String(v) => Ok(AttributeValue::String(v)),
+            SpooledValue::ListU64(v) => Ok(AttributeValue::ListU64(v)),
+            _ => anyhow::bail!("invalid attribute value"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::{
+            Metric, RawMetricId,
+            duplicate::{DuplicateCriteria, DuplicateReaction},
+            registry::MetricRegistry,
+        },
+        resources::{Resource, ResourceConsumer},
+        units::Unit,
+    };
+
+    use std::{path::Path, time::Duration};
+
+    use crate::pipeline::naming::OutputName;
+
+    use super::{Spool, SpoolConfig};
+
+    fn open(dir: &Path, max_size: u64) -> Spool {
+        let config = SpoolConfig {
+            directory: dir.to_owned(),
+            max_size,
+            retry_interval: Duration::from_secs(1),
+        };
+        Spool::open(&config, &OutputName::from_str("test", "out")).unwrap()
+    }
+
+    fn register(metrics: &mut MetricRegistry, name: &str) -> RawMetricId {
+        let metric = Metric {
+            name: name.to_owned(),
+            description: String::new(),
+            value_type: crate::measurement::WrappedMeasurementType::U64,
+            unit: Unit::Unity.into(),
+        };
+        metrics
+            .register(metric, DuplicateCriteria::Strict, DuplicateReaction::Error)
+            .unwrap()
+    }
+
+    fn buffer(metric: RawMetricId, value: u64) -> MeasurementBuffer {
+        let point = MeasurementPoint::new_untyped(
+            Timestamp::from_unix_timestamp(1700000000, 42),
+            metric,
+            Resource::CpuPackage { id: 1 },
+            ResourceConsumer::Process { pid: 123 },
+            WrappedMeasurementValue::U64(value),
+        )
+        .with_attr("domain", "package")
+        .with_attr("n", 12_u64);
+        MeasurementBuffer::from(vec![point])
+    }
+
+    #[test]
+    fn queue_order_and_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut metrics = MetricRegistry::new();
+        let m = register(&mut metrics, "energy");
+
+        let mut spool = open(dir.path(), u64::MAX);
+        assert!(spool.is_empty());
+        for i in 0..3 {
+            spool.push(&buffer(m, i), &metrics).unwrap();
+        }
+        assert_eq!(spool.len(), 3);
+
+        let (_, first) = spool.front(&metrics).unwrap().unwrap();
+        let point = first.iter().next().unwrap();
+        assert_eq!(point.value, WrappedMeasurementValue::U64(0));
+        assert_eq!(point.resource, Resource::CpuPackage { id: 1 });
+        assert_eq!(point.consumer, ResourceConsumer::Process { pid: 123 });
+        assert_eq!(point.timestamp, Timestamp::from_unix_timestamp(1700000000, 42));
+        assert_eq!(point.attributes_len(), 2);
+        spool.pop_front().unwrap();
+        drop(spool);
+
+        // Reopen the spool in a new "process", where the metric has another id.
+        let mut metrics = MetricRegistry::new();
+        register(&mut metrics, "other");
+        let m = register(&mut metrics, "energy");
+        let mut spool = open(dir.path(), u64::MAX);
+        assert_eq!(spool.len(), 2);
+        for expected in 1..3 {
+            let (_, buf) = spool.front(&metrics).unwrap().unwrap();
+            let point = buf.iter().next().unwrap();
+            assert_eq!(point.metric, m);
+            assert_eq!(point.value, WrappedMeasurementValue::U64(expected));
+            spool.pop_front().unwrap();
+        }
+        assert!(spool.front(&metrics).unwrap().is_none());
+    }
+
+    #[test]
+    fn size_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut metrics = MetricRegistry::new();
+        let m = register(&mut metrics, "energy");
+
+        let mut spool = open(dir.path(), u64::MAX);
+        spool.push(&buffer(m, 0), &metrics).unwrap();
+        let file_size = spool.total_size;
+
+        // room for two buffers
+        let mut spool = open(dir.path(), 2 * file_size);
+        spool.push(&buffer(m, 1), &metrics).unwrap();
+        spool.push(&buffer(m, 2), &metrics).unwrap();
+        assert_eq!(spool.len(), 2);
+        let (_, buf) = spool.front(&metrics).unwrap().unwrap();
+        assert_eq!(buf.iter().next().unwrap().value, WrappedMeasurementValue::U64(1));
+    }
+
+    #[test]
+    fn size_cap_keeps_pinned() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut metrics = MetricRegistry::new();
+        let m = register(&mut metrics, "energy");
+
+        let mut spool = open(dir.path(), u64::MAX);
+        spool.push(&buffer(m, 0), &metrics).unwrap();
+        let file_size = spool.total_size;
+
+        // room for two buffers, the oldest one is being written
+        let mut spool = open(dir.path(), 2 * file_size);
+        let (writing, _) = spool.front(&metrics).unwrap().unwrap();
+        spool.pin(Some(writing));
+        for i in 1..5 {
+            spool.push(&buffer(m, i), &metrics).unwrap();
+        }
+        assert_eq!(spool.len(), 2);
+
+        // the write succeeds: exactly the pinned buffer is removed
+        spool.remove(writing).unwrap();
+        spool.pin(None);
+        assert_eq!(spool.len(), 1);
+        let (_, buf) = spool.front(&metrics).unwrap().unwrap();
+        assert_eq!(buf.iter().next().unwrap().value, WrappedMeasurementValue::U64(4));
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
 use num_enum::{FromPrimitive, IntoPrimitive};
@@ -12,14 +13,17 @@ use tokio_util::sync::CancellationToken;
 use crate::measurement::MeasurementBuffer;
 use crate::metrics::online::{MetricReader, MetricSender};
 use crate::pipeline::control::matching::SourceMatcher;
+use crate::pipeline::control::request::ElementStatus;
 use crate::pipeline::elements::source::builder::SourcePace;
 use crate::pipeline::elements::source::run::{run_autonomous, run_managed};
 use crate::pipeline::error::PipelineError;
+use crate::pipeline::health::PipelineHealth;
 use crate::pipeline::matching::{ElementNamePattern, SourceNamePattern};
 use crate::pipeline::naming::{ElementKind, ElementName};
 use crate::pipeline::naming::{SourceName, namespace::Namespace2};
 
 use super::builder;
+use super::trigger::group::TriggerGroups;
 use super::trigger::{Trigger, TriggerConstraints, TriggerSpec};
 
 /// A control message for sources.
@@ -40,6 +44,10 @@ pub enum ControlMessage {
     /// and processes it. Sources must be configured to accept manual trigger, otherwise this message
     /// will do nothing.
     TriggerManually(TriggerMessage),
+    /// Changes the period of a trigger group.
+    ///
+    /// Every source of the group is affected.
+    SetGroupPeriod(GroupPeriodMessage),
 }
 
 #[derive(Debug)]
@@ -66,6 +74,14 @@ pub struct TriggerMessage {
     pub matcher: SourceMatcher,
 }
 
+#[derive(Debug)]
+pub struct GroupPeriodMessage {
+    /// Name of the trigger group.
+    pub group: String,
+    /// New period of the group.
+    pub period: Duration,
+}
+
 /// A command to send to a managed [`Source`].
 #[derive(Debug)]
 pub enum ConfigureCommand {
@@ -74,6 +90,11 @@ pub enum ConfigureCommand {
     Stop,
     Flush,
     SetTrigger(TriggerSpec),
+    /// Changes the intervals of the sources that are triggered at regular intervals, and only of them.
+    SetInterval {
+        poll_interval: Duration,
+        flush_interval: Option<Duration>,
+    },
 }
 
 #[derive(Debug)]
@@ -116,6 +137,9 @@ struct TaskManager {
     /// Constraints to apply to the new source triggers.
     trigger_constraints: TriggerConstraints,
 
+    /// Trigger groups shared by the sources.
+    trigger_groups: TriggerGroups,
+
     /// Sends measurements from Sources.
     ///
     /// This is used for creating new sources.
@@ -127,6 +151,9 @@ struct TaskManager {
 
     /// Handle of the "priority" async runtime. Used for creating new sources.
     rt_priority: runtime::Handle,
+
+    /// Statistics about the pipeline, to which the new sources are added.
+    health: Arc<PipelineHealth>,
 }
 
 impl SourceControl {
@@ -137,16 +164,19 @@ impl SourceControl {
         rt_normal: runtime::Handle,
         rt_priority: runtime::Handle,
         metrics: (MetricReader, MetricSender),
+        health: Arc<PipelineHealth>,
     ) -> Self {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
                 controllers: Vec::new(),
+                trigger_groups: TriggerGroups::new(rt_priority.clone(), shutdown_token.clone()),
                 shutdown_token,
                 trigger_constraints,
                 in_tx,
                 rt_normal,
                 rt_priority,
+                health,
             },
             metrics,
         }
@@ -212,6 +242,7 @@ impl SourceControl {
             ControlMessage::CreateOne(msg) => self.create_sources(vec![(msg.name, msg.builder)]).await?,
             ControlMessage::CreateMany(msg) => self.create_sources(msg.builders).await?,
             ControlMessage::TriggerManually(msg) => self.tasks.trigger_manually(msg),
+            ControlMessage::SetGroupPeriod(msg) => self.tasks.trigger_groups.set_period(&msg.group, msg.period)?,
         }
         Ok(())
     }
@@ -239,6 +270,18 @@ impl SourceControl {
         }
     }
 
+    pub fn element_status(&self, buf: &mut Vec<ElementStatus>, pat: &ElementNamePattern) {
+        if pat.kind.is_none() || pat.kind == Some(ElementKind::Source) {
+            buf.extend(
+                self.tasks
+                    .controllers
+                    .iter()
+                    .filter(|(name, _)| pat.matches(name))
+                    .map(|(name, controller)| controller.status(name.to_owned().into())),
+            )
+        }
+    }
+
     pub async fn shutdown<F>(mut self, mut handle_task_result: F)
     where
         F: FnMut(Result<Result<(), PipelineError>, tokio::task::JoinError>),
@@ -334,22 +377,27 @@ impl TaskManager {
 
                 // Create the source trigger, which may be interruptible by a config change (depending on the TriggerSpec).
                 // Some triggers need to be built with an executor available, therefore we use `Handle::enter()`.
+                let spec = source.trigger_spec.clone();
                 let trigger = {
                     match &dedicated_rt {
                         Some(rt) => {
                             let _guard = rt.enter();
-                            Trigger::new(source.trigger_spec).context("error in Trigger::new")?
+                            Trigger::new(source.trigger_spec, &mut self.trigger_groups)
+                                .context("error in Trigger::new")?
                         }
                         None => {
                             let _guard = runtime.enter();
-                            Trigger::new(source.trigger_spec).context("error in Trigger::new")?
+                            Trigger::new(source.trigger_spec, &mut self.trigger_groups)
+                                .context("error in Trigger::new")?
                         }
                     }
                 };
                 log::trace!("new trigger created from the spec: {trigger:?}");
 
                 // Create a controller to control the async task.
-                let (controller, config) = super::task_controller::new_managed(trigger, source.initial_state);
+                let stats = self.health.register_source(name.clone());
+                let (controller, config) =
+                    super::task_controller::new_managed(trigger, spec, source.initial_state, stats);
                 self.controllers.push((name.clone(), controller));
                 log::trace!("new controller initialized");
 
@@ -418,12 +466,44 @@ impl TaskManager {
                 spec.constrain(&self.trigger_constraints);
                 Reconfiguration::SetTrigger(spec)
             }
+            ConfigureCommand::SetInterval {
+                poll_interval,
+                flush_interval,
+            } => return self.set_interval(&msg.matcher, poll_interval, flush_interval),
         };
 
         for (name, source_controller) in &mut self.controllers {
             if msg.matcher.matches(name) {
-                source_controller.reconfigure(&command);
+                source_controller.reconfigure(&command, &mut self.trigger_groups);
+            }
+        }
+    }
+
+    /// Changes the intervals of the matching sources, but only if they are triggered at regular intervals.
+    ///
+    /// The sources keep their trigger group: since all the sources of a group share the same clock,
+    /// the period of the group is changed.
+    fn set_interval(&mut self, matcher: &SourceMatcher, poll_interval: Duration, flush_interval: Option<Duration>) {
+        let mut retargeted_groups = Vec::new();
+        for (name, source_controller) in &mut self.controllers {
+            if !matcher.matches(name) {
+                continue;
+            }
+            let spec = source_controller.trigger_spec();
+            let Some(mut spec) = spec.and_then(|s| s.with_interval(poll_interval, flush_interval)) else {
+                log::debug!("{name} is not triggered at regular intervals, its trigger is left unchanged");
+                continue;
+            };
+            if let Some(group) = spec.group()
+                && !retargeted_groups.iter().any(|g| g == group)
+            {
+                if let Err(e) = self.trigger_groups.set_period(group, poll_interval) {
+                    log::error!("Could not change the period of the trigger group of {name}: {e:#}");
+                }
+                retargeted_groups.push(group.to_owned());
             }
+            spec.constrain(&self.trigger_constraints);
+            source_controller.reconfigure(&Reconfiguration::SetTrigger(spec), &mut self.trigger_groups);
         }
     }
 
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::time::Instant;
 
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::TrySendError;
@@ -78,6 +79,8 @@ pub(crate) async fn run_managed(
         .take_new_trigger()
         .expect("the Trigger must be set before starting the source");
     log::trace!("{source_name} got initial config");
+    let stats = &config.stats;
+    stats.set_poll_interval(init_trigger.poll_interval);
 
     // Store measurements in this buffer, and replace it every `flush_rounds` rounds.
     // For now, we don't know how many measurements the source will produce, so we allocate 1 per round.
@@ -134,8 +137,15 @@ pub(crate) async fn run_managed(
         match reason {
             TriggerReason::Triggered => {
                 // poll the source
-                let timestamp = Timestamp::now();
-                match source.poll(&mut buffer.as_accumulator(), timestamp) {
+                let prev_interval = trigger.poll_interval;
+                let timestamp = trigger.poll_timestamp();
+                if trigger.poll_interval != prev_interval {
+                    stats.set_poll_interval(trigger.poll_interval);
+                }
+                let t0 = Instant::now();
+                let res = source.poll(&mut buffer.as_accumulator(), timestamp);
+                stats.record_poll(timestamp, t0.elapsed());
+                match res {
                     Ok(()) => (),
                     Err(PollError::NormalStop) => {
                         log::info!("Source {source_name} stopped itself.");
@@ -177,6 +187,7 @@ pub(crate) async fn run_managed(
                 let new_flush_rounds = new_trigger.params.flush_rounds;
                 adapt_buffer_after_trigger_change(&mut buffer, prev_flush_rounds, new_flush_rounds);
                 // use the new trigger
+                stats.set_poll_interval(new_trigger.poll_interval);
                 trigger = new_trigger;
             }
             match new_state.into() {
@@ -6,8 +6,13 @@ use std::sync::{
 use tokio::sync::Notify;
 use tokio_util::sync::CancellationToken;
 
+use crate::pipeline::control::request::{ElementState, ElementStatus};
+use crate::pipeline::health::SourceStats;
+use crate::pipeline::naming::ElementName;
+
 use super::control::{Reconfiguration, TaskState};
-use super::trigger::{ManualTrigger, Trigger};
+use super::trigger::group::TriggerGroups;
+use super::trigger::{ManualTrigger, Trigger, TriggerSpec};
 
 /// A controller for a single source.
 pub enum SingleSourceController {
@@ -29,19 +34,27 @@ pub struct SharedSourceConfig {
     pub change_notifier: Notify,
     pub atomic_state: AtomicU8,
     pub new_trigger: Mutex<Option<Trigger>>,
+    /// Specification of the current trigger, to derive new triggers from it.
+    pub trigger_spec: Mutex<TriggerSpec>,
     pub manual_trigger: Option<ManualTrigger>,
+    /// Statistics about the source, updated by the source task.
+    pub(crate) stats: Arc<SourceStats>,
 }
 
-pub fn new_managed(
+pub(crate) fn new_managed(
     initial_trigger: Trigger,
+    initial_spec: TriggerSpec,
     initial_state: TaskState,
+    stats: Arc<SourceStats>,
 ) -> (SingleSourceController, Arc<SharedSourceConfig>) {
     let manual_trigger = initial_trigger.manual_trigger();
     let config = Arc::new(SharedSourceConfig {
         change_notifier: Notify::new(),
         atomic_state: AtomicU8::new(initial_state as u8),
         new_trigger: Mutex::new(Some(initial_trigger)),
+        trigger_spec: Mutex::new(initial_spec),
         manual_trigger,
+        stats,
     });
     (SingleSourceController::Managed(config.clone()), config)
 }
@@ -57,7 +70,7 @@ impl SharedSourceConfig {
 }
 
 impl SingleSourceController {
-    pub fn reconfigure(&mut self, command: &Reconfiguration) {
+    pub fn reconfigure(&mut self, command: &Reconfiguration, groups: &mut TriggerGroups) {
         match self {
             SingleSourceController::Managed(shared) => {
                 match &command {
@@ -66,8 +79,9 @@ impl SingleSourceController {
                         shared.atomic_state.store(*new_state as u8, Ordering::Relaxed);
                     }
                     Reconfiguration::SetTrigger(new_spec) => {
-                        let trigger = Trigger::new(new_spec.to_owned()).unwrap();
+                        let trigger = Trigger::new(new_spec.to_owned(), groups).unwrap();
                         *shared.new_trigger.lock().unwrap() = Some(trigger);
+                        *shared.trigger_spec.lock().unwrap() = new_spec.to_owned();
                     }
                 }
                 log::trace!("reconfiguring source with {:p}", *shared);
@@ -84,6 +98,42 @@ impl SingleSourceController {
         }
     }
 
+    /// Returns the specification of the current trigger, if the source is managed.
+    pub fn trigger_spec(&self) -> Option<TriggerSpec> {
+        match self {
+            SingleSourceController::Managed(shared) => Some(shared.trigger_spec.lock().unwrap().clone()),
+            SingleSourceController::Autonomous(_) => None,
+        }
+    }
+
+    pub fn status(&self, name: ElementName) -> ElementStatus {
+        match self {
+            SingleSourceController::Managed(shared) => {
+                let state = match TaskState::from(shared.atomic_state.load(Ordering::Relaxed)) {
+                    TaskState::Run | TaskState::RunFlush => ElementState::Running,
+                    TaskState::Pause => ElementState::Paused,
+                    TaskState::Stop => ElementState::Stopped,
+                };
+                ElementStatus {
+                    name,
+                    state,
+                    poll_interval: shared.stats.poll_interval(),
+                    last_poll: shared.stats.last_poll(),
+                }
+            }
+            SingleSourceController::Autonomous(shutdown_token) => ElementStatus {
+                name,
+                state: if shutdown_token.is_cancelled() {
+                    ElementState::Stopped
+                } else {
+                    ElementState::Running
+                },
+                poll_interval: None,
+                last_poll: None,
+            },
+        }
+    }
+
     pub fn trigger_now(&mut self) {
         match self {
             SingleSourceController::Managed(shared) => {
@@ -5,7 +5,11 @@ use std::time::Duration;
 use std::{fmt, time};
 use std::{future::Future, pin::Pin};
 
-use tokio::sync::Notify;
+use tokio::sync::{Notify, watch};
+
+use crate::measurement::Timestamp;
+
+use group::{Tick, TriggerGroups};
 
 /// A boxed future, from the `futures` crate.
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
@@ -23,12 +27,16 @@ pub struct TriggerSpec {
     allow_manual_trigger: bool,
     use_realtime_priority: bool,
     loop_params: TriggerLoopParams,
+    /// The trigger group that the source joins, if any.
+    group: Option<String>,
 }
 
 /// Controls when the [`Source`](super::Source) is polled for measurements.
 #[derive(Debug)]
 pub(crate) struct Trigger {
     pub params: TriggerLoopParams,
+    /// Interval between two polls, if the trigger is based on a time interval.
+    pub poll_interval: Option<time::Duration>,
     inner: TriggerImpl,
 }
 
@@ -78,6 +86,7 @@ pub struct TriggerConstraints {
 ///
 /// See [`builder::time_interval`].
 pub mod builder;
+pub(crate) mod group;
 
 pub(crate) mod private_impl {
     // TODO FIXME: this actually implements PartialEq for TriggerSpec publicly
@@ -86,6 +95,9 @@ pub(crate) mod private_impl {
 
     impl PartialEq for TriggerSpec {
         fn eq(&self, other: &Self) -> bool {
+            if self.group != other.group {
+                return false;
+            }
             match (&self.mechanism, &other.mechanism) {
                 (
                     super::TriggerMechanismSpec::TimeInterval(_, duration_a),
@@ -153,6 +165,43 @@ impl TriggerSpec {
     pub(crate) fn requests_realtime_priority(&self) -> bool {
         self.use_realtime_priority
     }
+
+    /// Returns the trigger group that the source joins, if any.
+    pub(crate) fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Returns a copy of this specification with new poll and flush intervals,
+    /// or `None` if the trigger is not based on a time interval.
+    ///
+    /// The other settings, including the trigger group, are kept.
+    /// Like in [`builder::TimeTriggerBuilder`], a missing `flush_interval` means that
+    /// the measurements are flushed after each poll.
+    pub(crate) fn with_interval(
+        &self,
+        poll_interval: Duration,
+        flush_interval: Option<Duration>,
+    ) -> Option<TriggerSpec> {
+        let TriggerMechanismSpec::TimeInterval(start, _) = self.mechanism else {
+            return None;
+        };
+        if poll_interval.is_zero() {
+            return None;
+        }
+        let flush_rounds = match flush_interval {
+            Some(flush_interval) => ((flush_interval.as_nanos() / poll_interval.as_nanos()) as usize).max(1),
+            None => 1,
+        };
+        Some(TriggerSpec {
+            mechanism: TriggerMechanismSpec::TimeInterval(start, poll_interval),
+            use_realtime_priority: self.use_realtime_priority || poll_interval <= Duration::from_millis(3),
+            loop_params: TriggerLoopParams {
+                flush_rounds,
+                update_rounds: self.loop_params.update_rounds,
+            },
+            ..self.clone()
+        })
+    }
 }
 
 impl Default for TriggerConstraints {
@@ -192,10 +241,17 @@ impl From<bool> for Interruptible {
 }
 
 impl Trigger {
-    pub fn new(spec: TriggerSpec) -> Result<Self, std::io::Error> {
+    pub fn new(spec: TriggerSpec, groups: &mut TriggerGroups) -> Result<Self, std::io::Error> {
         let interruptible = Interruptible::from(spec.interruptible);
         let manual_only = matches!(spec.mechanism, TriggerMechanismSpec::ManualOnly);
-        let mechanism = TriggerMechanism::try_from(spec.mechanism)?;
+        let poll_interval = match spec.mechanism {
+            TriggerMechanismSpec::TimeInterval(_, interval) => Some(interval),
+            _ => None,
+        };
+        let mechanism = match (spec.group, poll_interval) {
+            (Some(name), Some(interval)) => TriggerMechanism::Group(groups.subscribe(&name, interval), None),
+            _ => TriggerMechanism::try_from(spec.mechanism)?,
+        };
         let inner = if spec.allow_manual_trigger && !manual_only {
             let manual = TriggerMechanism::Manual(Arc::new(Notify::new()));
             TriggerImpl::Double(mechanism, manual, interruptible)
@@ -204,6 +260,7 @@ impl Trigger {
         };
         Ok(Self {
             params: spec.loop_params,
+            poll_interval,
             inner,
         })
     }
@@ -216,6 +273,7 @@ impl Trigger {
                 flush_rounds: 1,
                 update_rounds: 1,
             },
+            poll_interval: None,
             inner,
         }
     }
@@ -229,6 +287,26 @@ impl Trigger {
         }
     }
 
+    /// Returns the timestamp of the current poll.
+    ///
+    /// The sources of a trigger group receive the timestamp of the group tick, which is the same for
+    /// every source of the group. Otherwise, the timestamp is the current time.
+    /// If the period of the group has changed, `poll_interval` is updated.
+    pub fn poll_timestamp(&mut self) -> Timestamp {
+        let tick = match &mut self.inner {
+            TriggerImpl::Single(TriggerMechanism::Group(_, tick), _)
+            | TriggerImpl::Double(TriggerMechanism::Group(_, tick), _, _) => tick.take(),
+            _ => None,
+        };
+        match tick {
+            Some(tick) => {
+                self.poll_interval = Some(tick.period);
+                tick.timestamp
+            }
+            None => Timestamp::now(),
+        }
+    }
+
     /// Waits for the next tick of the trigger, or for an interruption (if enabled).
     pub async fn next(&mut self, interrupt: &Notify) -> anyhow::Result<TriggerReason> {
         match &mut self.inner {
@@ -310,6 +388,11 @@ enum TriggerMechanism {
     ///
     /// The source is polled each time `f().await` returns.
     Future(fn() -> BoxFuture<'static, SourceTriggerOutput>),
+
+    /// A trigger based on the clock of a [trigger group](group), shared by multiple sources.
+    ///
+    /// The source is polled on each tick of the group, which is stored until the poll.
+    Group(watch::Receiver<Tick>, Option<Tick>),
 }
 
 impl TryFrom<TriggerMechanismSpec> for TriggerMechanism {
@@ -354,6 +437,15 @@ impl TriggerMechanism {
             }
             TriggerMechanism::Future(f) => f().await,
             TriggerMechanism::Manual(notify) => Ok(notify.notified().await),
+            TriggerMechanism::Group(rx, tick) => {
+                if rx.changed().await.is_err() {
+                    // The group clock is gone, which only happens when the pipeline shuts down.
+                    // Only an interruption can wake the trigger up now.
+                    std::future::pending::<()>().await;
+                }
+                *tick = Some(*rx.borrow_and_update());
+                Ok(())
+            }
         }
     }
 }
@@ -366,6 +458,7 @@ impl fmt::Debug for TriggerMechanism {
             Self::Sleep(_, _) => f.write_str("TriggerMechanism::Sleep"),
             Self::Future(ptr) => write!(f, "TriggerMechanism::Future({ptr:?})"),
             Self::Manual(notify) => write!(f, "TriggerMechanism::Manual({:p})", *notify),
+            Self::Group(_, _) => f.write_str("TriggerMechanism::Group"),
         }
     }
 }
@@ -478,4 +571,27 @@ mod tests {
         assert_eq!(trigger.loop_params.flush_rounds, 5);
         assert_eq!(trigger.loop_params.update_rounds, 1);
     }
+
+    #[test]
+    fn with_interval_keeps_the_group() {
+        let trigger = builder::time_interval(Duration::from_secs(1))
+            .flush_interval(Duration::from_secs(5))
+            .group("node")
+            .build()
+            .unwrap();
+        let new = trigger
+            .with_interval(Duration::from_millis(500), Some(Duration::from_secs(2)))
+            .unwrap();
+        assert!(matches!(new.mechanism, TriggerMechanismSpec::TimeInterval(_, d) if d == Duration::from_millis(500)));
+        assert_eq!(new.group(), Some("node"));
+        assert!(new.interruptible);
+        assert_eq!(new.loop_params.flush_rounds, 4);
+
+        let new = trigger.with_interval(Duration::from_secs(2), None).unwrap();
+        assert_eq!(new.loop_params.flush_rounds, 1);
+        assert!(trigger.with_interval(Duration::ZERO, None).is_none());
+
+        let manual = builder::manual().build().unwrap();
+        assert!(manual.with_interval(Duration::from_secs(1), None).is_none());
+    }
 }
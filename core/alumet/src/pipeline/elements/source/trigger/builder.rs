@@ -39,6 +39,7 @@ struct TriggerSpecBuilder {
     interruptible: bool,
     manual_allowed: bool,
     realtime_sched_priority: bool,
+    group: Option<String>,
 }
 
 /// Builder for a trigger that wakes up at regular intervals.
@@ -75,6 +76,7 @@ impl TriggerSpecBuilder {
             interruptible: false,
             manual_allowed: false,
             realtime_sched_priority: false,
+            group: None,
         }
     }
 
@@ -85,6 +87,7 @@ impl TriggerSpecBuilder {
             allow_manual_trigger: self.manual_allowed,
             use_realtime_priority: self.realtime_sched_priority,
             loop_params: self.loop_params.clone(),
+            group: self.group.clone(),
         }
     }
 
@@ -190,12 +193,39 @@ impl TimeTriggerBuilder {
         self
     }
 
+    /// Polls the source from the clock of a trigger group, shared with other sources.
+    ///
+    /// All the sources of a group are polled at the same time, on the wall-clock boundaries that are
+    /// multiples of the group period, and they receive the same timestamp.
+    /// The first source that joins the group sets its period: the `poll_interval` of the next
+    /// sources is ignored. The period can be changed later with the control API
+    /// (see [`request::set_group_period`](crate::pipeline::control::request::set_group_period)).
+    ///
+    /// The start time set by [`starting_at`](Self::starting_at) does not apply to grouped sources.
+    ///
+    /// Because the period of the group can change at any time, a grouped source checks its commands at
+    /// every poll and its trigger can be interrupted: [`build`](Self::build) forces `update_rounds = 1`
+    /// and makes the trigger interruptible, regardless of [`update_rounds`](Self::update_rounds) and
+    /// [`update_interval`](Self::update_interval).
+    pub fn group(&mut self, name: impl Into<String>) -> &mut Self {
+        self.0.group = Some(name.into());
+        self
+    }
+
     /// Builds the trigger specification.
     pub fn build(&mut self) -> Result<TriggerSpec, Error> {
         let poll_interval = *self.poll_interval();
         if poll_interval.is_zero() {
             return Err(Error::InvalidConfig(String::from("poll_interval must be non-zero")));
         }
+        if self.0.group.as_ref().is_some_and(|name| name.is_empty()) {
+            return Err(Error::InvalidConfig(String::from("the group name must not be empty")));
+        }
+        if self.0.group.is_some() {
+            // The period of the group can change at any time, don't rely on it to apply the source commands.
+            self.0.loop_params.update_rounds = 1;
+            self.0.interruptible = true;
+        }
 
         // automatically enable `realtime_priority` in some cases
         // TODO make this configurable
@@ -0,0 +1,221 @@
+//! Trigger groups: polls several sources from a single clock.
+//!
+//! Each group runs a clock that ticks on the boundaries of the wall clock that are
+//! multiples of the group period (e.g. every full second for a period of 1s).
+//! Every source of the group is polled on each tick and receives the timestamp of
+//! the boundary, which makes the measurements of different sources directly comparable.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use tokio::runtime;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::measurement::Timestamp;
+
+/// A tick of a group clock.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Tick {
+    /// The wall-clock boundary on which the tick occurred.
+    pub timestamp: Timestamp,
+    /// The period of the group at the time of the tick.
+    pub period: Duration,
+}
+
+/// The trigger groups of a pipeline.
+pub(crate) struct TriggerGroups {
+    groups: HashMap<String, GroupHandle>,
+    /// The runtime on which the group clocks are spawned.
+    rt: runtime::Handle,
+    /// Cancelled when the pipeline shuts down, stops the group clocks.
+    shutdown_token: CancellationToken,
+}
+
+struct GroupHandle {
+    period_tx: watch::Sender<Duration>,
+    tick_tx: Arc<watch::Sender<Tick>>,
+}
+
+impl TriggerGroups {
+    pub fn new(rt: runtime::Handle, shutdown_token: CancellationToken) -> Self {
+        Self {
+            groups: HashMap::new(),
+            rt,
+            shutdown_token,
+        }
+    }
+
+    /// Subscribes to the ticks of a group.
+    ///
+    /// If the group does not exist yet, it is created with the given period.
+    /// Otherwise, the group keeps its current period.
+    pub fn subscribe(&mut self, name: &str, period: Duration) -> watch::Receiver<Tick> {
+        if let Some(group) = self.groups.get(name) {
+            let group_period = *group.period_tx.borrow();
+            if group_period != period {
+                log::warn!(
+                    "Trigger group '{name}' has a period of {group_period:?}, the requested period of {period:?} is ignored."
+                );
+            }
+            return group.tick_tx.subscribe();
+        }
+
+        log::debug!("Creating trigger group '{name}' with a period of {period:?}");
+        let (period_tx, period_rx) = watch::channel(period);
+        let (tick_tx, _) = watch::channel(Tick {
+            timestamp: Timestamp::now(),
+            period,
+        });
+        let tick_tx = Arc::new(tick_tx);
+        let clock = run_clock(tick_tx.clone(), period_rx, self.shutdown_token.clone());
+        self.rt.spawn(clock);
+
+        let rx = tick_tx.subscribe();
+        self.groups.insert(name.to_owned(), GroupHandle { period_tx, tick_tx });
+        rx
+    }
+
+    /// Changes the period of an existing group.
+    ///
+    /// The new period applies from the next wall-clock boundary.
+    pub fn set_period(&mut self, name: &str, period: Duration) -> anyhow::Result<()> {
+        if period.is_zero() {
+            return Err(anyhow!(
+                "invalid period for trigger group '{name}': it must be non-zero"
+            ));
+        }
+        let group = self
+            .groups
+            .get(name)
+            .ok_or_else(|| anyhow!("there is no trigger group named '{name}'"))?;
+        log::debug!("Setting the period of trigger group '{name}' to {period:?}");
+        group.period_tx.send_replace(period);
+        Ok(())
+    }
+}
+
+/// Runs the clock of a group until the pipeline shuts down.
+async fn run_clock(
+    tick_tx: Arc<watch::Sender<Tick>>,
+    mut period_rx: watch::Receiver<Duration>,
+    shutdown_token: CancellationToken,
+) {
+    loop {
+        let period = *period_rx.borrow_and_update();
+        let (delay, boundary) = next_boundary(SystemTime::now(), period);
+        tokio::select! {
+            biased;
+
+            _ = shutdown_token.cancelled() => break,
+            res = period_rx.changed() => {
+                if res.is_err() {
+                    break; // the group has been dropped
+                }
+                // the period has changed: compute the next boundary again
+            }
+            res = sleep(delay) => {
+                if let Err(e) = res {
+                    log::error!("Error in the clock of a trigger group: {e}");
+                    break;
+                }
+                tick_tx.send_replace(Tick {
+                    timestamp: Timestamp::from(boundary),
+                    period,
+                });
+            }
+        }
+    }
+}
+
+/// Sleeps for the given duration, as precisely as possible.
+async fn sleep(delay: Duration) -> Result<(), std::io::Error> {
+    #[cfg(target_os = "linux")]
+    {
+        tokio_timerfd::Delay::new(std::time::Instant::now() + delay)?.await
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+/// Returns the next wall-clock boundary after `now` that is a multiple of `period`,
+/// and the time remaining until that boundary.
+fn next_boundary(now: SystemTime, period: Duration) -> (Duration, SystemTime) {
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let period_nanos = period.as_nanos();
+    let next = (since_epoch / period_nanos + 1) * period_nanos;
+    let boundary = UNIX_EPOCH + Duration::from_nanos(next as u64);
+    let delay = boundary.duration_since(now).unwrap_or_default();
+    (delay, boundary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::{TriggerGroups, next_boundary};
+
+    #[test]
+    fn boundaries() {
+        let period = Duration::from_secs(1);
+        let now = UNIX_EPOCH + Duration::from_millis(10_250);
+        assert_eq!(
+            next_boundary(now, period),
+            (Duration::from_millis(750), UNIX_EPOCH + Duration::from_secs(11))
+        );
+
+        // exactly on a boundary: wait for the next one
+        let now = UNIX_EPOCH + Duration::from_secs(10);
+        assert_eq!(
+            next_boundary(now, period),
+            (Duration::from_secs(1), UNIX_EPOCH + Duration::from_secs(11))
+        );
+
+        let period = Duration::from_millis(15);
+        let now = UNIX_EPOCH + Duration::from_millis(100);
+        assert_eq!(
+            next_boundary(now, period),
+            (Duration::from_millis(5), UNIX_EPOCH + Duration::from_millis(105))
+        );
+    }
+
+    #[tokio::test]
+    async fn shared_ticks() {
+        let token = CancellationToken::new();
+        let mut groups = TriggerGroups::new(tokio::runtime::Handle::current(), token.clone());
+        let period = Duration::from_millis(20);
+        let mut rx1 = groups.subscribe("g", period);
+        let mut rx2 = groups.subscribe("g", Duration::from_secs(1)); // different period: ignored
+
+        rx1.changed().await.unwrap();
+        rx2.changed().await.unwrap();
+        let (tick1, tick2) = (*rx1.borrow_and_update(), *rx2.borrow_and_update());
+        assert_eq!(tick1.timestamp, tick2.timestamp);
+        assert_eq!(tick1.period, period);
+        let (_, nanos) = tick1.timestamp.to_unix_timestamp();
+        assert_eq!(nanos % 20_000_000, 0, "the tick should be aligned on the period");
+
+        groups.set_period("g", Duration::from_millis(30)).unwrap();
+        let tick = loop {
+            rx1.changed().await.unwrap();
+            let tick = *rx1.borrow_and_update();
+            if tick.period == Duration::from_millis(30) {
+                break tick;
+            }
+        };
+        let (_, nanos) = tick.timestamp.to_unix_timestamp();
+        assert_eq!(nanos % 10_000_000, 0);
+
+        groups.set_period("unknown", period).unwrap_err();
+        groups.set_period("g", Duration::ZERO).unwrap_err();
+        token.cancel();
+    }
+}
@@ -3,6 +3,7 @@
 pub mod builder;
 pub(crate) mod control;
 pub mod error;
+pub mod graph;
 pub mod interface;
 pub mod run;
 
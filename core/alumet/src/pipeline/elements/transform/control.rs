@@ -1,6 +1,6 @@
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::Context;
 use tokio::task::{JoinError, JoinSet};
@@ -12,27 +12,29 @@ use tokio::{
 use crate::measurement::MeasurementBuffer;
 use crate::metrics::online::MetricReader;
 use crate::pipeline::control::matching::TransformMatcher;
+use crate::pipeline::control::request::{ElementState, ElementStatus};
 use crate::pipeline::error::PipelineError;
+use crate::pipeline::health::PipelineHealth;
 use crate::pipeline::matching::ElementNamePattern;
 use crate::pipeline::naming::{ElementKind, ElementName, TransformName};
 
 use super::Transform;
 use super::builder::{BuildContext, TransformBuilder};
-use super::run::run_all_in_order;
+use super::graph::{TransformGraph, TransformUpstream};
+use super::run::{Downstream, Router, TransformBatch, run_sources_dispatch, run_transform};
 
 /// Controls the transforms of a measurement pipeline.
 ///
-/// There can be a maximum of 64 transforms for the moment.
+/// The transforms are organized as a [`TransformGraph`], each transform runs on its own thread.
 pub(crate) struct TransformControl {
     tasks: TaskManager,
 }
 
 struct TaskManager {
-    // Even though there is only one task, we don't use its JoinHandle directly,
-    // because awaiting it consumes the task.
+    // We don't use the JoinHandles of the threads directly, because awaiting a handle consumes the task.
     spawned_tasks: JoinSet<Result<(), PipelineError>>,
-    active_bitset: Arc<AtomicU64>,
-    names_by_bitset_position: Vec<TransformName>,
+    /// The transforms, in topological order, with a flag that indicates whether they are enabled.
+    transforms: Vec<(TransformName, Arc<AtomicBool>)>,
 }
 
 impl TransformControl {
@@ -40,18 +42,22 @@ impl TransformControl {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
-                active_bitset: Arc::new(AtomicU64::new(0)),
-                names_by_bitset_position: Vec::new(),
+                transforms: Vec::new(),
             },
         }
     }
 
+    /// Builds the transforms and starts the graph.
+    ///
+    /// `transforms` must contain one builder per node of the `graph`, in the same order.
     pub fn with_transforms(
         transforms: Vec<(TransformName, Box<dyn TransformBuilder>)>,
+        graph: TransformGraph,
         metrics: MetricReader,
-        rx: mpsc::Receiver<MeasurementBuffer>,
-        tx: broadcast::Sender<MeasurementBuffer>,
+        channels: (mpsc::Receiver<MeasurementBuffer>, broadcast::Sender<MeasurementBuffer>),
+        channel_size: usize,
         rt_normal: &runtime::Handle,
+        health: &PipelineHealth,
     ) -> anyhow::Result<Self> {
         let metrics_r = metrics.blocking_read();
         let mut built = Vec::with_capacity(transforms.len());
@@ -62,7 +68,8 @@ impl TransformControl {
                 .inspect_err(|e| log::error!("Failed to build transform {full_name}: {e:#}"))?;
             built.push((full_name, transform));
         }
-        let tasks = TaskManager::spawn(built, metrics.clone(), rx, tx, rt_normal);
+        drop(metrics_r);
+        let tasks = TaskManager::spawn(built, graph, metrics.clone(), channels, channel_size, rt_normal, health);
         Ok(Self { tasks })
     }
 
@@ -86,10 +93,10 @@ impl TransformControl {
     where
         F: FnMut(Result<Result<(), PipelineError>, tokio::task::JoinError>),
     {
-        // Nothing to do here to stop the tasks: the transform task will naturally
-        // stop when the input channel is closed. See `run.rs`.
+        // Nothing to do here to stop the tasks: the transform tasks will naturally
+        // stop when their input channel is closed. See `run.rs`.
 
-        // We simply wait for the task to finish.
+        // We simply wait for the tasks to finish.
         while let Some(res) = self.tasks.spawned_tasks.join_next().await {
             handle_task_result(res);
         }
@@ -97,7 +104,7 @@ impl TransformControl {
 
     pub fn list_elements(&self, buf: &mut Vec<ElementName>, pat: &ElementNamePattern) {
         if pat.kind == None || pat.kind == Some(ElementKind::Transform) {
-            buf.extend(self.tasks.names_by_bitset_position.iter().filter_map(|name| {
+            buf.extend(self.tasks.transforms.iter().filter_map(|(name, _)| {
                 if pat.matches(name) {
                     Some(name.to_owned().into())
                 } else {
@@ -106,75 +113,153 @@ impl TransformControl {
             }))
         }
     }
+
+    pub fn element_status(&self, buf: &mut Vec<ElementStatus>, pat: &ElementNamePattern) {
+        if pat.kind.is_none() || pat.kind == Some(ElementKind::Transform) {
+            buf.extend(
+                self.tasks
+                    .transforms
+                    .iter()
+                    .filter(|(name, _)| pat.matches(name))
+                    .map(|(name, enabled)| ElementStatus {
+                        name: name.to_owned().into(),
+                        state: if enabled.load(Ordering::Relaxed) {
+                            ElementState::Running
+                        } else {
+                            ElementState::Paused
+                        },
+                        poll_interval: None,
+                        last_poll: None,
+                    }),
+            )
+        }
+    }
 }
 
 impl TaskManager {
     pub fn spawn(
         transforms: Vec<(TransformName, Box<dyn Transform>)>,
+        graph: TransformGraph,
         metrics_r: MetricReader,
-        rx: mpsc::Receiver<MeasurementBuffer>,
-        tx: broadcast::Sender<MeasurementBuffer>,
+        (rx, tx): (mpsc::Receiver<MeasurementBuffer>, broadcast::Sender<MeasurementBuffer>),
+        channel_size: usize,
         rt_normal: &runtime::Handle,
+        health: &PipelineHealth,
     ) -> Self {
-        // Prepare the active bitset.
-        let mut active_bitset: u64 = 0;
-        let mut names_by_bitset_position = Vec::with_capacity(transforms.len());
+        log::trace!(
+            "Running transforms: {}",
+            graph
+                .names()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
 
-        for (i, (name, _)) in transforms.iter().enumerate() {
-            active_bitset |= 1 << i;
-            names_by_bitset_position.push(name.clone());
+        // Create the input channel of each transform.
+        let (node_tx, node_rx): (Vec<_>, Vec<_>) = (0..graph.nodes().len())
+            .map(|_| mpsc::channel::<TransformBatch>(channel_size))
+            .unzip();
+        for (name, tx) in graph.names().zip(&node_tx) {
+            health.watch_transform_channel(name.to_owned(), tx);
         }
+        let node_stats: Vec<_> = graph
+            .names()
+            .map(|name| health.register_transform(name.to_owned()))
+            .collect();
 
-        // Start the transforms thread.
-        // Transforms functions can be CPU intensive, which is why they run on their own thread, isolated from the tokio runtime.
-        let active_bitset = Arc::new(AtomicU64::new(active_bitset));
-        let active_bitset_2 = Arc::clone(&active_bitset);
-        let (res_tx, res_rx) = tokio::sync::oneshot::channel();
-        std::thread::spawn(move || {
-            let res = match std::panic::catch_unwind(AssertUnwindSafe(move || {
-                run_all_in_order(transforms, rx, tx, active_bitset_2, metrics_r)
-            })) {
-                Ok(res) => res,
-                Err(panic) => Err(PipelineError::internal(anyhow::anyhow!(
-                    "the task that runs the transforms panicked: {panic:?}"
-                ))),
-            };
-            res_tx.send(res).expect("the receiver dropped");
-        });
-
-        // Start a task on a JoinSet so that we can asynchronously wait for it to finish.
-        let mut set = JoinSet::new();
-        let thread_waiter = async move {
-            let res = res_rx.await.expect("the sender dropped, has the thread panicked?");
-            res
+        // Connect each element of the graph to its downstream transforms.
+        let make_router = |upstream: &TransformUpstream| {
+            let downstream = graph
+                .downstream_of(upstream)
+                .into_iter()
+                .map(|i| {
+                    let (name, inputs) = &graph.nodes()[i];
+                    Downstream {
+                        name: name.to_owned(),
+                        metrics: inputs.metrics.clone(),
+                        tx: node_tx[i].clone(),
+                        stats: node_stats[i].clone(),
+                    }
+                })
+                .collect();
+            Router::new(downstream, tx.clone())
         };
-        set.spawn_on(thread_waiter, rt_normal);
+        let sources_router = make_router(&TransformUpstream::Sources);
+        let routers: Vec<Router> = graph
+            .names()
+            .map(|name| make_router(&TransformUpstream::Transform(name.to_owned())))
+            .collect();
+        // Drop the original senders, so that each transform stops when all its upstream elements have stopped.
+        drop(node_tx);
+
+        // Start the threads.
+        // Transforms functions can be CPU intensive, which is why they run on their own threads, isolated from the tokio runtime.
+        let mut set = JoinSet::new();
+        let mut flags = Vec::with_capacity(transforms.len());
+
+        let metrics = metrics_r.clone();
+        spawn_thread(
+            move || run_sources_dispatch(rx, sources_router, metrics),
+            String::from("the task that dispatches the measurements to the transforms"),
+            &mut set,
+            rt_normal,
+        );
+
+        let nodes = transforms.into_iter().zip(node_rx).zip(routers).zip(node_stats);
+        for ((((name, transform), rx), router), stats) in nodes {
+            let enabled = Arc::new(AtomicBool::new(true));
+            flags.push((name.clone(), enabled.clone()));
+
+            let metrics = metrics_r.clone();
+            let description = format!("the task that runs transform {name}");
+            spawn_thread(
+                move || run_transform(name, transform, rx, router, enabled, metrics, stats),
+                description,
+                &mut set,
+                rt_normal,
+            );
+        }
+
         Self {
             spawned_tasks: set,
-            active_bitset,
-            names_by_bitset_position,
+            transforms: flags,
         }
     }
 
     fn reconfigure(&mut self, msg: ControlMessage) {
-        let mut bitset = self.active_bitset.load(Ordering::Relaxed);
-        for (i, name) in self.names_by_bitset_position.iter().enumerate() {
+        let enabled = msg.new_state == TaskState::Enabled;
+        for (name, flag) in &self.transforms {
             if msg.matcher.matches(name) {
-                match msg.new_state {
-                    TaskState::Enabled => {
-                        bitset |= 1 << i;
-                    }
-                    TaskState::Disabled => {
-                        bitset &= !(1 << i);
-                    }
-                }
+                flag.store(enabled, Ordering::Relaxed);
+                log::trace!(
+                    "transform {name} is now {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
             }
         }
-        self.active_bitset.store(bitset, Ordering::Relaxed);
-        log::trace!("new 'enabled' bitset: {bitset}");
     }
 }
 
+/// Runs `f` on a new thread, and adds a task to `set` in order to asynchronously wait for its result.
+fn spawn_thread<F>(f: F, description: String, set: &mut JoinSet<Result<(), PipelineError>>, rt: &runtime::Handle)
+where
+    F: FnOnce() -> Result<(), PipelineError> + Send + 'static,
+{
+    let (res_tx, res_rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let res = match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(res) => res,
+            Err(panic) => Err(PipelineError::internal(anyhow::anyhow!(
+                "{description} panicked: {panic:?}"
+            ))),
+        };
+        res_tx.send(res).expect("the receiver dropped");
+    });
+
+    let thread_waiter = async move { res_rx.await.expect("the sender dropped, has the thread panicked?") };
+    set.spawn_on(thread_waiter, rt);
+}
+
 /// A control message for transforms.
 #[derive(Debug)]
 pub struct ControlMessage {
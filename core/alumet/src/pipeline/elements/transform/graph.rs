@@ -0,0 +1,314 @@
+//! Organization of the transforms as a directed acyclic graph (DAG).
+//!
+//! By default, the transforms form a single chain: each transform receives the output
+//! of the previous one, and the last transform sends its output to the outputs.
+//!
+//! With [`TransformInputs`], a transform can instead subscribe to the output of the sources
+//! or of other transforms, and only to some metrics. Transforms that are on independent
+//! branches of the graph run in parallel.
+//!
+//! # Routing
+//! When a pipeline element (the sources or a transform) produces measurements, each point is routed
+//! according to the subscriptions of the downstream transforms:
+//! - If some downstream transforms have subscribed to the metric of the point, they receive it.
+//! - Otherwise, the point is given to the downstream transforms that did not restrict their metrics.
+//! - If no downstream transform accepts the point, it is sent to the outputs.
+//!
+//! When a point goes to several downstream transforms, only the first one receives the original point,
+//! the others receive a copy. A transform never forwards the copies that it leaves untouched to the outputs,
+//! only the points that it produces or modifies. This avoids sending the same point to the outputs several times.
+//!
+//! Each transform has a bounded input queue. When an element has a single downstream transform, like in the
+//! default chain, it waits for the queue to have some room. When it has several downstream transforms and
+//! one of them is too slow, the new measurements are dropped for this transform (see the
+//! `alumet_transform_dropped_buffers` metric of the [self-monitoring](crate::pipeline::health)),
+//! instead of blocking the other branches of the graph.
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use thiserror::Error;
+
+use crate::pipeline::{matching::StringPattern, naming::TransformName};
+
+/// An element that a transform can take its measurements from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TransformUpstream {
+    /// The measurements produced by the sources.
+    Sources,
+    /// The measurements produced by another transform.
+    Transform(TransformName),
+}
+
+/// Declares which measurements a transform receives.
+///
+/// # Example
+/// ```
+/// use alumet::pipeline::elements::transform::graph::{TransformInputs, TransformUpstream};
+/// use alumet::pipeline::matching::StringPattern;
+/// use alumet::pipeline::naming::TransformName;
+///
+/// // Receive the `rapl_*` measurements produced by the sources.
+/// let inputs = TransformInputs::sources().with_metrics(vec![StringPattern::StartWith(String::from("rapl_"))]);
+///
+/// // Receive the output of two other transforms.
+/// let inputs = TransformInputs::transform(TransformName::from_str("plugin", "a"))
+///     .and(TransformUpstream::Transform(TransformName::from_str("plugin", "b")));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformInputs {
+    pub(crate) upstreams: Vec<TransformUpstream>,
+    pub(crate) metrics: Option<Vec<StringPattern>>,
+}
+
+impl TransformInputs {
+    /// Subscribes to the measurements produced by the sources.
+    pub fn sources() -> Self {
+        Self::from(TransformUpstream::Sources)
+    }
+
+    /// Subscribes to the measurements produced by the transform `name`.
+    pub fn transform(name: TransformName) -> Self {
+        Self::from(TransformUpstream::Transform(name))
+    }
+
+    /// Also subscribes to the measurements produced by another element.
+    pub fn and(mut self, upstream: TransformUpstream) -> Self {
+        if !self.upstreams.contains(&upstream) {
+            self.upstreams.push(upstream);
+        }
+        self
+    }
+
+    /// Restricts the subscription to the metrics whose name matches one of the `patterns`.
+    ///
+    /// By default, the transform receives every metric that is not claimed by another transform.
+    pub fn with_metrics(mut self, patterns: Vec<StringPattern>) -> Self {
+        self.metrics = Some(patterns);
+        self
+    }
+
+    /// Returns the elements that the transform takes its measurements from.
+    pub fn upstreams(&self) -> &[TransformUpstream] {
+        &self.upstreams
+    }
+
+    /// Returns the patterns of the metrics that the transform subscribes to,
+    /// or `None` if the subscription is not restricted.
+    pub fn metrics(&self) -> Option<&[StringPattern]> {
+        self.metrics.as_deref()
+    }
+}
+
+impl From<TransformUpstream> for TransformInputs {
+    fn from(value: TransformUpstream) -> Self {
+        Self {
+            upstreams: vec![value],
+            metrics: None,
+        }
+    }
+}
+
+/// A validated graph of transforms.
+#[derive(Debug)]
+pub(crate) struct TransformGraph {
+    /// The transforms with their inputs, sorted in topological order:
+    /// a transform always appears after its upstream transforms.
+    nodes: Vec<(TransformName, TransformInputs)>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TransformGraphError {
+    #[error("inputs were declared for a transform that does not exist: {0}")]
+    UnknownTransform(TransformName),
+    #[error("transform {0} subscribes to {1}, which does not exist")]
+    UnknownUpstream(TransformName, TransformName),
+    #[error("transform {0} has no upstream, it would never receive any measurement")]
+    NoUpstream(TransformName),
+    #[error("the transforms contain a cycle, involving: {}", .0.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", "))]
+    Cycle(Vec<TransformName>),
+}
+
+impl TransformGraph {
+    /// Builds a graph of transforms.
+    ///
+    /// The transforms that have no explicit `inputs` are chained in the given `order`,
+    /// starting from the sources, as they would be in a simple pipeline.
+    pub fn new(
+        order: Vec<TransformName>,
+        mut inputs: FxHashMap<TransformName, TransformInputs>,
+    ) -> Result<Self, TransformGraphError> {
+        let known: FxHashSet<&TransformName> = order.iter().collect();
+        if let Some(unknown) = inputs.keys().find(|name| !known.contains(name)) {
+            return Err(TransformGraphError::UnknownTransform(unknown.to_owned()));
+        }
+
+        // Assign the inputs, chaining the implicit transforms.
+        let mut previous_in_chain = TransformUpstream::Sources;
+        let mut nodes = Vec::with_capacity(order.len());
+        for name in &order {
+            let node_inputs = match inputs.remove(name) {
+                Some(explicit) => explicit,
+                None => {
+                    let implicit = TransformInputs::from(previous_in_chain);
+                    previous_in_chain = TransformUpstream::Transform(name.to_owned());
+                    implicit
+                }
+            };
+            if node_inputs.upstreams.is_empty() {
+                return Err(TransformGraphError::NoUpstream(name.to_owned()));
+            }
+            for upstream in &node_inputs.upstreams {
+                match upstream {
+                    TransformUpstream::Transform(up) if up == name => {
+                        return Err(TransformGraphError::Cycle(vec![name.to_owned()]));
+                    }
+                    TransformUpstream::Transform(up) if !known.contains(up) => {
+                        return Err(TransformGraphError::UnknownUpstream(name.to_owned(), up.to_owned()));
+                    }
+                    _ => (),
+                }
+            }
+            nodes.push((name.to_owned(), node_inputs));
+        }
+
+        // Sort the transforms in topological order (Kahn's algorithm),
+        // keeping the given order between independent transforms.
+        let mut sorted: Vec<(TransformName, TransformInputs)> = Vec::with_capacity(nodes.len());
+        let mut done: FxHashSet<TransformName> = FxHashSet::default();
+        while !nodes.is_empty() {
+            let ready = nodes.iter().position(|(_, inputs)| {
+                inputs.upstreams.iter().all(|up| match up {
+                    TransformUpstream::Sources => true,
+                    TransformUpstream::Transform(name) => done.contains(name),
+                })
+            });
+            match ready {
+                Some(i) => {
+                    let node = nodes.remove(i);
+                    done.insert(node.0.clone());
+                    sorted.push(node);
+                }
+                None => {
+                    let involved = nodes.into_iter().map(|(name, _)| name).collect();
+                    return Err(TransformGraphError::Cycle(involved));
+                }
+            }
+        }
+        Ok(Self { nodes: sorted })
+    }
+
+    /// Returns the transforms with their inputs, in topological order.
+    pub fn nodes(&self) -> &[(TransformName, TransformInputs)] {
+        &self.nodes
+    }
+
+    /// Returns the names of the transforms, in topological order.
+    pub fn names(&self) -> impl Iterator<Item = &TransformName> {
+        self.nodes.iter().map(|(name, _)| name)
+    }
+
+    /// Returns the indices of the transforms that subscribe to `upstream`.
+    pub fn downstream_of(&self, upstream: &TransformUpstream) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, inputs))| inputs.upstreams.contains(upstream).then_some(i))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashMap;
+
+    use super::{TransformGraph, TransformGraphError, TransformInputs, TransformUpstream};
+    use crate::pipeline::{matching::StringPattern, naming::TransformName};
+
+    fn name(n: &str) -> TransformName {
+        TransformName::from_str("test", n)
+    }
+
+    fn names(graph: &TransformGraph) -> Vec<&str> {
+        graph.names().map(|n| n.transform()).collect()
+    }
+
+    #[test]
+    fn default_chain() {
+        let order = vec![name("a"), name("b"), name("c")];
+        let graph = TransformGraph::new(order, FxHashMap::default()).unwrap();
+        assert_eq!(names(&graph), vec!["a", "b", "c"]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Sources), vec![0]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Transform(name("a"))), vec![1]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Transform(name("b"))), vec![2]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Transform(name("c"))), vec![]);
+    }
+
+    #[test]
+    fn explicit_branch() {
+        // `b` takes some metrics from the sources, outside of the chain `a -> c`
+        let order = vec![name("a"), name("b"), name("c")];
+        let b_inputs = TransformInputs::sources().with_metrics(vec![StringPattern::Exact(String::from("m"))]);
+        let inputs = FxHashMap::from_iter([(name("b"), b_inputs.clone())]);
+        let graph = TransformGraph::new(order, inputs).unwrap();
+        assert_eq!(names(&graph), vec!["a", "b", "c"]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Sources), vec![0, 1]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Transform(name("a"))), vec![2]);
+        assert_eq!(graph.downstream_of(&TransformUpstream::Transform(name("b"))), vec![]);
+        assert_eq!(graph.nodes()[1].1, b_inputs);
+    }
+
+    #[test]
+    fn topological_order() {
+        // `a` is registered first but depends on `b` and `c`
+        let order = vec![name("a"), name("b"), name("c")];
+        let inputs = FxHashMap::from_iter([(
+            name("a"),
+            TransformInputs::transform(name("c")).and(TransformUpstream::Transform(name("b"))),
+        )]);
+        let graph = TransformGraph::new(order, inputs).unwrap();
+        assert_eq!(names(&graph), vec!["b", "c", "a"]);
+        assert_eq!(
+            graph.downstream_of(&TransformUpstream::Transform(name("b"))),
+            vec![1, 2]
+        );
+        assert_eq!(graph.downstream_of(&TransformUpstream::Transform(name("c"))), vec![2]);
+    }
+
+    #[test]
+    fn more_than_64_transforms() {
+        let order: Vec<_> = (0..100).map(|i| name(&format!("t{i}"))).collect();
+        let graph = TransformGraph::new(order, FxHashMap::default()).unwrap();
+        assert_eq!(graph.nodes().len(), 100);
+    }
+
+    #[test]
+    fn errors() {
+        let order = vec![name("a"), name("b")];
+
+        let inputs = FxHashMap::from_iter([(name("z"), TransformInputs::sources())]);
+        let err = TransformGraph::new(order.clone(), inputs).unwrap_err();
+        assert_eq!(err, TransformGraphError::UnknownTransform(name("z")));
+
+        let inputs = FxHashMap::from_iter([(name("a"), TransformInputs::transform(name("z")))]);
+        let err = TransformGraph::new(order.clone(), inputs).unwrap_err();
+        assert_eq!(err, TransformGraphError::UnknownUpstream(name("a"), name("z")));
+
+        let inputs = FxHashMap::from_iter([(name("a"), TransformInputs::transform(name("a")))]);
+        let err = TransformGraph::new(order.clone(), inputs).unwrap_err();
+        assert_eq!(err, TransformGraphError::Cycle(vec![name("a")]));
+
+        let inputs = FxHashMap::from_iter([
+            (name("a"), TransformInputs::transform(name("b"))),
+            (name("b"), TransformInputs::transform(name("a"))),
+        ]);
+        let err = TransformGraph::new(order.clone(), inputs).unwrap_err();
+        assert_eq!(err, TransformGraphError::Cycle(vec![name("a"), name("b")]));
+
+        let no_upstream = TransformInputs {
+            upstreams: vec![],
+            metrics: None,
+        };
+        let inputs = FxHashMap::from_iter([(name("b"), no_upstream)]);
+        let err = TransformGraph::new(order, inputs).unwrap_err();
+        assert_eq!(err, TransformGraphError::NoUpstream(name("b")));
+    }
+}
@@ -1,21 +1,354 @@
-//! Runtime implementation of the task that executes transforms.
+//! Runtime implementation of the tasks that execute transforms.
 
+use std::hash::{Hash, Hasher};
 use std::sync::{
     Arc,
-    atomic::{AtomicU64, Ordering},
+    atomic::{AtomicBool, AtomicU64, Ordering},
 };
+use std::time::Instant;
 
 use anyhow::Context;
-use tokio::sync::{broadcast, mpsc};
+use rustc_hash::{FxHashMap, FxHashSet, FxHasher};
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, error::TrySendError},
+};
 
 use crate::{
-    measurement::MeasurementBuffer,
-    metrics::online::MetricReader,
-    pipeline::{error::PipelineError, naming::TransformName},
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    metrics::{def::RawMetricId, online::MetricReader, registry::MetricRegistry},
+    pipeline::{error::PipelineError, health::TransformStats, matching::StringPattern, naming::TransformName},
 };
 
 use super::{Transform, TransformContext, error::TransformError};
 
+/// Measurements sent to a transform of the graph.
+pub(crate) struct TransformBatch {
+    pub measurements: MeasurementBuffer,
+    /// Fingerprints of the points that are copies: another branch of the graph has received the original points
+    /// and is responsible for forwarding them to the outputs. This transform must only forward what it produces,
+    /// therefore the points that it leaves untouched do not go to the outputs, but the ones that it modifies do.
+    pub copies: FxHashSet<u64>,
+}
+
+impl From<MeasurementBuffer> for TransformBatch {
+    fn from(measurements: MeasurementBuffer) -> Self {
+        Self {
+            measurements,
+            copies: FxHashSet::default(),
+        }
+    }
+}
+
+/// A transform that receives measurements from the current element of the graph.
+pub(super) struct Downstream {
+    pub name: TransformName,
+    /// Patterns of the metrics that the transform subscribes to, `None` means "everything else".
+    pub metrics: Option<Vec<StringPattern>>,
+    pub tx: mpsc::Sender<TransformBatch>,
+    pub stats: Arc<TransformStats>,
+}
+
+/// Where a piece of a `MeasurementBuffer` goes.
+#[derive(Debug)]
+enum Destination {
+    Downstream(usize),
+    Outputs,
+}
+
+/// Routes the measurements produced by an element of the graph (the sources or a transform)
+/// to the downstream transforms or to the outputs.
+pub(super) struct Router {
+    downstream: Vec<Downstream>,
+    out_tx: broadcast::Sender<MeasurementBuffer>,
+    /// Cache: indices of the downstream transforms that receive each metric.
+    /// An empty list means that the metric goes to the outputs.
+    routes: FxHashMap<RawMetricId, Vec<usize>>,
+}
+
+impl Router {
+    pub fn new(downstream: Vec<Downstream>, out_tx: broadcast::Sender<MeasurementBuffer>) -> Self {
+        Self {
+            downstream,
+            out_tx,
+            routes: FxHashMap::default(),
+        }
+    }
+
+    /// Splits the measurements according to the subscriptions of the downstream transforms.
+    ///
+    /// When a point goes to several downstream transforms, the first one receives the original point
+    /// and the others receive a copy, which will not be forwarded to the outputs.
+    fn split(&mut self, batch: TransformBatch, metrics: &MetricRegistry) -> Vec<(Destination, TransformBatch)> {
+        let TransformBatch {
+            mut measurements,
+            copies,
+        } = batch;
+        if self.downstream.is_empty() {
+            // The original points have already been sent to the outputs by another branch.
+            if !copies.is_empty() {
+                measurements.retain(|p| !copies.contains(&fingerprint(p)));
+                if measurements.is_empty() {
+                    return vec![];
+                }
+            }
+            return vec![(Destination::Outputs, TransformBatch::from(measurements))];
+        }
+        if self.downstream.iter().all(|d| d.metrics.is_none()) {
+            // Fast path: every downstream transform receives every measurement.
+            let n = self.downstream.len();
+            let mut res = Vec::with_capacity(n);
+            if n > 1 {
+                let mut all_copies = copies.clone();
+                all_copies.extend(measurements.iter().map(fingerprint));
+                for i in 1..n {
+                    let copy = TransformBatch {
+                        measurements: measurements.clone(),
+                        copies: all_copies.clone(),
+                    };
+                    res.push((Destination::Downstream(i), copy));
+                }
+            }
+            res.insert(0, (Destination::Downstream(0), TransformBatch { measurements, copies }));
+            return res;
+        }
+
+        let mut parts: Vec<TransformBatch> = (0..self.downstream.len())
+            .map(|_| TransformBatch::from(MeasurementBuffer::new()))
+            .collect();
+        let mut to_outputs = MeasurementBuffer::new();
+        for point in measurements {
+            let downstream = &self.downstream;
+            let targets = self
+                .routes
+                .entry(point.metric)
+                .or_insert_with(|| compute_route(downstream, metrics, &point.metric));
+            let fp = (!copies.is_empty() || targets.len() > 1).then(|| fingerprint(&point));
+            let is_copy = fp.is_some_and(|fp| copies.contains(&fp));
+            match targets.as_slice() {
+                [] => {
+                    if !is_copy {
+                        to_outputs.push(point);
+                    }
+                }
+                [first, others @ ..] => {
+                    for i in others {
+                        parts[*i].copies.extend(fp);
+                        parts[*i].measurements.push(point.clone());
+                    }
+                    if is_copy {
+                        parts[*first].copies.extend(fp);
+                    }
+                    parts[*first].measurements.push(point);
+                }
+            }
+        }
+
+        let mut res: Vec<(Destination, TransformBatch)> = parts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, batch)| !batch.measurements.is_empty())
+            .map(|(i, batch)| (Destination::Downstream(i), batch))
+            .collect();
+        if !to_outputs.is_empty() {
+            res.push((Destination::Outputs, TransformBatch::from(to_outputs)));
+        }
+        res
+    }
+
+    /// Sends the pieces obtained with [`split`](Self::split) to their destination.
+    ///
+    /// If there is only one downstream transform, like in the default chain of transforms, this waits
+    /// for its input queue to have some room, which applies backpressure to the upstream elements.
+    /// Otherwise, this never waits for a downstream transform: if its input queue is full, the measurements
+    /// are dropped (and counted in its statistics), so that a slow transform does not stall the other branches.
+    fn send(&mut self, parts: Vec<(Destination, TransformBatch)>) -> Result<(), PipelineError> {
+        let mut stopped = Vec::new();
+        let wait = self.downstream.len() == 1;
+        for (destination, batch) in parts {
+            match destination {
+                Destination::Downstream(i) => {
+                    let downstream = &self.downstream[i];
+                    let res = if wait {
+                        downstream
+                            .tx
+                            .blocking_send(batch)
+                            .map_err(|mpsc::error::SendError(batch)| TrySendError::Closed(batch))
+                    } else {
+                        downstream.tx.try_send(batch)
+                    };
+                    match res {
+                        Ok(()) => (),
+                        Err(TrySendError::Full(_)) => {
+                            log::warn!(
+                                "Transform {} is too slow, it lost a buffer of measurements.",
+                                downstream.name
+                            );
+                            downstream.stats.record_dropped(1);
+                        }
+                        Err(TrySendError::Closed(_)) => {
+                            // The transform has stopped because of a fatal error, which has already been reported.
+                            log::warn!(
+                                "Transform {} has stopped, the measurements that it would have received are now routed as if it did not exist.",
+                                downstream.name
+                            );
+                            stopped.push(i);
+                        }
+                    }
+                }
+                Destination::Outputs => {
+                    self.out_tx
+                        .send(batch.measurements)
+                        .context("could not send the measurements from transforms to the outputs")?;
+                }
+            }
+        }
+        if !stopped.is_empty() {
+            // Remove in reverse order to keep the indices valid.
+            for i in stopped.into_iter().rev() {
+                self.downstream.remove(i);
+            }
+            self.routes.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Computes a fingerprint of the content of a point, to recognize the copies that a transform has not modified.
+fn fingerprint(point: &MeasurementPoint) -> u64 {
+    let mut hasher = FxHasher::default();
+    point.metric.hash(&mut hasher);
+    point.timestamp.to_unix_timestamp().hash(&mut hasher);
+    match &point.value {
+        WrappedMeasurementValue::F64(x) => (0u8, x.to_bits()).hash(&mut hasher),
+        WrappedMeasurementValue::U64(x) => (1u8, x).hash(&mut hasher),
+        WrappedMeasurementValue::I64(x) => (2u8, x).hash(&mut hasher),
+        WrappedMeasurementValue::Histogram(h) => {
+            3u8.hash(&mut hasher);
+            h.bounds().iter().for_each(|b| b.to_bits().hash(&mut hasher));
+            h.counts().hash(&mut hasher);
+            h.sum().to_bits().hash(&mut hasher);
+        }
+    }
+    point.resource.hash(&mut hasher);
+    point.consumer.hash(&mut hasher);
+    // the order of the attributes does not matter
+    let attributes = point
+        .attributes()
+        .map(|kv| {
+            let mut h = FxHasher::default();
+            kv.hash(&mut h);
+            h.finish()
+        })
+        .fold(0u64, u64::wrapping_add);
+    attributes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Determines which downstream transforms receive the measurements of a metric.
+fn compute_route(downstream: &[Downstream], metrics: &MetricRegistry, metric: &RawMetricId) -> Vec<usize> {
+    let metric_name = metrics.by_id(metric).map(|m| m.name.as_str());
+    let subscribed: Vec<usize> = downstream
+        .iter()
+        .enumerate()
+        .filter_map(|(i, d)| match (&d.metrics, metric_name) {
+            (Some(patterns), Some(name)) if patterns.iter().any(|p| p.matches(name)) => Some(i),
+            _ => None,
+        })
+        .collect();
+    if !subscribed.is_empty() {
+        return subscribed;
+    }
+    downstream
+        .iter()
+        .enumerate()
+        .filter_map(|(i, d)| d.metrics.is_none().then_some(i))
+        .collect()
+}
+
+/// Dispatches the measurements produced by the sources to the first transforms of the graph.
+pub(super) fn run_sources_dispatch(
+    mut rx: mpsc::Receiver<MeasurementBuffer>,
+    mut router: Router,
+    metrics_reader: MetricReader,
+) -> Result<(), PipelineError> {
+    while let Some(measurements) = rx.blocking_recv() {
+        let parts = router.split(TransformBatch::from(measurements), &metrics_reader.blocking_read());
+        router.send(parts)?;
+    }
+    log::debug!("The channel connected to the transform step has been closed, the transforms will stop.");
+    Ok(())
+}
+
+/// Runs one transform of the graph.
+///
+/// The transform stops when all its upstream elements have stopped.
+pub(super) fn run_transform(
+    name: TransformName,
+    mut transform: Box<dyn Transform>,
+    mut rx: mpsc::Receiver<TransformBatch>,
+    mut router: Router,
+    enabled: Arc<AtomicBool>,
+    metrics_reader: MetricReader,
+    stats: Arc<TransformStats>,
+) -> Result<(), PipelineError> {
+    while let Some(mut batch) = rx.blocking_recv() {
+        let parts = {
+            // Build the transform context.
+            // This will block the publication of any modification to the MetricRegistry until the context is dropped.
+            // TODO this need to change: if transforms take a "long" time to execute, the registry will be blocked for a long time,
+            // which is bad. Usually, transforms don't need to use the MetricRegistry for a long time (see next TODO).
+            // Or, we could store a separate copy of the registry just for transforms.
+            // TODO: this point should be emphasized in the transforms docs so that people don't implement bad transforms.
+            let metrics = &metrics_reader.blocking_read();
+
+            // Run the transform if it is enabled. If it fails, the ability to continue running depends on the error type.
+            if enabled.load(Ordering::Relaxed) {
+                let ctx = TransformContext { metrics };
+                let t0 = Instant::now();
+                let res = transform.apply(&mut batch.measurements, &ctx);
+                stats.record_apply(t0.elapsed());
+                match res {
+                    Ok(()) => (),
+                    Err(TransformError::UnexpectedInput(e)) => {
+                        log::error!("Transform {name} received unexpected measurements: {e:#}");
+                    }
+                    Err(TransformError::Fatal(e)) => {
+                        log::error!("Fatal error in transform {name} (this stops the transform!): {e:?}");
+                        return Err(PipelineError::for_element(name, e));
+                    }
+                }
+            }
+            router.split(batch, metrics)
+        };
+
+        // Send the results to the next transforms or to the outputs.
+        // The registry lock has been released: waiting for a slow transform does not block it.
+        router.send(parts)?;
+    }
+    log::debug!("The upstream elements of transform {name} have stopped, it will now finish.");
+
+    // the channel has been closed, which means that the pipeline is shutting down
+    let metrics = &metrics_reader.blocking_read();
+    let ctx = TransformContext { metrics };
+    match transform.finish(&ctx) {
+        Ok(()) => Ok(()),
+        Err(TransformError::UnexpectedInput(e)) => {
+            log::error!("Transform {name} received unexpected measurements during finish: {e:#}");
+            Ok(())
+        }
+        Err(TransformError::Fatal(e)) => {
+            log::error!("Fatal error in transform {name} during finish: {e:?}");
+            Err(PipelineError::for_element(name, e))
+        }
+    }
+}
+
+/// Runs all the transforms in a single chain, in the given order, and sends the result to the outputs.
+///
+/// The pipeline no longer uses this function: the transforms now form a graph, where each transform
+/// runs on its own thread (see [`graph`](super::graph)).
+#[deprecated(note = "the pipeline runs the transforms as a graph, see the `graph` module")]
 pub fn run_all_in_order(
     mut transforms: Vec<(TransformName, Box<dyn Transform>)>,
     mut rx: mpsc::Receiver<MeasurementBuffer>,
@@ -91,3 +424,307 @@ pub fn run_all_in_order(
     }
     err
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::{broadcast, mpsc};
+
+    use super::{Destination, Downstream, Router, TransformBatch, fingerprint};
+    use crate::{
+        measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::{
+            Metric, RawMetricId,
+            duplicate::{DuplicateCriteria, DuplicateReaction},
+            registry::MetricRegistry,
+        },
+        pipeline::{
+            health::{ElementStats, PipelineHealth, TransformStats},
+            matching::StringPattern,
+            naming::TransformName,
+        },
+        resources::{Resource, ResourceConsumer},
+        units::Unit,
+    };
+
+    fn registry(names: &[&str]) -> (MetricRegistry, Vec<RawMetricId>) {
+        let mut registry = MetricRegistry::new();
+        let ids = names
+            .iter()
+            .map(|name| {
+                let metric = Metric {
+                    name: name.to_string(),
+                    description: String::new(),
+                    value_type: crate::measurement::WrappedMeasurementType::U64,
+                    unit: Unit::Unity.into(),
+                };
+                registry
+                    .register(metric, DuplicateCriteria::Strict, DuplicateReaction::Error)
+                    .unwrap()
+            })
+            .collect();
+        (registry, ids)
+    }
+
+    fn point(metric: RawMetricId) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(1),
+        )
+    }
+
+    fn downstream(name: &str, metrics: Option<Vec<StringPattern>>) -> Downstream {
+        let (tx, _rx) = mpsc::channel(1);
+        Downstream {
+            name: TransformName::from_str("test", name),
+            metrics,
+            tx,
+            stats: Arc::new(TransformStats::default()),
+        }
+    }
+
+    fn batch(points: Vec<MeasurementPoint>) -> TransformBatch {
+        TransformBatch::from(MeasurementBuffer::from(points))
+    }
+
+    fn summary(parts: &[(Destination, TransformBatch)]) -> Vec<(Option<usize>, usize)> {
+        parts
+            .iter()
+            .map(|(dest, batch)| match dest {
+                Destination::Downstream(i) => (Some(*i), batch.measurements.len()),
+                Destination::Outputs => (None, batch.measurements.len()),
+            })
+            .collect()
+    }
+
+    /// Marks the points of the given metric as copies.
+    fn mark_copies(batch: &mut TransformBatch, metric: RawMetricId) {
+        let fingerprints = batch
+            .measurements
+            .iter()
+            .filter(|p| p.metric == metric)
+            .map(fingerprint);
+        batch.copies.extend(fingerprints);
+    }
+
+    /// Returns, for each part, the metrics of the points that are copies.
+    fn copies(parts: &[(Destination, TransformBatch)]) -> Vec<Vec<RawMetricId>> {
+        parts
+            .iter()
+            .map(|(_, batch)| {
+                let mut copies: Vec<RawMetricId> = batch
+                    .measurements
+                    .iter()
+                    .filter(|p| batch.copies.contains(&fingerprint(p)))
+                    .map(|p| p.metric)
+                    .collect();
+                copies.sort_by_key(|id| id.as_u64());
+                copies.dedup();
+                copies
+            })
+            .collect()
+    }
+
+    #[test]
+    fn no_downstream() {
+        let (registry, ids) = registry(&["a"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let mut router = Router::new(vec![], out_tx);
+        let parts = router.split(batch(vec![point(ids[0]), point(ids[0])]), &registry);
+        assert_eq!(summary(&parts), vec![(None, 2)]);
+    }
+
+    #[test]
+    fn catch_all_only() {
+        let (registry, ids) = registry(&["a"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let mut router = Router::new(vec![downstream("x", None), downstream("y", None)], out_tx);
+        let parts = router.split(batch(vec![point(ids[0])]), &registry);
+        assert_eq!(summary(&parts), vec![(Some(0), 1), (Some(1), 1)]);
+        // only the first transform forwards the original points to the outputs
+        assert_eq!(copies(&parts), vec![vec![], vec![ids[0]]]);
+    }
+
+    #[test]
+    fn subscriptions() {
+        let (registry, ids) = registry(&["rapl_energy", "cpu_time", "other"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let attribution = downstream(
+            "attribution",
+            Some(vec![
+                StringPattern::StartWith(String::from("rapl_")),
+                StringPattern::Exact(String::from("cpu_time")),
+            ]),
+        );
+        let rapl_only = downstream(
+            "rapl_only",
+            Some(vec![StringPattern::Exact(String::from("rapl_energy"))]),
+        );
+        let mut router = Router::new(vec![attribution, rapl_only], out_tx);
+        let parts = router.split(
+            batch(vec![point(ids[0]), point(ids[1]), point(ids[2]), point(ids[1])]),
+            &registry,
+        );
+        // rapl_energy goes to both, cpu_time only to the first one, and "other" to the outputs
+        assert_eq!(summary(&parts), vec![(Some(0), 3), (Some(1), 1), (None, 1)]);
+        // rapl_only receives a copy of rapl_energy, which it must not forward to the outputs
+        assert_eq!(copies(&parts), vec![vec![], vec![ids[0]], vec![]]);
+    }
+
+    #[test]
+    fn copies_are_not_forwarded_to_outputs() {
+        let (registry, ids) = registry(&["a", "b"]);
+        let (out_tx, mut out_rx) = broadcast::channel(4);
+        let mut router = Router::new(vec![], out_tx);
+
+        // a transform received a copy of `a` and produced `b`: only `b` goes to the outputs
+        let mut received = batch(vec![point(ids[0]), point(ids[1]), point(ids[0])]);
+        mark_copies(&mut received, ids[0]);
+        let parts = router.split(received, &registry);
+        assert_eq!(summary(&parts), vec![(None, 1)]);
+        router.send(parts).unwrap();
+        assert_eq!(out_rx.try_recv().unwrap().iter().next().unwrap().metric, ids[1]);
+
+        // nothing is sent if all the points are copies
+        let mut received = batch(vec![point(ids[0])]);
+        mark_copies(&mut received, ids[0]);
+        let parts = router.split(received, &registry);
+        assert!(parts.is_empty());
+    }
+
+    #[test]
+    fn modified_copies_are_forwarded_to_outputs() {
+        let (registry, ids) = registry(&["a"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let mut router = Router::new(vec![], out_tx);
+
+        // a transform received a copy of `a` and modified it in place: the new point is its own
+        let mut received = batch(vec![point(ids[0]), point(ids[0])]);
+        mark_copies(&mut received, ids[0]);
+        let mut points = received.measurements.into_iter();
+        let modified = points.next().unwrap().with_attr("k", AttributeValue::U64(1));
+        received.measurements = MeasurementBuffer::from(vec![modified.clone(), points.next().unwrap()]);
+        let parts = router.split(received, &registry);
+        assert_eq!(summary(&parts), vec![(None, 1)]);
+        assert_eq!(parts[0].1.measurements.iter().next().unwrap(), &modified);
+    }
+
+    #[test]
+    fn copies_are_propagated_downstream() {
+        let (registry, ids) = registry(&["a", "b"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let only_a = downstream("only_a", Some(vec![StringPattern::Exact(String::from("a"))]));
+        let mut router = Router::new(vec![only_a], out_tx);
+
+        let mut received = batch(vec![point(ids[0]), point(ids[1])]);
+        mark_copies(&mut received, ids[0]);
+        mark_copies(&mut received, ids[1]);
+        let parts = router.split(received, &registry);
+        // `a` is still a copy in the next transform, `b` is dropped instead of being sent to the outputs
+        assert_eq!(summary(&parts), vec![(Some(0), 1)]);
+        assert_eq!(copies(&parts), vec![vec![ids[0]]]);
+    }
+
+    #[test]
+    fn single_downstream_waits() {
+        let (registry, ids) = registry(&["a"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let (tx, mut rx) = mpsc::channel(1);
+        let health = PipelineHealth::default();
+        let name = TransformName::from_str("test", "slow");
+        // keep the stats after the router is dropped, otherwise the health forgets the transform
+        let stats = health.register_transform(name.clone());
+        let slow = Downstream {
+            stats: stats.clone(),
+            name,
+            metrics: None,
+            tx,
+        };
+        let mut router = Router::new(vec![slow], out_tx);
+
+        // the queue only holds one buffer: the router waits for the transform instead of dropping the others
+        let sender = std::thread::spawn(move || {
+            for _ in 0..3 {
+                let parts = router.split(batch(vec![point(ids[0])]), &registry);
+                router.send(parts).unwrap();
+            }
+        });
+        for _ in 0..3 {
+            assert_eq!(rx.blocking_recv().unwrap().measurements.len(), 1);
+        }
+        sender.join().unwrap();
+        let ElementStats::Transform { dropped, .. } = health.snapshot().elements[0].1 else {
+            unreachable!()
+        };
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn slow_branch_drops_measurements() {
+        let (registry, ids) = registry(&["a"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let (tx, mut rx) = mpsc::channel(1);
+        let health = PipelineHealth::default();
+        let name = TransformName::from_str("test", "slow");
+        let slow = Downstream {
+            stats: health.register_transform(name.clone()),
+            name,
+            metrics: None,
+            tx,
+        };
+        let (tx, _fast_rx) = mpsc::channel(4);
+        let fast = Downstream {
+            name: TransformName::from_str("test", "fast"),
+            metrics: None,
+            tx,
+            stats: Arc::new(TransformStats::default()),
+        };
+        let mut router = Router::new(vec![slow, fast], out_tx);
+
+        // the second buffer does not fit in the queue: it is dropped instead of blocking the other branch
+        for _ in 0..2 {
+            let parts = router.split(batch(vec![point(ids[0])]), &registry);
+            router.send(parts).unwrap();
+        }
+        assert_eq!(rx.try_recv().unwrap().measurements.len(), 1);
+        assert!(rx.try_recv().is_err());
+        let ElementStats::Transform { dropped, .. } = health.snapshot().elements[0].1 else {
+            unreachable!()
+        };
+        assert_eq!(dropped, 1);
+    }
+
+    #[test]
+    fn subscriptions_with_catch_all() {
+        let (registry, ids) = registry(&["a", "b"]);
+        let (out_tx, _) = broadcast::channel(1);
+        let only_a = downstream("only_a", Some(vec![StringPattern::Exact(String::from("a"))]));
+        let everything_else = downstream("everything_else", None);
+        let mut router = Router::new(vec![only_a, everything_else], out_tx);
+        let parts = router.split(batch(vec![point(ids[0]), point(ids[1]), point(ids[1])]), &registry);
+        assert_eq!(summary(&parts), vec![(Some(0), 1), (Some(1), 2)]);
+    }
+
+    #[test]
+    fn stopped_downstream_is_removed() {
+        let (registry, ids) = registry(&["a"]);
+        let (out_tx, mut out_rx) = broadcast::channel(1);
+        // the receiver of this downstream transform is dropped, as if the transform had stopped
+        let stopped = downstream("stopped", Some(vec![StringPattern::Exact(String::from("a"))]));
+        let mut router = Router::new(vec![stopped], out_tx);
+
+        let parts = router.split(batch(vec![point(ids[0])]), &registry);
+        assert_eq!(summary(&parts), vec![(Some(0), 1)]);
+        router.send(parts).unwrap();
+
+        // the measurements now go to the outputs
+        let parts = router.split(batch(vec![point(ids[0])]), &registry);
+        assert_eq!(summary(&parts), vec![(None, 1)]);
+        router.send(parts).unwrap();
+        assert_eq!(out_rx.try_recv().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,744 @@
+//! Self-monitoring of the measurement pipeline.
+//!
+//! The pipeline elements always keep a few statistics about themselves: how long the sources take to poll,
+//! how long the transforms and outputs take to process the measurements, how many errors they encounter, etc.
+//! These statistics are cheap to collect (a few atomic operations per buffer of measurements).
+//!
+//! When self-monitoring is enabled with [`Builder::self_monitoring`](super::Builder::self_monitoring),
+//! a builtin source named `alumet/self_monitoring` periodically turns the statistics into measurements,
+//! which go through the pipeline like any other measurement.
+//! The measurements that are related to a pipeline element have two attributes, `plugin` and `element`,
+//! which give the name of the element.
+//!
+//! # Metrics
+//! Each value covers the period since the previous poll of the self-monitoring source.
+//!
+//! | Metric | Type | Description |
+//! |--------|------|-------------|
+//! | `alumet_source_poll_duration` | F64 (s) | average duration of a poll of a managed source |
+//! | `alumet_source_poll_overruns` | U64 | number of polls that took longer than the poll interval |
+//! | `alumet_transform_apply_duration` | F64 (s) | average duration of a call to [`Transform::apply`](super::Transform::apply) |
+//! | `alumet_transform_dropped_buffers` | U64 | number of buffers that the transform lost because it was too slow |
+//! | `alumet_output_write_duration` | F64 (s) | average duration of a call to [`Output::write`](super::Output::write) |
+//! | `alumet_output_write_errors` | U64 | number of writes that failed |
+//! | `alumet_output_dropped_buffers` | U64 | number of buffers that the output lost because it was too slow |
+//! | `alumet_channel_queue_depth` | U64 | number of buffers that are waiting in a channel of the pipeline |
+//! | `alumet_metric_registry_size` | U64 | number of metrics in the registry |
+//!
+//! The channel that the sources write to is labelled with `channel = "sources"`, the channel that the outputs
+//! read from (when it is different) is labelled with `channel = "outputs"`. The input channels of the transforms
+//! are labelled with `channel = "transform"`, in addition to the `plugin` and `element` attributes of the transform.
+//!
+//! Asynchronous outputs only report their dropped buffers, since Alumet does not see their writes.
+//!
+//! # Statistics
+//! The underlying counters never reset. A snapshot of them can be obtained at any time,
+//! without disturbing the self-monitoring source, with the control request
+//! [`pipeline_stats`](super::control::request::pipeline_stats).
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc};
+
+use crate::measurement::{MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, MeasurementType, Timestamp};
+use crate::metrics::{
+    Metric, TypedMetricId,
+    duplicate::{DuplicateCriteria, DuplicateReaction},
+    error::MetricCreationError,
+    online::MetricReader,
+    registry::MetricRegistry,
+};
+use crate::pipeline::Source;
+use crate::pipeline::elements::error::PollError;
+use crate::pipeline::elements::transform::run::TransformBatch;
+use crate::pipeline::naming::ElementName;
+use crate::resources::{Resource, ResourceConsumer};
+use crate::units::{PrefixedUnit, Unit};
+
+/// Statistics about the elements and channels of a pipeline.
+#[derive(Default)]
+pub(crate) struct PipelineHealth {
+    elements: Mutex<Vec<(ElementName, StatsRef)>>,
+    channels: Mutex<Vec<(ChannelLabel, ChannelProbe)>>,
+}
+
+enum StatsRef {
+    Source(Arc<SourceStats>),
+    Transform(Arc<TransformStats>),
+    Output(Arc<OutputStats>),
+}
+
+/// Statistics about a managed source.
+#[derive(Default)]
+pub(crate) struct SourceStats {
+    polls: AtomicU64,
+    poll_nanos: AtomicU64,
+    overruns: AtomicU64,
+    /// Current poll interval, in nanoseconds, or 0 if the source is not polled at regular intervals.
+    poll_interval_nanos: AtomicU64,
+    /// Time of the last poll, in nanoseconds since the UNIX epoch, or 0 if the source has never been polled.
+    last_poll_nanos: AtomicU64,
+}
+
+/// Statistics about a transform.
+#[derive(Default)]
+pub(crate) struct TransformStats {
+    applies: AtomicU64,
+    apply_nanos: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Statistics about an output.
+#[derive(Default)]
+pub(crate) struct OutputStats {
+    writes: AtomicU64,
+    write_nanos: AtomicU64,
+    errors: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// A snapshot of the statistics of the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineStats {
+    /// Statistics of each running pipeline element, since its creation.
+    pub elements: Vec<(ElementName, ElementStats)>,
+    /// Number of buffers that are waiting in each channel.
+    pub channels: Vec<(ChannelLabel, usize)>,
+}
+
+/// Cumulative statistics about a pipeline element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementStats {
+    /// Statistics about a managed source.
+    Source {
+        polls: u64,
+        poll_time: Duration,
+        overruns: u64,
+    },
+    /// Statistics about a transform.
+    Transform {
+        applies: u64,
+        apply_time: Duration,
+        dropped: u64,
+    },
+    /// Statistics about an output.
+    Output {
+        writes: u64,
+        write_time: Duration,
+        errors: u64,
+        dropped: u64,
+    },
+}
+
+/// Identifies a channel of the pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelLabel {
+    /// The channel that the sources write to.
+    Sources,
+    /// The channel that the outputs read from, when it differs from the sources channel.
+    Outputs,
+    /// The input channel of a transform.
+    Transform(ElementName),
+}
+
+/// Gives the number of buffers in a channel, without keeping it open.
+enum ChannelProbe {
+    Mpsc(mpsc::WeakSender<MeasurementBuffer>),
+    Transform(mpsc::WeakSender<TransformBatch>),
+    Broadcast(broadcast::WeakSender<MeasurementBuffer>),
+}
+
+impl PipelineHealth {
+    pub fn register_source(&self, name: impl Into<ElementName>) -> Arc<SourceStats> {
+        let stats = Arc::new(SourceStats::default());
+        self.push_element(name.into(), StatsRef::Source(stats.clone()));
+        stats
+    }
+
+    pub fn register_transform(&self, name: impl Into<ElementName>) -> Arc<TransformStats> {
+        let stats = Arc::new(TransformStats::default());
+        self.push_element(name.into(), StatsRef::Transform(stats.clone()));
+        stats
+    }
+
+    pub fn register_output(&self, name: impl Into<ElementName>) -> Arc<OutputStats> {
+        let stats = Arc::new(OutputStats::default());
+        self.push_element(name.into(), StatsRef::Output(stats.clone()));
+        stats
+    }
+
+    /// Returns the current value of the statistics.
+    pub fn snapshot(&self) -> PipelineStats {
+        let mut elements = self.elements.lock().unwrap();
+        forget_stopped(&mut elements);
+        let elements = elements
+            .iter()
+            .map(|(name, stats)| (name.clone(), stats.snapshot()))
+            .collect();
+        let channels = self
+            .channels
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(label, probe)| Some((label.clone(), probe.depth()?)))
+            .collect();
+        PipelineStats { elements, channels }
+    }
+
+    /// Watches the channel that the sources write to.
+    pub fn watch_sources_channel(&self, tx: &mpsc::Sender<MeasurementBuffer>) {
+        self.push_channel(ChannelLabel::Sources, ChannelProbe::Mpsc(tx.downgrade()));
+    }
+
+    /// Watches the channel that the outputs read from, when it differs from the sources channel.
+    pub fn watch_outputs_channel(&self, tx: &broadcast::Sender<MeasurementBuffer>) {
+        self.push_channel(ChannelLabel::Outputs, ChannelProbe::Broadcast(tx.downgrade()));
+    }
+
+    /// Watches the input channel of a transform.
+    pub fn watch_transform_channel(&self, name: impl Into<ElementName>, tx: &mpsc::Sender<TransformBatch>) {
+        self.push_channel(
+            ChannelLabel::Transform(name.into()),
+            ChannelProbe::Transform(tx.downgrade()),
+        );
+    }
+
+    fn push_element(&self, name: ElementName, stats: StatsRef) {
+        let mut elements = self.elements.lock().unwrap();
+        forget_stopped(&mut elements);
+        elements.push((name, stats));
+    }
+
+    fn push_channel(&self, label: ChannelLabel, probe: ChannelProbe) {
+        self.channels.lock().unwrap().push((label, probe));
+    }
+}
+
+impl SourceStats {
+    /// Records a poll that started at `timestamp` and took `duration`.
+    pub fn record_poll(&self, timestamp: Timestamp, duration: Duration) {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.poll_nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        if self.poll_interval().is_some_and(|interval| duration > interval) {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        let (secs, nanos) = timestamp.to_unix_timestamp();
+        self.last_poll_nanos
+            .store(secs * NANOS_PER_SEC + nanos as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the interval at which the source is polled, `None` if it is not polled at regular intervals.
+    pub fn set_poll_interval(&self, interval: Option<Duration>) {
+        let nanos = interval.map_or(0, |d| d.as_nanos() as u64);
+        self.poll_interval_nanos.store(nanos, Ordering::Relaxed);
+    }
+
+    pub fn poll_interval(&self) -> Option<Duration> {
+        let nanos = self.poll_interval_nanos.load(Ordering::Relaxed);
+        (nanos != 0).then(|| Duration::from_nanos(nanos))
+    }
+
+    pub fn last_poll(&self) -> Option<Timestamp> {
+        let nanos = self.last_poll_nanos.load(Ordering::Relaxed);
+        (nanos != 0).then(|| Timestamp::from_unix_timestamp(nanos / NANOS_PER_SEC, (nanos % NANOS_PER_SEC) as u32))
+    }
+}
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+impl TransformStats {
+    pub fn record_apply(&self, duration: Duration) {
+        self.applies.fetch_add(1, Ordering::Relaxed);
+        self.apply_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, n: u64) {
+        self.dropped.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+impl OutputStats {
+    pub fn record_write(&self, duration: Duration, success: bool) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        self.write_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        if !success {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_dropped(&self, n: u64) {
+        self.dropped.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+impl StatsRef {
+    fn snapshot(&self) -> ElementStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        match self {
+            StatsRef::Source(s) => ElementStats::Source {
+                polls: load(&s.polls),
+                poll_time: Duration::from_nanos(load(&s.poll_nanos)),
+                overruns: load(&s.overruns),
+            },
+            StatsRef::Transform(s) => ElementStats::Transform {
+                applies: load(&s.applies),
+                apply_time: Duration::from_nanos(load(&s.apply_nanos)),
+                dropped: load(&s.dropped),
+            },
+            StatsRef::Output(s) => ElementStats::Output {
+                writes: load(&s.writes),
+                write_time: Duration::from_nanos(load(&s.write_nanos)),
+                errors: load(&s.errors),
+                dropped: load(&s.dropped),
+            },
+        }
+    }
+
+    fn is_shared(&self) -> bool {
+        match self {
+            StatsRef::Source(s) => Arc::strong_count(s) > 1,
+            StatsRef::Transform(s) => Arc::strong_count(s) > 1,
+            StatsRef::Output(s) => Arc::strong_count(s) > 1,
+        }
+    }
+
+    /// Identifies the statistics of an element, even if another element with the same name replaces it.
+    fn id(&self) -> usize {
+        match self {
+            StatsRef::Source(s) => Arc::as_ptr(s) as usize,
+            StatsRef::Transform(s) => Arc::as_ptr(s) as usize,
+            StatsRef::Output(s) => Arc::as_ptr(s) as usize,
+        }
+    }
+}
+
+/// Forgets the elements that have stopped: nobody else holds their statistics.
+fn forget_stopped(elements: &mut Vec<(ElementName, StatsRef)>) {
+    elements.retain(|(_, stats)| stats.is_shared());
+}
+
+impl ElementStats {
+    /// Returns the statistics accumulated since the `previous` snapshot of the same element.
+    ///
+    /// The counters never decrease, but the subtractions saturate anyway: a wrong `previous` snapshot
+    /// must not make the self-monitoring source panic.
+    fn since(self, previous: Option<&ElementStats>) -> ElementStats {
+        match (self, previous) {
+            (
+                ElementStats::Source {
+                    polls,
+                    poll_time,
+                    overruns,
+                },
+                Some(ElementStats::Source {
+                    polls: polls0,
+                    poll_time: poll_time0,
+                    overruns: overruns0,
+                }),
+            ) => ElementStats::Source {
+                polls: polls.saturating_sub(*polls0),
+                poll_time: poll_time.saturating_sub(*poll_time0),
+                overruns: overruns.saturating_sub(*overruns0),
+            },
+            (
+                ElementStats::Transform {
+                    applies,
+                    apply_time,
+                    dropped,
+                },
+                Some(ElementStats::Transform {
+                    applies: applies0,
+                    apply_time: apply_time0,
+                    dropped: dropped0,
+                }),
+            ) => ElementStats::Transform {
+                applies: applies.saturating_sub(*applies0),
+                apply_time: apply_time.saturating_sub(*apply_time0),
+                dropped: dropped.saturating_sub(*dropped0),
+            },
+            (
+                ElementStats::Output {
+                    writes,
+                    write_time,
+                    errors,
+                    dropped,
+                },
+                Some(ElementStats::Output {
+                    writes: writes0,
+                    write_time: write_time0,
+                    errors: errors0,
+                    dropped: dropped0,
+                }),
+            ) => ElementStats::Output {
+                writes: writes.saturating_sub(*writes0),
+                write_time: write_time.saturating_sub(*write_time0),
+                errors: errors.saturating_sub(*errors0),
+                dropped: dropped.saturating_sub(*dropped0),
+            },
+            (current, _) => current,
+        }
+    }
+}
+
+impl ChannelLabel {
+    /// Returns the value of the `channel` attribute that identifies this kind of channel.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ChannelLabel::Sources => "sources",
+            ChannelLabel::Outputs => "outputs",
+            ChannelLabel::Transform(_) => "transform",
+        }
+    }
+}
+
+impl ChannelProbe {
+    /// Returns the number of buffers in the channel, or `None` if it has been closed.
+    fn depth(&self) -> Option<usize> {
+        match self {
+            ChannelProbe::Mpsc(weak) => weak.upgrade().map(|tx| tx.max_capacity() - tx.capacity()),
+            ChannelProbe::Transform(weak) => weak.upgrade().map(|tx| tx.max_capacity() - tx.capacity()),
+            ChannelProbe::Broadcast(weak) => weak.upgrade().map(|tx| tx.len()),
+        }
+    }
+}
+
+/// Returns the average duration, in seconds, of `count` operations that took `total` time.
+fn mean_seconds(count: u64, total: Duration) -> Option<f64> {
+    (count > 0).then(|| total.as_secs_f64() / count as f64)
+}
+
+/// Metrics of the self-monitoring source.
+#[derive(Clone, Copy)]
+pub(crate) struct HealthMetrics {
+    source_poll_duration: TypedMetricId<f64>,
+    source_poll_overruns: TypedMetricId<u64>,
+    transform_apply_duration: TypedMetricId<f64>,
+    transform_dropped_buffers: TypedMetricId<u64>,
+    output_write_duration: TypedMetricId<f64>,
+    output_write_errors: TypedMetricId<u64>,
+    output_dropped_buffers: TypedMetricId<u64>,
+    channel_queue_depth: TypedMetricId<u64>,
+    metric_registry_size: TypedMetricId<u64>,
+}
+
+impl HealthMetrics {
+    pub fn register(registry: &mut MetricRegistry) -> Result<Self, MetricCreationError> {
+        fn create<T: MeasurementType>(
+            registry: &mut MetricRegistry,
+            name: &str,
+            unit: impl Into<PrefixedUnit>,
+            description: &str,
+        ) -> Result<TypedMetricId<T>, MetricCreationError> {
+            let metric = Metric {
+                name: name.to_owned(),
+                description: description.to_owned(),
+                value_type: T::wrapped_type(),
+                unit: unit.into(),
+            };
+            let id = registry.register(metric, DuplicateCriteria::Incompatible, DuplicateReaction::Error)?;
+            Ok(TypedMetricId(id, PhantomData))
+        }
+
+        Ok(Self {
+            source_poll_duration: create(
+                registry,
+                "alumet_source_poll_duration",
+                Unit::Second,
+                "average time taken by a source to poll its measurements",
+            )?,
+            source_poll_overruns: create(
+                registry,
+                "alumet_source_poll_overruns",
+                Unit::Unity,
+                "number of polls that took longer than the poll interval of the source",
+            )?,
+            transform_apply_duration: create(
+                registry,
+                "alumet_transform_apply_duration",
+                Unit::Second,
+                "average time taken by a transform to process a buffer of measurements",
+            )?,
+            transform_dropped_buffers: create(
+                registry,
+                "alumet_transform_dropped_buffers",
+                Unit::Unity,
+                "number of buffers of measurements that a transform lost because it was too slow",
+            )?,
+            output_write_duration: create(
+                registry,
+                "alumet_output_write_duration",
+                Unit::Second,
+                "average time taken by an output to write a buffer of measurements",
+            )?,
+            output_write_errors: create(
+                registry,
+                "alumet_output_write_errors",
+                Unit::Unity,
+                "number of buffers of measurements that an output failed to write",
+            )?,
+            output_dropped_buffers: create(
+                registry,
+                "alumet_output_dropped_buffers",
+                Unit::Unity,
+                "number of buffers of measurements that an output lost because it was too slow",
+            )?,
+            channel_queue_depth: create(
+                registry,
+                "alumet_channel_queue_depth",
+                Unit::Unity,
+                "number of buffers of measurements waiting in a channel of the pipeline",
+            )?,
+            metric_registry_size: create(
+                registry,
+                "alumet_metric_registry_size",
+                Unit::Unity,
+                "number of metrics in the registry",
+            )?,
+        })
+    }
+}
+
+/// Source that measures the health of the pipeline.
+pub(crate) struct HealthSource {
+    health: Arc<PipelineHealth>,
+    metrics: HealthMetrics,
+    metrics_reader: MetricReader,
+    /// Statistics at the previous poll, to compute the measurements of the current period.
+    ///
+    /// They are indexed by [`StatsRef::id`] rather than by name, because a stopped element and
+    /// its replacement can have the same name.
+    reported: HashMap<usize, ElementStats>,
+}
+
+impl HealthSource {
+    pub fn new(health: Arc<PipelineHealth>, metrics: HealthMetrics, metrics_reader: MetricReader) -> Self {
+        Self {
+            health,
+            metrics,
+            metrics_reader,
+            reported: HashMap::new(),
+        }
+    }
+}
+
+impl Source for HealthSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let m = &self.metrics;
+        let consumer = ResourceConsumer::Process {
+            pid: std::process::id(),
+        };
+        let point = |metric, value: f64| {
+            MeasurementPoint::new(timestamp, metric, Resource::LocalMachine, consumer.clone(), value)
+        };
+        let count = |metric, value: u64| {
+            MeasurementPoint::new(timestamp, metric, Resource::LocalMachine, consumer.clone(), value)
+        };
+        let labelled = |p: MeasurementPoint, name: &ElementName| {
+            p.with_attr("plugin", name.plugin.clone())
+                .with_attr("element", name.element.clone())
+        };
+
+        let mut elements = self.health.elements.lock().unwrap();
+        for (name, stats) in elements.iter() {
+            let current = stats.snapshot();
+            let delta = current.since(self.reported.get(&stats.id()));
+            self.reported.insert(stats.id(), current);
+            match delta {
+                ElementStats::Source {
+                    polls,
+                    poll_time,
+                    overruns,
+                } => {
+                    if let Some(mean) = mean_seconds(polls, poll_time) {
+                        measurements.push(labelled(point(m.source_poll_duration, mean), name));
+                    }
+                    measurements.push(labelled(count(m.source_poll_overruns, overruns), name));
+                }
+                ElementStats::Transform {
+                    applies,
+                    apply_time,
+                    dropped,
+                } => {
+                    if let Some(mean) = mean_seconds(applies, apply_time) {
+                        measurements.push(labelled(point(m.transform_apply_duration, mean), name));
+                    }
+                    measurements.push(labelled(count(m.transform_dropped_buffers, dropped), name));
+                }
+                ElementStats::Output {
+                    writes,
+                    write_time,
+                    errors,
+                    dropped,
+                } => {
+                    if let Some(mean) = mean_seconds(writes, write_time) {
+                        measurements.push(labelled(point(m.output_write_duration, mean), name));
+                    }
+                    measurements.push(labelled(count(m.output_write_errors, errors), name));
+                    measurements.push(labelled(count(m.output_dropped_buffers, dropped), name));
+                }
+            }
+        }
+        forget_stopped(&mut elements);
+        self.reported
+            .retain(|id, _| elements.iter().any(|(_, stats)| stats.id() == *id));
+        drop(elements);
+
+        let mut channels = self.health.channels.lock().unwrap();
+        channels.retain(|(label, probe)| {
+            let Some(depth) = probe.depth() else {
+                return false; // the channel has been closed
+            };
+            let p = count(m.channel_queue_depth, depth as u64).with_attr("channel", label.kind());
+            let p = match label {
+                ChannelLabel::Transform(name) => labelled(p, name),
+                _ => p,
+            };
+            measurements.push(p);
+            true
+        });
+        drop(channels);
+
+        // The registry may be locked for writing, in which case we simply skip this measurement.
+        if let Some(registry) = self.metrics_reader.try_read() {
+            measurements.push(count(m.metric_registry_size, registry.len() as u64));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::{broadcast, mpsc};
+
+    use super::{ElementStats, PipelineHealth, mean_seconds};
+    use crate::measurement::{MeasurementBuffer, Timestamp};
+    use crate::pipeline::naming::{OutputName, SourceName, TransformName};
+
+    #[test]
+    fn stats() {
+        let health = PipelineHealth::default();
+        let source = health.register_source(SourceName::from_str("p", "s"));
+        let t0 = Timestamp::now();
+        source.set_poll_interval(Some(Duration::from_millis(10)));
+        source.record_poll(t0, Duration::from_millis(1));
+        source.record_poll(t0, Duration::from_millis(20));
+        source.set_poll_interval(None);
+        source.record_poll(t0, Duration::from_millis(30));
+        assert_eq!(source.poll_interval(), None);
+        assert_eq!(source.last_poll(), Some(t0));
+
+        let transform = health.register_transform(TransformName::from_str("p", "t"));
+        transform.record_apply(Duration::from_secs(2));
+        transform.record_dropped(1);
+
+        let output = health.register_output(OutputName::from_str("p", "o"));
+        output.record_write(Duration::from_secs(1), true);
+        output.record_write(Duration::from_secs(3), false);
+        output.record_dropped(5);
+
+        let snapshot = health.snapshot();
+        let stats: Vec<_> = snapshot.elements.iter().map(|(_, s)| *s).collect();
+        assert_eq!(
+            stats,
+            vec![
+                ElementStats::Source {
+                    polls: 3,
+                    poll_time: Duration::from_millis(51),
+                    overruns: 1
+                },
+                ElementStats::Transform {
+                    applies: 1,
+                    apply_time: Duration::from_secs(2),
+                    dropped: 1
+                },
+                ElementStats::Output {
+                    writes: 2,
+                    write_time: Duration::from_secs(4),
+                    errors: 1,
+                    dropped: 5
+                },
+            ]
+        );
+        // taking a snapshot does not reset the counters
+        assert_eq!(health.snapshot(), snapshot);
+
+        // the self-monitoring source measures the difference between two snapshots
+        source.record_poll(t0, Duration::from_millis(9));
+        let delta = health.snapshot().elements[0].1.since(Some(&stats[0]));
+        assert_eq!(
+            delta,
+            ElementStats::Source {
+                polls: 1,
+                poll_time: Duration::from_millis(9),
+                overruns: 0
+            }
+        );
+        let mean = mean_seconds(3, Duration::from_millis(51)).unwrap();
+        assert!((mean - 0.017).abs() < 1e-9, "wrong mean {mean}");
+        assert_eq!(mean_seconds(0, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn stopped_elements() {
+        let health = PipelineHealth::default();
+        let old = health.register_output(OutputName::from_str("p", "o"));
+        old.record_write(Duration::from_secs(1), true);
+        old.record_write(Duration::from_secs(1), true);
+        let previous = health.snapshot().elements[0].1;
+
+        // the output is replaced by a new one with the same name
+        drop(old);
+        let new = health.register_output(OutputName::from_str("p", "o"));
+        new.record_write(Duration::from_secs(1), true);
+        let snapshot = health.snapshot();
+        assert_eq!(snapshot.elements.len(), 1, "the stopped output should be forgotten");
+        let current = snapshot.elements[0].1;
+
+        // comparing with the statistics of the old output must not underflow
+        assert_eq!(
+            current.since(Some(&previous)),
+            ElementStats::Output {
+                writes: 0,
+                write_time: Duration::ZERO,
+                errors: 0,
+                dropped: 0
+            }
+        );
+
+        drop(new);
+        assert_eq!(health.snapshot().elements, vec![]);
+    }
+
+    #[test]
+    fn channel_depth() {
+        let health = PipelineHealth::default();
+        let (tx, rx) = mpsc::channel::<MeasurementBuffer>(4);
+        let out_tx = broadcast::Sender::<MeasurementBuffer>::new(4);
+        let _out_rx = out_tx.subscribe();
+        health.watch_sources_channel(&tx);
+        health.watch_outputs_channel(&out_tx);
+
+        tx.try_send(MeasurementBuffer::new()).unwrap();
+        tx.try_send(MeasurementBuffer::new()).unwrap();
+        out_tx.send(MeasurementBuffer::new()).unwrap();
+        let depths: Vec<_> = health.channels.lock().unwrap().iter().map(|(_, p)| p.depth()).collect();
+        assert_eq!(depths, vec![Some(2), Some(1)]);
+
+        // the probes do not keep the channels open
+        drop(tx);
+        drop(rx);
+        drop(out_tx);
+        let depths: Vec<_> = health.channels.lock().unwrap().iter().map(|(_, p)| p.depth()).collect();
+        assert_eq!(depths, vec![None, None]);
+    }
+}
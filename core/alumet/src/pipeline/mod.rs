@@ -14,6 +14,7 @@ pub mod builder;
 pub mod control;
 pub mod elements;
 pub mod error;
+pub mod health;
 pub mod naming;
 pub(crate) mod util;
 
@@ -3,7 +3,7 @@ use thiserror::Error;
 
 use crate::pipeline::naming::ElementKind;
 
-use super::matching::StringPattern;
+use super::matching::{ElementNamePattern, StringPattern};
 
 /// Parses a string to an `ElementKind`.
 ///
@@ -19,10 +19,51 @@ pub fn parse_kind(kind: &str) -> Result<Option<ElementKind>, KindParseError> {
     }
 }
 
+/// Parses a pattern of the form `kind/plugin/element` or `kind`.
+///
+/// The kind is parsed with [`parse_kind`], the plugin and element names are [`StringPattern`]s.
+/// For instance, `sources/rapl/*` matches every source of the `rapl` plugin, and `outputs` matches every output.
+pub fn parse_pattern(pat: &str) -> Result<ElementNamePattern, PatternParseError> {
+    let parse_kind = |kind: &str| parse_kind(kind).map_err(|_| PatternParseError::Kind(kind.to_owned()));
+    let parse_string = |s: &str| {
+        StringPattern::from_str(s).map_err(|error| PatternParseError::Name {
+            pattern: s.to_owned(),
+            error,
+        })
+    };
+    let parts: Vec<_> = pat.splitn(3, '/').collect();
+    match parts[..] {
+        [kind, plugin, element] => Ok(ElementNamePattern {
+            kind: parse_kind(kind)?,
+            plugin: parse_string(plugin)?,
+            element: parse_string(element)?,
+        }),
+        [kind] => Ok(ElementNamePattern {
+            kind: parse_kind(kind)?,
+            plugin: StringPattern::Any,
+            element: StringPattern::Any,
+        }),
+        _ => Err(PatternParseError::Format(pat.to_owned())),
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("invalid element kind")]
 pub struct KindParseError;
 
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PatternParseError {
+    #[error("bad kind: '{0}'")]
+    Kind(String),
+    #[error("bad pattern '{pattern}': {error}")]
+    Name {
+        pattern: String,
+        error: NamePatternParseError,
+    },
+    #[error("bad pattern, expected kind/plugin/element but got '{0}'")]
+    Format(String),
+}
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum NamePatternParseError {
     #[error("invalid pattern: asterisk '*' in the middle of the string")]
@@ -67,7 +108,8 @@ impl FromStr for StringPattern {
 
 #[cfg(test)]
 mod tests {
-    use super::{NamePatternParseError, StringPattern};
+    use super::{NamePatternParseError, PatternParseError, StringPattern, parse_pattern};
+    use crate::pipeline::naming::{ElementKind, matching::ElementNamePattern};
     use std::str::FromStr;
 
     #[test]
@@ -90,4 +132,37 @@ mod tests {
         assert_eq!(StringPattern::from_str(""), Err(NamePatternParseError::Empty));
         Ok(())
     }
+
+    #[test]
+    fn parse_element_pattern() {
+        assert_eq!(
+            parse_pattern("source/rapl/*"),
+            Ok(ElementNamePattern {
+                kind: Some(ElementKind::Source),
+                plugin: StringPattern::Exact(String::from("rapl")),
+                element: StringPattern::Any,
+            })
+        );
+        assert_eq!(
+            parse_pattern("outputs"),
+            Ok(ElementNamePattern {
+                kind: Some(ElementKind::Output),
+                plugin: StringPattern::Any,
+                element: StringPattern::Any,
+            })
+        );
+        assert_eq!(parse_pattern("*"), Ok(ElementNamePattern::wildcard()));
+        assert_eq!(
+            parse_pattern("source/without-element").unwrap_err().to_string(),
+            "bad pattern, expected kind/plugin/element but got 'source/without-element'"
+        );
+        assert_eq!(
+            parse_pattern("nope/x/y"),
+            Err(PatternParseError::Kind(String::from("nope")))
+        );
+        assert_eq!(
+            parse_pattern("src/a*b/*").unwrap_err().to_string(),
+            "bad pattern 'a*b': invalid pattern: asterisk '*' in the middle of the string"
+        );
+    }
 }
@@ -39,6 +39,11 @@ impl SharedStreamState {
         self.state.store(state as u8, Ordering::Relaxed);
         self.waker.wake();
     }
+
+    /// Returns the current state of the stream.
+    pub fn get(&self) -> StreamState {
+        StreamState::from(self.state.load(Ordering::Relaxed))
+    }
 }
 
 impl<S: Stream> ControlledStream<S> {
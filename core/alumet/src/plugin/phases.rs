@@ -14,7 +14,7 @@ use crate::pipeline::elements::source::builder::{ManagedSource, SourceBuilder, S
 use crate::pipeline::elements::source::control::TaskState;
 use crate::pipeline::elements::source::trigger::TriggerSpec;
 use crate::pipeline::elements::{output, source, transform};
-use crate::pipeline::naming::{PluginName, namespace::DuplicateNameError};
+use crate::pipeline::naming::{PluginName, TransformName, namespace::DuplicateNameError};
 use crate::pipeline::{self, Output, Source, Transform};
 use crate::units::PrefixedUnit;
 
@@ -306,6 +306,28 @@ impl<'a> AlumetPluginStart<'a> {
             .add_transform_builder(plugin, name, Box::new(builder))
     }
 
+    /// Declares which measurements a transform of the current plugin receives.
+    ///
+    /// By default, the transforms form a chain: each transform receives the output of
+    /// the previous one. With this method, a transform can subscribe to some metrics only,
+    /// or to the output of a particular transform. Independent transforms run in parallel.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use alumet::pipeline::elements::transform::graph::TransformInputs;
+    /// use alumet::pipeline::matching::StringPattern;
+    /// # use alumet::plugin::AlumetPluginStart;
+    /// # let alumet: &mut AlumetPluginStart = todo!();
+    ///
+    /// // Only receive the measurements of `rapl_consumed_energy`, directly from the sources.
+    /// let inputs = TransformInputs::sources().with_metrics(vec![StringPattern::Exact(String::from("rapl_consumed_energy"))]);
+    /// alumet.set_transform_inputs("name", inputs);
+    /// ```
+    pub fn set_transform_inputs(&mut self, name: &str, inputs: transform::graph::TransformInputs) {
+        let name = TransformName::new(self.current_plugin.0.clone(), name.to_owned());
+        self.pipeline_builder.transform_inputs(name, inputs);
+    }
+
     /// Adds a _blocking_ output to the Alumet pipeline.
     ///
     /// # Example
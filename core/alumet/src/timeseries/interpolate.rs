@@ -62,6 +62,9 @@ impl Interpolator2 for LinearInterpolator {
         point.value = match point.value {
             WrappedMeasurementValue::F64(_) => WrappedMeasurementValue::F64(interpolated),
             WrappedMeasurementValue::U64(_) => WrappedMeasurementValue::U64(interpolated.round() as u64),
+            WrappedMeasurementValue::I64(_) => WrappedMeasurementValue::I64(interpolated.round() as i64),
+            // a distribution cannot be interpolated, keep the previous one
+            WrappedMeasurementValue::Histogram(h) => WrappedMeasurementValue::Histogram(h),
         };
         point
     }
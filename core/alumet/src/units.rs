@@ -5,6 +5,7 @@ use std::{
     fmt::{self, Debug, Display},
     str::FromStr,
 };
+use thiserror::Error;
 
 /// A unit of measurement.
 ///
@@ -80,6 +81,32 @@ pub struct PrefixedUnit {
     pub prefix: UnitPrefix,
 }
 
+/// An affine conversion from one unit to another, obtained with [`PrefixedUnit::conversion_to`].
+///
+/// # Example
+/// ```
+/// use alumet::units::{Unit, PrefixedUnit};
+///
+/// let to_wh = PrefixedUnit::from(Unit::Joule).conversion_to(&Unit::WattHour.into()).unwrap();
+/// assert_eq!(to_wh.apply(7200.0), 2.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    // The conversion is computed as `(value * numerator + offset) / denominator`,
+    // which gives exact results for the common conversions (J to Wh, °C to °F, kJ to J, ...).
+    numerator: f64,
+    offset: f64,
+    denominator: f64,
+}
+
+/// Error returned when converting between units that do not measure the same quantity.
+#[derive(Debug, Error)]
+#[error("cannot convert from '{}' to '{}'", .from.unique_name(), .to.unique_name())]
+pub struct ConversionError {
+    pub from: PrefixedUnit,
+    pub to: PrefixedUnit,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UnitPrefix {
     Nano,
@@ -140,6 +167,19 @@ impl Unit {
         }
     }
 
+    /// Returns the reference unit of the quantity measured by this unit, and how to express values in it.
+    ///
+    /// A value `v` in this unit is equal to `v * size + offset` in the reference unit.
+    /// The reference unit of temperatures is a ninth of a degree Celsius, so that the sizes are integers.
+    fn reference(&self) -> (&Unit, f64, f64) {
+        match self {
+            Unit::WattHour => (&Unit::Joule, 3600.0, 0.0),
+            Unit::DegreeCelsius => (&Unit::DegreeCelsius, 9.0, 0.0),
+            Unit::DegreeFahrenheit => (&Unit::DegreeCelsius, 5.0, -160.0),
+            unit => (unit, 1.0, 0.0),
+        }
+    }
+
     fn with_prefix(self, scale: UnitPrefix) -> PrefixedUnit {
         PrefixedUnit {
             base_unit: self,
@@ -224,6 +264,61 @@ impl PrefixedUnit {
     pub fn display_name(&self) -> String {
         format!("{self}")
     }
+
+    /// Returns the conversion from this unit to the unit `to`.
+    ///
+    /// Fails if the two units do not measure the same quantity.
+    /// Besides the prefixes, the supported conversions are Joule ↔ Watt-hour and °C ↔ °F.
+    pub fn conversion_to(&self, to: &PrefixedUnit) -> Result<Conversion, ConversionError> {
+        let (from_ref, from_size, from_offset) = self.base_unit.reference();
+        let (to_ref, to_size, to_offset) = to.base_unit.reference();
+        if from_ref != to_ref {
+            return Err(ConversionError {
+                from: self.clone(),
+                to: to.clone(),
+            });
+        }
+        // Apply the difference of prefixes to the numerator or to the denominator, so that
+        // we only multiply by powers of ten, which are exact.
+        let exponent = self.prefix.exponent() - to.prefix.exponent();
+        let (numerator, denominator) = if exponent >= 0 {
+            (from_size * pow10(exponent), to_size)
+        } else {
+            (from_size, to_size * pow10(-exponent))
+        };
+        // The offsets are not affected by the prefix of `self`, but they are by the prefix of `to`.
+        let offset = (from_offset - to_offset) * denominator / (to_size * to.prefix.factor());
+        Ok(Conversion {
+            numerator,
+            offset,
+            denominator,
+        })
+    }
+
+    /// Converts a value from this unit to the unit `to`.
+    ///
+    /// # Example
+    /// ```
+    /// use alumet::units::{Unit, PrefixedUnit};
+    ///
+    /// let celsius = PrefixedUnit::from(Unit::DegreeCelsius);
+    /// let fahrenheit = PrefixedUnit::from(Unit::DegreeFahrenheit);
+    /// assert_eq!(celsius.convert(100.0, &fahrenheit).unwrap(), 212.0);
+    /// ```
+    pub fn convert(&self, value: f64, to: &PrefixedUnit) -> Result<f64, ConversionError> {
+        self.conversion_to(to).map(|c| c.apply(value))
+    }
+}
+
+impl Conversion {
+    /// Converts a value.
+    pub fn apply(&self, value: f64) -> f64 {
+        (value * self.numerator + self.offset) / self.denominator
+    }
+}
+
+fn pow10(exponent: i32) -> f64 {
+    10_f64.powi(exponent)
 }
 
 impl From<Unit> for PrefixedUnit {
@@ -292,6 +387,32 @@ impl UnitPrefix {
             UnitPrefix::Giga => "G",
         }
     }
+
+    /// Returns the power of ten that corresponds to the prefix, for instance 3 for `Kilo`.
+    pub fn exponent(&self) -> i32 {
+        match self {
+            UnitPrefix::Nano => -9,
+            UnitPrefix::Micro => -6,
+            UnitPrefix::Milli => -3,
+            UnitPrefix::Plain => 0,
+            UnitPrefix::Kilo => 3,
+            UnitPrefix::Mega => 6,
+            UnitPrefix::Giga => 9,
+        }
+    }
+
+    /// Returns the multiplier that corresponds to the prefix, for instance `1e3` for `Kilo`.
+    pub fn factor(&self) -> f64 {
+        match self {
+            UnitPrefix::Nano => 1e-9,
+            UnitPrefix::Micro => 1e-6,
+            UnitPrefix::Milli => 1e-3,
+            UnitPrefix::Plain => 1.0,
+            UnitPrefix::Kilo => 1e3,
+            UnitPrefix::Mega => 1e6,
+            UnitPrefix::Giga => 1e9,
+        }
+    }
 }
 
 impl Display for UnitPrefix {
@@ -383,4 +504,53 @@ mod tests {
         assert!("dW".parse::<PrefixedUnit>().is_err()); // non-standard prefixes
         assert!(" kW".parse::<PrefixedUnit>().is_err()); // whitespace
     }
+
+    #[test]
+    fn conversions() {
+        fn convert(value: f64, from: &str, to: &str) -> f64 {
+            let from: PrefixedUnit = from.parse().unwrap();
+            let to: PrefixedUnit = to.parse().unwrap();
+            from.convert(value, &to)
+                .unwrap_or_else(|e| panic!("conversion should succeed: {e}"))
+        }
+        // prefixes
+        assert_eq!(convert(1.5, "kJ", "J"), 1500.0);
+        assert_eq!(convert(1500.0, "J", "kJ"), 1.5);
+        assert_eq!(convert(2_000_000.0, "μJ", "J"), 2.0);
+        assert_eq!(convert(3.0, "W", "mW"), 3000.0);
+        assert_eq!(convert(42.0, "s", "s"), 42.0);
+        // energy
+        assert_eq!(convert(7200.0, "J", "W.h"), 2.0);
+        assert_eq!(convert(2.0, "W.h", "J"), 7200.0);
+        assert_eq!(convert(1.0, "kW.h", "MJ"), 3.6);
+        assert_eq!(convert(3_600_000_000.0, "μJ", "W.h"), 1.0);
+        // temperature
+        assert_eq!(convert(100.0, "Cel", "[degF]"), 212.0);
+        assert_eq!(convert(-40.0, "[degF]", "Cel"), -40.0);
+        assert_eq!(convert(32.0, "[degF]", "Cel"), 0.0);
+        assert_eq!(convert(45500.0, "mCel", "Cel"), 45.5);
+        assert_eq!(convert(37.0, "Cel", "mCel"), 37000.0);
+        assert_eq!(convert(0.0, "mCel", "[degF]"), 32.0);
+    }
+
+    #[test]
+    fn invalid_conversions() {
+        let joule = PrefixedUnit::from(Unit::Joule);
+        assert!(joule.convert(1.0, &Unit::Watt.into()).is_err());
+        assert!(joule.convert(1.0, &Unit::DegreeCelsius.into()).is_err());
+        assert!(
+            PrefixedUnit::from(Unit::Unity)
+                .convert(1.0, &Unit::Percent.into())
+                .is_err()
+        );
+
+        let custom = |name: &str| {
+            PrefixedUnit::from(Unit::Custom {
+                unique_name: name.to_owned(),
+                display_name: name.to_owned(),
+            })
+        };
+        assert_eq!(custom("g_CO2").convert(5.0, &custom("g_CO2")).unwrap(), 5.0);
+        assert!(custom("g_CO2").convert(5.0, &custom("other")).is_err());
+    }
 }
@@ -192,6 +192,8 @@ impl Transform for TestTransform {
             res.value = match res.value {
                 f @ WrappedMeasurementValue::F64(_) => f,
                 WrappedMeasurementValue::U64(i) => WrappedMeasurementValue::F64(i as f64),
+                WrappedMeasurementValue::I64(i) => WrappedMeasurementValue::F64(i as f64),
+                h @ WrappedMeasurementValue::Histogram(_) => h,
             };
             res
         }
@@ -6,10 +6,11 @@ use alumet::{
         Output, Source, Transform,
         control::{
             handle::SendWaitError,
-            request::{self, ElementListFilter},
+            request::{self, ElementListFilter, ElementState},
         },
         elements::source::trigger::TriggerSpec,
-        naming::{ElementKind, ElementName, PluginName, SourceName},
+        health::ElementStats,
+        naming::{ElementKind, ElementName, PluginName, SourceName, TransformName},
     },
     plugin::{PluginMetadata, rust::AlumetPlugin},
     static_plugins,
@@ -221,6 +222,223 @@ fn list_filter() {
     );
 }
 
+#[test]
+fn status_and_stats() {
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+    let plugins = PluginSet::from(static_plugins![TestPlugin]);
+
+    let agent = agent::Builder::new(plugins).build_and_start().unwrap();
+    let handle = agent.pipeline.control_handle();
+    let rt = current_thread_runtime();
+
+    // reconfigure some elements
+    let source = SourceName::from_str("plugin", "dummy_src");
+    let request = request::source(source).set_trigger(TriggerSpec::at_interval(Duration::from_millis(20)));
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    let request = request::transform(TransformName::from_str("plugin", "dummy_tr")).disable();
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    // the trigger of the source is not interruptible: the new period applies after the next 1s tick
+    std::thread::sleep(Duration::from_millis(1300));
+
+    // the status reflects the new configuration
+    let mut status = rt
+        .block_on(handle.send_wait(request::element_status(ElementListFilter::kind_any()), TIMEOUT))
+        .unwrap();
+    status.sort_by_key(|s| s.name.kind as u8);
+    let states: Vec<_> = status.iter().map(|s| (s.name.element.as_str(), s.state)).collect();
+    assert_eq!(
+        states,
+        vec![
+            ("dummy_src", ElementState::Running),
+            ("dummy_tr", ElementState::Paused),
+            ("dummy_out", ElementState::Running),
+        ]
+    );
+    assert_eq!(status[0].poll_interval, Some(Duration::from_millis(20)));
+    assert!(status[0].last_poll.is_some());
+    assert_eq!(status[1].poll_interval, None);
+    assert_eq!(status[1].last_poll, None);
+
+    // the statistics are available
+    let stats = rt
+        .block_on(handle.send_wait(request::pipeline_stats(), TIMEOUT))
+        .unwrap();
+    let (_, source_stats) = stats
+        .elements
+        .iter()
+        .find(|(name, _)| name.element == "dummy_src")
+        .expect("the source should have statistics");
+    match source_stats {
+        ElementStats::Source { polls, .. } => assert!(*polls > 2, "not enough polls: {polls}"),
+        s => panic!("unexpected statistics for a source: {s:?}"),
+    }
+    assert!(!stats.channels.is_empty());
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+#[test]
+fn trigger_group() {
+    use alumet::measurement::Timestamp;
+    use std::sync::{Arc, Mutex};
+
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+    let agent = agent::Builder::new(PluginSet::new()).build_and_start().unwrap();
+    let handle = agent.pipeline.control_handle();
+    let plugin_handle = handle.clone().with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    // create two sources in the same group, the second period is ignored
+    let timestamps_a = Arc::new(Mutex::new(Vec::new()));
+    let timestamps_b = Arc::new(Mutex::new(Vec::new()));
+    let trigger = |period| TriggerSpec::builder(period).group("sync").build().unwrap();
+    let request = request::create_many()
+        .add_source(
+            "a",
+            Box::new(RecordingSource(timestamps_a.clone())),
+            trigger(Duration::from_millis(50)),
+        )
+        .add_source(
+            "b",
+            Box::new(RecordingSource(timestamps_b.clone())),
+            trigger(Duration::from_secs(10)),
+        )
+        .build();
+    rt.block_on(plugin_handle.send_wait(request, TIMEOUT)).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+
+    // change the period of the group
+    let request = request::set_group_period("sync", Duration::from_millis(100));
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    let request = request::set_group_period("unknown", Duration::from_millis(100));
+    let res = rt.block_on(handle.send_wait(request, TIMEOUT));
+    assert!(
+        matches!(res, Err(SendWaitError::Operation(_))),
+        "setting the period of an unknown group should fail"
+    );
+    std::thread::sleep(Duration::from_millis(400));
+
+    let status = rt
+        .block_on(handle.send_wait(
+            request::element_status(ElementListFilter::kind(ElementKind::Source)),
+            TIMEOUT,
+        ))
+        .unwrap();
+    assert_eq!(status.len(), 2);
+    for s in status {
+        assert_eq!(
+            s.poll_interval,
+            Some(Duration::from_millis(100)),
+            "wrong period for {}",
+            s.name
+        );
+    }
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+
+    // the sources have been polled at the same time, on the boundaries of the period
+    let a = timestamps_a.lock().unwrap();
+    let b = timestamps_b.lock().unwrap();
+    let n = a.len().min(b.len());
+    assert!(n > 5, "not enough polls: {n}");
+    assert_eq!(a[..n], b[..n]);
+    for t in a.iter() {
+        let (_, nanos) = t.to_unix_timestamp();
+        assert_eq!(nanos % 50_000_000, 0, "timestamp not aligned: {t:?}");
+    }
+    let last_interval = a[n - 1].duration_since(a[n - 2]).unwrap();
+    assert_eq!(last_interval, Duration::from_millis(100));
+
+    struct RecordingSource(Arc<Mutex<Vec<Timestamp>>>);
+
+    impl Source for RecordingSource {
+        fn poll(
+            &mut self,
+            _measurements: &mut alumet::measurement::MeasurementAccumulator,
+            timestamp: Timestamp,
+        ) -> Result<(), alumet::pipeline::elements::error::PollError> {
+            self.0.lock().unwrap().push(timestamp);
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn set_interval() {
+    use alumet::pipeline::{
+        elements::source::trigger::builder,
+        matching::{SourceNamePattern, StringPattern},
+    };
+
+    let _ = env_logger::try_init_from_env(env_logger::Env::default());
+    let agent = agent::Builder::new(PluginSet::new()).build_and_start().unwrap();
+    let handle = agent.pipeline.control_handle();
+    let plugin_handle = handle.clone().with_plugin(PluginName(String::from("test")));
+    let rt = current_thread_runtime();
+
+    // one source in a group, one source polled on demand
+    let grouped = TriggerSpec::builder(Duration::from_millis(50))
+        .group("sync")
+        .build()
+        .unwrap();
+    let manual = builder::manual().build().unwrap();
+    let request = request::create_many()
+        .add_source("grouped", Box::new(DummySource), grouped)
+        .add_source("manual", Box::new(DummySource), manual)
+        .build();
+    rt.block_on(plugin_handle.send_wait(request, TIMEOUT)).unwrap();
+
+    // change the interval of every source of the plugin
+    let sources = SourceNamePattern::new(StringPattern::Exact(String::from("test")), StringPattern::Any);
+    let request = request::source(sources).set_interval(Duration::from_millis(100), None);
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+
+    let mut status = rt
+        .block_on(handle.send_wait(
+            request::element_status(ElementListFilter::kind(ElementKind::Source)),
+            TIMEOUT,
+        ))
+        .unwrap();
+    status.sort_by(|a, b| a.name.element.cmp(&b.name.element));
+    // the source polled on demand is left unchanged
+    assert_eq!(status[0].name.element, "grouped");
+    assert_eq!(status[0].poll_interval, Some(Duration::from_millis(100)));
+    assert_eq!(status[1].name.element, "manual");
+    assert_eq!(status[1].poll_interval, None);
+
+    // the grouped source is still in its group
+    let stats = rt
+        .block_on(handle.send_wait(request::pipeline_stats(), TIMEOUT))
+        .unwrap();
+    let polls = |stats: &alumet::pipeline::health::PipelineStats| {
+        stats
+            .elements
+            .iter()
+            .find_map(|(name, s)| match s {
+                ElementStats::Source { polls, .. } if name.element == "grouped" => Some(*polls),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let polls_before = polls(&stats);
+    let request = request::set_group_period("sync", Duration::from_millis(20));
+    rt.block_on(handle.send_wait(request, TIMEOUT)).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    let stats = rt
+        .block_on(handle.send_wait(request::pipeline_stats(), TIMEOUT))
+        .unwrap();
+    assert!(
+        polls(&stats) - polls_before > 6,
+        "the source should follow the period of its group"
+    );
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
 #[test]
 fn source_flush() {
     let _ = env_logger::try_init_from_env(env_logger::Env::default());
@@ -0,0 +1,173 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use alumet::{
+    agent::{self, plugin::PluginSet},
+    measurement::{MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{
+        self,
+        elements::{
+            error::PollError,
+            output::{
+                OutputContext, WriteError,
+                routing::{AttributePattern, MeasurementSelector, OutputRoute},
+            },
+            source::trigger::TriggerSpec,
+        },
+        matching::{OutputNamePattern, StringPattern},
+    },
+    plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin},
+    resources::{Resource, ResourceConsumer},
+    static_plugins,
+    units::Unit,
+};
+
+/// The (output, metric name, domain) of every point written by the outputs.
+static WRITES: Mutex<Vec<(&'static str, String, String)>> = Mutex::new(Vec::new());
+
+struct RoutingPlugin;
+
+struct TwoMetricsSource {
+    perf: TypedMetricId<u64>,
+    energy: TypedMetricId<u64>,
+}
+
+struct RecordingOutput(&'static str);
+
+impl AlumetPlugin for RoutingPlugin {
+    fn name() -> &'static str {
+        "routing"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(RoutingPlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let source = TwoMetricsSource {
+            perf: alumet.create_metric("perf_instructions", Unit::Unity, "")?,
+            energy: alumet.create_metric("energy", Unit::Unity, "")?,
+        };
+        let trigger = TriggerSpec::at_interval(Duration::from_millis(20));
+        alumet.add_source("two", Box::new(source), trigger)?;
+        alumet.add_blocking_output("perf", Box::new(RecordingOutput("perf")))?;
+        alumet.add_blocking_output("package", Box::new(RecordingOutput("package")))?;
+        alumet.add_blocking_output("all", Box::new(RecordingOutput("all")))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Source for TwoMetricsSource {
+    fn poll(&mut self, m: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        let point = |metric, domain: &'static str| {
+            MeasurementPoint::new(t, metric, Resource::LocalMachine, ResourceConsumer::LocalMachine, 1)
+                .with_attr("domain", domain)
+        };
+        m.push(point(self.perf, "core"));
+        m.push(point(self.energy, "package"));
+        m.push(point(self.energy, "dram"));
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for RecordingOutput {
+    fn write(&mut self, m: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut writes = WRITES.lock().unwrap();
+        for p in m {
+            let metric = ctx.metrics.by_id(&p.metric).unwrap().name.clone();
+            let domain = p.attributes().find(|(k, _)| *k == "domain").unwrap().1.to_string();
+            writes.push((self.0, metric, domain));
+        }
+        Ok(())
+    }
+}
+
+fn output(name: &str) -> OutputNamePattern {
+    OutputNamePattern::new(
+        StringPattern::Exact(String::from("routing")),
+        StringPattern::Exact(name.to_owned()),
+    )
+}
+
+#[test]
+fn routed_outputs_receive_their_selection() -> anyhow::Result<()> {
+    let mut pipeline_builder = pipeline::Builder::new();
+    pipeline_builder.route_outputs(OutputRoute {
+        outputs: output("perf"),
+        selector: MeasurementSelector::metrics(vec![StringPattern::StartWith(String::from("perf_"))]),
+    });
+    pipeline_builder.route_outputs(OutputRoute {
+        outputs: output("package"),
+        selector: MeasurementSelector {
+            attributes: vec![AttributePattern {
+                key: String::from("domain"),
+                value: StringPattern::Exact(String::from("package")),
+            }],
+            ..Default::default()
+        },
+    });
+    let plugins = PluginSet::from(static_plugins![RoutingPlugin]);
+    let agent = agent::Builder::from_pipeline(plugins, pipeline_builder).build_and_start()?;
+
+    // wait for a few polls to reach every output
+    let start = SystemTime::now();
+    while start.elapsed()? < Duration::from_secs(5) {
+        let writes = WRITES.lock().unwrap();
+        if ["perf", "package", "all"]
+            .iter()
+            .all(|out| writes.iter().filter(|(o, _, _)| o == out).count() >= 3)
+        {
+            break;
+        }
+        drop(writes);
+        thread::sleep(Duration::from_millis(20));
+    }
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(Duration::from_secs(2))?;
+
+    let writes = WRITES.lock().unwrap();
+    let received = |out: &str| -> Vec<(String, String)> {
+        writes
+            .iter()
+            .filter(|(o, _, _)| *o == out)
+            .map(|(_, metric, domain)| (metric.clone(), domain.clone()))
+            .collect()
+    };
+    let perf = received("perf");
+    assert!(perf.len() >= 3, "the perf output should receive measurements");
+    assert!(perf.iter().all(|(metric, _)| metric == "perf_instructions"), "{perf:?}");
+
+    let package = received("package");
+    assert!(package.len() >= 3, "the package output should receive measurements");
+    assert!(
+        package
+            .iter()
+            .all(|(metric, domain)| metric == "energy" && domain == "package"),
+        "{package:?}"
+    );
+
+    let all = received("all");
+    for domain in ["core", "package", "dram"] {
+        assert!(
+            all.iter().any(|(_, d)| d == domain),
+            "the output without route should receive every measurement: {all:?}"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use alumet::{
+    agent::{self, plugin::PluginSet},
+    measurement::{MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::TypedMetricId,
+    pipeline::{
+        self, Output, Source,
+        elements::{
+            error::{PollError, WriteError},
+            output::{
+                OutputContext,
+                spool::{OutputSpool, SpoolConfig},
+            },
+            source::trigger::TriggerSpec,
+        },
+        matching::OutputNamePattern,
+    },
+    plugin::{
+        AlumetPluginStart, ConfigTable, PluginMetadata,
+        rust::{AlumetPlugin, serialize_config},
+    },
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+use anyhow::{Context, anyhow};
+
+const PLUGIN: &str = "spool_test";
+
+/// State shared between the test and the plugin.
+#[derive(Default)]
+struct Shared {
+    /// When true, the output fails to write.
+    backend_down: AtomicBool,
+    /// When true, the next write takes some time, then fails.
+    next_write_slow_failure: AtomicBool,
+    /// The values received by the output.
+    received: Mutex<Vec<u64>>,
+}
+
+struct TestPlugin {
+    shared: Arc<Shared>,
+}
+
+impl TestPlugin {
+    fn metadata_with(shared: Arc<Shared>) -> PluginMetadata {
+        PluginMetadata {
+            name: Self::name().to_owned(),
+            version: Self::version().to_owned(),
+            init: Box::new(move |_| Ok(Box::new(Self { shared }))),
+            default_config: Box::new(Self::default_config),
+        }
+    }
+}
+
+impl AlumetPlugin for TestPlugin {
+    fn name() -> &'static str {
+        PLUGIN
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Duration::from_secs(1))?))
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        unreachable!()
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let metric = alumet.create_metric::<u64>("counter", Unit::Unity, "")?;
+        let source = CounterSource {
+            metric,
+            next: AtomicU64::new(0),
+        };
+        alumet.add_source(
+            "counter",
+            Box::new(source),
+            TriggerSpec::at_interval(Duration::from_millis(10)),
+        )?;
+        let output = UnreliableOutput {
+            shared: self.shared.clone(),
+        };
+        alumet.add_blocking_output("unreliable", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct CounterSource {
+    metric: TypedMetricId<u64>,
+    next: AtomicU64,
+}
+
+impl Source for CounterSource {
+    fn poll(&mut self, m: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        let value = self.next.fetch_add(1, Ordering::Relaxed);
+        m.push(MeasurementPoint::new(
+            t,
+            self.metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            value,
+        ));
+        Ok(())
+    }
+}
+
+struct UnreliableOutput {
+    shared: Arc<Shared>,
+}
+
+impl Output for UnreliableOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, _ctx: &OutputContext) -> Result<(), WriteError> {
+        if self.shared.backend_down.load(Ordering::Relaxed) {
+            return Err(WriteError::CanRetry(anyhow!("backend is down")));
+        }
+        if self.shared.next_write_slow_failure.swap(false, Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(100));
+            return Err(WriteError::CanRetry(anyhow!("timeout")));
+        }
+        let mut received = self.shared.received.lock().unwrap();
+        for p in measurements.iter() {
+            match p.value {
+                WrappedMeasurementValue::U64(v) => received.push(v),
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn spooled_output_does_not_lose_measurements() -> anyhow::Result<()> {
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).try_init();
+
+    let spool_dir = tempfile::tempdir()?;
+    let shared = Arc::new(Shared::default());
+    shared.backend_down.store(true, Ordering::Relaxed);
+
+    let plugins = PluginSet::from(vec![TestPlugin::metadata_with(shared.clone())]);
+    let mut pipeline_builder = pipeline::Builder::new();
+    pipeline_builder.spool_outputs(OutputSpool {
+        outputs: OutputNamePattern::exact(PLUGIN, "unreliable"),
+        config: SpoolConfig {
+            directory: spool_dir.path().to_owned(),
+            max_size: 16 * 1024 * 1024,
+            retry_interval: Duration::from_millis(20),
+        },
+    });
+    let agent = agent::Builder::from_pipeline(plugins, pipeline_builder)
+        .build_and_start()
+        .expect("agent should start fine");
+
+    // The backend is down for a while, then comes back.
+    thread::sleep(Duration::from_millis(300));
+    assert!(shared.received.lock().unwrap().is_empty());
+    shared.backend_down.store(false, Ordering::Relaxed);
+    thread::sleep(Duration::from_millis(300));
+
+    agent.pipeline.control_handle().shutdown();
+    agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .context("error while shutting down")?;
+
+    // Every measurement has been written, in order.
+    let received = shared.received.lock().unwrap();
+    assert!(received.len() > 30, "too few measurements: {}", received.len());
+    let expected: Vec<u64> = (0..received.len() as u64).collect();
+    assert_eq!(*received, expected);
+    Ok(())
+}
+
+#[test]
+fn spooled_output_replays_failed_write_in_order() -> anyhow::Result<()> {
+    let spool_dir = tempfile::tempdir()?;
+    let shared = Arc::new(Shared::default());
+    // The first write fails after a while, during which new measurements arrive.
+    shared.next_write_slow_failure.store(true, Ordering::Relaxed);
+
+    let plugins = PluginSet::from(vec![TestPlugin::metadata_with(shared.clone())]);
+    let mut pipeline_builder = pipeline::Builder::new();
+    pipeline_builder.spool_outputs(OutputSpool {
+        outputs: OutputNamePattern::exact(PLUGIN, "unreliable"),
+        config: SpoolConfig {
+            directory: spool_dir.path().to_owned(),
+            max_size: 16 * 1024 * 1024,
+            retry_interval: Duration::from_millis(20),
+        },
+    });
+    let agent = agent::Builder::from_pipeline(plugins, pipeline_builder)
+        .build_and_start()
+        .expect("agent should start fine");
+
+    thread::sleep(Duration::from_millis(400));
+    agent.pipeline.control_handle().shutdown();
+    agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .context("error while shutting down")?;
+
+    // The measurements of the failed write are replayed before the ones that arrived later.
+    let received = shared.received.lock().unwrap();
+    assert!(received.len() > 20, "too few measurements: {}", received.len());
+    let expected: Vec<u64> = (0..received.len() as u64).collect();
+    assert_eq!(*received, expected);
+    Ok(())
+}
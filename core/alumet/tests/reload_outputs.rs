@@ -0,0 +1,215 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::PluginSet,
+        reload::{AppliedConfig, ReloadAction},
+    },
+    measurement::{MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{
+        self,
+        elements::{
+            error::PollError,
+            output::{OutputContext, WriteError},
+            source::trigger::TriggerSpec,
+        },
+    },
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    resources::{Resource, ResourceConsumer},
+    static_plugins,
+    units::Unit,
+};
+use serde::{Deserialize, Serialize};
+
+/// The tags of the outputs that have written measurements, in order.
+static WRITES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct SourcePlugin;
+struct TickSource(TypedMetricId<u64>);
+
+struct OutputPlugin {
+    config: OutputConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OutputConfig {
+    tag: String,
+}
+
+struct TagOutput(String);
+
+impl AlumetPlugin for SourcePlugin {
+    fn name() -> &'static str {
+        "src"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(SourcePlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let metric = alumet.create_metric("ticks", Unit::Unity, "number of ticks")?;
+        let trigger = TriggerSpec::at_interval(Duration::from_millis(20));
+        alumet.add_source("ticks", Box::new(TickSource(metric)), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Source for TickSource {
+    fn poll(&mut self, m: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        m.push(MeasurementPoint::new(
+            t,
+            self.0,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            1,
+        ));
+        Ok(())
+    }
+}
+
+impl AlumetPlugin for OutputPlugin {
+    fn name() -> &'static str {
+        "out"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(OutputConfig {
+            tag: String::from("old"),
+        })?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(OutputPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.add_blocking_output("tagged", Box::new(TagOutput(self.config.tag.clone())))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for TagOutput {
+    fn write(&mut self, _m: &MeasurementBuffer, _ctx: &OutputContext) -> Result<(), WriteError> {
+        WRITES.lock().unwrap().push(self.0.clone());
+        Ok(())
+    }
+}
+
+fn config(tag: &str) -> toml::Table {
+    toml::from_str(&format!("[plugins.src]\n[plugins.out]\ntag = \"{tag}\"\n")).unwrap()
+}
+
+fn wait_for_write(tag: &str) -> bool {
+    let start = SystemTime::now();
+    while start.elapsed().unwrap() < Duration::from_secs(5) {
+        if WRITES.lock().unwrap().iter().any(|t| t == tag) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[test]
+fn reload_recreates_outputs() -> anyhow::Result<()> {
+    let plugins = PluginSet::from(static_plugins![SourcePlugin, OutputPlugin]);
+    // The pipeline is simplified (one output, no transform), the output is replaced in place.
+    let pipeline_builder = pipeline::Builder::new();
+    let agent = agent::Builder::from_pipeline(plugins, pipeline_builder).build_and_start()?;
+    assert!(
+        wait_for_write("old"),
+        "the initial output should write the measurements"
+    );
+
+    let running_plugins = vec![String::from("src"), String::from("out")];
+    let mut applied = AppliedConfig::new(config("old"), running_plugins, true)?.recreate_outputs(
+        vec![String::from("out")],
+        || static_plugins![SourcePlugin, OutputPlugin],
+        agent.pipeline.metrics_reader(),
+    );
+    let plan = applied.reload(config("new"))?;
+    assert_eq!(
+        plan.actions,
+        vec![ReloadAction::RecreateOutputs {
+            plugin: String::from("out"),
+            config: config("new")["plugins"]["out"].as_table().unwrap().clone(),
+        }]
+    );
+    assert!(plan.restart_required.is_empty());
+
+    let control = agent.pipeline.control_handle();
+    agent
+        .pipeline
+        .async_runtime()
+        .block_on(applied.apply(&plan, &control))?;
+    assert!(wait_for_write("new"), "the new output should write the measurements");
+    assert!(
+        applied.reload(config("new"))?.is_empty(),
+        "the new config should be applied"
+    );
+    // the old output has been replaced
+    thread::sleep(Duration::from_millis(200));
+    let writes = WRITES.lock().unwrap().clone();
+    let last_old = writes.iter().rposition(|t| t == "old").unwrap();
+    assert!(
+        writes.len() - last_old > 3,
+        "the old output should not run anymore: {writes:?}"
+    );
+
+    // a plugin that creates sources cannot be restarted that way
+    let mut applied = AppliedConfig::new(config("new"), vec![String::from("src")], true)?.recreate_outputs(
+        vec![String::from("src")],
+        || static_plugins![SourcePlugin, OutputPlugin],
+        agent.pipeline.metrics_reader(),
+    );
+    let mut new_config = config("new");
+    new_config["plugins"]["src"]
+        .as_table_mut()
+        .unwrap()
+        .insert(String::from("key"), toml::Value::Integer(1));
+    let plan = applied.reload(new_config.clone())?;
+    let err = agent
+        .pipeline
+        .async_runtime()
+        .block_on(applied.apply(&plan, &control))
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("does not only create outputs"), "{err:#}");
+    // the change has not been applied, it is reported again
+    assert_eq!(applied.reload(new_config)?.actions, plan.actions);
+
+    control.shutdown();
+    agent.wait_for_shutdown(Duration::from_secs(2))?;
+    Ok(())
+}
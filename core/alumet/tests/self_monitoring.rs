@@ -0,0 +1,187 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use alumet::{
+    agent::{self, plugin::PluginSet},
+    measurement::{MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::TypedMetricId,
+    pipeline::{
+        self, Output, Source,
+        elements::{
+            error::{PollError, WriteError},
+            output::OutputContext,
+            source::trigger::TriggerSpec,
+        },
+    },
+    plugin::{
+        AlumetPluginStart, ConfigTable, PluginMetadata,
+        rust::{AlumetPlugin, serialize_config},
+    },
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+use anyhow::{Context, anyhow};
+
+const PLUGIN: &str = "health_test";
+
+/// A measurement received by the output: metric name, attributes and value.
+type Received = (String, Vec<(String, String)>, WrappedMeasurementValue);
+
+struct TestPlugin {
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl TestPlugin {
+    fn metadata_with(received: Arc<Mutex<Vec<Received>>>) -> PluginMetadata {
+        PluginMetadata {
+            name: Self::name().to_owned(),
+            version: Self::version().to_owned(),
+            init: Box::new(move |_| Ok(Box::new(Self { received }))),
+            default_config: Box::new(Self::default_config),
+        }
+    }
+}
+
+impl AlumetPlugin for TestPlugin {
+    fn name() -> &'static str {
+        PLUGIN
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Duration::from_secs(1))?))
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        unreachable!()
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let metric = alumet.create_metric::<u64>("counter", Unit::Unity, "")?;
+        // This source is slower than its poll interval.
+        let source = SlowSource { metric };
+        alumet.add_source(
+            "slow",
+            Box::new(source),
+            TriggerSpec::at_interval(Duration::from_millis(10)),
+        )?;
+        let output = FlakyOutput {
+            received: self.received.clone(),
+            writes: AtomicU64::new(0),
+        };
+        alumet.add_blocking_output("flaky", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct SlowSource {
+    metric: TypedMetricId<u64>,
+}
+
+impl Source for SlowSource {
+    fn poll(&mut self, m: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        thread::sleep(Duration::from_millis(15));
+        m.push(MeasurementPoint::new(
+            t,
+            self.metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            1,
+        ));
+        Ok(())
+    }
+}
+
+/// An output that fails one time out of two.
+struct FlakyOutput {
+    received: Arc<Mutex<Vec<Received>>>,
+    writes: AtomicU64,
+}
+
+impl Output for FlakyOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut received = self.received.lock().unwrap();
+        for p in measurements.iter() {
+            let name = ctx.metrics.by_id(&p.metric).unwrap().name.clone();
+            let attributes = p.attributes().map(|(k, v)| (k.to_owned(), v.to_string())).collect();
+            received.push((name, attributes, p.value.clone()));
+        }
+        if self.writes.fetch_add(1, Ordering::Relaxed) % 2 == 1 {
+            return Err(WriteError::CanRetry(anyhow!("flaky backend")));
+        }
+        Ok(())
+    }
+}
+
+fn sum_u64<'a>(points: impl Iterator<Item = &'a Received>) -> u64 {
+    points
+        .map(|(_, _, value)| match value {
+            WrappedMeasurementValue::U64(v) => *v,
+            v => panic!("unexpected value {v:?}"),
+        })
+        .sum()
+}
+
+fn has_attr(attributes: &[(String, String)], key: &str, value: &str) -> bool {
+    attributes.iter().any(|(k, v)| k == key && v == value)
+}
+
+#[test]
+fn self_monitoring_reports_pipeline_health() -> anyhow::Result<()> {
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).try_init();
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let plugins = PluginSet::from(vec![TestPlugin::metadata_with(received.clone())]);
+    let mut pipeline_builder = pipeline::Builder::new();
+    pipeline_builder.self_monitoring(Duration::from_millis(50));
+    let agent = agent::Builder::from_pipeline(plugins, pipeline_builder)
+        .build_and_start()
+        .expect("agent should start fine");
+
+    thread::sleep(Duration::from_millis(500));
+
+    agent.pipeline.control_handle().shutdown();
+    agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .context("error while shutting down")?;
+
+    let received = received.lock().unwrap();
+    let of_metric = |metric: &'static str| received.iter().filter(move |(name, _, _)| name == metric);
+
+    // The slow source overruns its poll interval.
+    let slow = |(_, attrs, _): &&Received| has_attr(attrs, "plugin", PLUGIN) && has_attr(attrs, "element", "slow");
+    assert!(sum_u64(of_metric("alumet_source_poll_overruns").filter(slow)) > 0);
+    assert!(of_metric("alumet_source_poll_duration").filter(slow).count() > 0);
+
+    // The flaky output fails to write.
+    let flaky = |(_, attrs, _): &&Received| has_attr(attrs, "element", "flaky");
+    assert!(sum_u64(of_metric("alumet_output_write_errors").filter(flaky)) > 0);
+    assert!(of_metric("alumet_output_write_duration").filter(flaky).count() > 0);
+    assert_eq!(sum_u64(of_metric("alumet_output_dropped_buffers").filter(flaky)), 0);
+
+    // The channels and the registry are monitored.
+    assert!(
+        of_metric("alumet_channel_queue_depth")
+            .filter(|(_, attrs, _)| has_attr(attrs, "channel", "sources"))
+            .count()
+            > 0
+    );
+    let registry_size = of_metric("alumet_metric_registry_size")
+        .last()
+        .expect("missing registry size");
+    assert_eq!(registry_size.2, WrappedMeasurementValue::U64(10));
+    Ok(())
+}
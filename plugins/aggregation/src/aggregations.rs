@@ -1,53 +1,328 @@
-use alumet::measurement::{MeasurementPoint, WrappedMeasurementValue};
-use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
-#[derive(Deserialize, Serialize, Clone, Copy)]
+use alumet::{
+    measurement::{MeasurementPoint, WrappedMeasurementType, WrappedMeasurementValue},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::anyhow;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// An aggregation function, applied to the points of a time window.
+///
+/// In the configuration, functions are written in lowercase: `sum`, `mean`, `min`, `max`, `count`, `last`,
+/// `stddev`, `rate`, and `pN` for the N-th percentile (for instance `p95` or `p99.9`).
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
 pub(crate) enum Function {
     Sum,
     Mean,
+    Min,
+    Max,
+    /// Number of points in the window.
+    Count,
+    /// Value of the last point of the window.
+    Last,
+    /// Population standard deviation.
+    Stddev,
+    /// The p-th percentile, with p in [0, 100], computed with the nearest-rank method.
+    Percentile(f64),
+    /// Increase of a counter per second. Decreasing values are treated as counter resets.
+    Rate,
 }
 
 impl Function {
+    /// Returns the suffix of the aggregated metrics.
     pub(crate) fn name(self) -> String {
+        self.to_string().replace('.', "_")
+    }
+
+    /// Minimum number of points that the window must contain for the function to produce a value.
+    pub(crate) fn min_points(self) -> usize {
         match self {
-            Function::Sum => "sum".to_string(),
-            Function::Mean => "mean".to_string(),
+            Function::Rate => 2,
+            _ => 1,
         }
     }
 
-    pub(crate) fn function(self) -> fn(Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    /// Computes the aggregated value of the given points.
+    pub(crate) fn apply(self, sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
         match self {
-            Function::Sum => sum,
-            Function::Mean => mean,
+            Function::Sum => sum(sub_vec),
+            Function::Mean => mean(sub_vec),
+            Function::Min => min(sub_vec),
+            Function::Max => max(sub_vec),
+            Function::Count => count(sub_vec),
+            Function::Last => last(sub_vec),
+            Function::Stddev => stddev(sub_vec),
+            Function::Percentile(p) => percentile(sub_vec, p),
+            Function::Rate => rate(sub_vec),
+        }
+    }
+
+    /// Returns the type of the values produced by the function, or an error if the function
+    /// does not support the type of the input values.
+    pub(crate) fn output_type(self, input: &WrappedMeasurementType) -> anyhow::Result<WrappedMeasurementType> {
+        match (self, input) {
+            (Function::Sum | Function::Mean | Function::Last, t) => Ok(t.clone()),
+            (Function::Count, _) => Ok(WrappedMeasurementType::U64),
+            (_, WrappedMeasurementType::Histogram) => Err(anyhow!("function {self} does not support histograms")),
+            (Function::Min | Function::Max | Function::Percentile(_), t) => Ok(t.clone()),
+            (Function::Stddev | Function::Rate, _) => Ok(WrappedMeasurementType::F64),
+        }
+    }
+
+    /// Returns the unit of the values produced by the function.
+    pub(crate) fn output_unit(self, input: &PrefixedUnit) -> PrefixedUnit {
+        match self {
+            Function::Count => PrefixedUnit::from(Unit::Unity),
+            Function::Rate => {
+                let base_unit = match &input.base_unit {
+                    Unit::Unity => Unit::Hertz,
+                    Unit::Joule => Unit::Watt,
+                    unit => Unit::Custom {
+                        unique_name: format!("{}/s", unit.unique_name()),
+                        display_name: format!("{unit}/s"),
+                    },
+                };
+                PrefixedUnit {
+                    base_unit,
+                    prefix: input.prefix.clone(),
+                }
+            }
+            _ => input.clone(),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Function::Sum => f.write_str("sum"),
+            Function::Mean => f.write_str("mean"),
+            Function::Min => f.write_str("min"),
+            Function::Max => f.write_str("max"),
+            Function::Count => f.write_str("count"),
+            Function::Last => f.write_str("last"),
+            Function::Stddev => f.write_str("stddev"),
+            Function::Percentile(p) => write!(f, "p{p}"),
+            Function::Rate => f.write_str("rate"),
         }
     }
 }
 
+impl FromStr for Function {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The names are case-insensitive, for compatibility with older configurations (e.g. `"Sum"`).
+        let function = match s.to_ascii_lowercase().as_str() {
+            "sum" => Function::Sum,
+            "mean" => Function::Mean,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "count" => Function::Count,
+            "last" => Function::Last,
+            "stddev" => Function::Stddev,
+            "rate" => Function::Rate,
+            other => {
+                let p: f64 = other
+                    .strip_prefix('p')
+                    .and_then(|p| p.parse().ok())
+                    .ok_or_else(|| anyhow!("unknown aggregation function: {s}"))?;
+                if !(0.0..=100.0).contains(&p) {
+                    return Err(anyhow!("invalid percentile {s}: must be between p0 and p100"));
+                }
+                Function::Percentile(p)
+            }
+        };
+        Ok(function)
+    }
+}
+
+impl TryFrom<String> for Function {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Function> for String {
+    fn from(value: Function) -> Self {
+        value.to_string()
+    }
+}
+
+/// Deserializes either one function or a list of functions.
+pub(crate) fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Function>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Function),
+        Many(Vec<Function>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(f) => vec![f],
+        OneOrMany::Many(functions) => functions,
+    })
+}
+
 /// Returns the aggregated sum result of the given vec.
+///
+/// The sum of histograms is the histogram that contains all their values.
+/// Returns `None` if the vec is empty or if the histograms have different buckets.
 pub(crate) fn sum(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
-    sub_vec.iter().map(|x| x.clone().value).reduce(|x, y| match (x, y) {
-        (WrappedMeasurementValue::F64(fx), WrappedMeasurementValue::F64(fy)) => WrappedMeasurementValue::F64(fx + fy),
-        (WrappedMeasurementValue::U64(ux), WrappedMeasurementValue::U64(uy)) => WrappedMeasurementValue::U64(ux + uy),
-        (_, _) => unreachable!("should not receive mixed U64 and F64 values"),
-    })
+    let mut values = sub_vec.into_iter().map(|x| x.value);
+    let first = values.next()?;
+    values.try_fold(first, add)
 }
 
 /// Returns the aggregated mean result of the given vec.
+///
+/// The "mean" of histograms is the histogram that contains all their values, like [`sum`].
 pub(crate) fn mean(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
-    let Some(result) = sub_vec.iter().map(|x| x.clone().value).reduce(|x, y| match (x, y) {
-        (WrappedMeasurementValue::F64(fx), WrappedMeasurementValue::F64(fy)) => WrappedMeasurementValue::F64(fx + fy),
-        (WrappedMeasurementValue::U64(ux), WrappedMeasurementValue::U64(uy)) => WrappedMeasurementValue::U64(ux + uy),
-        (_, _) => unreachable!("should not receive mixed U64 and F64 values"),
-    }) else {
+    let len = sub_vec.len();
+    let result = sum(sub_vec)?;
+    Some(match result {
+        WrappedMeasurementValue::F64(fx) => WrappedMeasurementValue::F64(fx / len as f64),
+        WrappedMeasurementValue::U64(ux) => WrappedMeasurementValue::U64(ux / len as u64),
+        WrappedMeasurementValue::I64(ix) => WrappedMeasurementValue::I64(ix / len as i64),
+        h @ WrappedMeasurementValue::Histogram(_) => h,
+    })
+}
+
+/// Returns the smallest value of the given vec.
+pub(crate) fn min(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    let mut values = sub_vec.into_iter().map(|x| x.value);
+    let first = values.next()?;
+    values.try_fold(first, |x, y| Some(if compare(&y, &x)?.is_lt() { y } else { x }))
+}
+
+/// Returns the largest value of the given vec.
+pub(crate) fn max(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    let mut values = sub_vec.into_iter().map(|x| x.value);
+    let first = values.next()?;
+    values.try_fold(first, |x, y| Some(if compare(&y, &x)?.is_gt() { y } else { x }))
+}
+
+/// Returns the number of points in the given vec.
+pub(crate) fn count(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    Some(WrappedMeasurementValue::U64(sub_vec.len() as u64))
+}
+
+/// Returns the value of the last point of the given vec.
+pub(crate) fn last(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    sub_vec.into_iter().last().map(|x| x.value)
+}
+
+/// Returns the population standard deviation of the given vec.
+pub(crate) fn stddev(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    let values = scalars(&sub_vec)?;
+    if values.is_empty() {
         return None;
-    };
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    Some(WrappedMeasurementValue::F64(variance.sqrt()))
+}
 
-    Some(match result {
-        WrappedMeasurementValue::F64(fx) => WrappedMeasurementValue::F64(fx / sub_vec.len() as f64),
-        WrappedMeasurementValue::U64(ux) => WrappedMeasurementValue::U64(ux / sub_vec.len() as u64),
+/// Returns the p-th percentile of the given vec, using the nearest-rank method.
+///
+/// The result is always one of the values of the vec.
+pub(crate) fn percentile(sub_vec: Vec<MeasurementPoint>, p: f64) -> Option<WrappedMeasurementValue> {
+    let mut values: Vec<WrappedMeasurementValue> = sub_vec.into_iter().map(|x| x.value).collect();
+    if values.is_empty()
+        || values
+            .iter()
+            .any(|v| matches!(v, WrappedMeasurementValue::Histogram(_)))
+    {
+        return None;
+    }
+    values.sort_by(|x, y| compare(x, y).unwrap());
+    let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+    Some(values.swap_remove(rank.max(1) - 1))
+}
+
+/// Returns the rate of increase of a counter, per second, between the first and the last point of the given vec.
+///
+/// When a value is lower than the previous one, the counter is assumed to have been reset to zero.
+/// If the points come from several series (because some dimensions are dropped by the grouping),
+/// the rate of each series is computed separately, and the rates are summed.
+/// Returns `None` if no series has two points.
+pub(crate) fn rate(sub_vec: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
+    let mut series: Vec<Vec<&MeasurementPoint>> = Vec::new();
+    for point in &sub_vec {
+        match series.iter_mut().find(|s| same_series(s[0], point)) {
+            Some(s) => s.push(point),
+            None => series.push(vec![point]),
+        }
+    }
+
+    let mut total = None;
+    for points in series {
+        let (first, last) = (points.first()?, points.last()?);
+        let elapsed = last.timestamp.duration_since(first.timestamp).ok()?.as_secs_f64();
+        if elapsed == 0.0 {
+            continue;
+        }
+        let values = scalars(points.iter().copied())?;
+        let increase: f64 = values
+            .windows(2)
+            .map(|w| if w[1] >= w[0] { w[1] - w[0] } else { w[1] })
+            .sum();
+        *total.get_or_insert(0.0) += increase / elapsed;
+    }
+    total.map(WrappedMeasurementValue::F64)
+}
+
+/// Returns true if the two points belong to the same series, i.e. if they have the same metric,
+/// resource, consumer and attributes.
+fn same_series(a: &MeasurementPoint, b: &MeasurementPoint) -> bool {
+    a.metric == b.metric
+        && a.resource == b.resource
+        && a.consumer == b.consumer
+        && a.attributes_len() == b.attributes_len()
+        && a.attributes()
+            .all(|(k, v)| b.attributes().any(|(k2, v2)| k == k2 && v == v2))
+}
+
+fn add(x: WrappedMeasurementValue, y: WrappedMeasurementValue) -> Option<WrappedMeasurementValue> {
+    Some(match (x, y) {
+        (WrappedMeasurementValue::F64(fx), WrappedMeasurementValue::F64(fy)) => WrappedMeasurementValue::F64(fx + fy),
+        (WrappedMeasurementValue::U64(ux), WrappedMeasurementValue::U64(uy)) => WrappedMeasurementValue::U64(ux + uy),
+        (WrappedMeasurementValue::I64(ix), WrappedMeasurementValue::I64(iy)) => WrappedMeasurementValue::I64(ix + iy),
+        (WrappedMeasurementValue::Histogram(mut hx), WrappedMeasurementValue::Histogram(hy)) => {
+            hx.merge(&hy).ok()?;
+            WrappedMeasurementValue::Histogram(hx)
+        }
+        (_, _) => unreachable!("should not receive values of different types"),
     })
 }
 
+/// Compares two values of the same type. Histograms cannot be compared.
+fn compare(x: &WrappedMeasurementValue, y: &WrappedMeasurementValue) -> Option<Ordering> {
+    match (x, y) {
+        (WrappedMeasurementValue::F64(fx), WrappedMeasurementValue::F64(fy)) => Some(fx.total_cmp(fy)),
+        (WrappedMeasurementValue::U64(ux), WrappedMeasurementValue::U64(uy)) => Some(ux.cmp(uy)),
+        (WrappedMeasurementValue::I64(ix), WrappedMeasurementValue::I64(iy)) => Some(ix.cmp(iy)),
+        (WrappedMeasurementValue::Histogram(_), WrappedMeasurementValue::Histogram(_)) => None,
+        (_, _) => unreachable!("should not receive values of different types"),
+    }
+}
+
+/// Converts the values to f64, or returns `None` if they contain histograms.
+fn scalars<'a>(sub_vec: impl IntoIterator<Item = &'a MeasurementPoint>) -> Option<Vec<f64>> {
+    sub_vec
+        .into_iter()
+        .map(|x| match x.value {
+            WrappedMeasurementValue::Histogram(_) => None,
+            ref v => Some(v.as_f64()),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::aggregations::Function;
@@ -56,10 +331,128 @@ mod tests {
     fn test_function_get_string() {
         assert_eq!(Function::Mean.name(), "mean");
         assert_eq!(Function::Sum.name(), "sum");
+        assert_eq!(Function::Percentile(95.0).name(), "p95");
+        assert_eq!(Function::Percentile(99.9).name(), "p99_9");
+    }
+
+    #[test]
+    fn test_function_from_str() {
+        assert_eq!("Sum".parse::<Function>().unwrap(), Function::Sum);
+        assert_eq!("stddev".parse::<Function>().unwrap(), Function::Stddev);
+        assert_eq!("rate".parse::<Function>().unwrap(), Function::Rate);
+        assert_eq!("p99.9".parse::<Function>().unwrap(), Function::Percentile(99.9));
+        assert!("p101".parse::<Function>().is_err());
+        assert!("median".parse::<Function>().is_err());
+    }
+
+    #[test]
+    fn test_function_output_type() {
+        use alumet::measurement::WrappedMeasurementType;
+
+        assert_eq!(
+            Function::Count.output_type(&WrappedMeasurementType::F64).unwrap(),
+            WrappedMeasurementType::U64
+        );
+        assert_eq!(
+            Function::Rate.output_type(&WrappedMeasurementType::U64).unwrap(),
+            WrappedMeasurementType::F64
+        );
+        assert_eq!(
+            Function::Max.output_type(&WrappedMeasurementType::I64).unwrap(),
+            WrappedMeasurementType::I64
+        );
+        assert!(Function::Max.output_type(&WrappedMeasurementType::Histogram).is_err());
+    }
+
+    mod windowed {
+        use alumet::{measurement::WrappedMeasurementValue, resources::Resource};
+
+        use crate::{
+            aggregations::{count, last, max, min, percentile, rate, stddev},
+            transform::tests::new_point,
+        };
+
+        fn u64_points(values: &[(&str, u64)]) -> Vec<alumet::measurement::MeasurementPoint> {
+            values
+                .iter()
+                .map(|(t, v)| new_point(t, WrappedMeasurementValue::U64(*v), 0))
+                .collect()
+        }
+
+        #[test]
+        fn min_max_count_last() {
+            let points = u64_points(&[
+                ("2025-02-10T13:19:00Z", 4),
+                ("2025-02-10T13:19:01Z", 1),
+                ("2025-02-10T13:19:02Z", 9),
+                ("2025-02-10T13:19:03Z", 3),
+            ]);
+            assert_eq!(min(points.clone()), Some(WrappedMeasurementValue::U64(1)));
+            assert_eq!(max(points.clone()), Some(WrappedMeasurementValue::U64(9)));
+            assert_eq!(count(points.clone()), Some(WrappedMeasurementValue::U64(4)));
+            assert_eq!(last(points), Some(WrappedMeasurementValue::U64(3)));
+            assert_eq!(min(vec![]), None);
+        }
+
+        #[test]
+        fn stddev_f64() {
+            let points = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+                .into_iter()
+                .map(|v| new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(v), 0))
+                .collect();
+            assert_eq!(stddev(points), Some(WrappedMeasurementValue::F64(2.0)));
+        }
+
+        #[test]
+        fn percentiles() {
+            let points: Vec<_> = (1..=20)
+                .map(|v| new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::I64(21 - v), 0))
+                .collect();
+            assert_eq!(percentile(points.clone(), 0.0), Some(WrappedMeasurementValue::I64(1)));
+            assert_eq!(percentile(points.clone(), 50.0), Some(WrappedMeasurementValue::I64(10)));
+            assert_eq!(percentile(points.clone(), 95.0), Some(WrappedMeasurementValue::I64(19)));
+            assert_eq!(percentile(points, 100.0), Some(WrappedMeasurementValue::I64(20)));
+        }
+
+        #[test]
+        fn counter_rate() {
+            let points = u64_points(&[
+                ("2025-02-10T13:19:00Z", 100),
+                ("2025-02-10T13:19:02Z", 120),
+                ("2025-02-10T13:19:04Z", 150),
+            ]);
+            assert_eq!(rate(points), Some(WrappedMeasurementValue::F64(12.5)));
+        }
+
+        #[test]
+        fn counter_rate_with_reset() {
+            // the counter is reset between the second and the third point
+            let points = u64_points(&[
+                ("2025-02-10T13:19:00Z", 100),
+                ("2025-02-10T13:19:02Z", 120),
+                ("2025-02-10T13:19:04Z", 20),
+            ]);
+            assert_eq!(rate(points), Some(WrappedMeasurementValue::F64(10.0)));
+            assert_eq!(rate(u64_points(&[("2025-02-10T13:19:00Z", 100)])), None);
+        }
+
+        #[test]
+        fn counter_rate_of_several_series() {
+            // two counters of the same group, interleaved
+            let mut points = u64_points(&[
+                ("2025-02-10T13:19:00Z", 100),
+                ("2025-02-10T13:19:01Z", 5000),
+                ("2025-02-10T13:19:02Z", 120),
+                ("2025-02-10T13:19:03Z", 5010),
+            ]);
+            points[1].resource = Resource::CpuCore { id: 1 };
+            points[3].resource = Resource::CpuCore { id: 1 };
+            assert_eq!(rate(points), Some(WrappedMeasurementValue::F64(15.0)));
+        }
     }
 
     mod sum {
-        use alumet::measurement::WrappedMeasurementValue;
+        use alumet::measurement::{Histogram, WrappedMeasurementValue};
 
         use crate::{
             aggregations::{Function, sum},
@@ -111,7 +504,42 @@ mod tests {
                 new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(1.5), 0),
             ];
 
-            Function::Sum.function()(sub_vec);
+            Function::Sum.apply(sub_vec);
+        }
+
+        #[test]
+        fn histogram_sub_vec() {
+            let h1 = Histogram::from_parts(vec![1.0], vec![1, 0], 0.5).unwrap();
+            let h2 = Histogram::from_parts(vec![1.0], vec![1, 2], 7.0).unwrap();
+            let sub_vec = vec![
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::Histogram(h1), 0),
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::Histogram(h2), 0),
+            ];
+
+            let Some(WrappedMeasurementValue::Histogram(result)) = sum(sub_vec) else {
+                panic!("not a histogram")
+            };
+
+            assert_eq!(result.counts(), &[2, 2]);
+            assert_eq!(result.sum(), 7.5);
+        }
+
+        #[test]
+        fn histograms_with_different_bounds() {
+            let sub_vec = vec![
+                new_point(
+                    "2025-02-10T13:19:00Z",
+                    WrappedMeasurementValue::Histogram(Histogram::new(vec![1.0]).unwrap()),
+                    0,
+                ),
+                new_point(
+                    "2025-02-10T13:19:00Z",
+                    WrappedMeasurementValue::Histogram(Histogram::new(vec![2.0]).unwrap()),
+                    0,
+                ),
+            ];
+
+            assert_eq!(sum(sub_vec), None);
         }
     }
 
@@ -144,6 +572,20 @@ mod tests {
             assert_eq!(result, 15);
         }
 
+        #[test]
+        fn i64_sub_vec() {
+            let sub_vec = vec![
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::I64(-10), 0),
+                new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::I64(4), 0),
+            ];
+
+            let Some(WrappedMeasurementValue::I64(result)) = mean(sub_vec) else {
+                panic!("not an i64")
+            };
+
+            assert_eq!(result, -3);
+        }
+
         #[test]
         fn f64_sub_vec() {
             let sub_vec = vec![
@@ -168,7 +610,7 @@ mod tests {
                 new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::F64(1.5), 0),
             ];
 
-            Function::Mean.function()(sub_vec);
+            Function::Mean.apply(sub_vec);
         }
     }
 }
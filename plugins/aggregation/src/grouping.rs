@@ -0,0 +1,424 @@
+use std::{collections::HashMap, convert::Infallible, fmt::Display, path::Path, str::FromStr};
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementPoint},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::transform::SeriesKey;
+
+/// A dimension of the measurement points, which can be kept or dropped by the aggregation.
+///
+/// In the configuration, the dimensions are written as `resource`, `resource_kind`, `resource_id`,
+/// `resource_package`, `consumer`, `consumer_kind`, `consumer_id`. Any other string is the name of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Dimension {
+    /// The kind and id of the resource.
+    Resource,
+    ResourceKind,
+    ResourceId,
+    /// The CPU package that contains the resource: `CpuCore` resources are replaced by
+    /// the `CpuPackage` that they belong to, the other resources are kept.
+    ResourcePackage,
+    /// The kind and id of the consumer.
+    Consumer,
+    ConsumerKind,
+    ConsumerId,
+    Attribute(String),
+}
+
+/// How much of a resource or consumer is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    /// Keep the kind and the id.
+    Full,
+    /// Keep the kind only.
+    Kind,
+    /// Replace the CPU cores by their package (only for resources).
+    Package,
+    /// Drop everything: the resource (or consumer) becomes `LocalMachine`.
+    Nothing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AttributeFilter {
+    Only(Vec<String>),
+    Except(Vec<String>),
+}
+
+/// Determines which points are aggregated together.
+///
+/// The points that have the same metric and the same values for the kept dimensions
+/// belong to the same group, and are aggregated together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grouping {
+    resource: Level,
+    consumer: Level,
+    attributes: AttributeFilter,
+    /// Package of each CPU, used by [`Level::Package`].
+    cpu_packages: HashMap<u32, u32>,
+}
+
+/// Directory that contains one subdirectory per CPU.
+const CPU_SYSFS_DIR: &str = "/sys/devices/system/cpu";
+
+impl Default for Grouping {
+    /// Keeps every dimension: each series of points is aggregated separately.
+    fn default() -> Self {
+        Self {
+            resource: Level::Full,
+            consumer: Level::Full,
+            attributes: AttributeFilter::Except(Vec::new()),
+            cpu_packages: HashMap::new(),
+        }
+    }
+}
+
+impl Grouping {
+    /// Keeps only the given dimensions, and drops the others.
+    ///
+    /// To use [`Dimension::ResourcePackage`], the package of each CPU must be provided
+    /// with [`Grouping::with_cpu_packages`].
+    pub fn group_by(dimensions: &[Dimension]) -> Self {
+        let level = |full: Dimension, kind: Dimension, id: Dimension| {
+            if dimensions.contains(&full) || dimensions.contains(&id) {
+                Level::Full
+            } else if full == Dimension::Resource && dimensions.contains(&Dimension::ResourcePackage) {
+                Level::Package
+            } else if dimensions.contains(&kind) {
+                Level::Kind
+            } else {
+                Level::Nothing
+            }
+        };
+        Self {
+            resource: level(Dimension::Resource, Dimension::ResourceKind, Dimension::ResourceId),
+            consumer: level(Dimension::Consumer, Dimension::ConsumerKind, Dimension::ConsumerId),
+            attributes: AttributeFilter::Only(attribute_names(dimensions)),
+            cpu_packages: HashMap::new(),
+        }
+    }
+
+    /// Drops the given dimensions, and keeps the others.
+    pub fn drop(dimensions: &[Dimension]) -> Self {
+        let level = |full: Dimension, kind: Dimension, id: Dimension| {
+            if dimensions.contains(&full) || dimensions.contains(&kind) {
+                Level::Nothing
+            } else if dimensions.contains(&id) {
+                Level::Kind
+            } else {
+                Level::Full
+            }
+        };
+        Self {
+            resource: level(Dimension::Resource, Dimension::ResourceKind, Dimension::ResourceId),
+            consumer: level(Dimension::Consumer, Dimension::ConsumerKind, Dimension::ConsumerId),
+            attributes: AttributeFilter::Except(attribute_names(dimensions)),
+            cpu_packages: HashMap::new(),
+        }
+    }
+
+    /// Sets the package of each CPU, which is used to replace the `CpuCore` resources by `CpuPackage`.
+    pub fn with_cpu_packages(mut self, cpu_packages: HashMap<u32, u32>) -> Self {
+        self.cpu_packages = cpu_packages;
+        self
+    }
+
+    /// Builds the grouping from the `group_by` and `drop` options of the configuration.
+    ///
+    /// If needed, the CPU topology is read from the sysfs.
+    pub fn from_config(group_by: Option<&[Dimension]>, drop: &[Dimension]) -> anyhow::Result<Self> {
+        if drop.contains(&Dimension::ResourcePackage) {
+            return Err(anyhow!("resource_package can only be used in group_by"));
+        }
+        match (group_by, drop) {
+            (Some(_), [_, ..]) => Err(anyhow!("group_by and drop cannot be used at the same time")),
+            (Some(group_by), []) => {
+                let grouping = Self::group_by(group_by);
+                if grouping.resource == Level::Package {
+                    let cpu_packages = read_cpu_packages(Path::new(CPU_SYSFS_DIR))?;
+                    Ok(grouping.with_cpu_packages(cpu_packages))
+                } else {
+                    Ok(grouping)
+                }
+            }
+            (None, drop) => Ok(Self::drop(drop)),
+        }
+    }
+
+    /// Returns the key of the group that the point belongs to.
+    ///
+    /// The attributes of the key are sorted by name.
+    pub fn key(&self, point: &MeasurementPoint) -> SeriesKey {
+        let mut attributes: Vec<(String, AttributeValue)> = point
+            .attributes()
+            .filter(|(name, _)| match &self.attributes {
+                AttributeFilter::Only(names) => names.iter().any(|n| n == name),
+                AttributeFilter::Except(names) => !names.iter().any(|n| n == name),
+            })
+            .map(|(name, value)| (name.to_owned(), value.clone()))
+            .collect();
+        attributes.sort_by(|a, b| a.0.cmp(&b.0));
+        (
+            point.metric,
+            reduce_consumer(&point.consumer, self.consumer),
+            reduce_resource(&point.resource, self.resource, &self.cpu_packages),
+            attributes,
+        )
+    }
+}
+
+fn attribute_names(dimensions: &[Dimension]) -> Vec<String> {
+    dimensions
+        .iter()
+        .filter_map(|d| match d {
+            Dimension::Attribute(name) => Some(name.to_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Reads the package of each CPU from `cpu_dir` (usually `/sys/devices/system/cpu`).
+fn read_cpu_packages(cpu_dir: &Path) -> anyhow::Result<HashMap<u32, u32>> {
+    let mut res = HashMap::new();
+    let entries = std::fs::read_dir(cpu_dir).with_context(|| format!("failed to list {}", cpu_dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(cpu) = name.to_str().and_then(|n| n.strip_prefix("cpu")?.parse::<u32>().ok()) else {
+            continue;
+        };
+        // the topology of offline CPUs is not available
+        let path = entry.path().join("topology/physical_package_id");
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let package = content
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid package id in {}: {content}", path.display()))?;
+        res.insert(cpu, package);
+    }
+    Ok(res)
+}
+
+/// Id of the resources and consumers that group all the ids of a kind.
+const GROUP_ID: &str = "all";
+
+/// Removes the dropped parts of the resource.
+///
+/// When only the kind is kept, the resource becomes a custom resource of kind `<kind>_group` and id `all`.
+/// The custom kind is distinct from the original one, so that the result can still be parsed with
+/// [`Resource::parse`] (for instance, `cpu_core` requires a numeric id).
+/// When the package is kept, the CPU cores are replaced by their package.
+fn reduce_resource(resource: &Resource, level: Level, cpu_packages: &HashMap<u32, u32>) -> Resource {
+    match (level, resource) {
+        (Level::Package, Resource::CpuCore { id }) => match cpu_packages.get(id) {
+            Some(package) => Resource::CpuPackage { id: *package },
+            None => resource.clone(),
+        },
+        (Level::Full | Level::Package, r) => r.clone(),
+        (Level::Nothing, _) | (Level::Kind, Resource::LocalMachine) => Resource::LocalMachine,
+        (Level::Kind, r) => Resource::custom(format!("{}_group", r.kind()), GROUP_ID),
+    }
+}
+
+/// Removes the dropped parts of the consumer, like [`reduce_resource`].
+fn reduce_consumer(consumer: &ResourceConsumer, level: Level) -> ResourceConsumer {
+    match (level, consumer) {
+        (Level::Full | Level::Package, c) => c.clone(),
+        (Level::Nothing, _) | (Level::Kind, ResourceConsumer::LocalMachine) => ResourceConsumer::LocalMachine,
+        (Level::Kind, c) => ResourceConsumer::custom(format!("{}_group", c.kind()), GROUP_ID),
+    }
+}
+
+impl FromStr for Dimension {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "resource" => Dimension::Resource,
+            "resource_kind" => Dimension::ResourceKind,
+            "resource_id" => Dimension::ResourceId,
+            "resource_package" => Dimension::ResourcePackage,
+            "consumer" => Dimension::Consumer,
+            "consumer_kind" => Dimension::ConsumerKind,
+            "consumer_id" => Dimension::ConsumerId,
+            attribute => Dimension::Attribute(attribute.to_owned()),
+        })
+    }
+}
+
+impl From<String> for Dimension {
+    fn from(value: String) -> Self {
+        match value.parse() {
+            Ok(d) => d,
+            Err(e) => match e {},
+        }
+    }
+}
+
+impl Display for Dimension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dimension::Resource => f.write_str("resource"),
+            Dimension::ResourceKind => f.write_str("resource_kind"),
+            Dimension::ResourceId => f.write_str("resource_id"),
+            Dimension::ResourcePackage => f.write_str("resource_package"),
+            Dimension::Consumer => f.write_str("consumer"),
+            Dimension::ConsumerKind => f.write_str("consumer_kind"),
+            Dimension::ConsumerId => f.write_str("consumer_id"),
+            Dimension::Attribute(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<Dimension> for String {
+    fn from(value: Dimension) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::{AttributeValue, WrappedMeasurementValue},
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use std::collections::HashMap;
+
+    use super::{Dimension, Grouping, Level, read_cpu_packages, reduce_consumer, reduce_resource};
+    use crate::transform::tests::new_point;
+
+    fn dimensions(names: &[&str]) -> Vec<Dimension> {
+        names.iter().map(|n| Dimension::from(n.to_string())).collect()
+    }
+
+    #[test]
+    fn parse_dimensions() {
+        assert_eq!(
+            dimensions(&["resource_kind", "consumer", "domain"]),
+            vec![
+                Dimension::ResourceKind,
+                Dimension::Consumer,
+                Dimension::Attribute(String::from("domain"))
+            ]
+        );
+    }
+
+    #[test]
+    fn default_keeps_everything() {
+        let mut point = new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(0), 0);
+        point.resource = Resource::CpuCore { id: 3 };
+        point.consumer = ResourceConsumer::Process { pid: 42 };
+        point.add_attr("z", "last");
+        point.add_attr("a", "first");
+
+        let (_, consumer, resource, attributes) = Grouping::default().key(&point);
+        assert_eq!(consumer, ResourceConsumer::Process { pid: 42 });
+        assert_eq!(resource, Resource::CpuCore { id: 3 });
+        // the attributes are sorted
+        assert_eq!(
+            attributes,
+            vec![
+                (String::from("a"), AttributeValue::Str("first")),
+                (String::from("z"), AttributeValue::Str("last")),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_by() {
+        let mut point = new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(0), 0);
+        point.resource = Resource::CpuCore { id: 3 };
+        point.consumer = ResourceConsumer::Process { pid: 42 };
+        point.add_attr("domain", "package");
+        point.add_attr("other", 1_u64);
+
+        let grouping = Grouping::group_by(&dimensions(&["resource_kind", "domain"]));
+        let (_, consumer, resource, attributes) = grouping.key(&point);
+        assert_eq!(consumer, ResourceConsumer::LocalMachine);
+        assert_eq!(resource, Resource::custom("cpu_core_group", "all"));
+        assert_eq!(
+            attributes,
+            vec![(String::from("domain"), AttributeValue::Str("package"))]
+        );
+    }
+
+    #[test]
+    fn drop() {
+        let mut point = new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(0), 0);
+        point.resource = Resource::CpuCore { id: 3 };
+        point.consumer = ResourceConsumer::Process { pid: 42 };
+        point.add_attr("domain", "package");
+        point.add_attr("other", 1_u64);
+
+        let grouping = Grouping::drop(&dimensions(&["consumer_id", "other"]));
+        let (_, consumer, resource, attributes) = grouping.key(&point);
+        assert_eq!(consumer, ResourceConsumer::custom("process_group", "all"));
+        assert_eq!(resource, Resource::CpuCore { id: 3 });
+        assert_eq!(
+            attributes,
+            vec![(String::from("domain"), AttributeValue::Str("package"))]
+        );
+    }
+
+    #[test]
+    fn reduced_kind_can_be_parsed() {
+        let resources = [
+            Resource::CpuCore { id: 3 },
+            Resource::CpuPackage { id: 0 },
+            Resource::Dram { pkg_id: 1 },
+            Resource::LocalMachine,
+        ];
+        for r in resources {
+            let reduced = reduce_resource(&r, Level::Kind, &HashMap::new());
+            let id = reduced.id_string().unwrap_or_default();
+            assert_eq!(Resource::parse(reduced.kind().to_owned(), id).unwrap(), reduced);
+        }
+        for c in [
+            ResourceConsumer::Process { pid: 42 },
+            ResourceConsumer::ControlGroup { path: "/a".into() },
+        ] {
+            let reduced = reduce_consumer(&c, Level::Kind);
+            let id = reduced.id_string().unwrap_or_default();
+            assert_eq!(ResourceConsumer::parse(reduced.kind().to_owned(), id).unwrap(), reduced);
+        }
+    }
+
+    #[test]
+    fn group_by_package() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        for (cpu, package) in [(0, 0), (1, 0), (2, 1), (3, 1)] {
+            let dir = root.path().join(format!("cpu{cpu}/topology"));
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join("physical_package_id"), format!("{package}\n"))?;
+        }
+        // not a CPU
+        std::fs::create_dir_all(root.path().join("cpufreq"))?;
+        let cpu_packages = read_cpu_packages(root.path())?;
+        assert_eq!(cpu_packages, HashMap::from([(0, 0), (1, 0), (2, 1), (3, 1)]));
+
+        let grouping = Grouping::group_by(&dimensions(&["resource_package"])).with_cpu_packages(cpu_packages);
+        let mut point = new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(0), 0);
+        point.resource = Resource::CpuCore { id: 3 };
+        let (_, _, resource, _) = grouping.key(&point);
+        assert_eq!(resource, Resource::CpuPackage { id: 1 });
+
+        point.resource = Resource::Dram { pkg_id: 0 };
+        let (_, _, resource, _) = grouping.key(&point);
+        assert_eq!(resource, Resource::Dram { pkg_id: 0 });
+        Ok(())
+    }
+
+    #[test]
+    fn group_by_and_drop_are_exclusive() {
+        assert!(Grouping::from_config(Some(&dimensions(&["resource"])), &dimensions(&["consumer"])).is_err());
+        assert_eq!(Grouping::from_config(None, &[]).unwrap(), Grouping::default());
+        assert!(Grouping::from_config(None, &dimensions(&["resource_package"])).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 mod aggregations;
+mod grouping;
 mod transform;
 
 use std::{
@@ -16,15 +17,20 @@ use alumet::{
 };
 
 use anyhow::{Context, anyhow};
+use grouping::{Dimension, Grouping};
 use serde::{Deserialize, Serialize};
-use transform::AggregationTransform;
+use transform::{AggregationTransform, TimestampAlignment, Window};
 
 pub struct AggregationPlugin {
     config: Config,
 
+    /// Dimensions that are kept or dropped when grouping the points, built from the config.
+    grouping: Grouping,
+
     /// Store the correspondence table between aggregated metrics and the original ones.
-    /// The key is the original metric's id and the value is the id of the aggregated metric.
-    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
+    /// The key is the original metric's id and the value contains the ids of the aggregated metrics,
+    /// one per function.
+    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, Vec<RawMetricId>>>>,
 
     metrics_list: Vec<Metric>,
     old_ids: Vec<RawMetricId>,
@@ -44,21 +50,38 @@ impl AlumetPlugin for AggregationPlugin {
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
-        let config = deserialize_config(config)?;
+        let config: Config = deserialize_config(config)?;
+        if config.functions.is_empty() {
+            return Err(anyhow!("at least one aggregation function is required"));
+        }
+        if config.interval.is_zero() || config.slide.is_some_and(|s| s.is_zero()) {
+            return Err(anyhow!("the aggregation interval and slide must not be zero"));
+        }
+        let grouping = Grouping::from_config(config.group_by.as_deref(), &config.drop)?;
         Ok(Box::new(AggregationPlugin {
             config,
-            metric_correspondence_table: Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::new())),
+            grouping,
+            metric_correspondence_table: Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::new())),
             metrics_list: Vec::<Metric>::new(),
             old_ids: Vec::<RawMetricId>::new(),
         }))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
-        let transform = Box::new(AggregationTransform::new(
-            self.config.interval,
-            self.config.function,
-            self.metric_correspondence_table.clone(),
-        ));
+        let window = Window {
+            size: self.config.interval,
+            step: self.config.slide.unwrap_or(self.config.interval),
+            alignment: self.config.timestamp_alignment,
+        };
+        let transform = Box::new(
+            AggregationTransform::new(
+                window,
+                self.config.functions.clone(),
+                self.config.drop_input,
+                self.metric_correspondence_table.clone(),
+            )
+            .with_grouping(self.grouping.clone()),
+        );
         alumet.add_transform("plugin-aggregation", transform)?;
 
         // TODO: give metric sender to the transformPlugin P2
@@ -73,17 +96,22 @@ impl AlumetPlugin for AggregationPlugin {
                 .by_name(metric_name)
                 .with_context(|| format!("metric \"{}\" not found", &metric_name))?;
             self.old_ids.push(raw_metric_id);
-            let new_metric = Metric {
-                name: format!("{metric_name}_{}", self.config.function.name()),
-                unit: metric.unit.clone(),
-                description: metric.description.clone(),
-                value_type: metric.value_type.clone(),
-            };
-
-            self.metrics_list.push(new_metric);
+            for function in &self.config.functions {
+                let value_type = function
+                    .output_type(&metric.value_type)
+                    .with_context(|| format!("cannot aggregate metric \"{metric_name}\""))?;
+                let new_metric = Metric {
+                    name: format!("{metric_name}_{}", function.name()),
+                    unit: function.output_unit(&metric.unit),
+                    description: metric.description.clone(),
+                    value_type,
+                };
+
+                self.metrics_list.push(new_metric);
+            }
         }
 
-        if self.metrics_list.len() != self.old_ids.len() {
+        if self.metrics_list.len() != self.old_ids.len() * self.config.functions.len() {
             return Err(anyhow!(
                 "could not pre register one aggregated metric for each requested metrics"
             ));
@@ -101,6 +129,7 @@ impl AlumetPlugin for AggregationPlugin {
             &mut alumet.metrics_sender(),
             self.metrics_list.clone(),
             self.old_ids.clone(),
+            self.config.functions.len(),
             self.metric_correspondence_table.clone(),
         ))
     }
@@ -114,38 +143,65 @@ async fn register_new_metrics(
     metric_sender: &mut MetricSender,
     new_metrics: Vec<Metric>,
     old_ids: Vec<RawMetricId>,
-    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
+    functions_per_metric: usize,
+    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, Vec<RawMetricId>>>>,
 ) -> anyhow::Result<()> {
     let result = metric_sender
         .create_metrics(new_metrics, DuplicateReaction::Error)
         .await
         .map_err(|a| anyhow!("{a}"))?;
 
-    for (before, after) in std::iter::zip(old_ids, result) {
-        let new_id = after?;
+    // The new metrics are ordered by original metric, then by function.
+    let mut result = result.into_iter();
+    for before in old_ids {
+        let new_ids = result
+            .by_ref()
+            .take(functions_per_metric)
+            .collect::<Result<Vec<_>, _>>()?;
 
         let metric_correspondence_table_clone = &metric_correspondence_table.clone();
         let mut metric_correspondence_table_write = metric_correspondence_table_clone
             .write()
             .expect("metric_correspondence_table lock poisoned");
 
-        metric_correspondence_table_write.insert(before, new_id);
+        metric_correspondence_table_write.insert(before, new_ids);
     }
     Ok(())
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 struct Config {
-    /// Interval for the aggregation.
+    /// Interval for the aggregation, i.e. the size of the time windows.
     #[serde(with = "humantime_serde")]
     interval: Duration,
 
-    // TODO: add boolean about moving aggregation window. P3
+    /// Time between the start of two consecutive windows.
+    /// Leave empty to use non-overlapping windows, which is equivalent to `slide = interval`.
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    slide: Option<Duration>,
+
+    /// Position of the timestamp of the aggregated points in their window: "left", "center" or "right".
+    #[serde(default)]
+    timestamp_alignment: TimestampAlignment,
+
+    /// If true, the points of the aggregated metrics are removed, only the aggregated points are kept.
+    #[serde(default = "default_drop_input")]
+    drop_input: bool,
+
+    /// Functions to apply. Each function produces a metric named `<metric>_<function>`.
+    #[serde(alias = "function", deserialize_with = "aggregations::one_or_many")]
+    functions: Vec<aggregations::Function>,
 
-    // TODO: add boolean to drop or not the received metric point. P2
+    /// Dimensions to keep: the points that have the same values for these dimensions are aggregated together.
+    /// Possible dimensions: "resource", "resource_kind", "resource_package", "consumer", "consumer_kind",
+    /// or the name of an attribute. Leave empty to keep every dimension. Cannot be used with `drop`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    group_by: Option<Vec<Dimension>>,
 
-    // TODO: add possibility to choose if the generated timestamp is at the left, center or right of the interval. P3
-    function: aggregations::Function,
+    /// Dimensions to drop, the other ones are kept. Same format as `group_by`,
+    /// with "resource_id" and "consumer_id" to only drop the ids.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    drop: Vec<Dimension>,
 
     // List of metrics where to apply function.
     // Leave empty to apply function to every metrics. NO
@@ -153,11 +209,20 @@ struct Config {
     metrics: Vec<String>,
 }
 
+fn default_drop_input() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             interval: Duration::from_secs(60),
-            function: aggregations::Function::Sum,
+            slide: None,
+            timestamp_alignment: TimestampAlignment::default(),
+            drop_input: default_drop_input(),
+            functions: vec![aggregations::Function::Sum],
+            group_by: None,
+            drop: Vec::new(),
             metrics: Vec::<String>::new(),
         }
     }
@@ -165,6 +230,8 @@ impl Default for Config {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use alumet::plugin::rust::AlumetPlugin;
 
     use crate::AggregationPlugin;
@@ -183,4 +250,46 @@ mod tests {
     fn test_init() {
         let _ = AggregationPlugin::init(AggregationPlugin::default_config().unwrap().unwrap()).unwrap();
     }
+
+    #[test]
+    fn test_config() {
+        use crate::{Config, aggregations::Function, transform::TimestampAlignment};
+
+        // older configurations only contain one function
+        let config: Config = toml::from_str(
+            r#"
+            interval = "10s"
+            function = "Mean"
+            metrics = ["rapl_consumed_energy"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.functions, vec![Function::Mean]);
+        assert!(config.drop_input);
+        assert_eq!(config.slide, None);
+
+        let config: Config = toml::from_str(
+            r#"
+            interval = "1m"
+            slide = "10s"
+            timestamp_alignment = "right"
+            drop_input = false
+            functions = ["max", "p95", "rate"]
+            metrics = ["rapl_consumed_energy"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.functions,
+            vec![Function::Max, Function::Percentile(95.0), Function::Rate]
+        );
+        assert_eq!(config.slide, Some(Duration::from_secs(10)));
+        assert_eq!(config.timestamp_alignment, TimestampAlignment::Right);
+        assert!(!config.drop_input);
+
+        // the default config can be serialized and parsed again
+        let default = toml::to_string(&Config::default()).unwrap();
+        let parsed: Config = toml::from_str(&default).unwrap();
+        assert_eq!(parsed.functions, vec![Function::Sum]);
+    }
 }
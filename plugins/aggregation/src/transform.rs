@@ -1,13 +1,13 @@
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
-    time::{Duration, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::anyhow;
 
 use alumet::{
-    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp},
     metrics::RawMetricId,
     pipeline::{
         Transform,
@@ -15,40 +15,110 @@ use alumet::{
     },
     resources::{Resource, ResourceConsumer},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::{aggregations, grouping::Grouping};
+
+/// Identifies a group of measurement points that are aggregated together, see [`Grouping`].
+pub(crate) type SeriesKey = (RawMetricId, ResourceConsumer, Resource, Vec<(String, AttributeValue)>);
+
+/// The time windows on which the aggregation functions are computed.
+///
+/// Windows start at multiples of `step` (since the UNIX epoch) and last `size`.
+/// When `step == size`, the windows are "tumbling": they don't overlap.
+/// When `step < size`, the windows are "sliding": a point belongs to several windows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub size: Duration,
+    pub step: Duration,
+    pub alignment: TimestampAlignment,
+}
 
-use crate::aggregations::{self};
+/// Position of the timestamp of the aggregated points in their window.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampAlignment {
+    /// Start of the window.
+    #[default]
+    Left,
+    /// Middle of the window.
+    Center,
+    /// End of the window.
+    Right,
+}
+
+impl Window {
+    /// Returns the timestamp of the points computed on the window that starts at `start`.
+    fn timestamp(&self, start: Timestamp) -> Timestamp {
+        let offset = match self.alignment {
+            TimestampAlignment::Left => Duration::ZERO,
+            TimestampAlignment::Center => self.size / 2,
+            TimestampAlignment::Right => self.size,
+        };
+        start + offset
+    }
+
+    /// Returns the start of the first window that contains `t`.
+    fn first_start(&self, t: Timestamp) -> Timestamp {
+        match SystemTime::from(t).checked_sub(self.size) {
+            Some(before) if before >= UNIX_EPOCH => {
+                compute_min_timestamp(Timestamp::from(before), self.step) + self.step
+            }
+            _ => Timestamp::from(UNIX_EPOCH),
+        }
+    }
+}
 
 pub struct AggregationTransform {
-    /// Interval used to compute the aggregation.
-    interval: Duration,
+    /// Windows used to compute the aggregation.
+    window: Window,
 
     /// Buffer used to store every measurement point affected by the aggregation.
-    internal_buffer:
-        HashMap<(RawMetricId, ResourceConsumer, Resource, Vec<(String, AttributeValue)>), Vec<MeasurementPoint>>, // TODO: improve the attribute key parts P2.
+    internal_buffer: HashMap<SeriesKey, Vec<MeasurementPoint>>,
+
+    /// Start of the next window to compute, for each series.
+    next_window: HashMap<SeriesKey, Timestamp>,
 
     /// Store the correspondence table between aggregated metrics and the original ones.
-    /// The key is the original metric's id and the value is the id of the aggregated metric.
-    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
+    /// The key is the original metric's id and the value contains the ids of the aggregated metrics,
+    /// in the same order as the functions.
+    metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, Vec<RawMetricId>>>>,
+
+    /// Aggregation functions.
+    functions: Vec<aggregations::Function>,
 
-    /// Aggregation function.
-    function: fn(Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue>,
+    /// If true, the points of the aggregated metrics are removed from the pipeline.
+    drop_input: bool,
+
+    /// Dimensions that are kept or dropped when grouping the points.
+    grouping: Grouping,
 }
 
 impl AggregationTransform {
     /// Instantiates a new instance of the aggregation transform plugin.
     pub fn new(
-        interval: Duration,
-        function: aggregations::Function,
-        metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, RawMetricId>>>,
+        window: Window,
+        functions: Vec<aggregations::Function>,
+        drop_input: bool,
+        metric_correspondence_table: Arc<RwLock<HashMap<RawMetricId, Vec<RawMetricId>>>>,
     ) -> Self {
         Self {
-            interval,
+            window,
             internal_buffer: HashMap::new(),
+            next_window: HashMap::new(),
             metric_correspondence_table,
-            function: function.function(),
+            functions,
+            drop_input,
+            grouping: Grouping::default(),
         }
     }
 
+    /// Aggregates the points according to the given grouping, instead of keeping every dimension.
+    pub fn with_grouping(mut self, grouping: Grouping) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
     /// Empties the buffer and send the aggregated points to the MeasurementBuffer.
     fn buffer_bouncer(
         &mut self,
@@ -62,48 +132,75 @@ impl AggregationTransform {
         let mut aggregated_points = MeasurementBuffer::new();
         log::debug!("buffer size: {}", self.internal_buffer.len());
 
+        let window = self.window;
         for (key, values) in &mut self.internal_buffer {
             // TODO: Clean the internal_buffer by deleting the empty values/key P2.
             loop {
-                let min_timestamp = compute_min_timestamp(values[0].timestamp, self.interval);
-
-                if !contains_enough_data(self.interval, values, min_timestamp) {
-                    break;
+                // Drop the points that arrived too late for their window.
+                let next_window = self.next_window.get(key).copied();
+                if let Some(next) = next_window {
+                    values.retain(|p| p.timestamp >= next);
                 }
 
-                let (i, j) = get_ids(self.interval, values, min_timestamp)?;
-
-                let sub_vec: Vec<MeasurementPoint> = values.drain(i..=j).collect();
-
-                // Compute the value of the aggregated point.
-                let Some(value) = (self.function)(sub_vec.clone()) else {
-                    return Err(TransformError::UnexpectedInput(anyhow!(
-                        "could not compute the aggregation for the sub_vec of {key:?}"
-                    )));
+                let Some(first) = values.first() else {
+                    break;
+                };
+                let first_start = window.first_start(first.timestamp);
+                let min_timestamp = match next_window {
+                    Some(next) if next > first_start => next,
+                    _ => first_start,
                 };
 
-                // Init the new point.
-                let new_point = MeasurementPoint::new_untyped(
-                    compute_min_timestamp(sub_vec[0].timestamp, self.interval),
-                    *metric_correspondence_table_read
-                        .get(&key.clone().0)
-                        .ok_or(TransformError::UnexpectedInput(anyhow!(
-                            "the metric ID {} is not known by the correspondence table",
-                            &key.clone().0.as_u64()
-                        )))?,
-                    key.clone().2,
-                    key.clone().1,
-                    value,
-                )
-                .with_attr_vec(
-                    sub_vec[0]
-                        .attributes()
-                        .map(|(key, value)| (key.to_owned(), value.clone()))
-                        .collect(),
-                );
-
-                // Push the new point to the result buffer.
-                aggregated_points.push(new_point);
+                if !contains_enough_data(window.size, values, min_timestamp) {
+                    break;
+                }
+
+                let (i, j) = get_ids(window.size, values, min_timestamp)?;
+                let sub_vec: Vec<MeasurementPoint> = values[i..=j].to_vec();
+
+                // Remove the points that are not part of the next window.
+                let next_start = min_timestamp + window.step;
+                let n_expired = values.iter().take_while(|p| p.timestamp < next_start).count();
+                values.drain(..n_expired);
+                self.next_window.insert(key.clone(), next_start);
+
+                let new_ids = metric_correspondence_table_read
+                    .get(&key.0)
+                    .ok_or(TransformError::UnexpectedInput(anyhow!(
+                        "the metric ID {} is not known by the correspondence table",
+                        &key.0.as_u64()
+                    )))?;
+
+                for (function, new_id) in self.functions.iter().zip(new_ids) {
+                    if sub_vec.len() < function.min_points() {
+                        continue;
+                    }
+
+                    // Compute the value of the aggregated point.
+                    let value = match function.apply(sub_vec.clone()) {
+                        Some(value) => value,
+                        // none of the series of the group has two points in the window
+                        None if *function == aggregations::Function::Rate => continue,
+                        None => {
+                            return Err(TransformError::UnexpectedInput(anyhow!(
+                                "could not compute the aggregation for the sub_vec of {key:?}"
+                            )));
+                        }
+                    };
+
+                    // Init the new point.
+                    let new_point = MeasurementPoint::new_untyped(
+                        window.timestamp(min_timestamp),
+                        *new_id,
+                        key.2.clone(),
+                        key.1.clone(),
+                        value,
+                    )
+                    .with_attr_vec(key.3.clone());
+
+                    // Push the new point to the result buffer.
+                    aggregated_points.push(new_point);
+                }
             }
         }
 
@@ -130,20 +227,14 @@ impl Transform for AggregationTransform {
                 continue;
             }
 
-            let id = (
-                measurement.metric,
-                measurement.consumer.clone(),
-                measurement.resource.clone(),
-                measurement
-                    .attributes()
-                    .map(|attribute| (attribute.0.to_string(), attribute.1.clone()))
-                    .collect::<Vec<(String, AttributeValue)>>(),
-            );
+            let id = self.grouping.key(measurement);
 
-            // Add the measurement point to the internal buffer.
+            // Add the measurement point to the internal buffer, which is kept sorted by timestamp:
+            // a group can contain several series, whose points are not received in order.
             match self.internal_buffer.get_mut(&id) {
                 Some(vec_points) => {
-                    vec_points.push(measurement.clone());
+                    let pos = vec_points.partition_point(|p| p.timestamp <= measurement.timestamp);
+                    vec_points.insert(pos, measurement.clone());
                 }
                 None => {
                     self.internal_buffer.insert(id.clone(), vec![measurement.clone()]);
@@ -151,11 +242,11 @@ impl Transform for AggregationTransform {
             }
         }
 
-        // clear the measurementBuffer if needed (see TODO on config boolean)
-        measurements.clear();
-
-        // Fill it again with the not needed points.
-        measurements.merge(&mut not_needed_measurement_point);
+        if self.drop_input {
+            // Only keep the points that are not aggregated.
+            measurements.clear();
+            measurements.merge(&mut not_needed_measurement_point);
+        }
 
         self.buffer_bouncer(measurements)
     }
@@ -164,13 +255,15 @@ impl Transform for AggregationTransform {
 /// Returns true if the vec contains enough data to compute the aggregation
 /// for the configured window.
 fn contains_enough_data(interval: Duration, values: &Vec<MeasurementPoint>, min_timestamp: Timestamp) -> bool {
-    let elapsed = values[values.len() - 1]
-        .timestamp
-        .duration_since(min_timestamp)
-        .unwrap();
+    let elapsed = elapsed_since(values[values.len() - 1].timestamp, min_timestamp);
     elapsed >= interval
 }
 
+/// Returns the time elapsed between `earlier` and `t`, or zero if `t` is before `earlier`.
+fn elapsed_since(t: Timestamp, earlier: Timestamp) -> Duration {
+    t.duration_since(earlier).unwrap_or(Duration::ZERO)
+}
+
 /// Get the IDs of the first and last measurement point that are
 /// inside the interval window.
 fn get_ids(
@@ -181,7 +274,7 @@ fn get_ids(
     let i: usize = 0;
 
     let Some(j) = values.iter().position(|point| {
-        let elapsed = point.timestamp.duration_since(min_timestamp).unwrap();
+        let elapsed = elapsed_since(point.timestamp, min_timestamp);
         elapsed >= interval
     }) else {
         return Err(anyhow!("could not compute the IDs for the current sub_vec"));
@@ -222,10 +315,19 @@ pub(crate) mod tests {
         resources::{Resource, ResourceConsumer},
     };
 
-    use crate::transform::{compute_min_timestamp, contains_enough_data};
+    use crate::transform::{TimestampAlignment, Window, compute_min_timestamp, contains_enough_data};
 
     use super::get_ids;
 
+    /// Returns non-overlapping windows of the given size.
+    pub(crate) fn tumbling(size: Duration) -> Window {
+        Window {
+            size,
+            step: size,
+            alignment: TimestampAlignment::Left,
+        }
+    }
+
     /// Parses an RFC 3339 date-and-time string into a Timestamp value.
     pub(crate) fn timestamp_from_rfc3339(timestamp: &str) -> Timestamp {
         SystemTime::from(OffsetDateTime::parse(timestamp, &Rfc3339).unwrap()).into()
@@ -373,7 +475,7 @@ pub(crate) mod tests {
         use anyhow::anyhow;
 
         use alumet::{
-            measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+            measurement::{AttributeValue, Histogram, MeasurementBuffer, WrappedMeasurementValue},
             metrics::RawMetricId,
             pipeline::elements::error::TransformError,
             resources::{Resource, ResourceConsumer},
@@ -382,17 +484,18 @@ pub(crate) mod tests {
         use crate::{
             aggregations,
             transform::{
-                AggregationTransform,
-                tests::{measurement_buffer_to_comparable_vec, new_point, timestamp_from_rfc3339},
+                AggregationTransform, TimestampAlignment, Window,
+                tests::{measurement_buffer_to_comparable_vec, new_point, timestamp_from_rfc3339, tumbling},
             },
         };
 
         #[test]
         fn empty_buffer() {
             let mut transform_plugin = AggregationTransform::new(
-                Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::new())),
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Mean],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::new())),
             );
 
             let mut measurement_buffer = MeasurementBuffer::new();
@@ -405,11 +508,12 @@ pub(crate) mod tests {
         #[test]
         fn buffer_with_data() {
             let mut transform_plugin = AggregationTransform::new(
-                Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Mean],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([
+                    (RawMetricId::from_u64(1), vec![RawMetricId::from_u64(4)]),
+                    (RawMetricId::from_u64(2), vec![RawMetricId::from_u64(7)]),
                 ]))),
             );
 
@@ -490,25 +594,22 @@ pub(crate) mod tests {
         }
 
         #[test]
-        fn error_in_aggregation_calculation() {
-            fn aggregation_returns_none(_: Vec<MeasurementPoint>) -> Option<WrappedMeasurementValue> {
-                None
-            }
-
-            let mut transform_plugin = AggregationTransform {
-                interval: Duration::from_secs(10),
-                internal_buffer: HashMap::<
-                    (RawMetricId, ResourceConsumer, Resource, Vec<(String, AttributeValue)>),
-                    Vec<MeasurementPoint>,
-                >::new(),
-                metric_correspondence_table: Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
-                ]))),
-                function: aggregation_returns_none,
+        fn sliding_windows() {
+            let window = Window {
+                size: Duration::from_secs(10),
+                step: Duration::from_secs(5),
+                alignment: TimestampAlignment::Left,
             };
+            let mut transform_plugin = AggregationTransform::new(
+                window,
+                vec![aggregations::Function::Sum],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([(
+                    RawMetricId::from_u64(1),
+                    vec![RawMetricId::from_u64(4)],
+                )]))),
+            );
 
-            // Add first list of measurement points.
             let key = (
                 RawMetricId::from_u64(1),
                 ResourceConsumer::LocalMachine,
@@ -527,6 +628,160 @@ pub(crate) mod tests {
                 ],
             );
 
+            let mut measurement_buffer = MeasurementBuffer::new();
+            transform_plugin.buffer_bouncer(&mut measurement_buffer).unwrap();
+
+            // Each point belongs to two windows.
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer),
+                vec![
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:18:55Z"),
+                        WrappedMeasurementValue::U64(1),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                        WrappedMeasurementValue::U64(19),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:05Z"),
+                        WrappedMeasurementValue::U64(21),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:10Z"),
+                        WrappedMeasurementValue::U64(9),
+                        4
+                    ),
+                ]
+            );
+            // The points of the next windows are kept.
+            assert_eq!(transform_plugin.internal_buffer.get(&key).unwrap().len(), 2);
+
+            // A late point is ignored, and the windows that have already been computed are not computed again.
+            transform_plugin.internal_buffer.get_mut(&key).unwrap().extend([
+                new_point("2025-02-10T13:19:14Z", WrappedMeasurementValue::U64(100), 1),
+                new_point("2025-02-10T13:19:25Z", WrappedMeasurementValue::U64(1), 1),
+            ]);
+            let mut measurement_buffer = MeasurementBuffer::new();
+            transform_plugin.buffer_bouncer(&mut measurement_buffer).unwrap();
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer),
+                vec![(
+                    timestamp_from_rfc3339("2025-02-10T13:19:15Z"),
+                    WrappedMeasurementValue::U64(6),
+                    4
+                )]
+            );
+        }
+
+        #[test]
+        fn several_functions_and_alignment() {
+            let window = Window {
+                alignment: TimestampAlignment::Center,
+                ..tumbling(Duration::from_secs(10))
+            };
+            let mut transform_plugin = AggregationTransform::new(
+                window,
+                vec![
+                    aggregations::Function::Max,
+                    aggregations::Function::Count,
+                    aggregations::Function::Rate,
+                ],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([(
+                    RawMetricId::from_u64(1),
+                    vec![
+                        RawMetricId::from_u64(4),
+                        RawMetricId::from_u64(5),
+                        RawMetricId::from_u64(6),
+                    ],
+                )]))),
+            );
+
+            transform_plugin.internal_buffer.insert(
+                (
+                    RawMetricId::from_u64(1),
+                    ResourceConsumer::LocalMachine,
+                    Resource::LocalMachine,
+                    Vec::<(String, AttributeValue)>::new(),
+                ),
+                vec![
+                    new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(10), 1),
+                    new_point("2025-02-10T13:19:04Z", WrappedMeasurementValue::U64(30), 1),
+                    new_point("2025-02-10T13:19:12Z", WrappedMeasurementValue::U64(40), 1),
+                    new_point("2025-02-10T13:19:25Z", WrappedMeasurementValue::U64(50), 1),
+                ],
+            );
+
+            let mut measurement_buffer = MeasurementBuffer::new();
+            transform_plugin.buffer_bouncer(&mut measurement_buffer).unwrap();
+
+            // The rate is not computed on the second window, because it only contains one point.
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer),
+                vec![
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:05Z"),
+                        WrappedMeasurementValue::U64(30),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:05Z"),
+                        WrappedMeasurementValue::U64(2),
+                        5
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:05Z"),
+                        WrappedMeasurementValue::F64(5.0),
+                        6
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:15Z"),
+                        WrappedMeasurementValue::U64(40),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:15Z"),
+                        WrappedMeasurementValue::U64(1),
+                        5
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn error_in_aggregation_calculation() {
+            // The sum of histograms with different buckets cannot be computed.
+            let mut transform_plugin = AggregationTransform::new(
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Sum],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([
+                    (RawMetricId::from_u64(1), vec![RawMetricId::from_u64(4)]),
+                    (RawMetricId::from_u64(2), vec![RawMetricId::from_u64(7)]),
+                ]))),
+            );
+            let histogram = |bound: f64| WrappedMeasurementValue::Histogram(Histogram::new(vec![bound]).unwrap());
+
+            // Add first list of measurement points.
+            let key = (
+                RawMetricId::from_u64(1),
+                ResourceConsumer::LocalMachine,
+                Resource::LocalMachine,
+                Vec::<(String, AttributeValue)>::new(),
+            );
+            transform_plugin.internal_buffer.insert(
+                key.clone(),
+                vec![
+                    new_point("2025-02-10T13:19:00Z", histogram(1.0), 1),
+                    new_point("2025-02-10T13:19:01Z", histogram(2.0), 1),
+                    new_point("2025-02-10T13:19:10Z", histogram(1.0), 1),
+                ],
+            );
+
             let mut measurement_buffer = MeasurementBuffer::new();
 
             let result = transform_plugin.buffer_bouncer(&mut measurement_buffer);
@@ -543,11 +798,12 @@ pub(crate) mod tests {
         #[test]
         fn metric_correspondence_table_lock_poisoned() {
             let mut transform_plugin = AggregationTransform::new(
-                Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Mean],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([
+                    (RawMetricId::from_u64(1), vec![RawMetricId::from_u64(4)]),
+                    (RawMetricId::from_u64(2), vec![RawMetricId::from_u64(7)]),
                 ]))),
             );
 
@@ -590,9 +846,10 @@ pub(crate) mod tests {
 
         use crate::{
             aggregations,
+            grouping::{Dimension, Grouping},
             transform::{
                 AggregationTransform,
-                tests::{measurement_buffer_to_comparable_vec, timestamp_from_rfc3339},
+                tests::{measurement_buffer_to_comparable_vec, timestamp_from_rfc3339, tumbling},
             },
         };
 
@@ -607,11 +864,12 @@ pub(crate) mod tests {
             };
 
             let mut transform_plugin = AggregationTransform::new(
-                Duration::from_secs(10),
-                aggregations::Function::Sum,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(0), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Sum],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([
+                    (RawMetricId::from_u64(0), vec![RawMetricId::from_u64(4)]),
+                    (RawMetricId::from_u64(2), vec![RawMetricId::from_u64(7)]),
                 ]))),
             );
 
@@ -705,15 +963,152 @@ pub(crate) mod tests {
             );
         }
 
+        #[test]
+        fn keep_input() {
+            let builder: Builder = Builder::new();
+            let inspector = builder.inspect();
+            let test_tranform_context: TransformContext = TransformContext {
+                metrics: inspector.metrics(),
+            };
+
+            let mut transform_plugin = AggregationTransform::new(
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Sum],
+                false,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([(
+                    RawMetricId::from_u64(0),
+                    vec![RawMetricId::from_u64(4)],
+                )]))),
+            );
+
+            let mut measurement_buffer = MeasurementBuffer::new();
+            measurement_buffer.push(new_point("2025-02-10T13:19:00Z", WrappedMeasurementValue::U64(1), 0));
+            measurement_buffer.push(new_point("2025-02-10T13:19:05Z", WrappedMeasurementValue::U64(2), 0));
+            measurement_buffer.push(new_point("2025-02-10T13:19:10Z", WrappedMeasurementValue::U64(3), 0));
+
+            Transform::apply(&mut transform_plugin, &mut measurement_buffer, &test_tranform_context).unwrap();
+
+            // The input points are still there, followed by the aggregated point.
+            assert_eq!(measurement_buffer.len(), 4);
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer)[1],
+                (
+                    timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                    WrappedMeasurementValue::U64(3),
+                    4
+                )
+            );
+        }
+
+        #[test]
+        fn group_unordered_series() {
+            let builder: Builder = Builder::new();
+            let inspector = builder.inspect();
+            let test_tranform_context: TransformContext = TransformContext {
+                metrics: inspector.metrics(),
+            };
+
+            let grouping = Grouping::group_by(&[Dimension::ResourceKind]);
+            let mut transform_plugin = AggregationTransform::new(
+                tumbling(Duration::from_secs(1)),
+                vec![aggregations::Function::Sum],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([(
+                    RawMetricId::from_u64(0),
+                    vec![RawMetricId::from_u64(4)],
+                )]))),
+            )
+            .with_grouping(grouping);
+
+            // The points of the two cores are merged in the same group, but not received in order.
+            let mut measurement_buffer = MeasurementBuffer::new();
+            for (t, core, value) in [
+                ("2025-02-10T13:19:01Z", 0, 5),
+                ("2025-02-10T13:19:00Z", 1, 20),
+                ("2025-02-10T13:19:00Z", 0, 10),
+                ("2025-02-10T13:19:02Z", 1, 1),
+            ] {
+                let mut point = new_point(t, WrappedMeasurementValue::U64(value), 0);
+                point.resource = Resource::CpuCore { id: core };
+                measurement_buffer.push(point);
+            }
+
+            Transform::apply(&mut transform_plugin, &mut measurement_buffer, &test_tranform_context).unwrap();
+
+            assert_eq!(
+                measurement_buffer_to_comparable_vec(measurement_buffer),
+                vec![
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:00Z"),
+                        WrappedMeasurementValue::U64(30),
+                        4
+                    ),
+                    (
+                        timestamp_from_rfc3339("2025-02-10T13:19:01Z"),
+                        WrappedMeasurementValue::U64(5),
+                        4
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn group_cores() {
+            let builder: Builder = Builder::new();
+            let inspector = builder.inspect();
+            let test_tranform_context: TransformContext = TransformContext {
+                metrics: inspector.metrics(),
+            };
+
+            // Sum the power of every core, per domain.
+            let grouping = Grouping::group_by(&[Dimension::ResourceKind, Dimension::Attribute(String::from("domain"))]);
+            let mut transform_plugin = AggregationTransform::new(
+                tumbling(Duration::from_secs(1)),
+                vec![aggregations::Function::Sum],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([(
+                    RawMetricId::from_u64(0),
+                    vec![RawMetricId::from_u64(4)],
+                )]))),
+            )
+            .with_grouping(grouping);
+
+            let mut measurement_buffer = MeasurementBuffer::new();
+            for (t, core, value) in [
+                ("2025-02-10T13:19:00Z", 0, 10),
+                ("2025-02-10T13:19:00Z", 1, 20),
+                ("2025-02-10T13:19:01Z", 0, 5),
+                ("2025-02-10T13:19:01Z", 1, 5),
+            ] {
+                let mut point = new_point(t, WrappedMeasurementValue::U64(value), 0);
+                point.resource = Resource::CpuCore { id: core };
+                point.add_attr("domain", "core");
+                point.add_attr("pid", 123_u64);
+                measurement_buffer.push(point);
+            }
+
+            Transform::apply(&mut transform_plugin, &mut measurement_buffer, &test_tranform_context).unwrap();
+
+            assert_eq!(measurement_buffer.len(), 1);
+            let point = measurement_buffer.iter().next().unwrap();
+            assert_eq!(point.value, WrappedMeasurementValue::U64(30));
+            assert_eq!(point.resource, Resource::custom("cpu_core_group", "all"));
+            assert_eq!(
+                point.attributes().collect::<Vec<_>>(),
+                vec![("domain", &AttributeValue::Str("core"))]
+            );
+        }
+
         #[test]
         #[should_panic]
         fn metric_correspondence_table_lock_poisoned() {
             let mut transform_plugin = AggregationTransform::new(
-                Duration::from_secs(10),
-                aggregations::Function::Mean,
-                Arc::new(RwLock::new(HashMap::<RawMetricId, RawMetricId>::from([
-                    (RawMetricId::from_u64(1), RawMetricId::from_u64(4)),
-                    (RawMetricId::from_u64(2), RawMetricId::from_u64(7)),
+                tumbling(Duration::from_secs(10)),
+                vec![aggregations::Function::Mean],
+                true,
+                Arc::new(RwLock::new(HashMap::<RawMetricId, Vec<RawMetricId>>::from([
+                    (RawMetricId::from_u64(1), vec![RawMetricId::from_u64(4)]),
+                    (RawMetricId::from_u64(2), vec![RawMetricId::from_u64(7)]),
                 ]))),
             );
 
@@ -0,0 +1,64 @@
+use alumet::plugin::{
+    ConfigTable,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::Context;
+use rules::{Rule, RuleConfig};
+use serde::{Deserialize, Serialize};
+use transform::AttributesTransform;
+
+mod rules;
+mod transform;
+
+pub struct AttributesPlugin {
+    rules: Vec<Rule>,
+}
+
+impl AlumetPlugin for AttributesPlugin {
+    fn name() -> &'static str {
+        "attributes"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        let rules = config
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(i, rule)| Rule::try_from(rule).with_context(|| format!("invalid attribute rule #{}", i + 1)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Box::new(AttributesPlugin { rules }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        if self.rules.is_empty() {
+            log::warn!(
+                "attributes plugin was started but as there's no rule in its configuration, this will do nothing"
+            );
+            return Ok(());
+        }
+
+        let rules = std::mem::take(&mut self.rules);
+        alumet.add_transform_builder("transform", move |_| Ok(Box::new(AttributesTransform::new(rules))))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct Config {
+    /// The rules to apply, in order, to the attributes of the measurements.
+    #[serde(default)]
+    rules: Vec<RuleConfig>,
+}
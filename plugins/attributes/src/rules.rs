@@ -0,0 +1,397 @@
+use std::{borrow::Cow, convert::Infallible, fmt::Display, mem, str::FromStr};
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementPoint, WrappedMeasurementValue},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A rule, as written in the configuration.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RuleConfig {
+    /// Sets an attribute to a fixed value. If the attribute exists, its value is replaced.
+    Add { attribute: String, value: ConfigValue },
+    /// Renames an attribute. If an attribute with the new name exists, it is replaced.
+    Rename { from: String, to: String },
+    /// Removes some attributes.
+    Remove { attributes: Vec<String> },
+    /// Matches a part of the point against a regex, and sets an attribute to the `replacement`.
+    ///
+    /// The replacement can refer to the capture groups of the regex, for instance `$1` or `${name}`.
+    /// If the regex does not match, the point is left unchanged.
+    Rewrite {
+        from: Source,
+        regex: String,
+        replacement: String,
+        /// The attribute to set, defaults to `from` when `from` is an attribute.
+        to: Option<String>,
+    },
+}
+
+/// The value of an attribute, as written in the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Bool(bool),
+    U64(u64),
+    F64(f64),
+    String(String),
+}
+
+/// The part of a measurement point that a rewrite rule reads.
+///
+/// In the configuration, it is written as `resource_kind`, `resource_id`, `consumer_kind` or `consumer_id`.
+/// Any other string is the name of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Source {
+    ResourceKind,
+    ResourceId,
+    ConsumerKind,
+    ConsumerId,
+    Attribute(String),
+}
+
+/// A rule that is ready to be applied.
+#[derive(Debug)]
+pub enum Rule {
+    Add {
+        attribute: String,
+        value: AttributeValue,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    Remove {
+        attributes: Vec<String>,
+    },
+    Rewrite {
+        from: Source,
+        regex: Regex,
+        replacement: String,
+        to: String,
+    },
+}
+
+impl TryFrom<RuleConfig> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(config: RuleConfig) -> Result<Self, Self::Error> {
+        let rule = match config {
+            RuleConfig::Add { attribute, value } => Rule::Add {
+                attribute,
+                value: value.into(),
+            },
+            RuleConfig::Rename { from, to } => Rule::Rename { from, to },
+            RuleConfig::Remove { attributes } => Rule::Remove { attributes },
+            RuleConfig::Rewrite {
+                from,
+                regex,
+                replacement,
+                to,
+            } => {
+                let to = match (to, &from) {
+                    (Some(to), _) => to,
+                    (None, Source::Attribute(name)) => name.to_owned(),
+                    (None, source) => return Err(anyhow!("rewrite of {source} requires a target attribute 'to'")),
+                };
+                let regex = Regex::new(&regex).with_context(|| format!("invalid regex for the rewrite of {from}"))?;
+                Rule::Rewrite {
+                    from,
+                    regex,
+                    replacement,
+                    to,
+                }
+            }
+        };
+        Ok(rule)
+    }
+}
+
+impl Rule {
+    /// Applies the rule to the attributes of a point.
+    ///
+    /// The attributes are only modified if the rule concerns them: for instance, renaming an attribute
+    /// that the point does not have leaves the point untouched.
+    pub fn apply(&self, point: &mut MeasurementPoint) {
+        match self {
+            Rule::Add { attribute, value } => set(point, attribute, value.clone()),
+            Rule::Rename { from, to } => {
+                if from != to && point.attributes_keys().any(|k| k == from) {
+                    let attributes = point
+                        .attributes()
+                        .filter(|(k, _)| k != to)
+                        .map(|(k, v)| (if k == from { to } else { k }.to_owned(), v.clone()))
+                        .collect();
+                    replace_attributes(point, attributes);
+                }
+            }
+            Rule::Remove { attributes: names } => {
+                if point.attributes_keys().any(|k| names.iter().any(|name| name == k)) {
+                    let attributes = point
+                        .attributes()
+                        .filter(|(k, _)| !names.iter().any(|name| name == k))
+                        .map(|(k, v)| (k.to_owned(), v.clone()))
+                        .collect();
+                    replace_attributes(point, attributes);
+                }
+            }
+            Rule::Rewrite {
+                from,
+                regex,
+                replacement,
+                to,
+            } => {
+                let input = match from {
+                    Source::ResourceKind => Cow::Borrowed(point.resource.kind()),
+                    Source::ResourceId => Cow::Owned(point.resource.id_display().to_string()),
+                    Source::ConsumerKind => Cow::Borrowed(point.consumer.kind()),
+                    Source::ConsumerId => Cow::Owned(point.consumer.id_display().to_string()),
+                    Source::Attribute(name) => match point.attributes().find(|(k, _)| k == name) {
+                        Some((_, AttributeValue::Str(s))) => Cow::Borrowed(*s),
+                        Some((_, value)) => Cow::Owned(value.to_string()),
+                        None => return,
+                    },
+                };
+                if let Some(captures) = regex.captures(&input) {
+                    let mut output = String::new();
+                    captures.expand(replacement, &mut output);
+                    set(point, to, AttributeValue::String(output));
+                }
+            }
+        }
+    }
+}
+
+/// Sets the value of an attribute, replacing the existing one(s).
+fn set(point: &mut MeasurementPoint, name: &str, value: AttributeValue) {
+    if point.attributes_keys().any(|k| k == name) {
+        let attributes = point
+            .attributes()
+            .filter(|(k, _)| *k != name)
+            .map(|(k, v)| (k.to_owned(), v.clone()))
+            .collect();
+        replace_attributes(point, attributes);
+    }
+    point.add_attr(name.to_owned(), value);
+}
+
+/// Replaces the attributes of a point.
+///
+/// `MeasurementPoint` cannot remove its attributes, so we move its fields to a new point.
+fn replace_attributes(point: &mut MeasurementPoint, attributes: Vec<(String, AttributeValue)>) {
+    let resource = mem::replace(&mut point.resource, Resource::LocalMachine);
+    let consumer = mem::replace(&mut point.consumer, ResourceConsumer::LocalMachine);
+    let value = mem::replace(&mut point.value, WrappedMeasurementValue::U64(0));
+    *point = MeasurementPoint::new_untyped(point.timestamp, point.metric, resource, consumer, value)
+        .with_attr_vec(attributes);
+}
+
+impl From<ConfigValue> for AttributeValue {
+    fn from(value: ConfigValue) -> Self {
+        match value {
+            ConfigValue::Bool(b) => AttributeValue::Bool(b),
+            ConfigValue::U64(x) => AttributeValue::U64(x),
+            ConfigValue::F64(x) => AttributeValue::F64(x),
+            ConfigValue::String(s) => AttributeValue::String(s),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "resource_kind" => Source::ResourceKind,
+            "resource_id" => Source::ResourceId,
+            "consumer_kind" => Source::ConsumerKind,
+            "consumer_id" => Source::ConsumerId,
+            attribute => Source::Attribute(attribute.to_owned()),
+        })
+    }
+}
+
+impl From<String> for Source {
+    fn from(value: String) -> Self {
+        match value.parse() {
+            Ok(s) => s,
+            Err(e) => match e {},
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::ResourceKind => f.write_str("resource_kind"),
+            Source::ResourceId => f.write_str("resource_id"),
+            Source::ConsumerKind => f.write_str("consumer_kind"),
+            Source::ConsumerId => f.write_str("consumer_id"),
+            Source::Attribute(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<Source> for String {
+    fn from(value: Source) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::{AttributeValue, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::{ConfigValue, Rule, RuleConfig, Source};
+
+    fn apply(rule: RuleConfig, attributes: &[(&str, AttributeValue)]) -> Vec<(String, AttributeValue)> {
+        let rule = Rule::try_from(rule).unwrap();
+        let mut point = MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::ControlGroup {
+                path: "/sys/fs/cgroup/slurm/job_1234/step_0".into(),
+            },
+            WrappedMeasurementValue::U64(0),
+        );
+        for (k, v) in attributes {
+            point.add_attr(k.to_string(), v.clone());
+        }
+        rule.apply(&mut point);
+        // Str and String are not equal, use String everywhere to simplify the comparisons
+        point
+            .attributes()
+            .map(|(k, v)| match v {
+                AttributeValue::Str(s) => (k.to_string(), AttributeValue::String(s.to_string())),
+                v => (k.to_string(), v.clone()),
+            })
+            .collect()
+    }
+
+    fn attr(name: &str, value: &str) -> (String, AttributeValue) {
+        (name.to_owned(), AttributeValue::String(value.to_owned()))
+    }
+
+    #[test]
+    fn add() {
+        let rule = RuleConfig::Add {
+            attribute: String::from("site"),
+            value: ConfigValue::String(String::from("grenoble")),
+        };
+        assert_eq!(apply(rule.clone(), &[]), vec![attr("site", "grenoble")]);
+        assert_eq!(
+            apply(
+                rule,
+                &[("site", AttributeValue::Str("lyon")), ("a", AttributeValue::U64(1))]
+            ),
+            vec![(String::from("a"), AttributeValue::U64(1)), attr("site", "grenoble")]
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let rule = RuleConfig::Rename {
+            from: String::from("domain"),
+            to: String::from("rapl_domain"),
+        };
+        assert_eq!(
+            apply(rule.clone(), &[("domain", AttributeValue::Str("package"))]),
+            vec![attr("rapl_domain", "package")]
+        );
+        // the existing attribute is replaced
+        assert_eq!(
+            apply(
+                rule.clone(),
+                &[
+                    ("rapl_domain", AttributeValue::Str("old")),
+                    ("domain", AttributeValue::Str("package"))
+                ]
+            ),
+            vec![attr("rapl_domain", "package")]
+        );
+        // nothing to rename: the attributes are not modified
+        assert_eq!(
+            apply(rule, &[("rapl_domain", AttributeValue::Str("old"))]),
+            vec![attr("rapl_domain", "old")]
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let rule = RuleConfig::Remove {
+            attributes: vec![String::from("pid"), String::from("cmdline")],
+        };
+        assert_eq!(
+            apply(
+                rule,
+                &[
+                    ("pid", AttributeValue::U64(42)),
+                    ("kind", AttributeValue::Str("user")),
+                    ("cmdline", AttributeValue::Str("sleep 1")),
+                ]
+            ),
+            vec![attr("kind", "user")]
+        );
+    }
+
+    #[test]
+    fn rewrite() {
+        let job_id = RuleConfig::Rewrite {
+            from: Source::ConsumerId,
+            regex: String::from(r"/job_(?<id>\d+)"),
+            replacement: String::from("${id}"),
+            to: Some(String::from("job_id")),
+        };
+        assert_eq!(apply(job_id, &[]), vec![attr("job_id", "1234")]);
+
+        let in_place = RuleConfig::Rewrite {
+            from: Source::from(String::from("domain")),
+            regex: String::from("^(.*)$"),
+            replacement: String::from("rapl-$1"),
+            to: None,
+        };
+        assert_eq!(
+            apply(in_place.clone(), &[("domain", AttributeValue::Str("dram"))]),
+            vec![attr("domain", "rapl-dram")]
+        );
+        // missing attribute: nothing happens
+        assert_eq!(apply(in_place, &[]), vec![]);
+
+        let no_match = RuleConfig::Rewrite {
+            from: Source::ResourceKind,
+            regex: String::from("^gpu$"),
+            replacement: String::from("yes"),
+            to: Some(String::from("is_gpu")),
+        };
+        assert_eq!(apply(no_match, &[]), vec![]);
+    }
+
+    #[test]
+    fn invalid_rewrite() {
+        let missing_target = RuleConfig::Rewrite {
+            from: Source::ConsumerId,
+            regex: String::from(".*"),
+            replacement: String::from("$0"),
+            to: None,
+        };
+        assert!(Rule::try_from(missing_target).is_err());
+
+        let invalid_regex = RuleConfig::Rewrite {
+            from: Source::ConsumerId,
+            regex: String::from("("),
+            replacement: String::from("$0"),
+            to: Some(String::from("x")),
+        };
+        assert!(Rule::try_from(invalid_regex).is_err());
+    }
+}
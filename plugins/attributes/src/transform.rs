@@ -0,0 +1,32 @@
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::{
+        Transform,
+        elements::{error::TransformError, transform::TransformContext},
+    },
+};
+
+use crate::rules::Rule;
+
+/// Applies the rules, in order, to the attributes of every measurement point.
+pub struct AttributesTransform {
+    rules: Vec<Rule>,
+}
+
+impl AttributesTransform {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+}
+
+impl Transform for AttributesTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        // The rules edit the attributes in place, and leave alone the points that they don't concern.
+        for point in measurements.iter_mut() {
+            for rule in &self.rules {
+                rule.apply(point);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::RawMetricId,
+    pipeline::naming::TransformName,
+    plugin::PluginMetadata,
+    resources::{Resource, ResourceConsumer},
+    test::RuntimeExpectations,
+    units::Unit,
+};
+
+use plugin_attributes::AttributesPlugin;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const CONFIG: &str = r#"
+[[rules]]
+action = "add"
+attribute = "site"
+value = "grenoble"
+
+[[rules]]
+action = "remove"
+attributes = ["cmdline"]
+
+[[rules]]
+action = "rewrite"
+from = "consumer_id"
+regex = "/job_(\\d+)"
+replacement = "$1"
+to = "job_id"
+
+[[rules]]
+action = "rename"
+from = "job_id"
+to = "slurm_job"
+"#;
+
+#[test]
+fn test_rules() {
+    let transform_name = TransformName::from_str("attributes", "transform");
+    let ts = Timestamp::now();
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<u64>("metric_a", Unit::Unity)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let metric = input.metrics().by_name("metric_a").unwrap().0;
+                let mut buf = MeasurementBuffer::new();
+
+                buf.push(new_point(metric, ts, "/sys/fs/cgroup/slurm/job_1234").with_attr("cmdline", "sleep 60"));
+                buf.push(new_point(metric, ts, "/sys/fs/cgroup/system.slice").with_attr("kind", "user"));
+
+                buf
+            },
+            move |output| {
+                let metric = output.metrics().by_name("metric_a").unwrap().0;
+                let m = output.measurements().to_vec();
+
+                assert_eq!(
+                    m,
+                    vec![
+                        new_point(metric, ts, "/sys/fs/cgroup/slurm/job_1234")
+                            .with_attr("site", String::from("grenoble"))
+                            .with_attr("slurm_job", String::from("1234")),
+                        new_point(metric, ts, "/sys/fs/cgroup/system.slice")
+                            .with_attr("kind", "user")
+                            .with_attr("site", String::from("grenoble")),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG);
+}
+
+fn run_agent(runtime: RuntimeExpectations, config: &str) {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<AttributesPlugin>(),
+        enabled: true,
+        config: Some(toml::from_str(config).unwrap()),
+    });
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(runtime)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+fn new_point(metric: RawMetricId, ts: Timestamp, cgroup: &str) -> MeasurementPoint {
+    MeasurementPoint::new_untyped(
+        ts,
+        metric,
+        Resource::LocalMachine,
+        ResourceConsumer::ControlGroup {
+            path: cgroup.to_owned().into(),
+        },
+        WrappedMeasurementValue::U64(1),
+    )
+}
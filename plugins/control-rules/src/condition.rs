@@ -0,0 +1,199 @@
+//! Conditions that activate the rules.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use chrono::{DateTime, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+/// A condition, checked periodically by the rule engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Condition {
+    /// True while at least one cgroup matches the given pattern, for instance `slurm/job_*`.
+    ///
+    /// The pattern is relative to the root of the cgroup filesystem and can use wildcards.
+    CgroupExists(String),
+    /// True between two times of the day (local time), for instance from `22:00` to `06:00`.
+    TimeBetween { from: TimeOfDay, to: TimeOfDay },
+    /// True once the given time has elapsed since the start of the pipeline.
+    After(#[serde(with = "humantime_serde")] Duration),
+}
+
+/// A time of the day, in the format `HH:MM` or `HH:MM:SS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(NaiveTime);
+
+/// The information that the conditions are evaluated against.
+pub struct Context<'a> {
+    /// Current local time.
+    pub now: DateTime<Local>,
+    /// Time elapsed since the start of the pipeline.
+    pub elapsed: Duration,
+    /// Root of the cgroup filesystem.
+    pub cgroupfs_root: &'a Path,
+}
+
+impl Condition {
+    /// Checks that the condition is valid.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            Condition::CgroupExists(pattern) => {
+                glob::Pattern::new(pattern).with_context(|| format!("invalid cgroup pattern '{pattern}'"))?;
+                Ok(())
+            }
+            Condition::TimeBetween { .. } | Condition::After(_) => Ok(()),
+        }
+    }
+
+    /// Evaluates the condition.
+    pub fn is_met(&self, ctx: &Context) -> anyhow::Result<bool> {
+        match self {
+            Condition::CgroupExists(pattern) => cgroup_exists(ctx.cgroupfs_root, pattern),
+            Condition::TimeBetween { from, to } => {
+                let t = ctx.now.time();
+                if from <= to {
+                    Ok(from.0 <= t && t < to.0)
+                } else {
+                    // the interval spans midnight
+                    Ok(from.0 <= t || t < to.0)
+                }
+            }
+            Condition::After(delay) => Ok(ctx.elapsed >= *delay),
+        }
+    }
+}
+
+fn cgroup_exists(root: &Path, pattern: &str) -> anyhow::Result<bool> {
+    let full_pattern: PathBuf = root.join(pattern.trim_start_matches('/'));
+    let full_pattern = full_pattern
+        .to_str()
+        .with_context(|| format!("invalid path: {full_pattern:?}"))?;
+    for entry in glob::glob(full_pattern)? {
+        // a cgroup is a directory, ignore the control files
+        if entry?.is_dir() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&value, "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(&value, "%H:%M:%S"))
+            .map(TimeOfDay)
+            .with_context(|| format!("invalid time of day '{value}', expected HH:MM or HH:MM:SS"))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(value: TimeOfDay) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M:%S"))
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::CgroupExists(pattern) => write!(f, "cgroup {pattern} exists"),
+            Condition::TimeBetween { from, to } => write!(f, "time between {from} and {to}"),
+            Condition::After(delay) => write!(f, "{} elapsed", humantime::format_duration(*delay)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use chrono::{Local, NaiveTime, TimeZone};
+
+    use super::{Condition, Context, TimeOfDay};
+
+    fn context_at(hour: u32, minute: u32, root: &Path) -> Context<'_> {
+        let now = Local.with_ymd_and_hms(2024, 6, 1, hour, minute, 0).unwrap();
+        Context {
+            now,
+            elapsed: Duration::from_secs(60),
+            cgroupfs_root: root,
+        }
+    }
+
+    fn time(s: &str) -> TimeOfDay {
+        TimeOfDay::try_from(s.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn time_of_day() {
+        assert_eq!(time("22:00").0, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+        assert_eq!(time("06:30:15").0, NaiveTime::from_hms_opt(6, 30, 15).unwrap());
+        assert!(TimeOfDay::try_from(String::from("25:00")).is_err());
+        assert!(TimeOfDay::try_from(String::from("night")).is_err());
+    }
+
+    #[test]
+    fn time_between() {
+        let root = Path::new("/nonexistent");
+        let day = Condition::TimeBetween {
+            from: time("08:00"),
+            to: time("18:00"),
+        };
+        assert!(day.is_met(&context_at(12, 0, root)).unwrap());
+        assert!(day.is_met(&context_at(8, 0, root)).unwrap());
+        assert!(!day.is_met(&context_at(18, 0, root)).unwrap());
+        assert!(!day.is_met(&context_at(23, 0, root)).unwrap());
+
+        let night = Condition::TimeBetween {
+            from: time("22:00"),
+            to: time("06:00"),
+        };
+        assert!(night.is_met(&context_at(23, 30, root)).unwrap());
+        assert!(night.is_met(&context_at(2, 0, root)).unwrap());
+        assert!(!night.is_met(&context_at(6, 0, root)).unwrap());
+        assert!(!night.is_met(&context_at(12, 0, root)).unwrap());
+    }
+
+    #[test]
+    fn after() {
+        let root = Path::new("/nonexistent");
+        let ctx = context_at(12, 0, root);
+        assert!(Condition::After(Duration::from_secs(30)).is_met(&ctx).unwrap());
+        assert!(Condition::After(Duration::from_secs(60)).is_met(&ctx).unwrap());
+        assert!(!Condition::After(Duration::from_secs(3600)).is_met(&ctx).unwrap());
+    }
+
+    #[test]
+    fn cgroup_exists() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx = context_at(12, 0, root.path());
+        let slurm_job = Condition::CgroupExists(String::from("slurm/job_*"));
+        slurm_job.validate().unwrap();
+
+        assert!(!slurm_job.is_met(&ctx).unwrap());
+        std::fs::create_dir_all(root.path().join("slurm")).unwrap();
+        std::fs::write(root.path().join("slurm/job_file"), "not a cgroup").unwrap();
+        assert!(!slurm_job.is_met(&ctx).unwrap());
+        std::fs::create_dir(root.path().join("slurm/job_42")).unwrap();
+        assert!(slurm_job.is_met(&ctx).unwrap());
+
+        // absolute patterns are relative to the root too
+        let absolute = Condition::CgroupExists(String::from("/slurm/job_42"));
+        assert!(absolute.is_met(&ctx).unwrap());
+
+        assert!(Condition::CgroupExists(String::from("slurm/[")).validate().is_err());
+    }
+}
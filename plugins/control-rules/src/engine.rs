@@ -0,0 +1,95 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use alumet::pipeline::control::{PluginControlHandle, handle::SendWaitError};
+use chrono::Local;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+use crate::{condition::Context, rule::Rule};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Periodically evaluates the rules and applies their actions.
+pub struct RuleEngine {
+    pub rules: Vec<Rule>,
+    pub check_interval: Duration,
+    pub cgroupfs_root: PathBuf,
+}
+
+impl RuleEngine {
+    /// Runs the engine until the token is cancelled or the pipeline is shut down.
+    pub async fn run(mut self, handle: PluginControlHandle, cancel_token: CancellationToken) {
+        let start = Instant::now();
+        let mut ticker = tokio::time::interval(self.check_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                biased;
+
+                _ = cancel_token.cancelled() => break,
+                _ = ticker.tick() => {
+                    if let Err(e) = self.check(&handle, start.elapsed()).await {
+                        log::debug!("Stopping the control rules: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Evaluates every rule and applies the actions of the rules whose condition has changed.
+    ///
+    /// If an action cannot be applied, the rule keeps its previous state and the action is applied again
+    /// at the next check. Only fails if the pipeline is no longer available.
+    async fn check(&mut self, handle: &PluginControlHandle, elapsed: Duration) -> Result<(), SendWaitError> {
+        let ctx = Context {
+            now: Local::now(),
+            elapsed,
+            cgroupfs_root: &self.cgroupfs_root,
+        };
+        for rule in &mut self.rules {
+            let met = match rule.when.is_met(&ctx) {
+                Ok(met) => met,
+                Err(e) => {
+                    log::warn!(
+                        "Could not check the condition of rule '{}' ({}): {e:#}",
+                        rule.name,
+                        rule.when
+                    );
+                    continue;
+                }
+            };
+            let Some(action) = rule.update(met).cloned() else {
+                continue;
+            };
+            log::info!(
+                "Rule '{}': condition '{}' is now {met}, applying '{action}' to {}",
+                rule.name,
+                rule.when,
+                rule.elements
+            );
+            let requests = action
+                .requests(rule.pattern())
+                .expect("the actions should have been checked when creating the rule");
+            let mut applied = true;
+            for request in requests {
+                match handle.send_wait(request, REQUEST_TIMEOUT).await {
+                    Ok(()) => (),
+                    Err(SendWaitError::NotAvailable) => return Err(SendWaitError::NotAvailable),
+                    Err(e) => {
+                        log::error!("Rule '{}': could not apply '{action}', will retry: {e}", rule.name);
+                        applied = false;
+                        break;
+                    }
+                }
+            }
+            if applied {
+                rule.applied(met);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+mod condition;
+mod engine;
+mod rule;
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+pub use condition::{Condition, TimeOfDay};
+pub use rule::{Action, RuleConfig};
+
+use engine::RuleEngine;
+use rule::Rule;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// How often the conditions of the rules are checked.
+    #[serde(with = "humantime_serde")]
+    pub check_interval: Duration,
+    /// Root of the cgroup filesystem, used by the `cgroup_exists` conditions.
+    pub cgroupfs_root: PathBuf,
+    /// The rules to apply.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+}
+
+pub struct ControlRulesPlugin {
+    check_interval: Duration,
+    cgroupfs_root: PathBuf,
+    rules: Vec<Rule>,
+    cancel_token: CancellationToken,
+}
+
+impl AlumetPlugin for ControlRulesPlugin {
+    fn name() -> &'static str {
+        "control-rules"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(
+            !config.check_interval.is_zero(),
+            "invalid check_interval: it must be greater than zero"
+        );
+        let rules = config
+            .rules
+            .into_iter()
+            .map(Rule::try_from)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Box::new(ControlRulesPlugin {
+            check_interval: config.check_interval,
+            cgroupfs_root: config.cgroupfs_root,
+            rules,
+            cancel_token: CancellationToken::new(),
+        }))
+    }
+
+    fn start(&mut self, _alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
+        if self.rules.is_empty() {
+            log::warn!("No control rule configured, the plugin will do nothing.");
+            return Ok(());
+        }
+        let engine = RuleEngine {
+            rules: std::mem::take(&mut self.rules),
+            check_interval: self.check_interval,
+            cgroupfs_root: self.cgroupfs_root.clone(),
+        };
+        let n_rules = engine.rules.len();
+        let task = engine.run(alumet.pipeline_control(), self.cancel_token.clone());
+        alumet.async_runtime().spawn(task);
+        log::info!("{n_rules} control rules enabled.");
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        self.cancel_token.cancel();
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(1),
+            cgroupfs_root: PathBuf::from("/sys/fs/cgroup"),
+            rules: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Rules and the actions that they apply.
+
+use alumet::pipeline::{matching::ElementNamePattern, naming::parsing::parse_pattern};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::condition::Condition;
+
+pub use alumet::pipeline::control::request::Action;
+
+/// Configuration of a rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Name of the rule, used in the logs.
+    pub name: String,
+    /// Elements to control, in the form `kind/plugin/element` or `kind`.
+    pub elements: String,
+    /// When to apply the rule.
+    pub when: Condition,
+    /// Action to apply when the condition becomes true.
+    pub then: Action,
+    /// Action to apply when the condition becomes false again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otherwise: Option<Action>,
+}
+
+/// A rule that has been checked and is ready to be evaluated.
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+    /// The elements to control, as written in the configuration.
+    pub elements: String,
+    pattern: ElementNamePattern,
+    then: Action,
+    otherwise: Option<Action>,
+    /// Value of the condition when the last action was successfully applied.
+    active: bool,
+}
+
+impl TryFrom<RuleConfig> for Rule {
+    type Error = anyhow::Error;
+
+    fn try_from(config: RuleConfig) -> Result<Self, Self::Error> {
+        let name = config.name;
+        let pattern = parse_pattern(&config.elements).with_context(|| format!("invalid elements in rule '{name}'"))?;
+        config
+            .when
+            .validate()
+            .with_context(|| format!("invalid condition in rule '{name}'"))?;
+        // build the requests once to check that the actions can be applied to the elements
+        for action in std::iter::once(&config.then).chain(&config.otherwise) {
+            action
+                .requests(&pattern)
+                .with_context(|| format!("invalid action in rule '{name}'"))?;
+        }
+        Ok(Self {
+            name,
+            when: config.when,
+            elements: config.elements,
+            pattern,
+            then: config.then,
+            otherwise: config.otherwise,
+            active: false,
+        })
+    }
+}
+
+impl Rule {
+    /// Updates the state of the rule with the result of the condition.
+    ///
+    /// Returns the action to apply, if the state has changed and there is something to do.
+    /// The state only changes once the action has been applied, see [`applied`](Self::applied):
+    /// until then, the action is returned again on every update.
+    /// A rule starts inactive: `otherwise` is only applied after the condition has been true.
+    pub fn update(&mut self, condition_met: bool) -> Option<&Action> {
+        if condition_met == self.active {
+            return None;
+        }
+        match (condition_met, &self.otherwise) {
+            (true, _) => Some(&self.then),
+            (false, Some(otherwise)) => Some(otherwise),
+            (false, None) => {
+                self.active = false;
+                None
+            }
+        }
+    }
+
+    /// Records that the action returned by [`update`](Self::update) has been applied.
+    pub fn applied(&mut self, condition_met: bool) {
+        self.active = condition_met;
+    }
+
+    pub fn pattern(&self) -> &ElementNamePattern {
+        &self.pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use alumet::pipeline::{matching::StringPattern, naming::ElementKind};
+    use pretty_assertions::assert_eq;
+
+    use super::{Action, Rule, RuleConfig};
+    use crate::condition::Condition;
+
+    fn rule(elements: &str, then: Action, otherwise: Option<Action>) -> anyhow::Result<Rule> {
+        Rule::try_from(RuleConfig {
+            name: String::from("test"),
+            elements: elements.to_owned(),
+            when: Condition::After(Duration::from_secs(1)),
+            then,
+            otherwise,
+        })
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: RuleConfig = toml::from_str(
+            r#"
+            name = "fast-rapl"
+            elements = "source/rapl/*"
+            when = { cgroup_exists = "slurm/job_*" }
+            then = { action = "set-period", period = "10ms" }
+            otherwise = { action = "set-period", period = "1s" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            RuleConfig {
+                name: String::from("fast-rapl"),
+                elements: String::from("source/rapl/*"),
+                when: Condition::CgroupExists(String::from("slurm/job_*")),
+                then: Action::SetPeriod {
+                    period: Duration::from_millis(10)
+                },
+                otherwise: Some(Action::SetPeriod {
+                    period: Duration::from_secs(1)
+                }),
+            }
+        );
+
+        let config: RuleConfig = toml::from_str(
+            r#"
+            name = "gpu-night"
+            elements = "source/nvml/*"
+            when = { time_between = { from = "22:00", to = "06:00" } }
+            then = { action = "pause" }
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(config.when, Condition::TimeBetween { .. }));
+        assert_eq!(config.then, Action::Pause);
+        assert_eq!(config.otherwise, None);
+    }
+
+    #[test]
+    fn validation() {
+        let r = rule("source/rapl/*", Action::TriggerNow, None).unwrap();
+        assert_eq!(r.pattern().kind, Some(ElementKind::Source));
+        assert_eq!(r.pattern().plugin, StringPattern::Exact(String::from("rapl")));
+        rule("*", Action::Pause, Some(Action::Resume)).unwrap();
+        rule("output", Action::Stop, None).unwrap();
+
+        let err = rule("transform", Action::Stop, None).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "invalid action in rule 'test': action 'stop' cannot be applied to transforms"
+        );
+        let err = rule("*", Action::Pause, Some(Action::Stop)).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "invalid action in rule 'test': action 'stop' requires a pattern with an explicit element kind"
+        );
+        let err = rule("source/rapl", Action::Pause, None).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "invalid elements in rule 'test': bad pattern, expected kind/plugin/element but got 'source/rapl'"
+        );
+    }
+
+    #[test]
+    fn edges() {
+        let mut r = rule("source", Action::Pause, Some(Action::Resume)).unwrap();
+        assert_eq!(r.update(false), None, "a rule starts inactive");
+        assert_eq!(r.update(true), Some(&Action::Pause));
+        r.applied(true);
+        assert_eq!(r.update(true), None);
+        assert_eq!(r.update(false), Some(&Action::Resume));
+        r.applied(false);
+        assert_eq!(r.update(false), None);
+
+        let mut r = rule("source", Action::Pause, None).unwrap();
+        assert_eq!(r.update(true), Some(&Action::Pause));
+        r.applied(true);
+        assert_eq!(r.update(false), None);
+        assert_eq!(r.update(true), Some(&Action::Pause));
+    }
+
+    #[test]
+    fn failed_actions_are_retried() {
+        let mut r = rule("source", Action::Pause, Some(Action::Resume)).unwrap();
+        // the action has not been applied: the state of the rule does not change
+        assert_eq!(r.update(true), Some(&Action::Pause));
+        assert_eq!(r.update(true), Some(&Action::Pause));
+        r.applied(true);
+        assert_eq!(r.update(true), None);
+
+        // the condition went back to its previous value before the action could be applied: nothing to do
+        assert_eq!(r.update(false), Some(&Action::Resume));
+        assert_eq!(r.update(true), None);
+    }
+}
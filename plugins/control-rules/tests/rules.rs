@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementAccumulator, Timestamp},
+    pipeline::{
+        Source,
+        control::request::{self, ElementListFilter, ElementState},
+        elements::{error::PollError, source::trigger::TriggerSpec},
+        naming::ElementKind,
+    },
+    plugin::{
+        AlumetPluginStart, ConfigTable, PluginMetadata,
+        rust::{AlumetPlugin, serialize_config},
+    },
+};
+use plugin_control_rules::{Action, Condition, Config, ControlRulesPlugin, RuleConfig};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+struct TestPlugin;
+
+impl AlumetPlugin for TestPlugin {
+    fn name() -> &'static str {
+        "test-plugin"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(TestPlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        for name in ["a", "b"] {
+            alumet.add_source(
+                name,
+                Box::new(IdleSource),
+                TriggerSpec::at_interval(Duration::from_millis(100)),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct IdleSource;
+
+impl Source for IdleSource {
+    fn poll(&mut self, _m: &mut MeasurementAccumulator, _t: Timestamp) -> Result<(), PollError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn rules_are_applied() {
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).try_init();
+
+    let cgroupfs = tempfile::tempdir().unwrap();
+    let config = Config {
+        check_interval: Duration::from_millis(20),
+        cgroupfs_root: cgroupfs.path().to_owned(),
+        rules: vec![
+            RuleConfig {
+                name: String::from("pause-a-later"),
+                elements: String::from("source/test-plugin/a"),
+                when: Condition::After(Duration::from_millis(500)),
+                then: Action::Pause,
+                otherwise: None,
+            },
+            RuleConfig {
+                name: String::from("b-during-jobs"),
+                elements: String::from("source/test-plugin/b"),
+                when: Condition::CgroupExists(String::from("slurm/job_*")),
+                then: Action::Pause,
+                otherwise: Some(Action::Resume),
+            },
+        ],
+    };
+
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<ControlRulesPlugin>(),
+        enabled: true,
+        config: Some(serialize_config(config).unwrap().0),
+    });
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<TestPlugin>(),
+        enabled: true,
+        config: None,
+    });
+
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("alumet should start");
+    let handle = agent.pipeline.control_handle();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let states = || {
+        let filter = ElementListFilter::kind(ElementKind::Source).plugin("test-plugin");
+        let mut status = rt
+            .block_on(handle.send_wait(request::element_status(filter), TIMEOUT))
+            .unwrap();
+        status.sort_by(|a, b| a.name.element.cmp(&b.name.element));
+        status.into_iter().map(|s| s.state).collect::<Vec<_>>()
+    };
+
+    // nothing has happened yet
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(states(), vec![ElementState::Running, ElementState::Running]);
+
+    // a job starts: b is paused
+    std::fs::create_dir_all(cgroupfs.path().join("slurm/job_1")).unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(states(), vec![ElementState::Running, ElementState::Paused]);
+
+    // the job ends: b is resumed, and the delay of the first rule has elapsed
+    std::fs::remove_dir(cgroupfs.path().join("slurm/job_1")).unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    assert_eq!(states(), vec![ElementState::Paused, ElementState::Running]);
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).expect("alumet should stop");
+}
+
+#[test]
+fn invalid_rule() {
+    let config = Config {
+        rules: vec![RuleConfig {
+            name: String::from("bad"),
+            elements: String::from("transform/*/*"),
+            when: Condition::After(Duration::from_secs(1)),
+            then: Action::SetPeriod {
+                period: Duration::from_millis(10),
+            },
+            otherwise: None,
+        }],
+        ..Default::default()
+    };
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<ControlRulesPlugin>(),
+        enabled: true,
+        config: Some(serialize_config(config).unwrap().0),
+    });
+    let Err(err) = agent::Builder::new(plugins).build_and_start() else {
+        panic!("the rule should be rejected");
+    };
+    assert!(
+        format!("{err:#}").contains("action 'set-period' cannot be applied to transforms"),
+        "unexpected error: {err:#}"
+    );
+}
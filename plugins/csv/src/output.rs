@@ -1,11 +1,11 @@
 use std::{collections::HashSet, fs::File, path::Path, time::SystemTime};
 
 use crate::csv::{CsvParams, CsvWriter};
+use alumet::pipeline::Output;
 use alumet::{
     measurement::MeasurementBuffer,
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
-use alumet::{measurement::WrappedMeasurementValue, pipeline::Output};
 use anyhow::Context;
 use rustc_hash::FxHashMap;
 use time::OffsetDateTime;
@@ -105,10 +105,8 @@ impl Output for CsvOutput {
             let datetime: OffsetDateTime = SystemTime::from(m.timestamp).into();
             let datetime = datetime.format(&Rfc3339)?;
 
-            let value = match m.value {
-                WrappedMeasurementValue::F64(x) => x.to_string(),
-                WrappedMeasurementValue::U64(x) => x.to_string(),
-            };
+            // Histograms are written like `count=3 sum=1.5 buckets=[0.1:1 1:2 +Inf:0]`.
+            let value = m.value.to_string();
             let resource_kind = m.resource.kind().to_owned();
             let resource_id = m.resource.id_display().to_string();
             let consumer_kind = m.consumer.kind().to_owned();
@@ -121,9 +121,12 @@ impl Serialize for DocMeasurement<'_> {
         map.serialize_entry("consumer_id", &self.measurement.consumer.id_display().to_string())?;
 
         // value
-        match self.measurement.value {
-            WrappedMeasurementValue::F64(v) => map.serialize_entry("value", &v)?,
-            WrappedMeasurementValue::U64(v) => map.serialize_entry("value", &v)?,
+        match &self.measurement.value {
+            WrappedMeasurementValue::F64(v) => map.serialize_entry("value", v)?,
+            WrappedMeasurementValue::U64(v) => map.serialize_entry("value", v)?,
+            WrappedMeasurementValue::I64(v) => map.serialize_entry("value", v)?,
+            // the value field is numeric, use the mean of the distribution
+            WrappedMeasurementValue::Histogram(h) => map.serialize_entry("value", &h.mean())?,
         };
 
         // attributes
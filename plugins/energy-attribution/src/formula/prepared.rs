@@ -189,5 +189,8 @@ fn convert_value_for_eval(value: &WrappedMeasurementValue) -> evalexpr::Value {
                 .try_into()
                 .expect("point value exceeded the maximum integer value supported by evalexpr"),
         ),
+        WrappedMeasurementValue::I64(v) => evalexpr::Value::Int(*v),
+        // the formula works on scalar values, use the mean of the distribution
+        WrappedMeasurementValue::Histogram(h) => evalexpr::Value::Float(h.mean()),
     }
 }
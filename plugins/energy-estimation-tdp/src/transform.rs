@@ -85,9 +85,11 @@ impl Transform for EnergyEstimationTdpTransform {
                 let id = SystemTime::from(point.timestamp).duration_since(UNIX_EPOCH)?.as_secs();
                 log::trace!("we get a measurement for pod with timestamp: {}", id);
 
-                let value = match point.value {
+                let value = match &point.value {
                     WrappedMeasurementValue::F64(x) => x.to_string(),
                     WrappedMeasurementValue::U64(x) => x.to_string(),
+                    WrappedMeasurementValue::I64(x) => x.to_string(),
+                    WrappedMeasurementValue::Histogram(h) => h.mean().to_string(),
                 };
 
                 // energy = cpu_usage * nb_vcpu/nb_cpu * tdp / poll_interval
@@ -0,0 +1,139 @@
+mod transform;
+
+use std::collections::HashMap;
+
+use alumet::{
+    metrics::{RawMetricId, def::MetricId},
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use transform::{Derivation, DerivedMetric, EnergyPowerTransform};
+
+/// Alumet plugin that derives energy metrics from power metrics, and power metrics from energy metrics.
+pub struct EnergyPowerPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for EnergyPowerPlugin {
+    fn name() -> &'static str {
+        "energy-power"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(EnergyPowerPlugin { config }))
+    }
+
+    /// Registers the derived metrics and adds the transform to the pipeline.
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.power_to_energy.is_empty() && self.config.energy_to_power.is_empty() {
+            log::warn!(
+                "energy-power plugin was started but as there's neither 'power_to_energy' or 'energy_to_power' configuration set, this will do nothing"
+            );
+            return Ok(());
+        }
+
+        // (input metric name, derivation, output metric id)
+        let mut derived: Vec<(String, Derivation, RawMetricId)> = Vec::new();
+        for m in &self.config.power_to_energy {
+            let name = m.output_name("_power", "_energy");
+            let id = alumet.create_metric::<f64>(
+                name.as_str(),
+                Unit::Joule,
+                format!(
+                    "Energy consumed since the previous measurement, computed from {}",
+                    m.metric
+                ),
+            )?;
+            derived.push((m.metric.clone(), Derivation::Integrate, id.untyped_id()));
+        }
+        for m in &self.config.energy_to_power {
+            let name = m.output_name("_energy", "_power");
+            let id = alumet.create_metric::<f64>(
+                name.as_str(),
+                Unit::Watt,
+                format!(
+                    "Average power since the previous measurement, computed from {}",
+                    m.metric
+                ),
+            )?;
+            derived.push((m.metric.clone(), Derivation::Differentiate, id.untyped_id()));
+        }
+
+        alumet.add_transform_builder("transform", move |ctx| {
+            let mut metrics = HashMap::with_capacity(derived.len());
+            for (metric_name, derivation, output) in derived {
+                let (id, metric) = ctx
+                    .metric_by_name(&metric_name)
+                    .with_context(|| format!("metric not found: {metric_name}"))?;
+                let input_unit = match derivation {
+                    Derivation::Integrate => Unit::Watt,
+                    Derivation::Differentiate => Unit::Joule,
+                };
+                let conversion = metric
+                    .unit
+                    .conversion_to(&PrefixedUnit::from(input_unit))
+                    .with_context(|| format!("metric {metric_name} has an unexpected unit"))?;
+                metrics.insert(
+                    id,
+                    DerivedMetric {
+                        derivation,
+                        conversion,
+                        output,
+                    },
+                );
+            }
+            Ok(Box::new(EnergyPowerTransform::new(metrics)))
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Config {
+    /// Power metrics to integrate, in order to obtain energy metrics.
+    #[serde(default)]
+    power_to_energy: Vec<DerivedMetricConfig>,
+    /// Energy metrics to differentiate, in order to obtain power metrics.
+    #[serde(default)]
+    energy_to_power: Vec<DerivedMetricConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct DerivedMetricConfig {
+    /// Name of the input metric.
+    metric: String,
+    /// Name of the derived metric.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_metric: Option<String>,
+}
+
+impl DerivedMetricConfig {
+    /// Returns the name of the derived metric.
+    ///
+    /// By default, the suffix `from` of the input metric is replaced by `to`, or `to` is appended.
+    fn output_name(&self, from: &str, to: &str) -> String {
+        match (&self.output_metric, self.metric.strip_suffix(from)) {
+            (Some(name), _) => name.to_owned(),
+            (None, Some(base)) => format!("{base}{to}"),
+            (None, None) => format!("{}{to}", self.metric),
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    hash::{DefaultHasher, Hash, Hasher},
+    time::Duration,
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::RawMetricId,
+    pipeline::{
+        Transform,
+        elements::{error::TransformError, transform::TransformContext},
+    },
+    units::Conversion,
+};
+
+/// Identifies a timeseries: the derivation is computed on consecutive points of the same series.
+///
+/// It is a hash of the metric, resource, consumer and attributes of the points, which avoids
+/// cloning them for every point.
+type SeriesKey = u64;
+
+/// A series is forgotten when it has not received any point for this number of its intervals.
+const MAX_MISSED_INTERVALS: u32 = 10;
+
+/// A series that has received a single point is forgotten after this delay.
+const MAX_AGE_WITHOUT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How a metric is derived.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Derivation {
+    /// Power to energy: integrates the power over the time between two consecutive points.
+    ///
+    /// The integral is computed with the trapezoidal rule, i.e. the power is linearly interpolated between the points.
+    Integrate,
+    /// Energy to power: divides the energy by the time elapsed since the previous point.
+    ///
+    /// The energy must be the energy consumed since the previous point, which is the case of the energy
+    /// measured by the hardware sources of Alumet (RAPL, NVML, ...).
+    Differentiate,
+}
+
+/// A metric to derive.
+pub struct DerivedMetric {
+    pub derivation: Derivation,
+    /// Converts the input values to W (for `Integrate`) or J (for `Differentiate`).
+    pub conversion: Conversion,
+    /// The metric to produce, in J (for `Integrate`) or W (for `Differentiate`).
+    pub output: RawMetricId,
+}
+
+/// The last point of a series.
+struct LastPoint {
+    timestamp: Timestamp,
+    /// The value, converted to W or J.
+    value: f64,
+    /// Time between the last two points of the series, if it has received two points.
+    interval: Option<Duration>,
+}
+
+/// Turns power into energy and energy into power.
+pub struct EnergyPowerTransform {
+    metrics: HashMap<RawMetricId, DerivedMetric>,
+    last_points: HashMap<SeriesKey, LastPoint>,
+}
+
+impl EnergyPowerTransform {
+    pub fn new(metrics: HashMap<RawMetricId, DerivedMetric>) -> Self {
+        Self {
+            metrics,
+            last_points: HashMap::new(),
+        }
+    }
+}
+
+/// Computes the key of the series of `point`.
+fn series_key(point: &MeasurementPoint) -> SeriesKey {
+    let mut hasher = DefaultHasher::new();
+    point.metric.hash(&mut hasher);
+    point.resource.hash(&mut hasher);
+    point.consumer.hash(&mut hasher);
+    // The attributes may be in any order: combine their hashes with a commutative operation.
+    let attributes = point
+        .attributes()
+        .map(|attr| {
+            let mut hasher = DefaultHasher::new();
+            attr.hash(&mut hasher);
+            hasher.finish()
+        })
+        .fold(0u64, u64::wrapping_add);
+    attributes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Computes the derived point of `point`, and updates the last point of its series.
+fn derive(
+    last_points: &mut HashMap<SeriesKey, LastPoint>,
+    point: &MeasurementPoint,
+    derived: &DerivedMetric,
+) -> Option<MeasurementPoint> {
+    let value = match point.value {
+        WrappedMeasurementValue::F64(v) => v,
+        WrappedMeasurementValue::U64(v) => v as f64,
+        WrappedMeasurementValue::I64(v) => v as f64,
+        WrappedMeasurementValue::Histogram(_) => return None,
+    };
+    let value = derived.conversion.apply(value);
+
+    let last = match last_points.entry(series_key(point)) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            entry.insert(LastPoint {
+                timestamp: point.timestamp,
+                value,
+                interval: None,
+            });
+            return None;
+        }
+    };
+    let dt = match point.timestamp.duration_since(last.timestamp) {
+        Ok(dt) if !dt.is_zero() => dt,
+        _ => {
+            log::trace!("ignoring a point that is not after the previous one: {point:?}");
+            return None;
+        }
+    };
+    let v_prev = std::mem::replace(&mut last.value, value);
+    last.timestamp = point.timestamp;
+    last.interval = Some(dt);
+
+    // The integral is computed here instead of with `alumet::timeseries::interpolate`, which gives the
+    // value of a series at some timestamps: there is no timestamp to interpolate at, and the area of the
+    // trapezoid only needs the two points that we already have.
+    let dt = dt.as_secs_f64();
+    let derived_value = match derived.derivation {
+        Derivation::Integrate => (v_prev + value) / 2.0 * dt,
+        Derivation::Differentiate => value / dt,
+    };
+    let mut new_point = point.clone();
+    new_point.metric = derived.output;
+    new_point.value = WrappedMeasurementValue::F64(derived_value);
+    Some(new_point)
+}
+
+/// Forgets the series that have not received any point for a while, for instance because the
+/// resource or consumer that they measure has disappeared.
+fn prune(last_points: &mut HashMap<SeriesKey, LastPoint>, now: Timestamp) {
+    last_points.retain(|_, last| {
+        let max_age = match last.interval {
+            Some(interval) => interval * MAX_MISSED_INTERVALS,
+            None => MAX_AGE_WITHOUT_INTERVAL,
+        };
+        match now.duration_since(last.timestamp) {
+            Ok(age) => age <= max_age,
+            Err(_) => true,
+        }
+    });
+}
+
+impl Transform for EnergyPowerTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        // The points of a series may be unordered in the buffer.
+        let mut points: Vec<&MeasurementPoint> = measurements
+            .iter()
+            .filter(|p| self.metrics.contains_key(&p.metric))
+            .collect();
+        points.sort_by_key(|p| p.timestamp);
+
+        let Some(latest) = points.last().map(|p| p.timestamp) else {
+            return Ok(());
+        };
+
+        let mut derived_points = MeasurementBuffer::new();
+        for point in points {
+            if let Some(new_point) = derive(&mut self.last_points, point, &self.metrics[&point.metric]) {
+                derived_points.push(new_point);
+            }
+        }
+        prune(&mut self.last_points, latest);
+
+        measurements.merge(&mut derived_points);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use alumet::{
+        measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        pipeline::{Builder, Transform, elements::transform::TransformContext},
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+
+    use super::{Derivation, DerivedMetric, EnergyPowerTransform};
+
+    fn input_metric() -> RawMetricId {
+        RawMetricId::from_u64(0)
+    }
+
+    fn output_metric() -> RawMetricId {
+        RawMetricId::from_u64(1)
+    }
+
+    fn point(metric: RawMetricId, t: Timestamp, value: WrappedMeasurementValue, core: u32) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            t,
+            metric,
+            Resource::CpuCore { id: core },
+            ResourceConsumer::LocalMachine,
+            value,
+        )
+    }
+
+    fn transform(derivation: Derivation, input_unit: PrefixedUnit) -> EnergyPowerTransform {
+        let target = match derivation {
+            Derivation::Integrate => Unit::Watt,
+            Derivation::Differentiate => Unit::Joule,
+        };
+        let derived = DerivedMetric {
+            derivation,
+            conversion: input_unit.conversion_to(&target.into()).unwrap(),
+            output: output_metric(),
+        };
+        EnergyPowerTransform::new(HashMap::from([(input_metric(), derived)]))
+    }
+
+    fn apply(transform: &mut EnergyPowerTransform, input: Vec<MeasurementPoint>) -> Vec<MeasurementPoint> {
+        let builder = Builder::new();
+        let inspector = builder.inspect();
+        let ctx = TransformContext {
+            metrics: inspector.metrics(),
+        };
+        let mut buf = MeasurementBuffer::from(input);
+        transform.apply(&mut buf, &ctx).unwrap();
+        buf.iter().filter(|p| p.metric == output_metric()).cloned().collect()
+    }
+
+    #[test]
+    fn integrate() {
+        let t0 = Timestamp::now();
+        let t1 = t0 + Duration::from_secs(2);
+        let t2 = t1 + Duration::from_millis(500);
+        let mut transform = transform(Derivation::Integrate, PrefixedUnit::milli(Unit::Watt));
+
+        // first point: nothing to integrate yet
+        let input = vec![point(input_metric(), t0, WrappedMeasurementValue::U64(10_000), 0)];
+        assert_eq!(apply(&mut transform, input), vec![]);
+
+        // the points of the two cores are two separate series
+        let input = vec![
+            point(input_metric(), t2, WrappedMeasurementValue::U64(20_000), 0),
+            point(input_metric(), t1, WrappedMeasurementValue::U64(30_000), 0),
+            point(input_metric(), t1, WrappedMeasurementValue::U64(1_000), 1),
+        ];
+        assert_eq!(
+            apply(&mut transform, input),
+            vec![
+                // (10 + 30) / 2 * 2s
+                point(output_metric(), t1, WrappedMeasurementValue::F64(40.0), 0),
+                // (30 + 20) / 2 * 0.5s
+                point(output_metric(), t2, WrappedMeasurementValue::F64(12.5), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn differentiate() {
+        let t0 = Timestamp::now();
+        let t1 = t0 + Duration::from_secs(2);
+        let t2 = t1 + Duration::from_secs(1);
+        let mut transform = transform(Derivation::Differentiate, PrefixedUnit::from(Unit::WattHour));
+
+        let input = vec![
+            point(input_metric(), t0, WrappedMeasurementValue::F64(1.0), 0),
+            point(input_metric(), t1, WrappedMeasurementValue::F64(0.01), 0),
+        ];
+        assert_eq!(
+            apply(&mut transform, input),
+            // 0.01 Wh = 36 J, in 2s
+            vec![point(output_metric(), t1, WrappedMeasurementValue::F64(18.0), 0)]
+        );
+
+        // the points that are not after the previous one are ignored
+        let input = vec![
+            point(input_metric(), t0, WrappedMeasurementValue::F64(1.0), 0),
+            point(input_metric(), t2, WrappedMeasurementValue::F64(0.001), 0),
+        ];
+        assert_eq!(
+            apply(&mut transform, input),
+            vec![point(output_metric(), t2, WrappedMeasurementValue::F64(3.6), 0)]
+        );
+    }
+
+    #[test]
+    fn forget_old_series() {
+        let t0 = Timestamp::now();
+        let t1 = t0 + Duration::from_secs(1);
+        let mut transform = transform(Derivation::Differentiate, PrefixedUnit::from(Unit::Joule));
+
+        let input = vec![
+            point(input_metric(), t0, WrappedMeasurementValue::F64(1.0), 0),
+            point(input_metric(), t1, WrappedMeasurementValue::F64(1.0), 0),
+            point(input_metric(), t1, WrappedMeasurementValue::F64(1.0), 1),
+        ];
+        assert_eq!(apply(&mut transform, input).len(), 1);
+        assert_eq!(transform.last_points.len(), 2);
+
+        // core 0 has missed more than 10 intervals of 1s, core 1 has no interval yet
+        let t2 = t1 + Duration::from_secs(20);
+        let input = vec![point(input_metric(), t2, WrappedMeasurementValue::F64(1.0), 2)];
+        assert_eq!(apply(&mut transform, input), vec![]);
+        assert_eq!(transform.last_points.len(), 2);
+
+        let t3 = t2 + Duration::from_secs(1);
+        let input = vec![
+            point(input_metric(), t3, WrappedMeasurementValue::F64(1.0), 0),
+            point(input_metric(), t3, WrappedMeasurementValue::F64(4.0), 1),
+        ];
+        assert_eq!(
+            apply(&mut transform, input),
+            // 4 J in 21s for core 1, core 0 starts again
+            vec![point(output_metric(), t3, WrappedMeasurementValue::F64(4.0 / 21.0), 1)]
+        );
+    }
+}
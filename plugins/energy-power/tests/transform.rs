@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::RawMetricId,
+    pipeline::naming::TransformName,
+    plugin::PluginMetadata,
+    resources::{Resource, ResourceConsumer},
+    test::RuntimeExpectations,
+    units::{PrefixedUnit, Unit},
+};
+
+use plugin_energy_power::EnergyPowerPlugin;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const CONFIG: &str = r#"
+[[power_to_energy]]
+metric = "input_power"
+
+[[energy_to_power]]
+metric = "consumed_energy"
+output_metric = "average_power"
+"#;
+
+#[test]
+fn test_energy_power() {
+    let transform_name = TransformName::from_str("energy-power", "transform");
+    let t0 = Timestamp::now();
+    let t1 = t0 + Duration::from_secs(2);
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<u64>("input_power", PrefixedUnit::milli(Unit::Watt))
+        .create_metric::<f64>("consumed_energy", Unit::Joule)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let power = input.metrics().by_name("input_power").unwrap().0;
+                let energy = input.metrics().by_name("consumed_energy").unwrap().0;
+                let mut buf = MeasurementBuffer::new();
+
+                buf.push(new_point(power, t0, WrappedMeasurementValue::U64(1000)));
+                buf.push(new_point(energy, t0, WrappedMeasurementValue::F64(5.0)));
+                buf.push(new_point(power, t1, WrappedMeasurementValue::U64(3000)));
+                buf.push(new_point(energy, t1, WrappedMeasurementValue::F64(30.0)));
+
+                buf
+            },
+            move |output| {
+                let (power, _) = output.metrics().by_name("input_power").unwrap();
+                let (energy, _) = output.metrics().by_name("consumed_energy").unwrap();
+                let (input_energy, input_energy_def) = output.metrics().by_name("input_energy").unwrap();
+                let (average_power, average_power_def) = output.metrics().by_name("average_power").unwrap();
+                assert_eq!(input_energy_def.unit, Unit::Joule.into());
+                assert_eq!(average_power_def.unit, Unit::Watt.into());
+
+                assert_eq!(
+                    output.measurements().to_vec(),
+                    vec![
+                        new_point(power, t0, WrappedMeasurementValue::U64(1000)),
+                        new_point(energy, t0, WrappedMeasurementValue::F64(5.0)),
+                        new_point(power, t1, WrappedMeasurementValue::U64(3000)),
+                        new_point(energy, t1, WrappedMeasurementValue::F64(30.0)),
+                        // (1 W + 3 W) / 2 * 2 s
+                        new_point(input_energy, t1, WrappedMeasurementValue::F64(4.0)),
+                        // 30 J / 2 s
+                        new_point(average_power, t1, WrappedMeasurementValue::F64(15.0)),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG);
+}
+
+fn run_agent(runtime: RuntimeExpectations, config: &str) {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<EnergyPowerPlugin>(),
+        enabled: true,
+        config: Some(toml::from_str(config).unwrap()),
+    });
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(runtime)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+fn new_point(metric: RawMetricId, ts: Timestamp, value: WrappedMeasurementValue) -> MeasurementPoint {
+    MeasurementPoint::new_untyped(
+        ts,
+        metric,
+        Resource::LocalMachine,
+        ResourceConsumer::LocalMachine,
+        value,
+    )
+}
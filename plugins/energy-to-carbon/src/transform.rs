@@ -6,7 +6,7 @@ use alumet::{
         Transform,
         elements::{error::TransformError, transform::TransformContext},
     },
-    units::{Unit, UnitPrefix},
+    units::Unit,
 };
 
 const JOULES_PER_KWH: f64 = 3.6e6;
@@ -73,19 +73,14 @@ impl Transform for EnergyToCarbonTransform {
             }
 
             // Scale factor to convert the prefixed joule value to plain joules.
-            let factor = match &metric.unit.prefix {
-                UnitPrefix::Nano => 1e-9,
-                UnitPrefix::Micro => 1e-6,
-                UnitPrefix::Milli => 1e-3,
-                UnitPrefix::Plain => 1.0,
-                UnitPrefix::Kilo => 1e3,
-                UnitPrefix::Mega => 1e6,
-                UnitPrefix::Giga => 1e9,
-            };
+            let factor = metric.unit.prefix.factor();
 
             let energy = match m.value {
                 WrappedMeasurementValue::F64(v) => v,
                 WrappedMeasurementValue::U64(v) => v as f64,
+                WrappedMeasurementValue::I64(v) => v as f64,
+                // a distribution of energies cannot be converted to a single emission value
+                WrappedMeasurementValue::Histogram(_) => continue,
             };
 
             // Carry all attributes from the source joule measurement over to the carbon point.
@@ -0,0 +1,513 @@
+//! Filter expressions, i.e. predicates on measurement points.
+//!
+//! # Syntax
+//!
+//! An expression is made of conditions of the form `<field> <operator> <literal>`,
+//! combined with `and`, `or`, `not` (or `&&`, `||`, `!`) and parentheses.
+//!
+//! Fields:
+//! - `metric`: name of the metric
+//! - `value`: value of the measurement (the mean for histograms)
+//! - `resource.kind`, `resource.id`, `consumer.kind`, `consumer.id`
+//! - `attr.<name>`: value of the attribute `<name>`
+//!
+//! Operators: `==`, `!=`, `<`, `<=`, `>`, `>=`, `=~` (matches a regex) and `!~` (does not match a regex).
+//!
+//! Literals: strings between double or simple quotes, numbers and booleans (`true`, `false`).
+//!
+//! A condition on an attribute that the point does not have is always false, whatever the operator.
+//!
+//! # Example
+//!
+//! ```text
+//! metric == "cpu_time_delta" and value == 0
+//! consumer.kind == "process" && !(attr.kind =~ "^(user|system)$")
+//! ```
+use std::{borrow::Cow, fmt::Display, str::FromStr};
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementPoint},
+    metrics::registry::MetricRegistry,
+};
+use anyhow::{Context, anyhow};
+use regex::Regex;
+
+/// A boolean expression on measurement points.
+#[derive(Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Field, Test),
+}
+
+/// A part of a measurement point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Metric,
+    Value,
+    ResourceKind,
+    ResourceId,
+    ConsumerKind,
+    ConsumerId,
+    Attribute(String),
+}
+
+/// A test that is applied to a field.
+#[derive(Debug)]
+pub enum Test {
+    Eq(Literal),
+    Ne(Literal),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+    Matches(Regex),
+    NotMatches(Regex),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+}
+
+/// The value of a field of a measurement point.
+enum Operand<'a> {
+    Str(Cow<'a, str>),
+    Num(f64),
+    Bool(bool),
+}
+
+impl Expr {
+    /// Evaluates the expression on a measurement point.
+    pub fn matches(&self, point: &MeasurementPoint, metrics: &MetricRegistry) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(point, metrics) && b.matches(point, metrics),
+            Expr::Or(a, b) => a.matches(point, metrics) || b.matches(point, metrics),
+            Expr::Not(e) => !e.matches(point, metrics),
+            Expr::Condition(field, test) => match field.get(point, metrics) {
+                Some(operand) => test.apply(&operand),
+                None => false,
+            },
+        }
+    }
+}
+
+impl Field {
+    fn get<'a>(&self, point: &'a MeasurementPoint, metrics: &'a MetricRegistry) -> Option<Operand<'a>> {
+        match self {
+            Field::Metric => metrics
+                .by_id(&point.metric)
+                .map(|m| Operand::Str(Cow::Borrowed(m.name.as_str()))),
+            Field::Value => Some(Operand::Num(point.value.as_f64())),
+            Field::ResourceKind => Some(Operand::Str(Cow::Borrowed(point.resource.kind()))),
+            Field::ResourceId => Some(Operand::Str(Cow::Owned(point.resource.id_display().to_string()))),
+            Field::ConsumerKind => Some(Operand::Str(Cow::Borrowed(point.consumer.kind()))),
+            Field::ConsumerId => Some(Operand::Str(Cow::Owned(point.consumer.id_display().to_string()))),
+            Field::Attribute(name) => {
+                let (_, value) = point.attributes().find(|(key, _)| key == name)?;
+                Some(match value {
+                    AttributeValue::F64(x) => Operand::Num(*x),
+                    AttributeValue::U64(x) => Operand::Num(*x as f64),
+                    AttributeValue::Bool(b) => Operand::Bool(*b),
+                    AttributeValue::Str(s) => Operand::Str(Cow::Borrowed(s)),
+                    AttributeValue::String(s) => Operand::Str(Cow::Borrowed(s)),
+                    list @ AttributeValue::ListU64(_) => Operand::Str(Cow::Owned(list.to_string())),
+                })
+            }
+        }
+    }
+}
+
+impl Test {
+    fn apply(&self, operand: &Operand) -> bool {
+        match self {
+            Test::Eq(literal) => operand.equals(literal),
+            Test::Ne(literal) => !operand.equals(literal),
+            Test::Lt(x) => operand.as_number().is_some_and(|v| v < *x),
+            Test::Le(x) => operand.as_number().is_some_and(|v| v <= *x),
+            Test::Gt(x) => operand.as_number().is_some_and(|v| v > *x),
+            Test::Ge(x) => operand.as_number().is_some_and(|v| v >= *x),
+            Test::Matches(regex) => regex.is_match(&operand.as_string()),
+            Test::NotMatches(regex) => !regex.is_match(&operand.as_string()),
+        }
+    }
+}
+
+impl Operand<'_> {
+    fn equals(&self, literal: &Literal) -> bool {
+        match (self, literal) {
+            (Operand::Str(a), Literal::Str(b)) => a == b,
+            (Operand::Bool(a), Literal::Bool(b)) => a == b,
+            (Operand::Bool(_), _) | (_, Literal::Bool(_)) => false,
+            (operand, Literal::Num(b)) => operand.as_number().is_some_and(|a| a == *b),
+            (Operand::Num(a), Literal::Str(b)) => b.parse::<f64>().is_ok_and(|b| *a == b),
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Operand::Num(x) => Some(*x),
+            Operand::Str(s) => s.parse().ok(),
+            Operand::Bool(_) => None,
+        }
+    }
+
+    fn as_string(&self) -> Cow<'_, str> {
+        match self {
+            Operand::Str(s) => Cow::Borrowed(s),
+            Operand::Num(x) => Cow::Owned(x.to_string()),
+            Operand::Bool(b) => Cow::Owned(b.to_string()),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(t) => Err(anyhow!("unexpected {} at position {}", t.token, t.offset)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Symbol(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "'{s}'"),
+            Token::Str(s) => write!(f, "string {s:?}"),
+            Token::Num(x) => write!(f, "number {x}"),
+            Token::Symbol(s) => write!(f, "'{s}'"),
+        }
+    }
+}
+
+struct Spanned {
+    token: Token,
+    offset: usize,
+}
+
+const SYMBOLS: [&str; 13] = ["==", "!=", "<=", ">=", "=~", "!~", "&&", "||", "<", ">", "!", "(", ")"];
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Spanned>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(offset, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    // only the quotes and the backslash are escaped, to keep the regexes readable
+                    Some((_, '\\')) => match chars.peek() {
+                        Some(&(_, escaped)) if escaped == c || escaped == '\\' => {
+                            s.push(escaped);
+                            chars.next();
+                        }
+                        _ => s.push('\\'),
+                    },
+                    Some((_, end)) if end == c => {
+                        tokens.push(Spanned {
+                            token: Token::Str(s),
+                            offset,
+                        });
+                        break;
+                    }
+                    Some((_, other)) => s.push(other),
+                    None => return Err(anyhow!("unterminated string at position {offset}")),
+                }
+            }
+        } else if c.is_ascii_digit() || (c == '-' && input[offset + 1..].starts_with(|c: char| c.is_ascii_digit())) {
+            let end = input[offset + 1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .map_or(input.len(), |i| offset + 1 + i);
+            let number = &input[offset..end];
+            let value = number
+                .parse()
+                .with_context(|| format!("invalid number {number} at position {offset}"))?;
+            tokens.push(Spanned {
+                token: Token::Num(value),
+                offset,
+            });
+            while chars.peek().is_some_and(|(i, _)| *i < end) {
+                chars.next();
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let end = input[offset..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-'))
+                .map_or(input.len(), |i| offset + i);
+            tokens.push(Spanned {
+                token: Token::Ident(input[offset..end].to_owned()),
+                offset,
+            });
+            while chars.peek().is_some_and(|(i, _)| *i < end) {
+                chars.next();
+            }
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| input[offset..].starts_with(**s))
+                .ok_or_else(|| anyhow!("unexpected character '{c}' at position {offset}"))?;
+            tokens.push(Spanned {
+                token: Token::Symbol(symbol),
+                offset,
+            });
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Spanned> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> anyhow::Result<&Spanned> {
+        let t = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| anyhow!("unexpected end of expression"))?;
+        self.pos += 1;
+        Ok(t)
+    }
+
+    /// Consumes the next token if it is one of the given keywords or symbols.
+    fn eat(&mut self, keyword: &str, symbol: &str) -> bool {
+        let found = match self.peek().map(|t| &t.token) {
+            Some(Token::Ident(s)) => s == keyword,
+            Some(Token::Symbol(s)) => *s == symbol,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn parse_or(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.eat("or", "||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.eat("and", "&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> anyhow::Result<Expr> {
+        if self.eat("not", "!") {
+            Ok(Expr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> anyhow::Result<Expr> {
+        if self.eat("(", "(") {
+            let expr = self.parse_or()?;
+            let t = self.next()?;
+            if t.token != Token::Symbol(")") {
+                return Err(anyhow!("expected ')' at position {}, found {}", t.offset, t.token));
+            }
+            return Ok(expr);
+        }
+        let field = self.parse_field()?;
+        let t = self.next()?;
+        let (op, op_offset) = match t.token {
+            Token::Symbol(op) if !matches!(op, "&&" | "||" | "!" | "(" | ")") => (op, t.offset),
+            _ => {
+                return Err(anyhow!(
+                    "expected a comparison operator at position {}, found {}",
+                    t.offset,
+                    t.token
+                ));
+            }
+        };
+        let literal = self.parse_literal()?;
+        let number = |literal: Literal| match literal {
+            Literal::Num(x) => Ok(x),
+            _ => Err(anyhow!("operator {op} at position {op_offset} requires a number")),
+        };
+        let regex = |literal: Literal| match literal {
+            Literal::Str(s) => Regex::new(&s).with_context(|| format!("invalid regex at position {op_offset}")),
+            _ => Err(anyhow!("operator {op} at position {op_offset} requires a string")),
+        };
+        let test = match op {
+            "==" => Test::Eq(literal),
+            "!=" => Test::Ne(literal),
+            "<" => Test::Lt(number(literal)?),
+            "<=" => Test::Le(number(literal)?),
+            ">" => Test::Gt(number(literal)?),
+            ">=" => Test::Ge(number(literal)?),
+            "=~" => Test::Matches(regex(literal)?),
+            "!~" => Test::NotMatches(regex(literal)?),
+            _ => unreachable!("unknown operator {op}"),
+        };
+        Ok(Expr::Condition(field, test))
+    }
+
+    fn parse_field(&mut self) -> anyhow::Result<Field> {
+        let t = self.next()?;
+        let Token::Ident(name) = &t.token else {
+            return Err(anyhow!("expected a field at position {}, found {}", t.offset, t.token));
+        };
+        let field = match name.as_str() {
+            "metric" => Field::Metric,
+            "value" => Field::Value,
+            "resource.kind" => Field::ResourceKind,
+            "resource.id" => Field::ResourceId,
+            "consumer.kind" => Field::ConsumerKind,
+            "consumer.id" => Field::ConsumerId,
+            other => match other.strip_prefix("attr.") {
+                Some(attr) if !attr.is_empty() => Field::Attribute(attr.to_owned()),
+                _ => return Err(anyhow!("unknown field '{other}' at position {}", t.offset)),
+            },
+        };
+        Ok(field)
+    }
+
+    fn parse_literal(&mut self) -> anyhow::Result<Literal> {
+        let t = self.next()?;
+        match &t.token {
+            Token::Str(s) => Ok(Literal::Str(s.to_owned())),
+            Token::Num(x) => Ok(Literal::Num(*x)),
+            Token::Ident(b) if b == "true" => Ok(Literal::Bool(true)),
+            Token::Ident(b) if b == "false" => Ok(Literal::Bool(false)),
+            other => Err(anyhow!("expected a value at position {}, found {}", t.offset, other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        pipeline::Builder,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::Expr;
+
+    // The fields that require a registered metric are tested in `tests/transform.rs`.
+
+    fn process_point(pid: u32, value: u64) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            RawMetricId::from_u64(0),
+            Resource::CpuCore { id: 2 },
+            ResourceConsumer::Process { pid },
+            WrappedMeasurementValue::U64(value),
+        )
+        .with_attr("kind", "user")
+        .with_attr("nice", 5_u64)
+    }
+
+    fn eval(expr: &str, point: &MeasurementPoint) -> bool {
+        let builder = Builder::new();
+        expr.parse::<Expr>()
+            .unwrap_or_else(|e| panic!("{expr} should be valid: {e:#}"))
+            .matches(point, builder.inspect().metrics())
+    }
+
+    #[test]
+    fn conditions() {
+        let p = process_point(42, 0);
+        assert!(eval("value == 0", &p));
+        assert!(!eval("value > 0", &p));
+        assert!(eval("value <= 0.5", &p));
+        assert!(eval("resource.kind == 'cpu_core'", &p));
+        assert!(eval("resource.id == 2", &p));
+        assert!(eval(r#"consumer.id == "42""#, &p));
+        assert!(eval(r#"consumer.kind != "cgroup""#, &p));
+        assert!(eval(r#"attr.kind =~ "^(user|system)$""#, &p));
+        assert!(eval(r#"attr.kind !~ "guest""#, &p));
+        assert!(eval(r#"attr.kind =~ '^\w+$'"#, &p));
+        assert!(eval("attr.nice >= 5", &p));
+    }
+
+    #[test]
+    fn missing_field_is_false() {
+        let p = process_point(42, 0);
+        assert!(!eval(r#"attr.missing == "x""#, &p));
+        assert!(!eval(r#"attr.missing != "x""#, &p));
+        assert!(eval(r#"not attr.missing == "x""#, &p));
+        // the metric is not in the registry
+        assert!(!eval(r#"metric =~ ".*""#, &p));
+    }
+
+    #[test]
+    fn composition() {
+        let idle = process_point(42, 0);
+        let busy = process_point(43, 12);
+        let expr = r#"consumer.kind == "process" and value == 0"#;
+        assert!(eval(expr, &idle));
+        assert!(!eval(expr, &busy));
+
+        let expr = "consumer.id == 1 || consumer.id == 43";
+        assert!(!eval(expr, &idle));
+        assert!(eval(expr, &busy));
+
+        // "and" has a higher priority than "or"
+        let expr = "value > 100 and consumer.id == 43 or value == 0";
+        assert!(eval(expr, &idle));
+        assert!(!eval(expr, &busy));
+        let expr = "value > 100 and (consumer.id == 43 or value == 0)";
+        assert!(!eval(expr, &idle));
+
+        assert!(eval("!(value > 5) && not value == 1", &idle));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        for invalid in [
+            "",
+            "value",
+            "value ==",
+            "value < 'abc'",
+            "attr.x =~ 5",
+            "attr.x =~ '('",
+            "unknown == 1",
+            "attr. == 1",
+            "(value == 1",
+            "value == 1)",
+            "value == 1 and",
+            "value = 1",
+            "metric == \"unterminated",
+            "metric == \"unterminated\\\"",
+        ] {
+            assert!(invalid.parse::<Expr>().is_err(), "{invalid:?} should be rejected");
+        }
+    }
+}
@@ -5,8 +5,9 @@ use alumet::plugin::{
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use transform::FilterTransform;
+use transform::{FilterTransform, Predicate};
 
+mod expr;
 mod transform;
 
 pub struct FilterPlugin {
@@ -33,16 +34,24 @@ impl AlumetPlugin for FilterPlugin {
                 "filter transform cannot have both include and exclude configuration parameters defined"
             ));
         }
+        if config.keep.is_some() && config.drop.is_some() {
+            return Err(anyhow::anyhow!(
+                "filter transform cannot have both keep and drop configuration parameters defined"
+            ));
+        }
+        // check the expressions now, in order to report syntax errors early
+        config.predicate()?;
         Ok(Box::new(FilterPlugin { config }))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         let include = self.config.include.clone();
         let exclude = self.config.exclude.clone();
+        let predicate = self.config.predicate()?;
 
-        if include.is_none() && exclude.is_none() {
+        if include.is_none() && exclude.is_none() && predicate.is_none() {
             log::warn!(
-                "filter plugin was started but as there's neither 'include' or 'exclude' configuration set, this will do nothing"
+                "filter plugin was started but as there's neither 'include', 'exclude', 'keep' or 'drop' configuration set, this will do nothing"
             );
             return Ok(());
         }
@@ -76,8 +85,11 @@ impl AlumetPlugin for FilterPlugin {
                 None
             };
 
-            let transform = Box::new(FilterTransform::new(include_metrics_ids, exclude_metrics_ids)?);
-            Ok(transform)
+            let mut transform = FilterTransform::new(include_metrics_ids, exclude_metrics_ids)?;
+            if let Some(predicate) = predicate {
+                transform = transform.with_predicate(predicate);
+            }
+            Ok(Box::new(transform))
         })?;
         Ok(())
     }
@@ -91,4 +103,25 @@ impl AlumetPlugin for FilterPlugin {
 pub struct Config {
     include: Option<Vec<String>>,
     exclude: Option<Vec<String>>,
+    /// Only keep the measurements that match this expression.
+    keep: Option<String>,
+    /// Remove the measurements that match this expression.
+    drop: Option<String>,
+}
+
+impl Config {
+    fn predicate(&self) -> anyhow::Result<Option<Predicate>> {
+        let predicate = match (&self.keep, &self.drop) {
+            (Some(expr), _) => Some(Predicate::Keep(
+                expr.parse()
+                    .with_context(|| format!("invalid filter expression: {expr}"))?,
+            )),
+            (None, Some(expr)) => Some(Predicate::Drop(
+                expr.parse()
+                    .with_context(|| format!("invalid filter expression: {expr}"))?,
+            )),
+            (None, None) => None,
+        };
+        Ok(predicate)
+    }
 }
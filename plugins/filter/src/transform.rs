@@ -8,13 +8,26 @@ use alumet::{
 };
 use std::collections::HashSet;
 
+use crate::expr::Expr;
+
 enum FilterMode {
     Include(HashSet<RawMetricId>),
     Exclude(HashSet<RawMetricId>),
+    /// Every metric is accepted, the points are only filtered by the predicate.
+    Any,
+}
+
+/// A filter expression, with the action to take on the points that match it.
+pub enum Predicate {
+    /// Keep only the points that match the expression.
+    Keep(Expr),
+    /// Remove the points that match the expression.
+    Drop(Expr),
 }
 
 pub struct FilterTransform {
     mode: FilterMode,
+    predicate: Option<Predicate>,
 }
 
 impl FilterTransform {
@@ -22,22 +35,25 @@ impl FilterTransform {
         let mode = match (include, exclude) {
             (Some(ids), None) => FilterMode::Include(ids),
             (None, Some(ids)) => FilterMode::Exclude(ids),
-            (None, None) => {
-                // shouldn't happen as it's validated at plugin init stage
-                unreachable!("filter transform cannot have both include and exclude empty");
-            }
+            (None, None) => FilterMode::Any,
             (Some(_), Some(_)) => {
                 // shouldn't happen as it's validated at plugin init stage
                 unreachable!("filter transform cannot have both include and exclude set");
             }
         };
 
-        Ok(Self { mode })
+        Ok(Self { mode, predicate: None })
+    }
+
+    /// Filters the points with an expression, in addition to the include/exclude lists.
+    pub fn with_predicate(mut self, predicate: Predicate) -> Self {
+        self.predicate = Some(predicate);
+        self
     }
 }
 
 impl Transform for FilterTransform {
-    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, ctx: &TransformContext) -> Result<(), TransformError> {
         match &self.mode {
             FilterMode::Include(set) => {
                 measurements.retain(|p| set.contains(&p.metric));
@@ -46,6 +62,20 @@ impl Transform for FilterTransform {
             FilterMode::Exclude(set) => {
                 measurements.retain(|p| !set.contains(&p.metric));
             }
+
+            FilterMode::Any => (),
+        }
+
+        match &self.predicate {
+            Some(Predicate::Keep(expr)) => {
+                measurements.retain(|p| expr.matches(p, ctx.metrics));
+            }
+
+            Some(Predicate::Drop(expr)) => {
+                measurements.retain(|p| !expr.matches(p, ctx.metrics));
+            }
+
+            None => (),
         }
 
         Ok(())
@@ -26,6 +26,14 @@ const CONFIG_EXCLUDE: &str = r#"
 exclude = ["metric_a"]
 "#;
 
+const CONFIG_KEEP: &str = r#"
+keep = "metric == 'metric_a' or value > 1"
+"#;
+
+const CONFIG_DROP: &str = r#"
+drop = "metric =~ '_b$' and value == 0"
+"#;
+
 #[test]
 fn test_filter_include() {
     let transform_name = TransformName::from_str("filter", "transform");
@@ -104,6 +112,82 @@ fn test_filter_exclude() {
     run_agent(runtime, CONFIG_EXCLUDE);
 }
 
+#[test]
+fn test_filter_keep() {
+    let transform_name = TransformName::from_str("filter", "transform");
+    let ts1 = Timestamp::now();
+    let ts2 = ts1 + Duration::from_secs(1);
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<u64>("metric_a", Unit::Unity)
+        .create_metric::<u64>("metric_b", Unit::Unity)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let metrics = TestMetrics::find_in(input.metrics());
+                let mut buf = MeasurementBuffer::new();
+
+                buf.push(new_point(&metrics, metrics.metric_a, ts1, 0));
+                buf.push(new_point(&metrics, metrics.metric_b, ts1, 1));
+                buf.push(new_point(&metrics, metrics.metric_b, ts2, 2));
+
+                buf
+            },
+            move |output| {
+                let metrics = TestMetrics::find_in(output.metrics());
+                let m = output.measurements().to_vec();
+
+                assert_eq!(
+                    m,
+                    vec![
+                        new_point(&metrics, metrics.metric_a, ts1, 0),
+                        new_point(&metrics, metrics.metric_b, ts2, 2),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG_KEEP);
+}
+
+#[test]
+fn test_filter_drop() {
+    let transform_name = TransformName::from_str("filter", "transform");
+    let ts1 = Timestamp::now();
+    let ts2 = ts1 + Duration::from_secs(1);
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<u64>("metric_a", Unit::Unity)
+        .create_metric::<u64>("metric_b", Unit::Unity)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let metrics = TestMetrics::find_in(input.metrics());
+                let mut buf = MeasurementBuffer::new();
+
+                buf.push(new_point(&metrics, metrics.metric_a, ts1, 0));
+                buf.push(new_point(&metrics, metrics.metric_b, ts1, 0));
+                buf.push(new_point(&metrics, metrics.metric_b, ts2, 2));
+
+                buf
+            },
+            move |output| {
+                let metrics = TestMetrics::find_in(output.metrics());
+                let m = output.measurements().to_vec();
+
+                assert_eq!(
+                    m,
+                    vec![
+                        new_point(&metrics, metrics.metric_a, ts1, 0),
+                        new_point(&metrics, metrics.metric_b, ts2, 2),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG_DROP);
+}
+
 fn run_agent(runtime: RuntimeExpectations, config: &str) {
     let mut plugins = PluginSet::new();
     plugins.add_plugin(PluginInfo {
@@ -0,0 +1,355 @@
+//! HTTP endpoints of the control server.
+//!
+//! Every response body is a JSON object. Failed requests return `{"error": "<message>"}`
+//! with an appropriate HTTP status code.
+
+use std::{convert::Infallible, str::FromStr, sync::Arc, time::Duration};
+
+use alumet::{
+    metrics::online::MetricReader,
+    pipeline::{
+        control::{
+            AnonymousControlHandle,
+            handle::SendWaitError,
+            request::{self, Action, ElementListFilter},
+        },
+        matching::{ElementNamePattern, StringPattern},
+        naming::{
+            ElementKind, ElementName,
+            parsing::{parse_kind, parse_pattern},
+        },
+    },
+};
+use hyper::{
+    Body, HeaderMap, Method, Request, Response, StatusCode,
+    header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// State shared by all the connections.
+pub struct ApiState {
+    pub handle: AnonymousControlHandle,
+    pub metrics: MetricReader,
+    pub token: Option<String>,
+}
+
+/// An error that is turned into a JSON response.
+#[derive(Debug, PartialEq)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+/// A pipeline element, as returned by the API.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ElementInfo {
+    kind: String,
+    plugin: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct MetricInfo {
+    id: u64,
+    name: String,
+    description: String,
+    value_type: String,
+    unit: String,
+}
+
+/// Body of `POST /control`.
+#[derive(Debug, Deserialize)]
+struct ControlBody {
+    /// Selects the elements to control, in the form `kind/plugin/element` or `kind`.
+    pattern: String,
+    #[serde(flatten)]
+    action: Action,
+}
+
+#[derive(Serialize)]
+struct ControlResponse<'a> {
+    pattern: &'a str,
+    #[serde(flatten)]
+    action: &'a Action,
+    /// The elements that were selected by the pattern.
+    elements: Vec<ElementInfo>,
+}
+
+/// Handles an HTTP request.
+pub async fn handle(req: Request<Body>, state: Arc<ApiState>) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_owned();
+    let response = route(req, &method, &path, &state).await.unwrap_or_else(|e| {
+        log::debug!("{method} {path} failed with {}: {}", e.status, e.message);
+        e.into_response()
+    });
+    Ok(response)
+}
+
+async fn route(req: Request<Body>, method: &Method, path: &str, state: &ApiState) -> Result<Response<Body>, ApiError> {
+    authorize(state.token.as_deref(), req.headers())?;
+    match (method, path) {
+        (&Method::GET, "/elements") => list_elements(&state.handle, req.uri().query()).await,
+        (&Method::GET, "/metrics") => list_metrics(&state.metrics).await,
+        (&Method::POST, "/control") => control(&state.handle, req.into_body()).await,
+        (&Method::POST, "/shutdown") => {
+            state.handle.shutdown();
+            Ok(json_response(StatusCode::ACCEPTED, &json!({ "shutdown": true })))
+        }
+        (_, "/elements" | "/metrics" | "/control" | "/shutdown") => Err(ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("method {method} is not allowed on {path}"),
+        )),
+        _ => Err(ApiError::new(StatusCode::NOT_FOUND, format!("unknown endpoint {path}"))),
+    }
+}
+
+/// `GET /elements?kind=<kind>&plugin=<pattern>&name=<pattern>`, all parameters are optional.
+async fn list_elements(handle: &AnonymousControlHandle, query: Option<&str>) -> Result<Response<Body>, ApiError> {
+    let pattern = parse_query(query)?;
+    let elements = find_elements(handle, &pattern).await?;
+    Ok(json_response(StatusCode::OK, &json!({ "elements": elements })))
+}
+
+/// `GET /metrics`
+async fn list_metrics(metrics: &MetricReader) -> Result<Response<Body>, ApiError> {
+    let registry = metrics.read().await;
+    let mut metrics: Vec<MetricInfo> = registry
+        .iter()
+        .map(|(id, m)| MetricInfo {
+            id: id.as_u64(),
+            name: m.name.clone(),
+            description: m.description.clone(),
+            value_type: m.value_type.to_string(),
+            unit: m.unit.unique_name(),
+        })
+        .collect();
+    metrics.sort_by_key(|m| m.id);
+    Ok(json_response(StatusCode::OK, &json!({ "metrics": metrics })))
+}
+
+/// `POST /control`
+async fn control(handle: &AnonymousControlHandle, body: Body) -> Result<Response<Body>, ApiError> {
+    let body = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| ApiError::bad_request(format!("could not read the request body: {e}")))?;
+    let body: ControlBody = serde_json::from_slice(&body).map_err(|e| ApiError::bad_request(format!("{e}")))?;
+    let pattern = parse_pattern(&body.pattern).map_err(|e| ApiError::bad_request(e.to_string()))?;
+    let requests = body
+        .action
+        .requests(&pattern)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    let elements = find_elements(handle, &pattern).await?;
+    if elements.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("no element matches the pattern '{}'", body.pattern),
+        ));
+    }
+    for request in requests {
+        handle.send_wait(request, REQUEST_TIMEOUT).await?;
+    }
+    let response = ControlResponse {
+        pattern: &body.pattern,
+        action: &body.action,
+        elements,
+    };
+    Ok(json_response(StatusCode::OK, &response))
+}
+
+async fn find_elements(
+    handle: &AnonymousControlHandle,
+    pattern: &ElementNamePattern,
+) -> Result<Vec<ElementInfo>, ApiError> {
+    let filter = match pattern.kind {
+        Some(kind) => ElementListFilter::kind(kind),
+        None => ElementListFilter::kind_any(),
+    }
+    .plugin_pat(pattern.plugin.clone())
+    .name_pat(pattern.element.clone());
+    let elements = handle
+        .send_wait(request::list_elements(filter), REQUEST_TIMEOUT)
+        .await?;
+    Ok(elements.into_iter().map(ElementInfo::from).collect())
+}
+
+/// Checks the bearer token of the request, if a token is required.
+fn authorize(token: Option<&str>, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(expected) = token else {
+        return Ok(());
+    };
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match provided {
+        Some(t) if constant_time_eq(t.as_bytes(), expected.as_bytes()) => Ok(()),
+        Some(_) => Err(ApiError::new(StatusCode::UNAUTHORIZED, "invalid bearer token")),
+        None => Err(ApiError::new(StatusCode::UNAUTHORIZED, "missing bearer token")),
+    }
+}
+
+/// Compares two byte strings without short-circuiting, to avoid leaking the token through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Parses the query parameters of `GET /elements`.
+fn parse_query(query: Option<&str>) -> Result<ElementNamePattern, ApiError> {
+    let mut pattern = ElementNamePattern {
+        kind: None,
+        plugin: StringPattern::Any,
+        element: StringPattern::Any,
+    };
+    for (key, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        match key.as_ref() {
+            "kind" => pattern.kind = parse_kind_param(&value)?,
+            "plugin" => pattern.plugin = parse_string_pattern(&value)?,
+            "name" => pattern.element = parse_string_pattern(&value)?,
+            _ => return Err(ApiError::bad_request(format!("unknown query parameter '{key}'"))),
+        }
+    }
+    Ok(pattern)
+}
+
+fn parse_kind_param(kind: &str) -> Result<Option<ElementKind>, ApiError> {
+    parse_kind(kind).map_err(|_| ApiError::bad_request(format!("bad kind: '{kind}'")))
+}
+
+fn parse_string_pattern(pat: &str) -> Result<StringPattern, ApiError> {
+    StringPattern::from_str(pat).map_err(|e| ApiError::bad_request(format!("bad pattern '{pat}': {e}")))
+}
+
+impl From<ElementName> for ElementInfo {
+    fn from(value: ElementName) -> Self {
+        Self {
+            kind: value.kind.to_string(),
+            plugin: value.plugin,
+            name: value.element,
+        }
+    }
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn into_response(self) -> Response<Body> {
+        let mut response = json_response(self.status, &json!({ "error": self.message }));
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+        }
+        response
+    }
+}
+
+impl From<SendWaitError> for ApiError {
+    fn from(value: SendWaitError) -> Self {
+        let status = match &value {
+            SendWaitError::NotAvailable => StatusCode::SERVICE_UNAVAILABLE,
+            SendWaitError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, format!("{:#}", anyhow::Error::new(value)))
+    }
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+    let body = serde_json::to_vec(body).expect("API responses should always be serializable");
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use alumet::pipeline::{
+        matching::{ElementNamePattern, StringPattern},
+        naming::ElementKind,
+    };
+    use hyper::{HeaderMap, StatusCode, header::AUTHORIZATION};
+    use pretty_assertions::assert_eq;
+
+    use super::{Action, ControlBody, authorize, parse_query};
+
+    #[test]
+    fn query() {
+        assert_eq!(
+            parse_query(None).unwrap(),
+            ElementNamePattern {
+                kind: None,
+                plugin: StringPattern::Any,
+                element: StringPattern::Any,
+            }
+        );
+        assert_eq!(
+            parse_query(Some("kind=transform&plugin=energy-%2A&name=a")).unwrap(),
+            ElementNamePattern {
+                kind: Some(ElementKind::Transform),
+                plugin: StringPattern::StartWith("energy-".to_owned()),
+                element: StringPattern::Exact("a".to_owned()),
+            }
+        );
+        assert_eq!(
+            parse_query(Some("foo=bar")).unwrap_err().status,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn control_body() {
+        let body: ControlBody =
+            serde_json::from_str(r#"{"pattern": "source/rapl/*", "action": "set-period", "period": "10ms"}"#).unwrap();
+        assert_eq!(body.pattern, "source/rapl/*");
+        assert_eq!(
+            body.action,
+            Action::SetPeriod {
+                period: Duration::from_millis(10)
+            }
+        );
+
+        let body: ControlBody = serde_json::from_str(r#"{"pattern": "*", "action": "pause"}"#).unwrap();
+        assert_eq!(body.action, Action::Pause);
+
+        assert!(serde_json::from_str::<ControlBody>(r#"{"pattern": "*", "action": "explode"}"#).is_err());
+        assert!(serde_json::from_str::<ControlBody>(r#"{"pattern": "*", "action": "set-period"}"#).is_err());
+    }
+
+    #[test]
+    fn bearer_token() {
+        let mut headers = HeaderMap::new();
+        assert!(authorize(None, &headers).is_ok());
+        assert_eq!(
+            authorize(Some("secret"), &headers).unwrap_err().status,
+            StatusCode::UNAUTHORIZED
+        );
+
+        headers.insert(AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert_eq!(
+            authorize(Some("secret"), &headers).unwrap_err().status,
+            StatusCode::UNAUTHORIZED
+        );
+
+        headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert!(authorize(Some("secret"), &headers).is_ok());
+        assert!(authorize(None, &headers).is_ok());
+    }
+}
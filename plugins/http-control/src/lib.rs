@@ -0,0 +1,79 @@
+mod api;
+mod server;
+
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable};
+use serde::{Deserialize, Serialize};
+use server::HttpControl;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address (host and port) on which the HTTP server listens.
+    pub address: String,
+    /// If set, every request must contain the header `Authorization: Bearer <token>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+pub struct HttpControlPlugin {
+    config: Config,
+    control: Option<HttpControl>,
+}
+
+impl AlumetPlugin for HttpControlPlugin {
+    fn name() -> &'static str {
+        "http-control"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(HttpControlPlugin { config, control: None }))
+    }
+
+    fn start(&mut self, _alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
+        if self.config.token.is_none() {
+            log::warn!("No token configured: anyone who can reach the HTTP control server can control the pipeline.");
+        }
+        let control = HttpControl::start_new(
+            alumet.pipeline_control(),
+            alumet.metrics_reader(),
+            &self.config.address,
+            self.config.token.clone(),
+        )?;
+        log::info!("HttpControl enabled on http://{}", control.local_addr());
+        self.control = Some(control);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        if let Some(control) = self.control.take() {
+            control.stop();
+            control.join();
+            log::info!("HttpControl stopped.");
+        }
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: String::from("127.0.0.1:8765"),
+            token: None,
+        }
+    }
+}
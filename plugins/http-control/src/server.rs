@@ -0,0 +1,89 @@
+use std::{
+    convert::Infallible,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+use alumet::{metrics::online::MetricReader, pipeline::control::PluginControlHandle};
+use anyhow::Context;
+use hyper::{
+    Server,
+    service::{make_service_fn, service_fn},
+};
+use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::{self, ApiState};
+
+pub struct HttpControl {
+    rt: Runtime,
+    cancel_token: CancellationToken,
+    local_addr: SocketAddr,
+}
+
+impl HttpControl {
+    pub fn start_new(
+        alumet_handle: PluginControlHandle,
+        metrics: MetricReader,
+        address: &str,
+        token: Option<String>,
+    ) -> anyhow::Result<HttpControl> {
+        let addr = address
+            .to_socket_addrs()
+            .with_context(|| format!("invalid address: {address}"))?
+            .next()
+            .with_context(|| format!("address {address} does not resolve to anything"))?;
+
+        // create single-threaded runtime
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+
+        // bind the listener now, so that errors are reported to the agent
+        let builder = {
+            let _guard = rt.enter();
+            Server::try_bind(&addr).with_context(|| format!("could not bind to {addr}"))?
+        };
+
+        let state = Arc::new(ApiState {
+            handle: alumet_handle.anonymous(),
+            metrics,
+            token,
+        });
+        let make_service = make_service_fn(move |_conn| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |req| api::handle(req, state.clone()))) }
+        });
+        let server = builder.serve(make_service);
+        let local_addr = server.local_addr();
+
+        // create token to stop the server on demand
+        let cancel_token = CancellationToken::new();
+        let server = server.with_graceful_shutdown(cancel_token.clone().cancelled_owned());
+        rt.spawn(async move {
+            if let Err(e) = server.await {
+                log::error!("HTTP control server error: {e:#}");
+            }
+        });
+
+        Ok(HttpControl {
+            rt,
+            cancel_token,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn stop(&self) {
+        self.cancel_token.cancel();
+    }
+
+    pub fn join(self) {
+        self.rt.shutdown_timeout(Duration::from_secs(1));
+    }
+}
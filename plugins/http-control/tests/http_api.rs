@@ -0,0 +1,223 @@
+use std::{net::TcpListener, time::Duration};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementAccumulator, Timestamp},
+    pipeline::{
+        Source,
+        elements::{error::PollError, source::trigger::TriggerSpec},
+    },
+    plugin::{
+        AlumetPluginStart, ConfigTable, PluginMetadata,
+        rust::{AlumetPlugin, serialize_config},
+    },
+    units::Unit,
+};
+use hyper::{Body, Client, Method, Request, StatusCode, header::AUTHORIZATION};
+use plugin_http_control::{Config, HttpControlPlugin};
+use pretty_assertions::assert_eq;
+use serde_json::{Value, json};
+
+const TOKEN: &str = "test-token";
+
+struct TestPlugin;
+
+impl AlumetPlugin for TestPlugin {
+    fn name() -> &'static str {
+        "test-plugin"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(TestPlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.create_metric::<u64>("test_counter", Unit::Unity, "a test metric")?;
+        alumet.add_source(
+            "idle",
+            Box::new(IdleSource),
+            TriggerSpec::at_interval(Duration::from_secs(1)),
+        )?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct IdleSource;
+
+impl Source for IdleSource {
+    fn poll(&mut self, _m: &mut MeasurementAccumulator, _t: Timestamp) -> Result<(), PollError> {
+        Ok(())
+    }
+}
+
+fn free_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+async fn call(
+    address: &str,
+    method: Method,
+    path: &str,
+    body: Option<Value>,
+    token: Option<&str>,
+) -> (StatusCode, Value) {
+    let mut req = Request::builder().method(method).uri(format!("http://{address}{path}"));
+    if let Some(token) = token {
+        req = req.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let body = match body {
+        Some(json) => Body::from(json.to_string()),
+        None => Body::empty(),
+    };
+    let res = Client::new().request(req.body(body).unwrap()).await.unwrap();
+    let status = res.status();
+    let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[test]
+fn control_over_http() {
+    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).try_init();
+
+    let address = free_address();
+    let plugin_config = serialize_config(Config {
+        address: address.clone(),
+        token: Some(TOKEN.to_owned()),
+    })
+    .unwrap()
+    .0;
+
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<HttpControlPlugin>(),
+        enabled: true,
+        config: Some(plugin_config),
+    });
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<TestPlugin>(),
+        enabled: true,
+        config: None,
+    });
+
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("alumet should start");
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let addr = address.as_str();
+
+        // the token is required
+        let (status, body) = call(addr, Method::GET, "/elements", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, json!({"error": "missing bearer token"}));
+        let (status, _) = call(addr, Method::GET, "/elements", None, Some("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // introspection
+        let (status, body) = call(addr, Method::GET, "/elements?kind=source", None, Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({"elements": [{"kind": "source", "plugin": "test-plugin", "name": "idle"}]})
+        );
+        let (status, body) = call(
+            addr,
+            Method::GET,
+            "/elements?kind=output&plugin=test-plugin",
+            None,
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"elements": []}));
+
+        let (status, body) = call(addr, Method::GET, "/metrics", None, Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        let metrics = body["metrics"].as_array().unwrap();
+        let metric = metrics.iter().find(|m| m["name"] == "test_counter").unwrap();
+        assert_eq!(metric["value_type"], "U64");
+        assert_eq!(metric["unit"], "1");
+        assert_eq!(metric["description"], "a test metric");
+
+        // control
+        let (status, body) = call(
+            addr,
+            Method::POST,
+            "/control",
+            Some(json!({"pattern": "source/test-plugin/*", "action": "set-period", "period": "100ms"})),
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "pattern": "source/test-plugin/*",
+                "action": "set-period",
+                "period": "100ms",
+                "elements": [{"kind": "source", "plugin": "test-plugin", "name": "idle"}],
+            })
+        );
+        let (status, _) = call(
+            addr,
+            Method::POST,
+            "/control",
+            Some(json!({"pattern": "*", "action": "pause"})),
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // errors
+        let (status, _) = call(
+            addr,
+            Method::POST,
+            "/control",
+            Some(json!({"pattern": "source/nope/*", "action": "resume"})),
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(
+            addr,
+            Method::POST,
+            "/control",
+            Some(json!({"pattern": "transform", "action": "trigger-now"})),
+            Some(TOKEN),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(addr, Method::GET, "/control", None, Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        let (status, _) = call(addr, Method::GET, "/nothing", None, Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // shutdown
+        let (status, _) = call(addr, Method::POST, "/shutdown", None, Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+    });
+
+    agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .expect("alumet should stop");
+}
@@ -1,6 +1,6 @@
 //! InfluxDB2 API.
 
-use alumet::measurement::Timestamp;
+use alumet::measurement::{Histogram, Timestamp};
 use anyhow::Context;
 use reqwest::{Url, header};
 use std::{
@@ -178,6 +178,28 @@ impl LineProtocolBuilder {
         self.field(key, if value { "T" } else { "F" })
     }
 
+    /// Writes a histogram to the current line, as multiple fields.
+    ///
+    /// Like in Prometheus, the histogram is represented by its total count `{key}_count`,
+    /// its sum `{key}_sum`, and the cumulative count of each bucket `{key}_bucket_le_{bound}`.
+    ///
+    /// Must be called after `tag` (or `measurement` if there's no tag).
+    pub fn field_histogram(&mut self, key: &str, value: &Histogram) -> &mut Self {
+        self.field_uint(&format!("{key}_count"), value.count());
+        self.field_float(&format!("{key}_sum"), value.sum());
+        let mut cumulative_count = 0;
+        for (bound, count) in value.buckets() {
+            cumulative_count += count;
+            let le = if bound.is_infinite() {
+                String::from("+Inf")
+            } else {
+                bound.to_string()
+            };
+            self.field_uint(&format!("{key}_bucket_le_{le}"), cumulative_count);
+        }
+        self
+    }
+
     /// Writes a tag to the current line.
     ///
     /// Must be called after `field`. Required.
@@ -226,7 +248,7 @@ mod tests {
 
     use super::{Client, LineProtocolBuilder, LineProtocolData};
     use crate::influxdb2::escape_string;
-    use alumet::measurement::Timestamp;
+    use alumet::measurement::{Histogram, Timestamp};
 
     async fn mock_influx_write(server: &mut ServerGuard, org: &str, bucket: &str, token: &str, body: &str) -> Mock {
         server
@@ -288,6 +310,18 @@ mod tests {
             expected_str: r#"myMeasurement,tag1=value1,tag2=value2 fieldKey="fieldValue" 1556813561098000000
 measurement_without_tags fieldKey="fieldValue",bool=T,float=123,int=-123i,uint=123u 1556813561098000000"#,
         });
+
+        let histogram = Histogram::from_parts(vec![0.5, 1.0], vec![1, 2, 3], 7.5).unwrap();
+        let mut builder = LineProtocolData::builder();
+        builder
+            .measurement("latency")
+            .field_histogram("value", &histogram)
+            .timestamp(Timestamp::from(UNIX_EPOCH + Duration::from_nanos(1556813561098000000)));
+        let line = builder.build();
+        tested_lines.push(TestedLineProtocolData {
+            line,
+            expected_str: r#"latency value_count=6u,value_sum=7.5,value_bucket_le_0.5=1u,value_bucket_le_1=3u,value_bucket_le_+Inf=6u 1556813561098000000"#,
+        });
         tested_lines
     }
 
@@ -147,9 +147,11 @@ impl Output for InfluxDbOutput {
             }
 
             // Alumet value is a field.
-            match m.value {
-                WrappedMeasurementValue::F64(v) => builder.field_float("value", v),
-                WrappedMeasurementValue::U64(v) => builder.field_uint("value", v),
+            match &m.value {
+                WrappedMeasurementValue::F64(v) => builder.field_float("value", *v),
+                WrappedMeasurementValue::U64(v) => builder.field_uint("value", *v),
+                WrappedMeasurementValue::I64(v) => builder.field_int("value", *v),
+                WrappedMeasurementValue::Histogram(h) => builder.field_histogram("value", h),
             };
 
             // And the timestamp comes last.
@@ -24,6 +24,7 @@ pub struct MeasureKwollect {
 #[serde(untagged)]
 pub enum SerializableMeasurementValue {
     U64(u64),
+    I64(i64),
     F64(f64),
 }
 
@@ -31,7 +32,10 @@ impl From<WrappedMeasurementValue> for SerializableMeasurementValue {
     fn from(value: WrappedMeasurementValue) -> Self {
         match value {
             WrappedMeasurementValue::U64(v) => Self::U64(v),
+            WrappedMeasurementValue::I64(v) => Self::I64(v),
             WrappedMeasurementValue::F64(v) => Self::F64(v),
+            // kwollect only supports numbers, use the mean of the distribution
+            WrappedMeasurementValue::Histogram(h) => Self::F64(h.mean()),
         }
     }
 }
@@ -40,6 +44,7 @@ impl From<SerializableMeasurementValue> for WrappedMeasurementValue {
     fn from(value: SerializableMeasurementValue) -> Self {
         match value {
             SerializableMeasurementValue::U64(v) => Self::U64(v),
+            SerializableMeasurementValue::I64(v) => Self::I64(v),
             SerializableMeasurementValue::F64(v) => Self::F64(v),
         }
     }
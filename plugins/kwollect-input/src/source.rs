@@ -49,9 +49,11 @@ impl Source for KwollectSource {
             };
 
             let metric_id = metric;
-            let value = match measure.value {
-                WrappedMeasurementValue::F64(v) => v,
-                WrappedMeasurementValue::U64(v) => v as f64,
+            let value = match &measure.value {
+                WrappedMeasurementValue::F64(v) => *v,
+                WrappedMeasurementValue::U64(v) => *v as f64,
+                WrappedMeasurementValue::I64(v) => *v as f64,
+                WrappedMeasurementValue::Histogram(h) => h.mean(),
             };
 
             let datetime = parse_timestamp(&measure.timestamp)?;
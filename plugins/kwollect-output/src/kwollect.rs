@@ -23,9 +23,12 @@ impl Serialize for Measure {
         map.serialize_entry("metric_id", &self.metric_id)?;
         map.serialize_entry("device_id", &self.device_id)?;
 
-        match self.value {
-            WrappedMeasurementValue::F64(v) => map.serialize_entry("value", &v)?,
-            WrappedMeasurementValue::U64(v) => map.serialize_entry("value", &v)?,
+        match &self.value {
+            WrappedMeasurementValue::F64(v) => map.serialize_entry("value", v)?,
+            WrappedMeasurementValue::U64(v) => map.serialize_entry("value", v)?,
+            WrappedMeasurementValue::I64(v) => map.serialize_entry("value", v)?,
+            // kwollect only supports numbers, send the mean of the distribution
+            WrappedMeasurementValue::Histogram(h) => map.serialize_entry("value", &h.mean())?,
         };
 
         struct LabelsSerializer<'a>(&'a HashMap<String, AttributeValue>);
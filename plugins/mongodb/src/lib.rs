@@ -140,12 +140,27 @@ impl Output for MongoDbOutput {
             }
 
             // Append alumet value
-            match m.value {
+            match &m.value {
                 WrappedMeasurementValue::F64(v) => {
-                    doc.insert("value", v);
+                    doc.insert("value", *v);
                 }
                 WrappedMeasurementValue::U64(v) => {
-                    doc.insert("value", u64_to_bson(v));
+                    doc.insert("value", u64_to_bson(*v));
+                }
+                WrappedMeasurementValue::I64(v) => {
+                    doc.insert("value", *v);
+                }
+                WrappedMeasurementValue::Histogram(h) => {
+                    let counts: Vec<_> = h.counts().iter().map(|c| u64_to_bson(*c)).collect();
+                    doc.insert(
+                        "value",
+                        doc! {
+                            "count": u64_to_bson(h.count()),
+                            "sum": h.sum(),
+                            "bounds": h.bounds().to_vec(),
+                            "counts": counts,
+                        },
+                    );
                 }
             }
 
@@ -8,7 +8,8 @@ use opentelemetry_proto::tonic::{
     collector::metrics::v1::{ExportMetricsServiceRequest, metrics_service_client::MetricsServiceClient},
     common::v1::{AnyValue, InstrumentationScope, KeyValue, any_value},
     metrics::v1::{
-        Gauge, Metric as OtelMetric, NumberDataPoint, ResourceMetrics, ScopeMetrics, metric, number_data_point::Value,
+        AggregationTemporality, Gauge, Histogram, HistogramDataPoint, Metric as OtelMetric, NumberDataPoint,
+        ResourceMetrics, ScopeMetrics, metric, number_data_point::Value,
     },
     resource::v1::Resource,
 };
@@ -102,14 +103,6 @@ impl alumet::pipeline::Output for OpenTelemetryOutput {
                 .map_err(|e| anyhow::anyhow!("invalid timestamp: {e}"))?
                 .as_nanos() as u64;
 
-            let data_point = NumberDataPoint {
-                attributes,
-                start_time_unix_nano: 0,
-                time_unix_nano,
-                value: Some(Value::AsDouble(m.value.as_f64())),
-                ..Default::default()
-            };
-
             // Lookup for the metric_name entry in the map, or create one if it doesn't exist
             let entry = self
                 .metric_map
@@ -118,15 +111,41 @@ impl alumet::pipeline::Output for OpenTelemetryOutput {
                     name: metric_name,
                     description: full_metric.description.to_string(),
                     unit: get_unit_string(full_metric, self.use_unit_display_name),
-                    data: Some(metric::Data::Gauge(Gauge {
-                        data_points: Vec::new(),
-                    })),
+                    data: Some(new_metric_data(&m.value)),
                     ..Default::default()
                 });
 
             // Push the data point to the existing (or new) OtelMetric
-            if let Some(metric::Data::Gauge(ref mut gauge)) = entry.data {
-                gauge.data_points.push(data_point);
+            match (&mut entry.data, &m.value) {
+                (Some(metric::Data::Histogram(histogram)), WrappedMeasurementValue::Histogram(h)) => {
+                    histogram.data_points.push(HistogramDataPoint {
+                        attributes,
+                        start_time_unix_nano: 0,
+                        time_unix_nano,
+                        count: h.count(),
+                        sum: Some(h.sum()),
+                        bucket_counts: h.counts().to_vec(),
+                        explicit_bounds: h.bounds().to_vec(),
+                        ..Default::default()
+                    });
+                }
+                (Some(metric::Data::Gauge(gauge)), value) => {
+                    let value = match value {
+                        WrappedMeasurementValue::I64(v) => Value::AsInt(*v),
+                        v => Value::AsDouble(v.as_f64()),
+                    };
+                    gauge.data_points.push(NumberDataPoint {
+                        attributes,
+                        start_time_unix_nano: 0,
+                        time_unix_nano,
+                        value: Some(value),
+                        ..Default::default()
+                    });
+                }
+                _ => log::warn!(
+                    "The values of metric {} do not all have the same type, some of them have been ignored.",
+                    entry.name
+                ),
             }
         }
 
@@ -169,6 +188,20 @@ impl alumet::pipeline::Output for OpenTelemetryOutput {
     }
 }
 
+/// Creates an empty OpenTelemetry metric that can hold values like `value`.
+fn new_metric_data(value: &WrappedMeasurementValue) -> metric::Data {
+    match value {
+        // Each Alumet histogram is the distribution of the values measured since the previous one.
+        WrappedMeasurementValue::Histogram(_) => metric::Data::Histogram(Histogram {
+            data_points: Vec::new(),
+            aggregation_temporality: AggregationTemporality::Delta as i32,
+        }),
+        _ => metric::Data::Gauge(Gauge {
+            data_points: Vec::new(),
+        }),
+    }
+}
+
 fn make_kv(key: impl Into<String>, value: impl Into<String>) -> KeyValue {
     KeyValue {
         key: key.into(),
@@ -28,7 +28,7 @@ use alumet::{
         self,
         plugin::{PluginInfo, PluginSet},
     },
-    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    measurement::{Histogram, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
     pipeline::naming::OutputName,
     plugin::PluginMetadata,
     resources::{Resource, ResourceConsumer},
@@ -295,6 +295,56 @@ fn write_single_f64_measurement() {
     run_agent(plugins, make_input, check_output);
 }
 
+/// Histograms are exported as OTLP histograms, with the same buckets.
+#[test]
+#[serial]
+fn write_histogram_measurement() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let (collector, addr) = rt.block_on(spawn_mock_collector());
+
+    let plugin_config = Config {
+        collector_host: format!("http://{}", addr),
+        prefix: String::new(),
+        suffix: String::new(),
+        use_unit_display_name: false,
+        add_attributes_to_labels: false,
+    };
+    let plugins = add_plugins(plugin_config);
+
+    let make_input = move |ctx: &mut OutputCheckInputContext| -> MeasurementBuffer {
+        let metric = ctx.metrics().by_name("dummy").expect("metric should exist").0;
+        let histogram = Histogram::from_parts(vec![0.5, 1.0], vec![1, 2, 3], 7.5).unwrap();
+        let mut buf = MeasurementBuffer::new();
+        buf.push(MeasurementPoint::new_untyped(
+            Timestamp::from(UNIX_EPOCH + Duration::from_nanos(1_000_000_000)),
+            metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::Histogram(histogram),
+        ));
+        buf
+    };
+
+    let collector_for_check = collector.clone();
+    let check_output = move || {
+        let metric = find_metric(&collector_for_check, "dummy");
+
+        let histogram = match &metric.data {
+            Some(opentelemetry_proto::tonic::metrics::v1::metric::Data::Histogram(h)) => h,
+            other => panic!("expected Histogram, got {:?}", other),
+        };
+
+        assert_eq!(histogram.data_points.len(), 1);
+        let dp = &histogram.data_points[0];
+        assert_eq!(dp.count, 6);
+        assert_eq!(dp.sum, Some(7.5));
+        assert_eq!(dp.explicit_bounds, vec![0.5, 1.0]);
+        assert_eq!(dp.bucket_counts, vec![1, 2, 3]);
+    };
+
+    run_agent(plugins, make_input, check_output);
+}
+
 /// Tests that multiple measurements for the same metric name are grouped into
 /// a single OTLP Metric object with multiple data points, while different
 /// metric names remain in separate objects.
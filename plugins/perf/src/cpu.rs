@@ -1,5 +1,5 @@
-//! Detection of online CPUs.
-use std::num::ParseIntError;
+//! Detection of online CPUs and of their topology.
+use std::{num::ParseIntError, path::Path};
 
 use anyhow::Context;
 
@@ -31,11 +31,80 @@ fn parse_cpu_list(cpulist: &str) -> anyhow::Result<Vec<u32>> {
     Ok(cpus)
 }
 
+/// Where the kernel describes the CPUs in sysfs.
+pub const SYSFS_CPU: &str = "/sys/devices/system/cpu";
+
 pub fn online_cpus() -> anyhow::Result<Vec<u32>> {
     let path = "/sys/devices/system/cpu/online";
     let list = std::fs::read_to_string(path).with_context(|| format!("Failed to parse {path}"))?;
     parse_cpu_list(&list)
 }
 
+/// Returns the id of the package (socket) that contains the given CPU.
+pub fn package_id(root: &Path, cpu: u32) -> anyhow::Result<u32> {
+    let content = read_topology(root, cpu, "physical_package_id")?;
+    content
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid package id for cpu {cpu}: {content}"))
+}
+
+/// Returns the lowest id among the hardware threads (SMT siblings) of the core that contains the given CPU.
+///
+/// It identifies the core with an id that is also a CPU id, which is what `perf stat --per-core` does.
+pub fn core_leader(root: &Path, cpu: u32) -> anyhow::Result<u32> {
+    let content = read_topology(root, cpu, "thread_siblings_list")?;
+    let siblings = parse_cpu_list(&content).with_context(|| format!("invalid siblings for cpu {cpu}: {content}"))?;
+    Ok(siblings.into_iter().min().unwrap_or(cpu))
+}
+
+fn read_topology(root: &Path, cpu: u32, file: &str) -> anyhow::Result<String> {
+    let path = root.join(format!("cpu{cpu}/topology/{file}"));
+    std::fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))
+}
+
 // TODO: it should be possible to handle the enabling/disabling of CPUs, instead
 // of assuming that the online CPUs never change.
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{core_leader, package_id, parse_cpu_list};
+
+    fn write_topology(root: &Path, cpu: u32, package: u32, siblings: &str) {
+        let dir = root.join(format!("cpu{cpu}/topology"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("physical_package_id"), format!("{package}\n")).unwrap();
+        std::fs::write(dir.join("thread_siblings_list"), format!("{siblings}\n")).unwrap();
+    }
+
+    #[test]
+    fn cpu_list() {
+        assert_eq!(parse_cpu_list("0-3\n").unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(parse_cpu_list("0,18").unwrap(), vec![0, 18]);
+        assert_eq!(parse_cpu_list("0-1,64-65").unwrap(), vec![0, 1, 64, 65]);
+        parse_cpu_list("0-1-2").unwrap_err();
+    }
+
+    #[test]
+    fn topology() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        // 2 packages with 2 cores each, 2 threads per core
+        write_topology(root, 0, 0, "0,4");
+        write_topology(root, 1, 0, "1,5");
+        write_topology(root, 2, 1, "2,6");
+        write_topology(root, 4, 0, "0,4");
+        write_topology(root, 6, 1, "2-6:4"); // unsupported syntax
+
+        assert_eq!(package_id(root, 0).unwrap(), 0);
+        assert_eq!(package_id(root, 2).unwrap(), 1);
+        assert_eq!(core_leader(root, 0).unwrap(), 0);
+        assert_eq!(core_leader(root, 4).unwrap(), 0);
+        assert_eq!(core_leader(root, 1).unwrap(), 1);
+        assert_eq!(core_leader(root, 2).unwrap(), 2);
+        core_leader(root, 6).unwrap_err();
+        package_id(root, 3).unwrap_err();
+    }
+}
@@ -17,13 +17,15 @@ use anyhow::Context;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
-use crate::source::{Observable, PerfEventSourceBuilder};
+use crate::source::{Observable, PerfEventSourceBuilder, SystemWideSourceBuilder};
+use crate::spec::EventScope;
 
 #[cfg(not(target_os = "linux"))]
 compile_error!("This plugin only works on Linux.");
 
 mod cpu;
 mod events;
+mod pmu;
 mod source;
 mod spec;
 
@@ -50,6 +52,8 @@ impl AlumetPlugin for PerfPlugin {
             // Store the source settings.
             poll_interval: config.poll_interval,
             flush_interval: config.flush_interval,
+            trigger_group: config.trigger_group,
+            system_wide: config.system_wide,
             // Parse the perf events with the unified syntax.
             events: config
                 .events
@@ -60,6 +64,22 @@ impl AlumetPlugin for PerfPlugin {
             // The metrics are initialized in start()
             metrics: Vec::new(),
         };
+
+        // The uncore and percore events can only be measured system-wide, reject them early.
+        if !config.system_wide {
+            for event in &config.events {
+                let system_wide_only = event.events.iter().any(|e| match e.scope() {
+                    EventScope::Core { percore } => *percore,
+                    EventScope::Uncore { .. } => true,
+                });
+                if system_wide_only {
+                    anyhow::bail!(
+                        "invalid event in config: {} can only be measured system-wide, set system_wide = true",
+                        event.metric_suffix
+                    );
+                }
+            }
+        }
         Ok(Box::new(PerfPlugin {
             config: Arc::new(Mutex::new(config)),
         }))
@@ -75,6 +95,22 @@ impl AlumetPlugin for PerfPlugin {
             metrics.push(metric);
         }
         config.metrics = metrics;
+
+        if config.system_wide {
+            let mut builder = SystemWideSourceBuilder::new()?;
+            for (event, metric) in config.events.iter().zip(&config.metrics) {
+                for encoding in &event.events {
+                    builder
+                        .add(encoding, *metric)
+                        .with_context(|| format!("could not configure event {}", event.metric_suffix))?;
+                }
+            }
+            let source = builder
+                .build()
+                .context("could not enable the system-wide perf events")?;
+            let trigger = config.trigger()?;
+            alumet.add_source("system", Box::new(source), trigger)?;
+        }
         Ok(())
     }
 
@@ -108,18 +144,16 @@ impl AlumetPlugin for PerfPlugin {
                     let config = config_cloned.lock().unwrap();
                     let mut builder = PerfEventSourceBuilder::observe(o)?;
                     for (event, metric) in config.events.iter().zip(&config.metrics) {
-                        builder
-                            .add(&event.event, *metric)
-                            .with_context(|| format!("could not configure event {}", event.metric_suffix))?;
+                        for encoding in &event.events {
+                            builder
+                                .add(encoding, *metric)
+                                .with_context(|| format!("could not configure event {}", event.metric_suffix))?;
+                        }
                     }
-                    let poll_interval = config.poll_interval;
-                    let flush_interval = config.flush_interval;
+                    let trigger = config.trigger()?;
                     drop(config);
 
                     let source = builder.build()?;
-                    let trigger = TriggerSpec::builder(poll_interval)
-                        .flush_interval(flush_interval)
-                        .build()?;
 
                     let request = request::create_one().add_source(&source_name, Box::new(source), trigger);
                     runtime.block_on(pipeline_control.dispatch(request, Duration::from_secs(1)))?;
@@ -144,6 +178,15 @@ struct Config {
     #[serde(with = "humantime_serde")]
     flush_interval: Duration,
 
+    /// Name of a trigger group, to poll the perf sources at the same time as the other sources of the group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trigger_group: Option<String>,
+
+    /// Measure the events on the whole system, per CPU (or per package for the uncore events),
+    /// in addition to the processes and cgroups that are observed on demand.
+    #[serde(default)]
+    system_wide: bool,
+
     /// The events to measure, described with the unified syntax (see [`spec`]).
     ///
     /// Each entry is either a bare string (`"REF_CPU_CYCLES"`, `"INSTRUCTIONS:u"`) or an inline
@@ -156,6 +199,8 @@ impl Default for Config {
         Self {
             poll_interval: Duration::from_secs(1), // 1Hz
             flush_interval: Duration::from_secs(5),
+            trigger_group: None,
+            system_wide: false,
 
             events: vec![
                 spec::EventEntry::Simple("REF_CPU_CYCLES".to_owned()),
@@ -171,7 +216,21 @@ impl Default for Config {
 struct ParsedConfig {
     poll_interval: Duration,
     flush_interval: Duration,
+    trigger_group: Option<String>,
+    system_wide: bool,
 
     events: Vec<spec::ParsedEvent>,
     metrics: Vec<TypedMetricId<u64>>,
 }
+
+impl ParsedConfig {
+    /// Returns the trigger of the perf sources.
+    fn trigger(&self) -> anyhow::Result<TriggerSpec> {
+        let mut builder = TriggerSpec::builder(self.poll_interval);
+        builder.flush_interval(self.flush_interval);
+        if let Some(group) = &self.trigger_group {
+            builder.group(group);
+        }
+        Ok(builder.build()?)
+    }
+}
@@ -0,0 +1,457 @@
+//! PMUs registered in sysfs and encoding of their events.
+//!
+//! Each PMU known to the kernel has a directory in `/sys/bus/event_source/devices/<pmu>` with:
+//! - `type`: the value of `perf_event_attr.type` for its events
+//! - `format/<param>`: the bits of `config`, `config1` or `config2` in which a named parameter is
+//!   encoded, e.g. `config:0-7` or `config1:0-15,32-35`
+//! - `events/<alias>` (optional): predefined events, written as a list of terms (`event=0x04,umask=0x03`)
+//! - `cpumask` (optional): the CPUs on which the events must be opened. Only "uncore" PMUs have one,
+//!   because they count the activity of a component that is shared by several cores (memory
+//!   controller, last-level cache…).
+//!
+//! An uncore PMU can have several instances, e.g. one `uncore_imc_<N>` per memory channel. Like
+//! `perf`, we accept the name without its numeric suffix and expand it to every instance.
+
+use std::{
+    collections::HashMap,
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+
+/// Directory in which the kernel registers the PMUs.
+pub const SYSFS_ROOT: &str = "/sys/bus/event_source/devices";
+
+/// A PMU registered in sysfs.
+#[derive(Debug)]
+pub struct Pmu {
+    pub name: String,
+    /// Value of `perf_event_attr.type` for the events of this PMU.
+    pub type_: u32,
+    /// CPUs on which the events must be opened, for uncore PMUs.
+    pub cpumask: Option<Vec<u32>>,
+    formats: HashMap<String, Format>,
+    dir: PathBuf,
+}
+
+/// Values of the config fields of `perf_event_attr`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Config {
+    pub config: u64,
+    pub config1: u64,
+    pub config2: u64,
+}
+
+/// Terms that qualify an event but are not encoded in its config fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Qualifiers {
+    /// Sum the counts of the hardware threads of each core.
+    pub percore: bool,
+}
+
+/// Layout of a named parameter in the config fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Format {
+    field: ConfigField,
+    /// The bits of the field that hold the value, from the least significant one.
+    bits: Vec<RangeInclusive<u32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigField {
+    Config,
+    Config1,
+    Config2,
+}
+
+impl Pmu {
+    /// Finds the PMU with the given name, or all its instances if it is an uncore PMU
+    /// whose name has been given without the instance number.
+    pub fn find(root: &Path, name: &str) -> anyhow::Result<Vec<Pmu>> {
+        if name.is_empty() || name.contains(['/', '.']) {
+            return Err(anyhow!("invalid PMU name '{name}'"));
+        }
+        let dir = root.join(name);
+        if dir.is_dir() {
+            return Ok(vec![Pmu::load(&dir)?]);
+        }
+
+        let prefix = format!("{name}_");
+        let mut instances: Vec<(u32, PathBuf)> = Vec::new();
+        let entries = fs::read_dir(root).with_context(|| format!("could not list the PMUs in {}", root.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(instance) = file_name.to_str().and_then(|n| n.strip_prefix(&prefix)) else {
+                continue;
+            };
+            if let Ok(n) = instance.parse() {
+                instances.push((n, entry.path()));
+            }
+        }
+        if instances.is_empty() {
+            return Err(anyhow!("unknown PMU '{name}': not found in {}", root.display()));
+        }
+        instances.sort_by_key(|(n, _)| *n);
+        instances.into_iter().map(|(_, dir)| Pmu::load(&dir)).collect()
+    }
+
+    /// Loads the description of a PMU from its sysfs directory.
+    pub fn load(dir: &Path) -> anyhow::Result<Pmu> {
+        let name = dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .with_context(|| format!("invalid PMU directory {}", dir.display()))?
+            .to_owned();
+
+        let type_path = dir.join("type");
+        let type_ = read_trimmed(&type_path)?
+            .parse()
+            .with_context(|| format!("invalid PMU type in {}", type_path.display()))?;
+
+        let cpumask_path = dir.join("cpumask");
+        let cpumask = if cpumask_path.exists() {
+            let list = read_trimmed(&cpumask_path)?;
+            Some(parse_cpu_list(&list).with_context(|| format!("invalid cpumask in {}", cpumask_path.display()))?)
+        } else {
+            None
+        };
+
+        let mut formats = HashMap::new();
+        let format_dir = dir.join("format");
+        if format_dir.is_dir() {
+            for entry in fs::read_dir(&format_dir)? {
+                let path = entry?.path();
+                let Some(param) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                let format = read_trimmed(&path)?
+                    .parse()
+                    .with_context(|| format!("invalid format in {}", path.display()))?;
+                formats.insert(param.to_owned(), format);
+            }
+        }
+
+        Ok(Pmu {
+            name,
+            type_,
+            cpumask,
+            formats,
+            dir: dir.to_owned(),
+        })
+    }
+
+    /// Encodes a list of comma-separated terms, such as `event=0x2e,umask=0x41` or `cas_count_read`.
+    ///
+    /// A term is either:
+    /// - `config=N`, `config1=N` or `config2=N`, which sets the whole field
+    /// - `param=N` or `param` (meaning `param=1`), where `param` is a format of the PMU
+    /// - `alias`, a predefined event of the PMU
+    /// - the `percore` qualifier
+    pub fn encode(&self, terms: &str) -> anyhow::Result<(Config, Qualifiers)> {
+        let mut config = Config::default();
+        let mut qualifiers = Qualifiers::default();
+        self.encode_terms(terms, &mut config, &mut qualifiers, true)?;
+        Ok((config, qualifiers))
+    }
+
+    fn encode_terms(
+        &self,
+        terms: &str,
+        config: &mut Config,
+        qualifiers: &mut Qualifiers,
+        allow_alias: bool,
+    ) -> anyhow::Result<()> {
+        for term in terms.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (key, value) = match term.split_once('=') {
+                Some((key, value)) => {
+                    let value =
+                        parse_number(value.trim()).with_context(|| format!("invalid value in term '{term}'"))?;
+                    (key.trim(), Some(value))
+                }
+                None => (term, None),
+            };
+            match (key, value) {
+                ("config", Some(v)) => config.config = v,
+                ("config1", Some(v)) => config.config1 = v,
+                ("config2", Some(v)) => config.config2 = v,
+                ("percore", None | Some(1)) => qualifiers.percore = true,
+                ("percore", Some(0)) => qualifiers.percore = false,
+                (key, value) => {
+                    if let Some(format) = self.formats.get(key) {
+                        format
+                            .encode(value.unwrap_or(1), config)
+                            .with_context(|| format!("invalid term '{term}' for PMU {}", self.name))?;
+                    } else if value.is_none()
+                        && allow_alias
+                        && let Some(alias) = self.alias(key)?
+                    {
+                        self.encode_terms(&alias, config, qualifiers, false)
+                            .with_context(|| format!("invalid event '{key}' of PMU {}", self.name))?;
+                    } else {
+                        let mut known: Vec<&str> = self.formats.keys().map(String::as_str).collect();
+                        known.sort_unstable();
+                        return Err(anyhow!(
+                            "unknown term '{key}' for PMU {}, the available formats are: {}",
+                            self.name,
+                            known.join(", ")
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the terms of a predefined event, if it exists.
+    fn alias(&self, name: &str) -> anyhow::Result<Option<String>> {
+        if name.contains(['/', '.']) {
+            return Ok(None);
+        }
+        let path = self.dir.join("events").join(name);
+        if path.is_file() {
+            Ok(Some(read_trimmed(&path)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Format {
+    /// Writes `value` in the bits of the format, leaving the other bits unchanged.
+    fn encode(&self, value: u64, config: &mut Config) -> anyhow::Result<()> {
+        let width: u32 = self.bits.iter().map(|r| r.end() - r.start() + 1).sum();
+        if width < 64 && value >> width != 0 {
+            return Err(anyhow!("value {value:#x} does not fit in {width} bits"));
+        }
+        let field = match self.field {
+            ConfigField::Config => &mut config.config,
+            ConfigField::Config1 => &mut config.config1,
+            ConfigField::Config2 => &mut config.config2,
+        };
+        let mut remaining = value;
+        for range in &self.bits {
+            for bit in range.clone() {
+                let mask = 1u64 << bit;
+                if remaining & 1 == 1 {
+                    *field |= mask;
+                } else {
+                    *field &= !mask;
+                }
+                remaining >>= 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    /// Parses a format such as `config:0-7`, `config1:8` or `config:0-7,32-35`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, ranges) = s.split_once(':').context("expected <field>:<bits>")?;
+        let field = match field {
+            "config" => ConfigField::Config,
+            "config1" => ConfigField::Config1,
+            "config2" => ConfigField::Config2,
+            other => return Err(anyhow!("unsupported field '{other}'")),
+        };
+        let bits = ranges
+            .split(',')
+            .map(|range| {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                let (start, end): (u32, u32) = (start.parse()?, end.parse()?);
+                if start > end || end > 63 {
+                    return Err(anyhow!("invalid bit range '{range}'"));
+                }
+                Ok(start..=end)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Format { field, bits })
+    }
+}
+
+/// Parses a number like `perf` does: hexadecimal with `0x`, octal with a leading `0`, decimal otherwise.
+pub fn parse_number(s: &str) -> anyhow::Result<u64> {
+    let res = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else if s.len() > 1 && s.starts_with('0') {
+        u64::from_str_radix(&s[1..], 8)
+    } else {
+        s.parse()
+    };
+    res.with_context(|| format!("invalid number '{s}'"))
+}
+
+/// Parses a list of CPUs such as `0`, `0,18` or `0-3,8-11`.
+fn parse_cpu_list(list: &str) -> anyhow::Result<Vec<u32>> {
+    let mut cpus = Vec::new();
+    for item in list.split(',').filter(|s| !s.is_empty()) {
+        match item.split_once('-') {
+            Some((start, end)) => cpus.extend(start.parse::<u32>()?..=end.parse()?),
+            None => cpus.push(item.parse()?),
+        }
+    }
+    Ok(cpus)
+}
+
+fn read_trimmed(path: &Path) -> anyhow::Result<String> {
+    let content = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    Ok(content.trim().to_owned())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs, path::Path};
+
+    use super::{Config, Pmu, Qualifiers, parse_number};
+
+    /// Creates a fake sysfs tree with a core PMU `cpu` and an uncore PMU `uncore_imc` with two instances.
+    pub(crate) fn fake_sysfs(root: &Path) {
+        fn pmu(
+            root: &Path,
+            name: &str,
+            type_: u32,
+            cpumask: Option<&str>,
+            formats: &[(&str, &str)],
+            events: &[(&str, &str)],
+        ) {
+            let dir = root.join(name);
+            fs::create_dir_all(dir.join("format")).unwrap();
+            fs::create_dir_all(dir.join("events")).unwrap();
+            fs::write(dir.join("type"), format!("{type_}\n")).unwrap();
+            if let Some(mask) = cpumask {
+                fs::write(dir.join("cpumask"), format!("{mask}\n")).unwrap();
+            }
+            for (param, format) in formats {
+                fs::write(dir.join("format").join(param), format!("{format}\n")).unwrap();
+            }
+            for (event, terms) in events {
+                fs::write(dir.join("events").join(event), format!("{terms}\n")).unwrap();
+            }
+        }
+
+        let cpu_formats = [
+            ("event", "config:0-7"),
+            ("umask", "config:8-15"),
+            ("edge", "config:18"),
+            ("inv", "config:23"),
+            ("cmask", "config:24-31"),
+            ("ldlat", "config1:0-15"),
+        ];
+        pmu(
+            root,
+            "cpu",
+            4,
+            None,
+            &cpu_formats,
+            &[("mem-loads", "event=0xcd,umask=0x1,ldlat=3")],
+        );
+        let imc_formats = [("event", "config:0-7"), ("umask", "config:8-15")];
+        let imc_events = [("cas_count_read", "event=0x04,umask=0x03")];
+        for (i, type_) in [(0, 20), (1, 21)] {
+            pmu(
+                root,
+                &format!("uncore_imc_{i}"),
+                type_,
+                Some("0,18"),
+                &imc_formats,
+                &imc_events,
+            );
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number("42").unwrap(), 42);
+        assert_eq!(parse_number("0x2e").unwrap(), 0x2e);
+        assert_eq!(parse_number("0X2E").unwrap(), 0x2e);
+        assert_eq!(parse_number("010").unwrap(), 8);
+        assert_eq!(parse_number("0").unwrap(), 0);
+        assert!(parse_number("0xzz").is_err());
+        assert!(parse_number("").is_err());
+    }
+
+    #[test]
+    fn core_pmu() {
+        let root = tempfile::tempdir().unwrap();
+        fake_sysfs(root.path());
+        let pmus = Pmu::find(root.path(), "cpu").unwrap();
+        assert_eq!(pmus.len(), 1);
+        let cpu = &pmus[0];
+        assert_eq!(cpu.type_, 4);
+        assert_eq!(cpu.cpumask, None);
+
+        let (config, qualifiers) = cpu.encode("event=0x2e,umask=0x41").unwrap();
+        assert_eq!(config.config, 0x412e);
+        assert_eq!(qualifiers, Qualifiers::default());
+
+        // flags, several fields and qualifiers
+        let (config, qualifiers) = cpu.encode("event=0x3c,edge,cmask=2,ldlat=0x10,percore").unwrap();
+        assert_eq!(config.config, 0x3c | 1 << 18 | 2 << 24);
+        assert_eq!(config.config1, 0x10);
+        assert!(qualifiers.percore);
+
+        // raw config fields
+        let (config, _) = cpu.encode("config=0x1234,config1=5").unwrap();
+        assert_eq!(
+            config,
+            Config {
+                config: 0x1234,
+                config1: 5,
+                config2: 0
+            }
+        );
+
+        // predefined event
+        let (config, _) = cpu.encode("mem-loads").unwrap();
+        assert_eq!(config.config, 0x01cd);
+        assert_eq!(config.config1, 3);
+
+        // errors
+        let err = cpu.encode("event=0x100").unwrap_err();
+        assert!(format!("{err:#}").contains("does not fit in 8 bits"), "got: {err:#}");
+        let err = cpu.encode("unknown=1").unwrap_err();
+        assert!(format!("{err:#}").contains("unknown term 'unknown'"), "got: {err:#}");
+        assert!(cpu.encode("event=?").is_err());
+    }
+
+    #[test]
+    fn uncore_instances() {
+        let root = tempfile::tempdir().unwrap();
+        fake_sysfs(root.path());
+        let pmus = Pmu::find(root.path(), "uncore_imc").unwrap();
+        let names: Vec<_> = pmus.iter().map(|p| (p.name.as_str(), p.type_)).collect();
+        assert_eq!(names, vec![("uncore_imc_0", 20), ("uncore_imc_1", 21)]);
+        for pmu in &pmus {
+            assert_eq!(pmu.cpumask, Some(vec![0, 18]));
+            let (config, _) = pmu.encode("cas_count_read").unwrap();
+            assert_eq!(config.config, 0x0304);
+        }
+
+        let pmus = Pmu::find(root.path(), "uncore_imc_1").unwrap();
+        assert_eq!(pmus.len(), 1);
+        assert!(Pmu::find(root.path(), "uncore_cha").is_err());
+        assert!(Pmu::find(root.path(), "../cpu").is_err());
+    }
+
+    #[test]
+    fn formats() {
+        use super::{ConfigField, Format};
+
+        let format: Format = "config1:0-7,32-35".parse().unwrap();
+        assert_eq!(format.field, ConfigField::Config1);
+        assert_eq!(format.bits, vec![0..=7, 32..=35]);
+        let mut config = Config::default();
+        format.encode(0xabc, &mut config).unwrap();
+        assert_eq!(config.config1, 0xbc | 0xa << 32);
+        assert!(format.encode(0x1000, &mut config).is_err());
+
+        assert!("config3:0-7".parse::<Format>().is_err());
+        assert!("config:7-0".parse::<Format>().is_err());
+        assert!("config:0-64".parse::<Format>().is_err());
+    }
+}
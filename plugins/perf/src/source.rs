@@ -1,5 +1,5 @@
 //! Source of measurements based on Linux perf_events.
-use std::{fs::File, io};
+use std::{fs::File, io, path::Path, time::Duration};
 
 use alumet::{
     measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
@@ -11,7 +11,7 @@ use anyhow::Context;
 use itertools::Itertools;
 
 use crate::cpu;
-use crate::spec::ConfiguredEvent;
+use crate::spec::{ConfiguredEvent, EventScope};
 
 #[derive(Debug)]
 pub enum Observable {
@@ -48,16 +48,13 @@ impl Source for PerfEventSource {
             let resource = &group.observed_resource;
             let consumer = &group.observed_consumer;
 
-            // TODO: check time_enabled and time_running to detect issues
-            log::trace!(
-                "Got perf_events measurements: time_enabled={:?}, time_running={:?}",
-                counts.time_enabled(),
-                counts.time_running()
-            );
+            // the counters of a group are scheduled together, they share the same times
+            let (time_enabled, time_running) = (counts.time_enabled(), counts.time_running());
+            log::trace!("Got perf_events measurements: time_enabled={time_enabled:?}, time_running={time_running:?}");
 
             // for each counter, push its value
             for (perf_counter, alumet_metric) in &group.counters {
-                let value = counts[perf_counter];
+                let value = scale(counts[perf_counter], time_enabled, time_running);
                 measurements.push(MeasurementPoint::new(
                     timestamp,
                     *alumet_metric,
@@ -71,6 +68,156 @@ impl Source for PerfEventSource {
     }
 }
 
+/// Source that measures the whole system, without attaching the events to a process or a cgroup.
+///
+/// The core events are opened on each CPU, and the uncore events once per package.
+pub struct SystemWideSource {
+    points: Vec<SystemWidePoint>,
+}
+
+/// A system-wide measurement point, obtained by summing one or several counters.
+///
+/// Several counters are needed for the `percore` events (one per hardware thread)
+/// and for the uncore PMUs that have several instances per package.
+struct SystemWidePoint {
+    metric: TypedMetricId<u64>,
+    resource: Resource,
+    counters: Vec<perf_event::Counter>,
+}
+
+impl Source for SystemWideSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        for point in &mut self.points {
+            let mut value = 0u64;
+            for counter in &mut point.counters {
+                let data = counter.read_full()?;
+                value = value.wrapping_add(scale(data.count(), data.time_enabled(), data.time_running()));
+            }
+            measurements.push(MeasurementPoint::new(
+                timestamp,
+                point.metric,
+                point.resource.clone(),
+                ResourceConsumer::LocalMachine,
+                value,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Scales a count to compensate for the multiplexing of the counters.
+///
+/// When there are more events than hardware counters, the kernel shares the counters between the
+/// events, and each event is only counted for a fraction of the time. Like `perf stat`, we estimate
+/// the full count with `count * time_enabled / time_running`.
+fn scale(count: u64, time_enabled: Option<Duration>, time_running: Option<Duration>) -> u64 {
+    match (time_enabled, time_running) {
+        (Some(enabled), Some(running)) if !running.is_zero() && running < enabled => {
+            let scaled = u128::from(count) * enabled.as_nanos() / running.as_nanos();
+            u64::try_from(scaled).unwrap_or(u64::MAX)
+        }
+        // Not multiplexed, or never scheduled (then the count is zero anyway).
+        _ => count,
+    }
+}
+
+/// Builder for the system-wide perf [`Source`].
+pub struct SystemWideSourceBuilder {
+    points: Vec<SystemWidePoint>,
+    /// The available CPUs to monitor.
+    online_cpus: Vec<u32>,
+}
+
+impl SystemWideSourceBuilder {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            points: Vec::new(),
+            online_cpus: cpu::online_cpus().context("could not detect online CPUs")?,
+        })
+    }
+
+    pub fn add(&mut self, event: &ConfiguredEvent, alumet_metric: TypedMetricId<u64>) -> anyhow::Result<&mut Self> {
+        let sysfs_cpu = Path::new(cpu::SYSFS_CPU);
+
+        // On which CPUs should the event be opened, and to which resource does each counter contribute?
+        let placements: Vec<(u32, Resource)> = match event.scope() {
+            EventScope::Core { percore: false } => self
+                .online_cpus
+                .iter()
+                .map(|&cpu| (cpu, Resource::CpuCore { id: cpu }))
+                .collect(),
+            EventScope::Core { percore: true } => self
+                .online_cpus
+                .iter()
+                .map(|&cpu| {
+                    Ok((
+                        cpu,
+                        Resource::CpuCore {
+                            id: cpu::core_leader(sysfs_cpu, cpu)?,
+                        },
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            EventScope::Uncore { cpumask, .. } => cpumask
+                .iter()
+                .map(|&cpu| {
+                    Ok((
+                        cpu,
+                        Resource::CpuPackage {
+                            id: cpu::package_id(sysfs_cpu, cpu)?,
+                        },
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+
+        for (cpu_id, resource) in placements {
+            let mut builder = perf_event::Builder::new(event.clone());
+            builder
+                .any_pid()
+                .one_cpu(cpu_id as usize)
+                .read_format(perf_event::ReadFormat::TOTAL_TIME_ENABLED | perf_event::ReadFormat::TOTAL_TIME_RUNNING);
+            event.configure(&mut builder);
+            let counter = builder
+                .build()
+                .with_context(|| format!("build with any_pid().one_cpu({cpu_id})"))?;
+
+            match self
+                .points
+                .iter_mut()
+                .find(|p| p.metric == alumet_metric && p.resource == resource)
+            {
+                Some(point) => point.counters.push(counter),
+                None => self.points.push(SystemWidePoint {
+                    metric: alumet_metric,
+                    resource,
+                    counters: vec![counter],
+                }),
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn build(mut self) -> io::Result<SystemWideSource> {
+        log::debug!(
+            "Built system-wide PerfEventSource with points [{}]",
+            self.points
+                .iter()
+                .map(|p| format!(
+                    "{{metric: {:?}, resource: {:?}, counters: {:?}}}",
+                    p.metric, p.resource, p.counters
+                ))
+                .join(", ")
+        );
+        for point in &mut self.points {
+            for counter in &mut point.counters {
+                counter.enable()?;
+            }
+        }
+        Ok(SystemWideSource { points: self.points })
+    }
+}
+
 /// Builder for the perf [`Source`].
 pub struct PerfEventSourceBuilder {
     /// Something to observe.
@@ -103,6 +250,18 @@ impl PerfEventSourceBuilder {
             builder
         }
 
+        match event.scope() {
+            EventScope::Core { percore: false } => (),
+            EventScope::Core { percore: true } => {
+                anyhow::bail!(
+                    "the percore qualifier only applies to system-wide monitoring, not to a process or a cgroup"
+                )
+            }
+            EventScope::Uncore { pmu, .. } => {
+                anyhow::bail!("the events of the uncore PMU {pmu} cannot be attached to a process or a cgroup")
+            }
+        }
+
         if self.groups.is_empty() {
             // create the group(s)
             match &self.observable {
@@ -167,7 +326,7 @@ impl PerfEventSourceBuilder {
                             observed_consumer: ResourceConsumer::ControlGroup {
                                 path: path.to_owned().into(),
                             },
-                            cpu_id: None,
+                            cpu_id: Some(cpu_id as u32),
                             counters: vec![(counter, alumet_metric)],
                         };
                         groups.push(group_with_info);
@@ -223,3 +382,26 @@ impl PerfEventSourceBuilder {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::scale;
+
+    #[test]
+    fn multiplexing() {
+        let ms = Duration::from_millis;
+        // not multiplexed
+        assert_eq!(scale(1000, Some(ms(100)), Some(ms(100))), 1000);
+        // counted half of the time
+        assert_eq!(scale(1000, Some(ms(100)), Some(ms(50))), 2000);
+        assert_eq!(scale(1000, Some(ms(90)), Some(ms(30))), 3000);
+        // never scheduled
+        assert_eq!(scale(0, Some(ms(100)), Some(Duration::ZERO)), 0);
+        // times not available
+        assert_eq!(scale(1000, None, None), 1000);
+        // no overflow
+        assert_eq!(scale(u64::MAX, Some(ms(100)), Some(ms(50))), u64::MAX);
+    }
+}
@@ -4,30 +4,32 @@
 //! parser owns the syntax: it splits off the modifiers (`:u`, `:k`, …) and applies them itself,
 //! so a modifier behaves identically whatever the event's source.
 //!
-//! `<event>` can take several forms. We support a subset for now and *recognise* the rest so the
-//! syntax stays stable across releases:
+//! `<event>` can take several forms:
 //!
-//! - a **symbolic event name**, e.g. `INSTRUCTIONS` or `LL_READ_MISS` **supported**, encoded from
-//!   the native kernel tables (hardware/software/cache). Encoding arbitrary names through libpfm is
-//!   planned.
+//! - a **symbolic event name**, e.g. `INSTRUCTIONS` or `LL_READ_MISS`, encoded from the native
+//!   kernel tables (hardware/software/cache). Encoding arbitrary names through libpfm is planned.
 //! - a **raw PMU event** `rN`, where `N` is a hexadecimal value representing the raw register
-//!   encoding, with the layout described by `/sys/bus/event_source/devices/cpu/format/*`
-//!   **not yet supported** (planned).
+//!   encoding, with the layout described by `/sys/bus/event_source/devices/cpu/format/*`.
 //! - a **symbolically formed PMU event** `pmu/config=M,config1=N,config2=K/`, where `M`, `N`, `K`
-//!   are numbers whose acceptable values are defined by
-//!   `/sys/bus/event_source/devices/<pmu>/format/*` **not yet supported** (planned).
-//! - the named-parameter variant `pmu/param1=0x3,param2/` and the uncore/`percore` qualifiers
-//!   **not yet supported** (planned).
+//!   are numbers whose acceptable values are defined by `/sys/bus/event_source/devices/<pmu>/format/*`.
+//! - the named-parameter variant `pmu/param1=0x3,param2/`, where the parameters are the formats
+//!   or the predefined events of the PMU, optionally with the `percore` qualifier.
 //!
-//! A not-yet-supported form is recognised by [`resolve_event`] and rejected with an explicit "planned for a
-//! future release" error.
+//! The PMU forms are encoded from sysfs, see [`pmu`]. The modifiers of a PMU event can also
+//! follow the closing `/` directly, as in `cpu/event=0x3c/u`.
 
-use anyhow::Context;
+use std::path::Path;
+
+use anyhow::{Context, anyhow};
 use perf_event::events::{Cache, Event, Hardware, Software};
 use perf_event_open_sys::bindings::perf_event_attr;
 use serde::{Deserialize, Serialize};
 
 use crate::events;
+use crate::pmu::{self, Pmu};
+
+/// `perf_event_attr.type` of the raw events, see `perf_event_open(2)`.
+const PERF_TYPE_RAW: u32 = 4;
 
 /// One entry of the `events` config list: a bare string, or a table with a metric `rename`.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -51,7 +53,7 @@ impl EventEntry {
 }
 
 /// perf event domain modifiers, e.g. `INSTRUCTIONS:u:k`.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Modifiers {
     user: bool,
     kernel: bool,
@@ -127,12 +129,17 @@ struct Excludes {
     idle: bool,
 }
 
-/// An encoded event, whatever its source. Future sources (`Raw`, `Pfm`) are added as new variants.
+/// An encoded event, whatever its source. Future sources (`Pfm`) are added as new variants.
 #[derive(Debug, Clone)]
 enum AnyEvent {
     Hardware(Hardware),
     Software(Software),
     Cache(Cache),
+    /// A raw event, or an event of a PMU described in sysfs.
+    Pmu {
+        type_: u32,
+        config: pmu::Config,
+    },
 }
 
 impl AnyEvent {
@@ -141,15 +148,37 @@ impl AnyEvent {
             AnyEvent::Hardware(e) => e.update_attrs(attr),
             AnyEvent::Software(e) => e.update_attrs(attr),
             AnyEvent::Cache(e) => e.update_attrs(attr),
+            AnyEvent::Pmu { type_, config } => {
+                attr.type_ = type_;
+                attr.config = config.config;
+                attr.__bindgen_anon_3.config1 = config.config1;
+                attr.__bindgen_anon_4.config2 = config.config2;
+            }
         }
     }
 }
 
+/// What an event counts, which determines how it can be opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventScope {
+    /// The event counts the activity of the CPU core it runs on.
+    ///
+    /// With `percore`, the counts of the hardware threads of a core are summed.
+    Core { percore: bool },
+    /// The event counts the activity of a component that is shared by several cores, such as
+    /// a memory controller. It must be opened system-wide, on the CPUs of the `cpumask`.
+    Uncore { pmu: String, cpumask: Vec<u32> },
+}
+
+/// The encodings of an event, one per PMU instance.
+type Encodings = Vec<(AnyEvent, EventScope)>;
+
 /// A fully-configured event ready to be added to a perf group: an encoding plus its modifiers.
 #[derive(Debug, Clone)]
 pub struct ConfiguredEvent {
     inner: AnyEvent,
     modifiers: Modifiers,
+    scope: EventScope,
 }
 
 impl Event for ConfiguredEvent {
@@ -165,7 +194,23 @@ impl ConfiguredEvent {
     /// Apply this event's modifiers to a freshly-created builder. Required for the modifiers to
     /// take effect.
     pub fn configure(&self, builder: &mut perf_event::Builder<'_>) {
-        self.modifiers.configure(builder);
+        match self.scope {
+            EventScope::Core { .. } => self.modifiers.configure(builder),
+            EventScope::Uncore { .. } => {
+                // Uncore PMUs reject the exclusion bits, because they don't know which domain is running.
+                builder
+                    .exclude_user(false)
+                    .exclude_kernel(false)
+                    .exclude_hv(false)
+                    .exclude_host(false)
+                    .exclude_guest(false)
+                    .exclude_idle(false);
+            }
+        }
+    }
+
+    pub fn scope(&self) -> &EventScope {
+        &self.scope
     }
 }
 
@@ -174,64 +219,125 @@ impl ConfiguredEvent {
 pub struct ParsedEvent {
     pub metric_suffix: String,
     pub description: String,
-    pub event: ConfiguredEvent,
+    /// The encodings of the event. There is exactly one, except for the uncore PMUs that have
+    /// several instances (e.g. one per memory channel): there is one encoding per instance.
+    pub events: Vec<ConfiguredEvent>,
 }
 
 /// Parse one config entry into a [`ParsedEvent`].
 pub fn parse(entry: &EventEntry) -> anyhow::Result<ParsedEvent> {
+    parse_with_sysfs(entry, Path::new(pmu::SYSFS_ROOT))
+}
+
+/// Like [`parse`], but reads the description of the PMUs in `sysfs_root`.
+fn parse_with_sysfs(entry: &EventEntry, sysfs_root: &Path) -> anyhow::Result<ParsedEvent> {
     let (input, rename) = entry.parts();
-    let (name, mods_str) = input.split_once(':').unwrap_or((input, ""));
+    let (name, mods_str) = split_modifiers(input);
     if name.is_empty() {
         anyhow::bail!("empty event name in '{input}'");
     }
 
     let ctx = || format!("invalid event '{input}'");
     let modifiers = Modifiers::parse(mods_str).with_context(ctx)?;
-    let (event, canonical_name, description) = resolve_event(name).with_context(ctx)?;
+    let (encodings, canonical_name, description) = resolve_event(name, sysfs_root).with_context(ctx)?;
+    let uncore = encodings
+        .iter()
+        .any(|(_, scope)| matches!(scope, EventScope::Uncore { .. }));
+    if uncore && modifiers != Modifiers::default() {
+        return Err(anyhow!("modifiers cannot be applied to uncore events")).with_context(ctx);
+    }
 
     let metric_suffix = match rename {
         Some(r) => sanitize(r),
         None => canonical_name,
     };
 
+    let events = encodings
+        .into_iter()
+        .map(|(inner, scope)| ConfiguredEvent {
+            inner,
+            modifiers,
+            scope,
+        })
+        .collect();
     Ok(ParsedEvent {
         metric_suffix,
         description,
-        event: ConfiguredEvent {
-            inner: event,
-            modifiers,
-        },
+        events,
     })
 }
 
-/// Resolve an event (no modifiers) to its encoding, returning the event, its canonical name (used
-/// for the metric name) and a description. See the module docs for the recognised forms.
-fn resolve_event(name: &str) -> anyhow::Result<(AnyEvent, String, String)> {
-    // Forms we recognise but do not encode yet: reject them explicitly so the syntax stays stable
-    // and the error is actionable.
+/// Splits an event into its name and its modifiers.
+///
+/// The modifiers of a PMU event can follow the closing `/`, with or without a colon.
+fn split_modifiers(input: &str) -> (&str, &str) {
+    match input.rfind('/') {
+        Some(end) => input.split_at(end + 1),
+        None => input.split_once(':').unwrap_or((input, "")),
+    }
+}
+
+/// Resolve an event (no modifiers) to its encodings, returning the encodings, its canonical name
+/// (used for the metric name) and a description. See the module docs for the recognised forms.
+fn resolve_event(name: &str, sysfs_root: &Path) -> anyhow::Result<(Encodings, String, String)> {
+    let core = EventScope::Core { percore: false };
 
     // Raw PMU event `rN` (hex register encoding).
-    let looks_raw = name
+    let raw_code = name
         .strip_prefix('r')
         .map(|d| d.trim_start_matches("0x").trim_start_matches("0X"))
-        .is_some_and(|d| !d.is_empty() && d.chars().all(|c| c.is_ascii_hexdigit()));
-    if looks_raw {
-        anyhow::bail!("raw PMU events (`{name}`) are not supported yet; this is planned for a future release");
+        .filter(|d| !d.is_empty() && d.chars().all(|c| c.is_ascii_hexdigit()));
+    if let Some(code) = raw_code {
+        let config = u64::from_str_radix(code, 16).with_context(|| format!("invalid raw event code '{code}'"))?;
+        let event = AnyEvent::Pmu {
+            type_: PERF_TYPE_RAW,
+            config: pmu::Config {
+                config,
+                ..Default::default()
+            },
+        };
+        let description = format!("Raw PMU event {config:#x}.");
+        return Ok((vec![(event, core)], sanitize(name), description));
     }
-    // Symbolically formed PMU event `pmu/.../`.
+
+    // Symbolically formed PMU event `pmu/terms/`.
     if name.contains('/') {
-        anyhow::bail!("PMU-term events (`{name}`) are not supported yet; this is planned for a future release");
+        let (pmu_name, terms) = name
+            .strip_suffix('/')
+            .and_then(|s| s.split_once('/'))
+            .filter(|(_, terms)| !terms.contains('/'))
+            .with_context(|| format!("invalid PMU event '{name}', expected pmu/terms/"))?;
+        let mut encodings = Vec::new();
+        for pmu in Pmu::find(sysfs_root, pmu_name)? {
+            let (config, qualifiers) = pmu.encode(terms)?;
+            let scope = match pmu.cpumask {
+                Some(_) if qualifiers.percore => {
+                    anyhow::bail!("the percore qualifier cannot be applied to the uncore PMU {}", pmu.name)
+                }
+                Some(cpumask) => EventScope::Uncore { pmu: pmu.name, cpumask },
+                None => EventScope::Core {
+                    percore: qualifiers.percore,
+                },
+            };
+            let event = AnyEvent::Pmu {
+                type_: pmu.type_,
+                config,
+            };
+            encodings.push((event, scope));
+        }
+        let description = format!("Event {terms} of the PMU {pmu_name}.");
+        return Ok((encodings, sanitize(name), description));
     }
 
     // Symbolic event name: encoded from the native kernel tables.
     if let Ok(e) = events::parse_hardware(name) {
-        return Ok((AnyEvent::Hardware(e.event), e.name, e.description));
+        return Ok((vec![(AnyEvent::Hardware(e.event), core)], e.name, e.description));
     }
     if let Ok(e) = events::parse_software(name) {
-        return Ok((AnyEvent::Software(e.event), e.name, e.description));
+        return Ok((vec![(AnyEvent::Software(e.event), core)], e.name, e.description));
     }
     if let Ok(e) = events::parse_cache(name) {
-        return Ok((AnyEvent::Cache(e.event), e.name, e.description));
+        return Ok((vec![(AnyEvent::Cache(e.event), core)], e.name, e.description));
     }
     anyhow::bail!(
         "Unknown event '{name}': not a native hardware/software/cache event. \
@@ -257,25 +363,36 @@ mod tests {
         parse(&EventEntry::Simple(s.to_owned())).unwrap()
     }
 
+    fn parse_pmu_event(s: &str) -> anyhow::Result<ParsedEvent> {
+        let root = tempfile::tempdir().unwrap();
+        pmu::tests::fake_sysfs(root.path());
+        parse_with_sysfs(&EventEntry::Simple(s.to_owned()), root.path())
+    }
+
+    fn single(e: &ParsedEvent) -> &ConfiguredEvent {
+        assert_eq!(e.events.len(), 1);
+        &e.events[0]
+    }
+
     #[test]
     fn native_hardware() {
         let e = parse_simple("REF_CPU_CYCLES");
         assert_eq!(e.metric_suffix, "REF_CPU_CYCLES");
-        assert!(matches!(e.event.inner, AnyEvent::Hardware(_)));
+        assert!(matches!(single(&e).inner, AnyEvent::Hardware(_)));
     }
 
     #[test]
     fn native_software() {
         let e = parse_simple("CONTEXT_SWITCHES");
         assert_eq!(e.metric_suffix, "CONTEXT_SWITCHES");
-        assert!(matches!(e.event.inner, AnyEvent::Software(_)));
+        assert!(matches!(single(&e).inner, AnyEvent::Software(_)));
     }
 
     #[test]
     fn native_cache() {
         let e = parse_simple("LL_READ_MISS");
         assert_eq!(e.metric_suffix, "LL_READ_MISS");
-        assert!(matches!(e.event.inner, AnyEvent::Cache(_)));
+        assert!(matches!(single(&e).inner, AnyEvent::Cache(_)));
     }
 
     #[test]
@@ -284,7 +401,7 @@ mod tests {
         let e = parse_simple("INSTRUCTIONS");
         assert_eq!(e.metric_suffix, "INSTRUCTIONS");
         assert_eq!(
-            e.event.modifiers.excludes(),
+            e.events[0].modifiers.excludes(),
             Excludes {
                 user: false,
                 kernel: true,
@@ -298,14 +415,14 @@ mod tests {
 
     #[test]
     fn user_modifier_matches_default() {
-        let x = parse_simple("INSTRUCTIONS:u").event.modifiers.excludes();
+        let x = parse_simple("INSTRUCTIONS:u").events[0].modifiers.excludes();
         assert!(!x.user && x.kernel && x.hv);
     }
 
     #[test]
     fn user_and_kernel_modifier() {
         // `:u:k` measures user and kernel, but still excludes the hypervisor.
-        let x = parse_simple("INSTRUCTIONS:u:k").event.modifiers.excludes();
+        let x = parse_simple("INSTRUCTIONS:u:k").events[0].modifiers.excludes();
         assert!(!x.user);
         assert!(!x.kernel);
         assert!(x.hv);
@@ -314,15 +431,15 @@ mod tests {
     #[test]
     fn grouped_and_chained_modifiers_are_equivalent() {
         // `:uk` (grouped) and `:u:k` (each after its own colon) must mean the same thing.
-        let grouped = parse_simple("INSTRUCTIONS:uk").event.modifiers.excludes();
-        let chained = parse_simple("INSTRUCTIONS:u:k").event.modifiers.excludes();
+        let grouped = parse_simple("INSTRUCTIONS:uk").events[0].modifiers.excludes();
+        let chained = parse_simple("INSTRUCTIONS:u:k").events[0].modifiers.excludes();
         assert_eq!(grouped, chained);
     }
 
     #[test]
     fn kernel_only_modifier() {
         // `:k` measures kernel only: user is excluded, kernel is counted.
-        let x = parse_simple("INSTRUCTIONS:k").event.modifiers.excludes();
+        let x = parse_simple("INSTRUCTIONS:k").events[0].modifiers.excludes();
         assert!(x.user);
         assert!(!x.kernel);
         assert!(x.hv);
@@ -330,7 +447,7 @@ mod tests {
 
     #[test]
     fn host_and_idle_modifiers() {
-        let x = parse_simple("INSTRUCTIONS:H:I").event.modifiers.excludes();
+        let x = parse_simple("INSTRUCTIONS:H:I").events[0].modifiers.excludes();
         assert!(x.guest); // host only -> exclude guest
         assert!(!x.host);
         assert!(x.idle); // exclude idle
@@ -352,16 +469,90 @@ mod tests {
     }
 
     #[test]
-    fn raw_code_rejected_for_now() {
-        // `rNNNN` is recognised by the syntax but not encoded yet.
-        let err = parse(&EventEntry::Simple("r0x412e".to_owned())).unwrap_err();
-        assert!(format!("{err:#}").contains("future release"), "got: {err:#}");
+    fn raw_code() {
+        for (input, suffix) in [("r412e", "r412e"), ("r0x412e", "r0x412e")] {
+            let e = parse_simple(input);
+            assert_eq!(e.metric_suffix, suffix);
+            let event = single(&e);
+            assert_eq!(event.scope, EventScope::Core { percore: false });
+            match event.inner {
+                AnyEvent::Pmu { type_, config } => {
+                    assert_eq!(type_, PERF_TYPE_RAW);
+                    assert_eq!(config.config, 0x412e);
+                }
+                ref other => panic!("unexpected encoding {other:?}"),
+            }
+        }
+        // a raw event accepts modifiers like any other event
+        let x = parse_simple("r412e:k").events[0].modifiers.excludes();
+        assert!(x.user && !x.kernel);
     }
 
     #[test]
-    fn pmu_term_rejected_for_now() {
-        let err = parse(&EventEntry::Simple("cpu/event=0x2e,umask=0x41/".to_owned())).unwrap_err();
-        assert!(format!("{err:#}").contains("future release"), "got: {err:#}");
+    fn pmu_terms() {
+        let e = parse_pmu_event("cpu/event=0x2e,umask=0x41/").unwrap();
+        assert_eq!(e.metric_suffix, "cpu_event_0x2e_umask_0x41");
+        let event = single(&e);
+        assert!(matches!(event.inner, AnyEvent::Pmu { type_: 4, config } if config.config == 0x412e));
+        assert_eq!(event.scope, EventScope::Core { percore: false });
+
+        // modifiers after the closing slash, with or without a colon
+        for input in ["cpu/event=0x3c/k", "cpu/event=0x3c/:k"] {
+            let x = single(&parse_pmu_event(input).unwrap()).modifiers.excludes();
+            assert!(x.user && !x.kernel, "wrong modifiers for {input}");
+        }
+
+        // raw config fields and percore qualifier
+        let e = parse_pmu_event("cpu/config=0x1234,config1=5,percore/").unwrap();
+        let event = single(&e);
+        assert!(matches!(event.inner, AnyEvent::Pmu { config, .. } if config.config == 0x1234 && config.config1 == 5));
+        assert_eq!(event.scope, EventScope::Core { percore: true });
+    }
+
+    #[test]
+    fn uncore_pmu() {
+        let root = tempfile::tempdir().unwrap();
+        pmu::tests::fake_sysfs(root.path());
+        let entry = EventEntry::Detailed {
+            event: "uncore_imc/cas_count_read/".to_owned(),
+            rename: Some("dram_reads".to_owned()),
+        };
+        let e = parse_with_sysfs(&entry, root.path()).unwrap();
+        assert_eq!(e.metric_suffix, "dram_reads");
+        let scopes: Vec<_> = e.events.iter().map(|e| e.scope.clone()).collect();
+        assert_eq!(
+            scopes,
+            vec![
+                EventScope::Uncore {
+                    pmu: "uncore_imc_0".to_owned(),
+                    cpumask: vec![0, 18]
+                },
+                EventScope::Uncore {
+                    pmu: "uncore_imc_1".to_owned(),
+                    cpumask: vec![0, 18]
+                },
+            ]
+        );
+
+        let err = parse_pmu_event("uncore_imc/cas_count_read/u").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("modifiers cannot be applied"),
+            "got: {err:#}"
+        );
+        let err = parse_pmu_event("uncore_imc/cas_count_read,percore/").unwrap_err();
+        assert!(format!("{err:#}").contains("percore"), "got: {err:#}");
+    }
+
+    #[test]
+    fn invalid_pmu_events() {
+        for input in [
+            "cpu/event=0x2e",
+            "cpu/event=0x2e/umask=1/",
+            "nope/event=1/",
+            "cpu/foo=1/",
+        ] {
+            assert!(parse_pmu_event(input).is_err(), "{input} should be rejected");
+        }
     }
 
     #[test]
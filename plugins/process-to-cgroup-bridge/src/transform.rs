@@ -203,5 +203,7 @@ fn extract_numeric_value(measurement: &MeasurementPoint) -> Option<f64> {
     match measurement.value {
         WrappedMeasurementValue::F64(v) => Some(v),
         WrappedMeasurementValue::U64(v) => Some(v as f64),
+        WrappedMeasurementValue::I64(v) => Some(v as f64),
+        WrappedMeasurementValue::Histogram(_) => None,
     }
 }
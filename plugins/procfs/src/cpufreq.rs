@@ -0,0 +1,113 @@
+//! CPU frequency read from `/sys/devices/system/cpu/cpu*/cpufreq`.
+
+use std::path::{Path, PathBuf};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::{TypedMetricId, error::MetricCreationError},
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+
+use crate::{cpus, sysfs};
+
+/// Reads the current frequency of each CPU.
+pub struct CpuFreqProbe {
+    /// Path to `scaling_cur_freq`, for each CPU that has one.
+    cpus: Vec<(u32, PathBuf)>,
+    metrics: CpuFreqMetrics,
+}
+
+pub struct CpuFreqMetrics {
+    frequency: TypedMetricId<u64>,
+}
+
+impl CpuFreqMetrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            frequency: alumet.create_metric(
+                "cpu_frequency",
+                PrefixedUnit::kilo(Unit::Hertz),
+                "current frequency of the CPU, as seen by the cpufreq driver",
+            )?,
+        })
+    }
+}
+
+impl CpuFreqProbe {
+    /// Creates a probe for the online CPUs of `cpu_dir` (usually `/sys/devices/system/cpu`).
+    ///
+    /// The CPUs without cpufreq information are ignored. If no CPU has it, an error is returned.
+    pub fn new(metrics: CpuFreqMetrics, cpu_dir: &Path) -> anyhow::Result<Self> {
+        let cpus = cpufreq_files(cpu_dir)?;
+        if cpus.is_empty() {
+            return Err(anyhow::anyhow!(
+                "no cpufreq information in {}, is the cpufreq driver loaded?",
+                cpu_dir.display()
+            ));
+        }
+        Ok(Self { cpus, metrics })
+    }
+}
+
+fn cpufreq_files(cpu_dir: &Path) -> anyhow::Result<Vec<(u32, PathBuf)>> {
+    let online = cpus::online_cpus_path(&cpu_dir.join("online"))?;
+    let files = online
+        .into_iter()
+        .map(|cpu| (cpu, cpu_dir.join(format!("cpu{cpu}/cpufreq/scaling_cur_freq"))))
+        .filter(|(_, path)| path.exists())
+        .collect();
+    Ok(files)
+}
+
+impl Source for CpuFreqProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        for (cpu, path) in &self.cpus {
+            // The file disappears when the CPU is put offline, skip it.
+            let freq_khz = match sysfs::read_u64(path) {
+                Ok(f) => f,
+                Err(e) => {
+                    log::debug!("skipping cpu {cpu}: {e:#}");
+                    continue;
+                }
+            };
+            acc.push(MeasurementPoint::new(
+                timestamp,
+                self.metrics.frequency,
+                Resource::CpuCore { id: *cpu },
+                ResourceConsumer::LocalMachine,
+                freq_khz,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::cpufreq_files;
+
+    #[test]
+    fn discover_cpufreq() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let cpu_dir = root.path();
+        fs::write(cpu_dir.join("online"), "0-2\n")?;
+        for cpu in [0, 2] {
+            let dir = cpu_dir.join(format!("cpu{cpu}/cpufreq"));
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("scaling_cur_freq"), "2400000\n")?;
+        }
+        // cpu1 has no cpufreq directory
+        fs::create_dir_all(cpu_dir.join("cpu1"))?;
+
+        let files = cpufreq_files(cpu_dir)?;
+        let cpus: Vec<u32> = files.iter().map(|(cpu, _)| *cpu).collect();
+        assert_eq!(cpus, vec![0, 2]);
+        assert_eq!(crate::sysfs::read_u64(&files[0].1)?, 2400000);
+        Ok(())
+    }
+}
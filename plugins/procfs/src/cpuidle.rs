@@ -0,0 +1,183 @@
+//! CPU idle states (C-states) read from `/sys/devices/system/cpu/cpu*/cpuidle`.
+
+use std::path::{Path, PathBuf};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::{TypedMetricId, error::MetricCreationError},
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::Context;
+
+use crate::{cpus, sysfs};
+
+/// Reads the time spent in each idle state, and the number of times it has been entered.
+pub struct CpuIdleProbe {
+    states: Vec<IdleState>,
+    metrics: CpuIdleMetrics,
+}
+
+pub struct CpuIdleMetrics {
+    time: TypedMetricId<u64>,
+    usage: TypedMetricId<u64>,
+}
+
+/// An idle state of a CPU, for instance `C1E` on cpu 3.
+struct IdleState {
+    cpu: u32,
+    /// Name of the state, from the `name` file.
+    name: String,
+    /// Directory `cpu{cpu}/cpuidle/state{i}`.
+    dir: PathBuf,
+    /// The previously measured (time, usage), to compute the difference.
+    previous: Option<IdleStateCounters>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct IdleStateCounters {
+    /// Total time spent in the idle state, in microseconds.
+    time: u64,
+    /// Number of times the idle state was entered.
+    usage: u64,
+}
+
+impl CpuIdleMetrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            time: alumet.create_metric(
+                "cpu_idle_time",
+                PrefixedUnit::micro(Unit::Second),
+                "time spent in the idle state",
+            )?,
+            usage: alumet.create_metric(
+                "cpu_idle_usage",
+                Unit::Unity,
+                "number of times the idle state was entered",
+            )?,
+        })
+    }
+}
+
+impl CpuIdleProbe {
+    /// Creates a probe for the online CPUs of `cpu_dir` (usually `/sys/devices/system/cpu`).
+    ///
+    /// The CPUs without cpuidle information are ignored. If no CPU has it, an error is returned.
+    pub fn new(metrics: CpuIdleMetrics, cpu_dir: &Path) -> anyhow::Result<Self> {
+        let states = idle_states(cpu_dir)?;
+        if states.is_empty() {
+            return Err(anyhow::anyhow!(
+                "no cpuidle information in {}, is a cpuidle driver loaded?",
+                cpu_dir.display()
+            ));
+        }
+        Ok(Self { states, metrics })
+    }
+}
+
+fn idle_states(cpu_dir: &Path) -> anyhow::Result<Vec<IdleState>> {
+    let mut res = Vec::new();
+    for cpu in cpus::online_cpus_path(&cpu_dir.join("online"))? {
+        let cpuidle_dir = cpu_dir.join(format!("cpu{cpu}/cpuidle"));
+        // state0, state1, ... are numbered without gaps
+        for i in 0.. {
+            let dir = cpuidle_dir.join(format!("state{i}"));
+            if !dir.exists() {
+                break;
+            }
+            let name = sysfs::read_str(&dir.join("name")).context("failed to read the name of the idle state")?;
+            res.push(IdleState {
+                cpu,
+                name,
+                dir,
+                previous: None,
+            });
+        }
+    }
+    Ok(res)
+}
+
+impl IdleState {
+    fn read(&self) -> anyhow::Result<IdleStateCounters> {
+        Ok(IdleStateCounters {
+            time: sysfs::read_u64(&self.dir.join("time"))?,
+            usage: sysfs::read_u64(&self.dir.join("usage"))?,
+        })
+    }
+}
+
+impl Source for CpuIdleProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        for state in &mut self.states {
+            // The files disappear when the CPU is put offline, skip them.
+            let now = match state.read() {
+                Ok(counters) => counters,
+                Err(e) => {
+                    log::debug!("skipping idle state {} of cpu {}: {e:#}", state.name, state.cpu);
+                    state.previous = None;
+                    continue;
+                }
+            };
+            // Only push deltas, not the baseline value before the plugin starts
+            if let Some(prev) = state.previous {
+                let resource = Resource::CpuCore { id: state.cpu };
+                acc.push(
+                    MeasurementPoint::new(
+                        timestamp,
+                        self.metrics.time,
+                        resource.clone(),
+                        ResourceConsumer::LocalMachine,
+                        now.time.saturating_sub(prev.time),
+                    )
+                    .with_attr("idle_state", state.name.clone()),
+                );
+                acc.push(
+                    MeasurementPoint::new(
+                        timestamp,
+                        self.metrics.usage,
+                        resource,
+                        ResourceConsumer::LocalMachine,
+                        now.usage.saturating_sub(prev.usage),
+                    )
+                    .with_attr("idle_state", state.name.clone()),
+                );
+            }
+            state.previous = Some(now);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{IdleStateCounters, idle_states};
+
+    #[test]
+    fn discover_idle_states() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let cpu_dir = root.path();
+        fs::write(cpu_dir.join("online"), "0-1\n")?;
+        for cpu in 0..2 {
+            for (i, name) in ["POLL", "C1", "C1E"].into_iter().enumerate() {
+                let dir = cpu_dir.join(format!("cpu{cpu}/cpuidle/state{i}"));
+                fs::create_dir_all(&dir)?;
+                fs::write(dir.join("name"), format!("{name}\n"))?;
+                fs::write(dir.join("time"), format!("{}\n", 1000 * (cpu + 1)))?;
+                fs::write(dir.join("usage"), format!("{}\n", 10 * i))?;
+            }
+        }
+
+        let states = idle_states(cpu_dir)?;
+        let found: Vec<(u32, &str)> = states.iter().map(|s| (s.cpu, s.name.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![(0, "POLL"), (0, "C1"), (0, "C1E"), (1, "POLL"), (1, "C1"), (1, "C1E")]
+        );
+        assert_eq!(states[4].read()?, IdleStateCounters { time: 2000, usage: 10 });
+        Ok(())
+    }
+}
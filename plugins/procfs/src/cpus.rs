@@ -32,7 +32,7 @@ fn parse_cpu_list(cpulist: &str) -> anyhow::Result<Vec<u32>> {
     Ok(cpus)
 }
 
-fn online_cpus_path(path: &std::path::Path) -> anyhow::Result<Vec<u32>> {
+pub fn online_cpus_path(path: &std::path::Path) -> anyhow::Result<Vec<u32>> {
     let list = std::fs::read_to_string(path).with_context(|| format!("failed to parse {}", path.display()))?;
     parse_cpu_list(&list)
 }
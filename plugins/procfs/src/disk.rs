@@ -0,0 +1,313 @@
+//! Block device I/O statistics read from `/proc/diskstats` or `/sys/block/*/stat`.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Seek},
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::{TypedMetricId, error::MetricCreationError},
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::Context;
+use procfs::{DiskStat, DiskStats, FromBufRead};
+use regex::Regex;
+
+use crate::sysfs;
+
+/// Size of a sector in the block layer statistics, regardless of the actual sector size of the device.
+const SECTOR_SIZE: u64 = 512;
+
+/// Reads I/O statistics for each block device and partition.
+pub struct DiskStatsProbe {
+    reader: DiskStatsReader,
+    filter: DeviceFilter,
+    /// The previously measured stats, to compute the difference.
+    previous: HashMap<String, DiskStat>,
+    metrics: DiskMetrics,
+}
+
+pub struct DiskMetrics {
+    bytes: TypedMetricId<u64>,
+    operations: TypedMetricId<u64>,
+    time_in_queue: TypedMetricId<u64>,
+    in_flight: TypedMetricId<u64>,
+}
+
+/// Selects the devices to monitor, by name (for instance `sda`, `sda1` or `nvme0n1p2`).
+pub struct DeviceFilter {
+    /// If set, only the devices that match this regex are monitored.
+    pub include: Option<Regex>,
+    /// If set, the devices that match this regex are not monitored.
+    pub exclude: Option<Regex>,
+}
+
+/// Where to read the statistics from.
+enum DiskStatsReader {
+    /// `/proc/diskstats`, which contains one line per device or partition.
+    ProcFs { path: PathBuf, reader: BufReader<File> },
+    /// `/sys/block`, which contains one directory per device, with a `stat` file
+    /// and one subdirectory per partition. The `stat` files contain the same
+    /// fields as `/proc/diskstats`, without the device numbers and name.
+    SysFs { block_dir: PathBuf },
+}
+
+impl DiskMetrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            bytes: alumet.create_metric(
+                "disk_bytes",
+                Unit::Byte,
+                "Number of bytes (read/write) per block device",
+            )?,
+            operations: alumet.create_metric(
+                "disk_operations",
+                Unit::Unity,
+                "Number of completed I/O operations (read/write) per block device",
+            )?,
+            time_in_queue: alumet.create_metric(
+                "disk_time_in_queue",
+                PrefixedUnit::milli(Unit::Second),
+                "Time spent by the I/O requests in the queue of the block device, summed over all requests",
+            )?,
+            in_flight: alumet.create_metric(
+                "disk_requests_in_flight",
+                Unit::Unity,
+                "Number of I/O requests currently in progress on the block device",
+            )?,
+        })
+    }
+}
+
+impl DeviceFilter {
+    pub fn accepts(&self, name: &str) -> bool {
+        let included = self.include.as_ref().is_none_or(|r| r.is_match(name));
+        let excluded = self.exclude.as_ref().is_some_and(|r| r.is_match(name));
+        included && !excluded
+    }
+}
+
+impl DiskStatsReader {
+    fn read(&mut self) -> anyhow::Result<Vec<DiskStat>> {
+        match self {
+            DiskStatsReader::ProcFs { path, reader } => {
+                reader.rewind().with_context(|| format!("failed to rewind {path:?}"))?;
+                let stats =
+                    DiskStats::from_buf_read(reader).with_context(|| format!("error while parsing {path:?}"))?;
+                Ok(stats.0)
+            }
+            DiskStatsReader::SysFs { block_dir } => read_sys_block(block_dir),
+        }
+    }
+}
+
+fn read_sys_block(block_dir: &Path) -> anyhow::Result<Vec<DiskStat>> {
+    let mut res = Vec::new();
+    let devices = std::fs::read_dir(block_dir).with_context(|| format!("failed to list {}", block_dir.display()))?;
+    for device in devices {
+        let device_dir = device?.path();
+        res.push(read_sys_stat(&device_dir)?);
+        for entry in std::fs::read_dir(&device_dir)? {
+            let dir = entry?.path();
+            if dir.join("partition").exists() {
+                res.push(read_sys_stat(&dir)?);
+            }
+        }
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(res)
+}
+
+/// Reads the `stat` file of a device or partition directory.
+fn read_sys_stat(dir: &Path) -> anyhow::Result<DiskStat> {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    let dev = sysfs::read_str(&dir.join("dev"))?;
+    let (major, minor) = dev
+        .split_once(':')
+        .with_context(|| format!("invalid device numbers in {}: {dev:?}", dir.display()))?;
+    let stat = sysfs::read_str(&dir.join("stat"))?;
+    // build a line of /proc/diskstats to reuse its parser
+    let line = format!("{major} {minor} {name} {stat}");
+    DiskStat::from_line(&line).with_context(|| format!("error while parsing {}/stat", dir.display()))
+}
+
+impl DiskStatsProbe {
+    /// Creates a probe that reads `proc_diskstats` (usually `/proc/diskstats`), or `sys_block`
+    /// (usually `/sys/block`) if the former cannot be opened.
+    pub fn new(
+        metrics: DiskMetrics,
+        filter: DeviceFilter,
+        proc_diskstats: impl Into<PathBuf>,
+        sys_block: impl Into<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let path = proc_diskstats.into();
+        let mut reader = match File::open(&path) {
+            Ok(file) => DiskStatsReader::ProcFs {
+                path,
+                reader: BufReader::new(file),
+            },
+            Err(e) => {
+                let block_dir = sys_block.into();
+                log::warn!("Cannot open {path:?} ({e}), the disk statistics will be read from {block_dir:?} instead.");
+                DiskStatsReader::SysFs { block_dir }
+            }
+        };
+        // check that the statistics can be read
+        reader.read().context("cannot read the disk statistics")?;
+        Ok(Self {
+            reader,
+            filter,
+            previous: HashMap::new(),
+            metrics,
+        })
+    }
+}
+
+impl Source for DiskStatsProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, ts: Timestamp) -> Result<(), PollError> {
+        let stats = self.reader.read()?;
+        let mut current = HashMap::with_capacity(stats.len());
+        for now in stats.into_iter().filter(|s| self.filter.accepts(&s.name)) {
+            let res = Resource::custom("block_device", now.name.clone());
+            let cons = ResourceConsumer::LocalMachine;
+            acc.push(MeasurementPoint::new(
+                ts,
+                self.metrics.in_flight,
+                res.clone(),
+                cons.clone(),
+                now.in_progress,
+            ));
+
+            // Only push deltas, not the baseline value before the plugin starts (or before the device appears).
+            // The counters are reset when a device is removed and added again, hence the saturating_sub.
+            if let Some(prev) = self.previous.get(&now.name) {
+                for (operation, sectors, ops) in [
+                    (
+                        "read",
+                        now.sectors_read.saturating_sub(prev.sectors_read),
+                        now.reads.saturating_sub(prev.reads),
+                    ),
+                    (
+                        "write",
+                        now.sectors_written.saturating_sub(prev.sectors_written),
+                        now.writes.saturating_sub(prev.writes),
+                    ),
+                ] {
+                    acc.push(
+                        MeasurementPoint::new(ts, self.metrics.bytes, res.clone(), cons.clone(), sectors * SECTOR_SIZE)
+                            .with_attr("operation", operation),
+                    );
+                    acc.push(
+                        MeasurementPoint::new(ts, self.metrics.operations, res.clone(), cons.clone(), ops)
+                            .with_attr("operation", operation),
+                    );
+                }
+                acc.push(MeasurementPoint::new(
+                    ts,
+                    self.metrics.time_in_queue,
+                    res,
+                    cons,
+                    now.weighted_time_in_progress
+                        .saturating_sub(prev.weighted_time_in_progress),
+                ));
+            }
+            current.insert(now.name.clone(), now);
+        }
+        self.previous = current;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use regex::Regex;
+
+    use super::{DeviceFilter, DiskStatsReader, read_sys_block};
+
+    #[test]
+    fn filter() {
+        let all = DeviceFilter {
+            include: None,
+            exclude: None,
+        };
+        assert!(all.accepts("sda"));
+        assert!(all.accepts("loop0"));
+
+        let no_loop = DeviceFilter {
+            include: None,
+            exclude: Some(Regex::new(r"^loop\d+$").unwrap()),
+        };
+        assert!(no_loop.accepts("sda"));
+        assert!(!no_loop.accepts("loop0"));
+
+        let nvme_partitions = DeviceFilter {
+            include: Some(Regex::new(r"^nvme\d+n\d+p\d+$").unwrap()),
+            exclude: Some(Regex::new(r"p1$").unwrap()),
+        };
+        assert!(!nvme_partitions.accepts("nvme0n1"));
+        assert!(!nvme_partitions.accepts("nvme0n1p1"));
+        assert!(nvme_partitions.accepts("nvme0n1p2"));
+    }
+
+    #[test]
+    fn procfs_diskstats() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let path = root.path().join("diskstats");
+        fs::write(
+            &path,
+            "   8       0 sda 1000 20 64000 500 2000 30 128000 900 3 1200 1400 0 0 0 0 10 5\n   8       1 sda1 900 20 60000 450 1900 30 120000 850 1 1100 1300 0 0 0 0 0 0\n",
+        )?;
+        let mut reader = DiskStatsReader::ProcFs {
+            path: path.clone(),
+            reader: std::io::BufReader::new(fs::File::open(&path)?),
+        };
+        for _ in 0..2 {
+            let stats = reader.read()?;
+            assert_eq!(stats.len(), 2);
+            assert_eq!(stats[0].name, "sda");
+            assert_eq!(stats[0].sectors_read, 64000);
+            assert_eq!(stats[0].writes, 2000);
+            assert_eq!(stats[0].in_progress, 3);
+            assert_eq!(stats[0].weighted_time_in_progress, 1400);
+            assert_eq!(stats[1].name, "sda1");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sysfs_block() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let block_dir = root.path();
+        let sda = block_dir.join("sda");
+        let sda1 = sda.join("sda1");
+        fs::create_dir_all(&sda1)?;
+        fs::create_dir_all(sda.join("queue"))?;
+        fs::write(sda.join("dev"), "8:0\n")?;
+        fs::write(
+            sda.join("stat"),
+            "    1000       20    64000      500     2000       30   128000      900        3     1200     1400\n",
+        )?;
+        fs::write(sda1.join("dev"), "8:1\n")?;
+        fs::write(sda1.join("partition"), "1\n")?;
+        fs::write(
+            sda1.join("stat"),
+            "     900       20    60000      450     1900       30   120000      850        1     1100     1300\n",
+        )?;
+
+        let stats = read_sys_block(block_dir)?;
+        let names: Vec<&str> = stats.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["sda", "sda1"]);
+        assert_eq!((stats[1].major, stats[1].minor), (8, 1));
+        assert_eq!(stats[1].sectors_written, 120000);
+        assert_eq!(stats[1].in_progress, 1);
+        Ok(())
+    }
+}
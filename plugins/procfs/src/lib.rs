@@ -10,13 +10,19 @@ use alumet::{
 use anyhow::Context;
 use procfs::{Current, CurrentSI};
 use rlimit::{Resource, getrlimit, setrlimit};
+use std::{path::Path, time::Duration};
 
+mod cpufreq;
+mod cpuidle;
 mod cpus;
+mod disk;
 mod kernel;
 mod memory;
 mod network;
 mod process;
 mod serde_regex;
+mod sysfs;
+mod thermal;
 
 pub struct ProcfsPlugin {
     config: Option<config::Config>,
@@ -46,14 +52,27 @@ impl AlumetPlugin for ProcfsPlugin {
         // Start the procfs-related sources that are enabled, according to the config.
         // Each subconfig is moved into the corresponding function, hence `config` is partially moved.
         let config = self.config.take().unwrap();
+        let group = config.trigger_group.as_deref();
         if config.kernel.enabled {
-            start_kernel_probe(config.kernel, alumet)?;
+            start_kernel_probe(config.kernel, group, alumet)?;
         }
         if config.memory.enabled {
-            start_memory_probe(config.memory, alumet)?;
+            start_memory_probe(config.memory, group, alumet)?;
         }
         if config.network.enabled {
-            start_network_probe(config.network, alumet)?;
+            start_network_probe(config.network, group, alumet)?;
+        }
+        if config.disks.enabled {
+            start_disk_probe(config.disks, group, alumet)?;
+        }
+        if config.cpufreq.enabled {
+            start_cpufreq_probe(config.cpufreq, group, alumet)?;
+        }
+        if config.cpuidle.enabled {
+            start_cpuidle_probe(config.cpuidle, group, alumet)?;
+        }
+        if config.thermal.enabled {
+            start_thermal_probe(config.thermal, group, alumet)?;
         }
         if config.processes.enabled {
             let metrics = process::ProcessMetrics {
@@ -90,11 +109,21 @@ impl AlumetPlugin for ProcfsPlugin {
     }
 }
 
+/// Returns the trigger of a source that is polled every `poll_interval`, in the trigger `group` if there is one.
+fn interval_trigger(poll_interval: Duration, group: Option<&str>) -> anyhow::Result<TriggerSpec> {
+    let mut builder = TriggerSpec::builder(poll_interval);
+    if let Some(group) = group {
+        builder.group(group);
+    }
+    Ok(builder.build()?)
+}
+
 fn start_kernel_probe(
     config_kernel: config::KernelStatsMonitoring,
+    group: Option<&str>,
     alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
 ) -> Result<(), anyhow::Error> {
-    let trigger = TriggerSpec::at_interval(config_kernel.poll_interval);
+    let trigger = interval_trigger(config_kernel.poll_interval, group)?;
     let metrics = kernel::KernelMetrics::new(alumet).context("unable to register metrics for kernel probe")?;
     let source =
         kernel::KernelStatsProbe::new(metrics, procfs::KernelStats::PATH).context("unable to create kernel probe")?;
@@ -104,9 +133,10 @@ fn start_kernel_probe(
 
 fn start_network_probe(
     config_network: config::NetworkMonitoring,
+    group: Option<&str>,
     alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
 ) -> Result<(), anyhow::Error> {
-    let trigger = TriggerSpec::at_interval(config_network.poll_interval);
+    let trigger = interval_trigger(config_network.poll_interval, group)?;
     let metrics = network::NetworkMetrics::new(alumet).context("unable to register metrics for network probe")?;
     let source = network::NetworkProbe::new(metrics, procfs::net::InterfaceDeviceStatus::PATH)
         .context("unable to create network probe")?;
@@ -114,11 +144,85 @@ fn start_network_probe(
     Ok(())
 }
 
+fn start_disk_probe(
+    config_disks: config::DiskMonitoring,
+    group: Option<&str>,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = interval_trigger(config_disks.poll_interval, group)?;
+    let metrics = disk::DiskMetrics::new(alumet).context("unable to register metrics for disk probe")?;
+    let filter = disk::DeviceFilter {
+        include: config_disks.include_regex,
+        exclude: config_disks.exclude_regex,
+    };
+    let source = disk::DiskStatsProbe::new(metrics, filter, procfs::DiskStats::PATH, sysfs::BLOCK_DIR)
+        .context("unable to create disk probe")?;
+    alumet.add_source("disks", Box::new(source), trigger)?;
+    Ok(())
+}
+
+// The sysfs files read by the following probes are not available on every machine (e.g. in virtual machines),
+// hence a missing file only disables the corresponding source instead of failing.
+
+fn start_cpufreq_probe(
+    config_cpufreq: config::CpuFreqMonitoring,
+    group: Option<&str>,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = interval_trigger(config_cpufreq.poll_interval, group)?;
+    let metrics = cpufreq::CpuFreqMetrics::new(alumet).context("unable to register metrics for cpufreq probe")?;
+    match cpufreq::CpuFreqProbe::new(metrics, Path::new(sysfs::CPU_DIR)) {
+        Ok(source) => {
+            alumet.add_source("cpufreq", Box::new(source), trigger)?;
+        }
+        Err(e) => log::warn!("CPU frequency monitoring is disabled: {e:#}"),
+    }
+    Ok(())
+}
+
+fn start_cpuidle_probe(
+    config_cpuidle: config::CpuIdleMonitoring,
+    group: Option<&str>,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = interval_trigger(config_cpuidle.poll_interval, group)?;
+    let metrics = cpuidle::CpuIdleMetrics::new(alumet).context("unable to register metrics for cpuidle probe")?;
+    match cpuidle::CpuIdleProbe::new(metrics, Path::new(sysfs::CPU_DIR)) {
+        Ok(source) => {
+            alumet.add_source("cpuidle", Box::new(source), trigger)?;
+        }
+        Err(e) => log::warn!("CPU idle states monitoring is disabled: {e:#}"),
+    }
+    Ok(())
+}
+
+fn start_thermal_probe(
+    config_thermal: config::ThermalMonitoring,
+    group: Option<&str>,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = interval_trigger(config_thermal.poll_interval, group)?;
+    let metrics = thermal::ThermalMetrics::new(alumet).context("unable to register metrics for thermal probe")?;
+    match thermal::ThermalProbe::new(
+        metrics,
+        Path::new(sysfs::THERMAL_DIR),
+        Path::new(sysfs::HWMON_DIR),
+        Path::new(sysfs::CPU_DIR),
+    ) {
+        Ok(source) => {
+            alumet.add_source("thermal", Box::new(source), trigger)?;
+        }
+        Err(e) => log::warn!("Thermal monitoring is disabled: {e:#}"),
+    }
+    Ok(())
+}
+
 fn start_memory_probe(
     config_memory: config::MeminfoMonitoring,
+    group: Option<&str>,
     alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
 ) -> Result<(), anyhow::Error> {
-    let trigger = TriggerSpec::at_interval(config_memory.poll_interval);
+    let trigger = interval_trigger(config_memory.poll_interval, group)?;
     let metrics: anyhow::Result<Vec<_>> = config_memory
         .metrics
         .into_iter()
@@ -232,6 +336,18 @@ mod config {
         pub memory: MeminfoMonitoring,
         pub network: NetworkMonitoring,
         pub processes: ProcessMonitoring,
+        #[serde(default)]
+        pub disks: DiskMonitoring,
+        #[serde(default)]
+        pub cpufreq: CpuFreqMonitoring,
+        #[serde(default)]
+        pub cpuidle: CpuIdleMonitoring,
+        #[serde(default)]
+        pub thermal: ThermalMonitoring,
+        /// Name of a trigger group, to poll the system-wide sources (not the processes) at the same time
+        /// as the other sources of the group.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub trigger_group: Option<String>,
     }
 
     #[derive(Serialize, Deserialize)]
@@ -250,6 +366,46 @@ mod config {
         pub poll_interval: Duration,
     }
 
+    #[derive(Serialize, Deserialize)]
+    pub struct DiskMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+
+        /// Only monitor the devices and partitions whose name matches this regex.
+        #[serde(default, with = "serde_regex::option")]
+        pub include_regex: Option<Regex>,
+
+        /// Do not monitor the devices and partitions whose name matches this regex.
+        #[serde(default, with = "serde_regex::option")]
+        pub exclude_regex: Option<Regex>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CpuFreqMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct CpuIdleMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ThermalMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+    }
+
     #[derive(Serialize, Deserialize)]
     pub struct MeminfoMonitoring {
         #[serde(default = "default_enabled")]
@@ -338,6 +494,45 @@ mod config {
         }
     }
 
+    impl Default for DiskMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+                include_regex: None,
+                // loop devices and RAM disks are rarely interesting and can be numerous
+                exclude_regex: Some(Regex::new(r"^(loop|ram|zram)\d+$").unwrap()),
+            }
+        }
+    }
+
+    impl Default for CpuFreqMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+            }
+        }
+    }
+
+    impl Default for CpuIdleMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+            }
+        }
+    }
+
+    impl Default for ThermalMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+            }
+        }
+    }
+
     impl Default for MeminfoMonitoring {
         fn default() -> Self {
             Self {
@@ -0,0 +1,38 @@
+//! Helpers for reading the sysfs virtual filesystem.
+use std::path::Path;
+
+use anyhow::Context;
+
+/// Directory that contains one subdirectory per CPU.
+pub const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+/// Directory that contains one subdirectory per block device.
+pub const BLOCK_DIR: &str = "/sys/block";
+
+/// Directory that contains the thermal zones.
+pub const THERMAL_DIR: &str = "/sys/class/thermal";
+
+/// Directory that contains the hardware monitoring devices, for instance `coretemp`.
+pub const HWMON_DIR: &str = "/sys/class/hwmon";
+
+/// Reads a sysfs attribute that contains a single line of text.
+pub fn read_str(path: &Path) -> anyhow::Result<String> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(content.trim_end().to_owned())
+}
+
+/// Reads a sysfs attribute that contains a single integer.
+pub fn read_u64(path: &Path) -> anyhow::Result<u64> {
+    let content = read_str(path)?;
+    content
+        .parse()
+        .with_context(|| format!("invalid integer in {}: {content:?}", path.display()))
+}
+
+/// Reads a sysfs attribute that contains a single signed integer.
+pub fn read_i64(path: &Path) -> anyhow::Result<i64> {
+    let content = read_str(path)?;
+    content
+        .parse()
+        .with_context(|| format!("invalid integer in {}: {content:?}", path.display()))
+}
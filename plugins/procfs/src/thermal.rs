@@ -0,0 +1,371 @@
+//! Temperatures read from `/sys/class/thermal/thermal_zone*` and from the `coretemp` hwmon driver.
+
+use std::path::{Path, PathBuf};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::{TypedMetricId, error::MetricCreationError},
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+use anyhow::Context;
+
+use crate::{cpus, sysfs};
+
+/// Reads the temperature of each thermal zone, and of each CPU core and package known to `coretemp`.
+///
+/// A thermal zone does not always correspond to a CPU (it can be a sensor on the motherboard, etc.),
+/// therefore the zones are reported on the `LocalMachine` resource, with attributes that identify the zone.
+/// The `x86_pkg_temp` zones are the exception: they measure a CPU package and are reported on `CpuPackage`.
+/// The per-core temperatures come from the `coretemp` driver, which is the only one to expose them.
+pub struct ThermalProbe {
+    zones: Vec<ThermalZone>,
+    cpu_sensors: Vec<CpuSensor>,
+    metrics: ThermalMetrics,
+}
+
+pub struct ThermalMetrics {
+    temperature: TypedMetricId<f64>,
+    cpu_temperature: TypedMetricId<f64>,
+}
+
+struct ThermalZone {
+    /// Name of the zone directory, for instance `thermal_zone0`.
+    name: String,
+    /// Type of the zone, for instance `x86_pkg_temp` or `acpitz`.
+    kind: String,
+    /// The package for `x86_pkg_temp` zones, `LocalMachine` for the others.
+    resource: Resource,
+    /// Path to the `temp` file, which contains the temperature in millidegrees Celsius.
+    temp_path: PathBuf,
+}
+
+/// A temperature sensor of the `coretemp` driver.
+struct CpuSensor {
+    /// Label of the sensor, for instance `Core 2` or `Package id 0`.
+    label: String,
+    /// `CpuCore` or `CpuPackage`.
+    resource: Resource,
+    /// Path to the `temp*_input` file, which contains the temperature in millidegrees Celsius.
+    temp_path: PathBuf,
+}
+
+/// Position of an online CPU in the topology.
+struct CpuLocation {
+    cpu: u32,
+    package: u32,
+    core: u32,
+}
+
+impl ThermalMetrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            temperature: alumet.create_metric(
+                "thermal_zone_temperature",
+                Unit::DegreeCelsius,
+                "temperature of the thermal zone",
+            )?,
+            cpu_temperature: alumet.create_metric(
+                "cpu_temperature",
+                Unit::DegreeCelsius,
+                "temperature of the CPU core or package, according to the coretemp driver",
+            )?,
+        })
+    }
+}
+
+impl ThermalProbe {
+    /// Creates a probe for the thermal zones of `thermal_dir` (usually `/sys/class/thermal`)
+    /// and the `coretemp` sensors of `hwmon_dir` (usually `/sys/class/hwmon`).
+    ///
+    /// `cpu_dir` (usually `/sys/devices/system/cpu`) gives the topology that is used to find the
+    /// package of the `x86_pkg_temp` zones and the CPU of the `coretemp` cores.
+    /// If there is no thermal zone and no `coretemp` sensor, an error is returned.
+    pub fn new(metrics: ThermalMetrics, thermal_dir: &Path, hwmon_dir: &Path, cpu_dir: &Path) -> anyhow::Result<Self> {
+        let cpus = cpu_locations(cpu_dir).unwrap_or_else(|e| {
+            log::debug!("the thermal sensors will not be mapped to the CPUs: {e:#}");
+            Vec::new()
+        });
+        let zones = thermal_zones(thermal_dir, &cpus)?;
+        let cpu_sensors = coretemp_sensors(hwmon_dir, &cpus)?;
+        if zones.is_empty() && cpu_sensors.is_empty() {
+            return Err(anyhow::anyhow!(
+                "no thermal zone in {} and no coretemp sensor in {}",
+                thermal_dir.display(),
+                hwmon_dir.display()
+            ));
+        }
+        Ok(Self {
+            zones,
+            cpu_sensors,
+            metrics,
+        })
+    }
+}
+
+fn cpu_locations(cpu_dir: &Path) -> anyhow::Result<Vec<CpuLocation>> {
+    let online = cpus::online_cpus_path(&cpu_dir.join("online"))?;
+    online
+        .into_iter()
+        .map(|cpu| {
+            let topology = cpu_dir.join(format!("cpu{cpu}/topology"));
+            Ok(CpuLocation {
+                cpu,
+                package: sysfs::read_u64(&topology.join("physical_package_id"))? as u32,
+                core: sysfs::read_u64(&topology.join("core_id"))? as u32,
+            })
+        })
+        .collect()
+}
+
+fn thermal_zones(thermal_dir: &Path, cpus: &[CpuLocation]) -> anyhow::Result<Vec<ThermalZone>> {
+    let mut res = Vec::new();
+    let entries =
+        std::fs::read_dir(thermal_dir).with_context(|| format!("failed to list {}", thermal_dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(n) = name.strip_prefix("thermal_zone") else {
+            // cooling devices are also listed here
+            continue;
+        };
+        let Ok(n) = n.parse::<u32>() else {
+            continue;
+        };
+        let dir = entry.path();
+        let kind = sysfs::read_str(&dir.join("type")).context("failed to read the type of the thermal zone")?;
+        res.push((
+            n,
+            ThermalZone {
+                name,
+                kind,
+                resource: Resource::LocalMachine,
+                temp_path: dir.join("temp"),
+            },
+        ));
+    }
+    res.sort_by_key(|(n, _)| *n);
+    let mut zones: Vec<ThermalZone> = res.into_iter().map(|(_, zone)| zone).collect();
+
+    // The x86_pkg_temp zones don't say which package they measure, but the kernel creates them
+    // when the first CPU of each package comes online, hence in the order of the packages.
+    let mut packages: Vec<u32> = cpus.iter().map(|c| c.package).collect();
+    packages.sort_unstable();
+    packages.dedup();
+    let pkg_zones: Vec<&mut ThermalZone> = zones.iter_mut().filter(|z| z.kind == "x86_pkg_temp").collect();
+    if pkg_zones.len() == packages.len() {
+        for (zone, id) in pkg_zones.into_iter().zip(packages) {
+            zone.resource = Resource::CpuPackage { id };
+        }
+    } else if !pkg_zones.is_empty() {
+        log::debug!(
+            "found {} x86_pkg_temp zones for {} packages, reporting them on the local machine",
+            pkg_zones.len(),
+            packages.len()
+        );
+    }
+    Ok(zones)
+}
+
+fn coretemp_sensors(hwmon_dir: &Path, cpus: &[CpuLocation]) -> anyhow::Result<Vec<CpuSensor>> {
+    let mut res = Vec::new();
+    let Ok(entries) = std::fs::read_dir(hwmon_dir) else {
+        // no hwmon driver, hence no coretemp sensor
+        return Ok(res);
+    };
+    for entry in entries {
+        let dir = entry?.path();
+        if !sysfs::read_str(&dir.join("name")).is_ok_and(|name| name == "coretemp") {
+            continue;
+        }
+        // The labels are "Package id <package>" and "Core <core_id>", one hwmon device per package.
+        let mut labels = Vec::new();
+        for file in std::fs::read_dir(&dir).with_context(|| format!("failed to list {}", dir.display()))? {
+            let file_name = file?.file_name().to_string_lossy().into_owned();
+            let Some(sensor) = file_name.strip_suffix("_label") else {
+                continue;
+            };
+            let label = sysfs::read_str(&dir.join(&file_name))?;
+            labels.push((label, dir.join(format!("{sensor}_input"))));
+        }
+        let package = labels
+            .iter()
+            .find_map(|(label, _)| label.strip_prefix("Package id ")?.parse::<u32>().ok());
+        for (label, temp_path) in labels {
+            let resource = if let Some(id) = label.strip_prefix("Package id ").and_then(|id| id.parse().ok()) {
+                Resource::CpuPackage { id }
+            } else if let Some(core) = label.strip_prefix("Core ").and_then(|core| core.parse::<u32>().ok())
+                && let Some(package) = package
+                && let Some(location) = cpus.iter().find(|c| c.package == package && c.core == core)
+            {
+                // report the physical core on its first logical CPU
+                Resource::CpuCore { id: location.cpu }
+            } else {
+                log::debug!("ignoring the coretemp sensor {label:?} of {}", dir.display());
+                continue;
+            };
+            res.push(CpuSensor {
+                label,
+                resource,
+                temp_path,
+            });
+        }
+    }
+    res.sort_by(|a, b| a.temp_path.cmp(&b.temp_path));
+    Ok(res)
+}
+
+/// Reads a temperature file, which contains millidegrees Celsius.
+fn read_celsius(path: &Path) -> anyhow::Result<f64> {
+    let millidegrees = sysfs::read_i64(path)?;
+    Ok(millidegrees as f64 / 1000.0)
+}
+
+impl Source for ThermalProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        for zone in &self.zones {
+            // Some drivers fail to read the temperature from time to time, skip the zone.
+            let temperature = match read_celsius(&zone.temp_path) {
+                Ok(t) => t,
+                Err(e) => {
+                    log::debug!("skipping {}: {e:#}", zone.name);
+                    continue;
+                }
+            };
+            acc.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.temperature,
+                    zone.resource.clone(),
+                    ResourceConsumer::LocalMachine,
+                    temperature,
+                )
+                .with_attr("thermal_zone", zone.name.clone())
+                .with_attr("zone_type", zone.kind.clone()),
+            );
+        }
+        for sensor in &self.cpu_sensors {
+            // The files disappear when the CPU is put offline, skip the sensor.
+            let temperature = match read_celsius(&sensor.temp_path) {
+                Ok(t) => t,
+                Err(e) => {
+                    log::debug!("skipping {}: {e:#}", sensor.label);
+                    continue;
+                }
+            };
+            acc.push(MeasurementPoint::new(
+                timestamp,
+                self.metrics.cpu_temperature,
+                sensor.resource.clone(),
+                ResourceConsumer::LocalMachine,
+                temperature,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use alumet::resources::Resource;
+
+    use super::{coretemp_sensors, cpu_locations, read_celsius, thermal_zones};
+
+    /// Creates a topology with two packages of two cores, with two threads per core.
+    fn write_topology(cpu_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(cpu_dir)?;
+        fs::write(cpu_dir.join("online"), "0-7\n")?;
+        for cpu in 0..8 {
+            let dir = cpu_dir.join(format!("cpu{cpu}/topology"));
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("physical_package_id"), format!("{}\n", cpu % 4 / 2))?;
+            fs::write(dir.join("core_id"), format!("{}\n", cpu % 2))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn discover_thermal_zones() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let thermal_dir = root.path().join("thermal");
+        let cpu_dir = root.path().join("cpu");
+        write_topology(&cpu_dir)?;
+        for (n, kind, temp) in [
+            (10, "x86_pkg_temp", "45000"),
+            (2, "acpitz", "-1500"),
+            (11, "x86_pkg_temp", "47000"),
+        ] {
+            let dir = thermal_dir.join(format!("thermal_zone{n}"));
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("type"), format!("{kind}\n"))?;
+            fs::write(dir.join("temp"), format!("{temp}\n"))?;
+        }
+        fs::create_dir_all(thermal_dir.join("cooling_device0"))?;
+
+        let zones = thermal_zones(&thermal_dir, &cpu_locations(&cpu_dir)?)?;
+        let found: Vec<(&str, &str, &Resource)> = zones
+            .iter()
+            .map(|z| (z.name.as_str(), z.kind.as_str(), &z.resource))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("thermal_zone2", "acpitz", &Resource::LocalMachine),
+                ("thermal_zone10", "x86_pkg_temp", &Resource::CpuPackage { id: 0 }),
+                ("thermal_zone11", "x86_pkg_temp", &Resource::CpuPackage { id: 1 }),
+            ]
+        );
+        assert_eq!(read_celsius(&zones[0].temp_path)?, -1.5);
+        assert_eq!(read_celsius(&zones[1].temp_path)?, 45.0);
+
+        // without topology, the packages are unknown
+        let zones = thermal_zones(&thermal_dir, &[])?;
+        assert!(zones.iter().all(|z| z.resource == Resource::LocalMachine));
+        Ok(())
+    }
+
+    #[test]
+    fn discover_coretemp() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let hwmon_dir = root.path().join("hwmon");
+        let cpu_dir = root.path().join("cpu");
+        write_topology(&cpu_dir)?;
+        let sensors = [
+            ("hwmon0", "acpitz", vec![]),
+            ("hwmon1", "coretemp", vec!["Package id 0", "Core 0", "Core 1"]),
+            ("hwmon2", "coretemp", vec!["Package id 1", "Core 0", "Core 1"]),
+        ];
+        for (hwmon, name, labels) in sensors {
+            let dir = hwmon_dir.join(hwmon);
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("name"), format!("{name}\n"))?;
+            for (i, label) in labels.into_iter().enumerate() {
+                fs::write(dir.join(format!("temp{}_label", i + 1)), format!("{label}\n"))?;
+                fs::write(dir.join(format!("temp{}_input", i + 1)), "52000\n")?;
+            }
+        }
+
+        let sensors = coretemp_sensors(&hwmon_dir, &cpu_locations(&cpu_dir)?)?;
+        let found: Vec<(&str, &Resource)> = sensors.iter().map(|s| (s.label.as_str(), &s.resource)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Package id 0", &Resource::CpuPackage { id: 0 }),
+                ("Core 0", &Resource::CpuCore { id: 0 }),
+                ("Core 1", &Resource::CpuCore { id: 1 }),
+                ("Package id 1", &Resource::CpuPackage { id: 1 }),
+                ("Core 0", &Resource::CpuCore { id: 2 }),
+                ("Core 1", &Resource::CpuCore { id: 3 }),
+            ]
+        );
+        assert_eq!(read_celsius(&sensors[0].temp_path)?, 52.0);
+
+        // no hwmon directory, no sensor
+        assert!(coretemp_sensors(&root.path().join("missing"), &[])?.is_empty());
+        Ok(())
+    }
+}
@@ -1,24 +1,37 @@
 use alumet::{
-    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    measurement::{Histogram, MeasurementBuffer, WrappedMeasurementValue},
     metrics::Metric,
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
 use anyhow::Context;
 use prometheus_client::{
-    metrics::{family::Family, gauge::Gauge},
+    encoding::{EncodeMetric, MetricEncoder},
+    metrics::{MetricType, TypedMetric, family::Family, gauge::Gauge},
     registry::Registry,
 };
 use std::{
     collections::HashMap,
     net::SocketAddr,
-    sync::{Arc, atomic::AtomicU64},
+    sync::{Arc, Mutex, atomic::AtomicU64},
 };
 use tokio::sync::RwLock;
 
+type Labels = Vec<(String, String)>;
+
 #[derive(Clone)]
 pub struct MetricState {
     pub registry: Arc<RwLock<Registry>>,
-    metrics: Arc<RwLock<HashMap<String, Family<Vec<(String, String)>, Gauge<f64, AtomicU64>>>>>,
+    metrics: Arc<RwLock<HashMap<String, Family<Labels, Gauge<f64, AtomicU64>>>>>,
+    histograms: Arc<RwLock<HashMap<String, Family<Labels, HistogramGauge>>>>,
+}
+
+/// A Prometheus histogram that is set to the last histogram measured by Alumet.
+///
+/// Unlike [`prometheus_client::metrics::histogram::Histogram`], which records individual observations,
+/// the whole distribution is replaced on each update.
+#[derive(Debug, Default, Clone)]
+struct HistogramGauge {
+    value: Arc<Mutex<Option<Histogram>>>,
 }
 
 #[derive(Clone)]
@@ -41,7 +54,12 @@ impl PrometheusOutput {
         // Create metric state
         let registry = Arc::new(RwLock::new(Registry::default()));
         let metrics = Arc::new(RwLock::new(HashMap::new()));
-        let state = MetricState { registry, metrics };
+        let histograms = Arc::new(RwLock::new(HashMap::new()));
+        let state = MetricState {
+            registry,
+            metrics,
+            histograms,
+        };
 
         // Configure the HTTP server to expose the metrics
         let addr: SocketAddr = format!("{}:{}", host, port)
@@ -66,6 +84,7 @@ impl alumet::pipeline::Output for PrometheusOutput {
 
         // Ensure threads reading and writing are handled correctly
         let mut metrics = self.state.metrics.blocking_write();
+        let mut histograms = self.state.histograms.blocking_write();
         let mut registry = self.state.registry.blocking_write();
 
         for m in measurements {
@@ -95,12 +114,29 @@ impl alumet::pipeline::Output for PrometheusOutput {
             }
             labels.sort_by(|a, b| a.0.cmp(&b.0));
 
+            // Histograms have their own type of metric.
+            if let WrappedMeasurementValue::Histogram(h) = &m.value {
+                let family = histograms.entry(metric_name.clone()).or_insert_with(|| {
+                    let family = Family::<Labels, HistogramGauge>::default();
+                    register_family(
+                        &mut registry,
+                        metric_name,
+                        &full_metric.description,
+                        get_unit_string(full_metric),
+                        family.clone(),
+                    );
+                    family
+                });
+                family.get_or_create(&labels).set(h.clone());
+                continue;
+            }
+
             // Each family vector contains a metric with all associated metrics and differentiated by the labels
             let family = if let Some(family) = metrics.get(&metric_name) {
                 family
             } else {
                 let unit_string = get_unit_string(full_metric);
-                let family = Family::<Vec<(String, String)>, Gauge<f64, AtomicU64>>::default();
+                let family = Family::<Labels, Gauge<f64, AtomicU64>>::default();
 
                 register_family(
                     &mut registry,
@@ -133,7 +169,7 @@ fn register_family(
     metric_name: String,
     description: &str,
     unit_string: String,
-    family: Family<Vec<(String, String)>, Gauge<f64, AtomicU64>>,
+    family: impl prometheus_client::registry::Metric,
 ) {
     if unit_string.is_empty() {
         registry.register(metric_name, description, family);
@@ -147,6 +183,37 @@ fn register_family(
     }
 }
 
+impl HistogramGauge {
+    fn set(&self, value: Histogram) {
+        *self.value.lock().unwrap() = Some(value);
+    }
+}
+
+impl TypedMetric for HistogramGauge {
+    const TYPE: MetricType = MetricType::Histogram;
+}
+
+impl EncodeMetric for HistogramGauge {
+    fn encode(&self, mut encoder: MetricEncoder) -> Result<(), std::fmt::Error> {
+        let value = self.value.lock().unwrap();
+        match value.as_ref() {
+            Some(h) => {
+                // The encoder computes the cumulative counts, and uses f64::MAX to denote +Inf.
+                let buckets: Vec<(f64, u64)> = h
+                    .buckets()
+                    .map(|(bound, count)| (if bound.is_infinite() { f64::MAX } else { bound }, count))
+                    .collect();
+                encoder.encode_histogram::<()>(h.sum(), h.count(), &buckets, None)
+            }
+            None => encoder.encode_histogram::<()>(0.0, 0, &[(f64::MAX, 0)], None),
+        }
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
 /// Helper function to ensure metric/label names follow Prometheus
 /// [naming rules](https://prometheus.io/docs/concepts/data_model/#metric-names-and-labels).
 fn sanitize_name(name: String) -> String {
@@ -196,7 +263,7 @@ mod tests {
         units::{PrefixedUnit, Unit, UnitPrefix},
     };
 
-    use crate::output::{get_unit_string, register_family, sanitize_name};
+    use crate::output::{HistogramGauge, Labels, get_unit_string, register_family, sanitize_name};
 
     #[test]
     fn test_sanitize_name() {
@@ -292,4 +359,50 @@ mod tests {
             "unitless metric should still be exported:\n{buf}"
         );
     }
+
+    #[test]
+    fn histogram_exposition() {
+        use alumet::measurement::Histogram;
+        use prometheus_client::encoding::text::encode;
+        use prometheus_client::metrics::family::Family;
+        use prometheus_client::registry::Registry;
+
+        let mut registry = Registry::default();
+        let family = Family::<Labels, HistogramGauge>::default();
+        family
+            .get_or_create(&vec![("resource_kind".to_string(), "local_machine".to_string())])
+            .set(Histogram::from_parts(vec![0.5, 1.0], vec![1, 2, 3], 7.5).unwrap());
+        register_family(
+            &mut registry,
+            "latency".to_string(),
+            "request latency",
+            "seconds".to_string(),
+            family,
+        );
+
+        let mut buf = String::new();
+        encode(&mut buf, &registry).unwrap();
+
+        assert!(buf.contains("# TYPE latency_seconds histogram"), "{buf}");
+        assert!(
+            buf.contains("latency_seconds_sum{resource_kind=\"local_machine\"} 7.5"),
+            "{buf}"
+        );
+        assert!(
+            buf.contains("latency_seconds_count{resource_kind=\"local_machine\"} 6"),
+            "{buf}"
+        );
+        assert!(
+            buf.contains("latency_seconds_bucket{le=\"0.5\",resource_kind=\"local_machine\"} 1"),
+            "{buf}"
+        );
+        assert!(
+            buf.contains("latency_seconds_bucket{le=\"1.0\",resource_kind=\"local_machine\"} 3"),
+            "{buf}"
+        );
+        assert!(
+            buf.contains("latency_seconds_bucket{le=\"+Inf\",resource_kind=\"local_machine\"} 6"),
+            "{buf}"
+        );
+    }
 }
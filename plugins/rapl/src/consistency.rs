@@ -1,6 +1,7 @@
 use crate::{
     cpus::{self, CpuVendor},
     domains::RaplDomainType,
+    msr::MsrDomain,
     perf_event::PowerEvent,
     powercap::PowerZone,
 };
@@ -11,6 +12,8 @@ pub struct SafeSubset {
     pub domains: Vec<RaplDomainType>,
     pub perf_events: Vec<PowerEvent>,
     pub power_zones: Vec<PowerZone>,
+    /// The domains to read in the MSRs, only filled when the MSRs are used.
+    pub msr_domains: Vec<MsrDomain>,
     pub is_whole: bool,
 }
 
@@ -23,6 +26,7 @@ impl SafeSubset {
             domains,
             perf_events,
             power_zones: Vec::new(),
+            msr_domains: Vec::new(),
             is_whole: true,
         }
     }
@@ -36,6 +40,20 @@ impl SafeSubset {
             domains,
             perf_events: Vec::new(),
             power_zones,
+            msr_domains: Vec::new(),
+            is_whole: true,
+        }
+    }
+
+    pub fn from_msr_only(msr_domains: Vec<MsrDomain>) -> Self {
+        let mut domains: Vec<RaplDomainType> = msr_domains.iter().map(|d| d.domain).collect();
+        domains.sort_by_key(|k| k.to_string());
+        domains.dedup_by_key(|k| k.to_string());
+        Self {
+            domains,
+            perf_events: Vec::new(),
+            power_zones: Vec::new(),
+            msr_domains,
             is_whole: true,
         }
     }
@@ -107,6 +125,7 @@ pub fn check_domains_consistency(perf_events: &Vec<PowerEvent>, power_zones: &Ve
             domains: domains_subset,
             perf_events: perf_events_subset,
             power_zones: power_zones_subset,
+            msr_domains: Vec::new(),
             is_whole: false,
         }
     } else {
@@ -114,6 +133,7 @@ pub fn check_domains_consistency(perf_events: &Vec<PowerEvent>, power_zones: &Ve
             domains: perf_rapl_domains,
             perf_events: perf_events.to_owned(),
             power_zones: power_zones.to_owned(),
+            msr_domains: Vec::new(),
             is_whole: true,
         }
     }
@@ -201,15 +221,76 @@ pub fn get_available_domains(
     })
 }
 
+/// Checks the RAPL domains found in the MSRs against the domains reported by the kernel
+/// (perf_events and powercap), and chooses the MSRs if they agree.
+///
+/// The kernel knows which registers are meaningful on each processor model, while we only probe
+/// the registers. Therefore, when the kernel reports some domains, the other MSR domains are dropped.
+/// The per-core domains of AMD processors are an exception: the kernel never reports them.
+/// If the MSRs cannot be used, the kernel interfaces are used instead.
+pub fn get_available_domains_with_msr(
+    msr_domains: anyhow::Result<Vec<MsrDomain>>,
+    kernel_domains: anyhow::Result<(SafeSubset, String)>,
+) -> anyhow::Result<(SafeSubset, String)> {
+    Ok(match (msr_domains, kernel_domains) {
+        (Ok(msr_domains), Ok((kernel_subset, kernel_origin))) => {
+            let (kept, dropped): (Vec<MsrDomain>, Vec<MsrDomain>) = msr_domains
+                .into_iter()
+                .partition(|d| d.domain == RaplDomainType::PerCore || kernel_subset.domains.contains(&d.domain));
+            if !dropped.is_empty() {
+                let mut dropped_domains: Vec<RaplDomainType> = dropped.iter().map(|d| d.domain).collect();
+                dropped_domains.dedup();
+                log::warn!(
+                    "The MSRs contain RAPL domains that the kernel does not report{kernel_origin}, I will not use them: {}",
+                    mkstring(&dropped_domains, ", ")
+                );
+            }
+            if kept.is_empty() {
+                log::warn!(
+                    "The MSRs and the kernel have no RAPL domain in common, I will not use the MSRs. Kernel domains{kernel_origin}: {}",
+                    mkstring(&kernel_subset.domains, ", ")
+                );
+                (kernel_subset, kernel_origin)
+            } else {
+                let is_whole = dropped.is_empty();
+                let mut subset = SafeSubset::from_msr_only(kept);
+                subset.is_whole = is_whole;
+                (subset, " (from msr, checked against the kernel)".to_string())
+            }
+        }
+        (Ok(msr_domains), Err(kernel_err)) => {
+            log::warn!(
+                "The RAPL domains found in the MSRs cannot be checked against the kernel, because the kernel interfaces are not available: {kernel_err:?}"
+            );
+            if msr_domains.is_empty() {
+                return Err(anyhow!(
+                    "No RAPL domain found in the MSRs, and the kernel interfaces are unavailable"
+                ));
+            }
+            (SafeSubset::from_msr_only(msr_domains), " (from msr)".to_string())
+        }
+        (Err(msr_err), Ok(kernel_domains)) => {
+            log::error!("Cannot read the RAPL domains in the MSRs: {msr_err:?}");
+            log::warn!("Because of the previous error, I will not use the MSRs.");
+            kernel_domains
+        }
+        (Err(msr_err), Err(kernel_err)) => Err(anyhow!(
+            "The MSRs, perf_events and powercap failed, unable to read RAPL counters: {msr_err}\n{kernel_err}"
+        ))?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::check_domains_consistency;
+    use super::{SafeSubset, check_domains_consistency, get_available_domains_with_msr};
     use crate::{
         consistency::{get_available_domains, mkstring},
         domains::RaplDomainType,
+        msr::MsrDomain,
         perf_event::PowerEvent,
         powercap::PowerZone,
     };
+    use alumet::resources::Resource;
     use anyhow::anyhow;
     use std::path::Path;
 
@@ -235,6 +316,17 @@ mod tests {
         }
     }
 
+    fn msr_domain(domain: RaplDomainType, resource: Resource) -> MsrDomain {
+        MsrDomain {
+            domain,
+            resource,
+            package: 0,
+            cpu: 0,
+            register: 0,
+            energy_status_unit: 14,
+        }
+    }
+
     #[test]
     fn test_check_domains_consistency_with_same_domain() -> anyhow::Result<()> {
         let power_events = vec![PowerEvent {
@@ -462,4 +554,61 @@ mod tests {
 
         assert!(result.to_string().contains("Both perf_events and powercap failed"));
     }
+
+    #[test]
+    fn test_get_available_domains_with_msr() {
+        let msr_domains = vec![
+            msr_domain(RaplDomainType::Package, Resource::CpuPackage { id: 0 }),
+            msr_domain(RaplDomainType::PP0, Resource::CpuPackage { id: 0 }),
+            msr_domain(RaplDomainType::PP1, Resource::CpuPackage { id: 0 }),
+            msr_domain(RaplDomainType::PerCore, Resource::CpuCore { id: 0 }),
+            msr_domain(RaplDomainType::PerCore, Resource::CpuCore { id: 1 }),
+        ];
+        let kernel = SafeSubset::from_powercap_only(vec![
+            power_zone(RaplDomainType::Package),
+            power_zone(RaplDomainType::PP0),
+        ]);
+
+        // pp1 is not reported by the kernel: it is dropped, but the per-core domains are kept
+        let (subset, origin) = get_available_domains_with_msr(Ok(msr_domains), Ok((kernel, String::new()))).unwrap();
+        assert_eq!(origin, " (from msr, checked against the kernel)");
+        assert_eq!(
+            subset.domains,
+            vec![RaplDomainType::Package, RaplDomainType::PerCore, RaplDomainType::PP0]
+        );
+        assert_eq!(subset.msr_domains.len(), 4);
+        assert!(!subset.is_whole);
+        assert!(subset.power_zones.is_empty());
+    }
+
+    #[test]
+    fn test_get_available_domains_with_msr_fallback() {
+        let kernel = || {
+            Ok((
+                SafeSubset::from_powercap_only(vec![power_zone(RaplDomainType::Package)]),
+                " (from powercap)".to_string(),
+            ))
+        };
+
+        // the MSRs are not available: use the kernel
+        let (subset, origin) = get_available_domains_with_msr(Err(anyhow!("no msr")), kernel()).unwrap();
+        assert_eq!(origin, " (from powercap)");
+        assert!(subset.msr_domains.is_empty());
+        assert_eq!(subset.power_zones.len(), 1);
+
+        // no domain in common: use the kernel
+        let msr_domains = vec![msr_domain(RaplDomainType::Dram, Resource::Dram { pkg_id: 0 })];
+        let (subset, origin) = get_available_domains_with_msr(Ok(msr_domains), kernel()).unwrap();
+        assert_eq!(origin, " (from powercap)");
+        assert!(subset.msr_domains.is_empty());
+
+        // the kernel interfaces are not available: use the MSRs without checking them
+        let msr_domains = vec![msr_domain(RaplDomainType::Dram, Resource::Dram { pkg_id: 0 })];
+        let (subset, origin) = get_available_domains_with_msr(Ok(msr_domains), Err(anyhow!("no kernel"))).unwrap();
+        assert_eq!(origin, " (from msr)");
+        assert_eq!(subset.domains, vec![RaplDomainType::Dram]);
+
+        let err = get_available_domains_with_msr(Err(anyhow!("no msr")), Err(anyhow!("no kernel"))).unwrap_err();
+        assert!(err.to_string().contains("unable to read RAPL counters"));
+    }
 }
@@ -15,6 +15,8 @@ pub enum RaplDomainType {
     Dram,
     /// psys (only available on recent client platforms like laptops)
     Platform,
+    /// one physical core (only available in the MSRs of AMD processors)
+    PerCore,
 }
 
 impl fmt::Display for RaplDomainType {
@@ -33,6 +35,7 @@ impl FromStr for RaplDomainType {
             "pp1" | "uncore" => Ok(RaplDomainType::PP1),
             "dram" | "ram" => Ok(RaplDomainType::Dram),
             "platform" | "psys" => Ok(RaplDomainType::Platform),
+            "per_core" => Ok(RaplDomainType::PerCore),
             _ => Err(s.to_owned()),
         }
     }
@@ -47,6 +50,8 @@ impl RaplDomainType {
             RaplDomainType::PP1 => Resource::CpuPackage { id: pkg_id },
             RaplDomainType::Dram => Resource::Dram { pkg_id },
             RaplDomainType::Platform => Resource::LocalMachine,
+            // the core is not known here, the MSR probe attaches the measurements to the right core
+            RaplDomainType::PerCore => Resource::CpuPackage { id: pkg_id },
         }
     }
 
@@ -57,6 +62,7 @@ impl RaplDomainType {
             RaplDomainType::PP1 => "pp1",
             RaplDomainType::Dram => "dram",
             RaplDomainType::Platform => "platform",
+            RaplDomainType::PerCore => "per_core",
         }
     }
 
@@ -67,6 +73,7 @@ impl RaplDomainType {
             RaplDomainType::PP1 => "pp1_total",
             RaplDomainType::Dram => "dram_total",
             RaplDomainType::Platform => "platform_total",
+            RaplDomainType::PerCore => "per_core_total",
         }
     }
 }
@@ -84,6 +91,7 @@ mod tests {
             ("pp1", Ok(RaplDomainType::PP1)),
             ("dram", Ok(RaplDomainType::Dram)),
             ("platform", Ok(RaplDomainType::Platform)),
+            ("per_core", Ok(RaplDomainType::PerCore)),
             ("unknown", Err("unknown".to_string())),
         ];
         for (input, expectation) in expectations {
@@ -9,20 +9,23 @@ use alumet::{
 };
 use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
-use std::{path::Path, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
-    consistency::{SafeSubset, get_available_domains},
+    consistency::{SafeSubset, get_available_domains, get_available_domains_with_msr},
+    msr::{MsrDomain, MsrProbe},
     perf_event::{PERF_SYSFS_DIR, PerfEventProbe, PowerEvent},
     powercap::{PowerZone, PowercapProbe},
 };
 
-#[cfg(test)]
-use std::path::PathBuf;
-
 mod consistency;
 mod cpus;
 mod domains;
+mod msr;
+mod overflow;
 mod perf_event;
 mod powercap;
 mod total;
@@ -54,6 +57,31 @@ impl RaplPlugin {
         Ok(powercap::all_power_zones_from_path(&self.config.powercap_test_path)?.flat)
     }
 
+    #[cfg(not(test))]
+    fn get_all_msr_domains(&self) -> anyhow::Result<Vec<MsrDomain>> {
+        msr::all_msr_domains()
+    }
+
+    #[cfg(test)]
+    fn get_all_msr_domains(&self) -> anyhow::Result<Vec<MsrDomain>> {
+        let base_path = &self.config.msr_test_path;
+        msr::all_msr_domains_from_path(
+            &base_path.join("dev/cpu"),
+            &base_path.join("sys/devices/system/cpu"),
+            &base_path.join("proc/cpuinfo"),
+        )
+    }
+
+    #[cfg(not(test))]
+    fn msr_dev_dir(&self) -> PathBuf {
+        PathBuf::from(msr::MSR_DEV_DIR)
+    }
+
+    #[cfg(test)]
+    fn msr_dev_dir(&self) -> PathBuf {
+        self.config.msr_test_path.join("dev/cpu")
+    }
+
     #[cfg(not(test))]
     fn perf_sysfs_dir(&self) -> &Path {
         Path::new(PERF_SYSFS_DIR)
@@ -110,13 +138,20 @@ impl AlumetPlugin for RaplPlugin {
         let try_perf_events = self.get_all_power_events();
         let try_power_zones = self.get_all_power_zones();
 
-        let (available_domains, subset_indicator) = get_available_domains(
+        let try_kernel_domains = get_available_domains(
             try_perf_events,
             try_power_zones,
             check_consistency,
             &mut use_perf,
             &mut use_powercap,
-        )?;
+        );
+
+        // If enabled, discover the RAPL domains available in the MSRs and check them against the kernel.
+        let (available_domains, subset_indicator) = if self.config.use_msr {
+            get_available_domains_with_msr(self.get_all_msr_domains(), try_kernel_domains)?
+        } else {
+            try_kernel_domains?
+        };
 
         // We have found a set of RAPL domains that we agree on (in the best case, perf_events and powercap both work, are accessible by the agent and report the same list of domains).
         log::info!(
@@ -132,7 +167,14 @@ impl AlumetPlugin for RaplPlugin {
         )?;
 
         // Create the measurement source.
-        let source = match (use_perf, use_powercap) {
+        let source: Box<dyn Source> = match (use_perf, use_powercap) {
+            _ if !available_domains.msr_domains.is_empty() => {
+                // the MSRs have been enabled and checked
+                Box::new(
+                    MsrProbe::new(metric, &available_domains.msr_domains, &self.msr_dev_dir())
+                        .context("Failed to create RAPL probe based on the MSRs")?,
+                )
+            }
             (true, true) => {
                 // prefer perf_events, fallback to powercap if it fails
                 setup_perf_events_probe_or_fallback(metric, &available_domains)?
@@ -160,11 +202,14 @@ impl AlumetPlugin for RaplPlugin {
         };
 
         // Configure the source and add it to Alumet
-        let trigger = builder::time_interval(self.config.poll_interval)
+        let mut trigger = builder::time_interval(self.config.poll_interval);
+        trigger
             .flush_interval(self.config.flush_interval)
-            .update_interval(self.config.flush_interval)
-            .build()
-            .unwrap();
+            .update_interval(self.config.flush_interval);
+        if let Some(group) = &self.config.trigger_group {
+            trigger.group(group);
+        }
+        let trigger = trigger.build().context("invalid trigger configuration")?;
         alumet.add_source("in", source, trigger)?;
         Ok(())
     }
@@ -204,10 +249,21 @@ pub struct Config {
     /// Set to true to disable perf_events and always use the powercap sysfs.
     pub no_perf_events: bool,
 
+    /// Set to true to read the RAPL counters directly in the MSRs of the CPUs (`/dev/cpu/*/msr`),
+    /// instead of using perf_events or powercap.
+    #[serde(default)]
+    pub use_msr: bool,
+
+    /// Name of a trigger group, to poll the RAPL source at the same time as the other sources of the group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_group: Option<String>,
+
     #[cfg(test)]
     pub perf_event_test_path: PathBuf,
     #[cfg(test)]
     pub powercap_test_path: PathBuf,
+    #[cfg(test)]
+    pub msr_test_path: PathBuf,
 }
 
 impl Default for Config {
@@ -216,11 +272,15 @@ impl Default for Config {
             poll_interval: Duration::from_secs(1), // 1Hz
             flush_interval: Duration::from_secs(5),
             no_perf_events: false, // prefer perf_events
+            use_msr: false,
+            trigger_group: None,
 
             #[cfg(test)]
             perf_event_test_path: PathBuf::from(""),
             #[cfg(test)]
             powercap_test_path: PathBuf::from(""),
+            #[cfg(test)]
+            msr_test_path: PathBuf::from(""),
         }
     }
 }
@@ -240,5 +300,15 @@ mod test {
         assert_eq!(config.poll_interval, Duration::from_secs(1));
         assert_eq!(config.flush_interval, Duration::from_secs(5));
         assert_eq!(config.no_perf_events, false);
+        assert!(!config.use_msr);
+        assert_eq!(config.trigger_group, None);
+    }
+
+    #[test]
+    fn test_trigger_group_config() {
+        let mut table = RaplPlugin::default_config().unwrap().unwrap();
+        table.0.insert(String::from("trigger_group"), toml::Value::from("energy"));
+        let config: Config = deserialize_config(table).unwrap();
+        assert_eq!(config.trigger_group.as_deref(), Some("energy"));
     }
 }
@@ -0,0 +1,593 @@
+//! RAPL energy counters read directly from the Model-Specific Registers (MSRs) of the CPUs,
+//! through the device files `/dev/cpu/<cpu>/msr` of the `msr` kernel module.
+//!
+//! This requires more privileges than perf_events and powercap, but it gives access to the
+//! per-core energy counters of AMD Zen processors, and to the raw `energy_status_unit`.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    plugin::util::{CounterDiff, CounterDiffUpdate},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+
+use crate::{cpus::CpuVendor, domains::RaplDomainType, overflow::OverflowCheck, total::DomainTotals};
+
+pub const MSR_DEV_DIR: &str = "/dev/cpu";
+pub const CPU_SYSFS_DIR: &str = "/sys/devices/system/cpu";
+pub const CPUINFO_PATH: &str = "/proc/cpuinfo";
+const PERMISSION_ADVICE: &str = "Make sure that the msr kernel module is loaded (sudo modprobe msr), and run the agent as root or give CAP_SYS_RAWIO to the application's binary.";
+
+/// The energy status registers are 32 bits wide, the upper bits are reserved.
+const ENERGY_STATUS_MAX: u64 = u32::MAX as u64;
+
+/// Registers of the Intel processors, see the Intel SDM, volume 4.
+mod intel {
+    pub const RAPL_POWER_UNIT: u64 = 0x606;
+    pub const PKG_ENERGY_STATUS: u64 = 0x611;
+    pub const DRAM_ENERGY_STATUS: u64 = 0x619;
+    pub const PP0_ENERGY_STATUS: u64 = 0x639;
+    pub const PP1_ENERGY_STATUS: u64 = 0x641;
+    pub const PLATFORM_ENERGY_STATUS: u64 = 0x64d;
+
+    /// Models (of family 6) whose DRAM domain ignores the `energy_status_unit` of the power unit register
+    /// and always counts in increments of 2^-16 J (15.3µJ): the servers since Haswell-EP and the Xeon Phi.
+    /// This is the same list as in the `intel_rapl_common` driver of the Linux kernel.
+    const FIXED_DRAM_UNIT_MODELS: &[u32] = &[
+        0x3f, // Haswell-X
+        0x4f, // Broadwell-X
+        0x55, // Skylake-X, Cascade Lake, Cooper Lake
+        0x57, // Xeon Phi Knights Landing
+        0x6a, // Ice Lake-X
+        0x6c, // Ice Lake-D
+        0x85, // Xeon Phi Knights Mill
+        0x8f, // Sapphire Rapids-X
+        0xad, // Granite Rapids-X
+        0xae, // Granite Rapids-D
+        0xaf, // Sierra Forest-X
+        0xcf, // Emerald Rapids-X
+    ];
+
+    /// Returns the `energy_status_unit` of the DRAM domain, if it does not follow the power unit register.
+    pub fn dram_energy_status_unit(family: u32, model: u32) -> Option<u8> {
+        (family == 6 && FIXED_DRAM_UNIT_MODELS.contains(&model)).then_some(16)
+    }
+}
+
+/// Registers of the AMD processors (Zen and later), see the AMD PPR.
+mod amd {
+    pub const RAPL_POWER_UNIT: u64 = 0xc001_0299;
+    pub const CORE_ENERGY_STATUS: u64 = 0xc001_029a;
+    pub const PKG_ENERGY_STATUS: u64 = 0xc001_029b;
+}
+
+/// A RAPL energy counter that can be read through the MSRs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsrDomain {
+    /// The RAPL domain type, as an enum.
+    pub domain: RaplDomainType,
+    /// The resource measured by the counter: a package, or a core for the AMD core domains.
+    pub resource: Resource,
+    /// The id of the package (socket) that contains the CPU.
+    pub package: u32,
+    /// The CPU whose MSRs must be read.
+    pub cpu: u32,
+    /// The address of the energy status register.
+    pub register: u64,
+    /// The `energy_status_unit` field of the power unit register: one increment of the counter
+    /// is `1/2^energy_status_unit` Joules.
+    pub energy_status_unit: u8,
+}
+
+/// The MSR device file of a CPU.
+#[derive(Debug)]
+struct Msr {
+    file: File,
+}
+
+/// Position of a CPU in the topology of the machine.
+#[derive(Debug, Clone, Copy)]
+struct CpuTopology {
+    cpu: u32,
+    package: u32,
+    core: u32,
+}
+
+/// manages MSR counter collection
+struct OpenedMsrDomain {
+    msr: Msr,
+    register: u64,
+    energy_unit: f64,
+    domain: RaplDomainType,
+    resource: Resource,
+    counter: CounterDiff,
+    overflow: OverflowCheck,
+}
+
+/// Energy probe based on the MSRs.
+pub struct MsrProbe {
+    metric: TypedMetricId<f64>,
+    domains: Vec<OpenedMsrDomain>,
+}
+
+/// Retrieves all the RAPL domains that can be read in the MSRs of the CPUs.
+pub fn all_msr_domains() -> anyhow::Result<Vec<MsrDomain>> {
+    all_msr_domains_from_path(
+        Path::new(MSR_DEV_DIR),
+        Path::new(CPU_SYSFS_DIR),
+        Path::new(CPUINFO_PATH),
+    )
+}
+
+/// Retrieves all the RAPL domains that can be read in the MSRs of the CPUs.
+///
+/// `dev_dir` contains one `<cpu>/msr` file per CPU (e.g. `/dev/cpu`), `sysfs_dir`
+/// describes the topology of the CPUs (e.g. `/sys/devices/system/cpu`) and `cpuinfo`
+/// gives the model of the processor (e.g. `/proc/cpuinfo`).
+///
+/// Like in the kernel, the registers that cannot be read or that are zero are considered
+/// to be unsupported (some processors have the registers of unused domains, e.g. PP1 on servers).
+pub fn all_msr_domains_from_path(dev_dir: &Path, sysfs_dir: &Path, cpuinfo: &Path) -> anyhow::Result<Vec<MsrDomain>> {
+    let cpus = cpus_with_msr(dev_dir)?;
+    let topology: Vec<CpuTopology> = cpus
+        .iter()
+        .map(|cpu| cpu_topology(sysfs_dir, *cpu))
+        .collect::<anyhow::Result<_>>()?;
+    let first_cpu = topology
+        .first()
+        .with_context(|| format!("No MSR device found in {dev_dir:?}. {PERMISSION_ADVICE}"))?;
+
+    // The power unit register is at a different address on Intel and AMD processors.
+    let msr = Msr::open(dev_dir, first_cpu.cpu)?;
+    let vendor = if msr.is_supported(intel::RAPL_POWER_UNIT) {
+        CpuVendor::Intel
+    } else if msr.is_supported(amd::RAPL_POWER_UNIT) {
+        CpuVendor::Amd
+    } else {
+        return Err(anyhow!(
+            "Could not read the RAPL power unit register of cpu {}, RAPL does not seem to be supported by this processor.",
+            first_cpu.cpu
+        ));
+    };
+    let power_unit_register = match vendor {
+        CpuVendor::Intel => intel::RAPL_POWER_UNIT,
+        CpuVendor::Amd => amd::RAPL_POWER_UNIT,
+    };
+    let dram_unit = match (vendor, cpu_family_and_model(cpuinfo)) {
+        (CpuVendor::Intel, Ok((family, model))) => intel::dram_energy_status_unit(family, model),
+        (CpuVendor::Intel, Err(e)) => {
+            log::warn!("Could not find the model of the processor, the DRAM energy unit may be wrong: {e:#}");
+            None
+        }
+        (CpuVendor::Amd, _) => None,
+    };
+
+    // The package-level counters can be read on any CPU of the package: use the first one.
+    let mut package_cpus: BTreeMap<u32, u32> = BTreeMap::new();
+    for t in &topology {
+        package_cpus.entry(t.package).or_insert(t.cpu);
+    }
+
+    let mut domains = Vec::new();
+    for (i, (&package, &cpu)) in package_cpus.iter().enumerate() {
+        let msr = Msr::open(dev_dir, cpu)?;
+        let energy_status_unit = energy_status_unit(msr.read(power_unit_register)?);
+        let registers: &[(RaplDomainType, u64)] = match vendor {
+            CpuVendor::Intel => &[
+                (RaplDomainType::Package, intel::PKG_ENERGY_STATUS),
+                (RaplDomainType::PP0, intel::PP0_ENERGY_STATUS),
+                (RaplDomainType::PP1, intel::PP1_ENERGY_STATUS),
+                (RaplDomainType::Dram, intel::DRAM_ENERGY_STATUS),
+                (RaplDomainType::Platform, intel::PLATFORM_ENERGY_STATUS),
+            ],
+            CpuVendor::Amd => &[(RaplDomainType::Package, amd::PKG_ENERGY_STATUS)],
+        };
+        for &(domain, register) in registers {
+            // psys covers the whole machine, it is the same on every package
+            if domain == RaplDomainType::Platform && i > 0 {
+                continue;
+            }
+            if msr.is_supported(register) {
+                let energy_status_unit = match (domain, dram_unit) {
+                    (RaplDomainType::Dram, Some(unit)) => unit,
+                    _ => energy_status_unit,
+                };
+                domains.push(MsrDomain {
+                    domain,
+                    resource: domain.to_resource(package),
+                    package,
+                    cpu,
+                    register,
+                    energy_status_unit,
+                });
+            }
+        }
+    }
+
+    // AMD processors also have one counter per core, shared by its hardware threads.
+    if vendor == CpuVendor::Amd {
+        let mut core_cpus: BTreeMap<(u32, u32), u32> = BTreeMap::new();
+        for t in &topology {
+            core_cpus.entry((t.package, t.core)).or_insert(t.cpu);
+        }
+        for (&(package, _), &cpu) in &core_cpus {
+            let msr = Msr::open(dev_dir, cpu)?;
+            if msr.is_supported(amd::CORE_ENERGY_STATUS) {
+                domains.push(MsrDomain {
+                    domain: RaplDomainType::PerCore,
+                    // identify the core by its first cpu, like the percore events of the perf plugin
+                    resource: Resource::CpuCore { id: cpu },
+                    package,
+                    cpu,
+                    register: amd::CORE_ENERGY_STATUS,
+                    energy_status_unit: energy_status_unit(msr.read(amd::RAPL_POWER_UNIT)?),
+                });
+            }
+        }
+    }
+    Ok(domains)
+}
+
+/// Lists the CPUs that have a MSR device file, in ascending order.
+fn cpus_with_msr(dev_dir: &Path) -> anyhow::Result<Vec<u32>> {
+    let mut cpus = Vec::new();
+    let entries = fs::read_dir(dev_dir).with_context(|| format!("Could not read {dev_dir:?}. {PERMISSION_ADVICE}"))?;
+    for e in entries {
+        let path = e?.path();
+        let cpu = path.file_name().and_then(|n| n.to_str()).and_then(|n| n.parse().ok());
+        if let Some(cpu) = cpu
+            && path.join("msr").exists()
+        {
+            cpus.push(cpu);
+        }
+    }
+    cpus.sort();
+    Ok(cpus)
+}
+
+fn cpu_topology(sysfs_dir: &Path, cpu: u32) -> anyhow::Result<CpuTopology> {
+    let read_id = |file: &str| -> anyhow::Result<u32> {
+        let path = sysfs_dir.join(format!("cpu{cpu}/topology/{file}"));
+        let read = fs::read_to_string(&path).with_context(|| format!("Failed to read {path:?}"))?;
+        read.trim_end()
+            .parse()
+            .with_context(|| format!("Failed to parse {path:?}: '{read}'"))
+    };
+    Ok(CpuTopology {
+        cpu,
+        package: read_id("physical_package_id")?,
+        core: read_id("core_id")?,
+    })
+}
+
+/// Reads the family and model of the first processor in `cpuinfo` (usually `/proc/cpuinfo`).
+fn cpu_family_and_model(cpuinfo: &Path) -> anyhow::Result<(u32, u32)> {
+    let content = fs::read_to_string(cpuinfo).with_context(|| format!("Failed to read {cpuinfo:?}"))?;
+    let field = |name: &str| -> anyhow::Result<u32> {
+        let line = content
+            .lines()
+            .find(|l| l.split(':').next().is_some_and(|key| key.trim_end() == name))
+            .with_context(|| format!("No '{name}' in {cpuinfo:?}"))?;
+        let value = line.split_once(':').map(|(_, v)| v.trim()).unwrap_or_default();
+        value
+            .parse()
+            .with_context(|| format!("Failed to parse the '{name}' in {cpuinfo:?}: '{value}'"))
+    };
+    Ok((field("cpu family")?, field("model")?))
+}
+
+/// Extracts the `energy_status_unit` field (bits 12:8) of the power unit register.
+fn energy_status_unit(power_unit: u64) -> u8 {
+    ((power_unit >> 8) & 0x1f) as u8
+}
+
+impl Msr {
+    fn open(dev_dir: &Path, cpu: u32) -> anyhow::Result<Self> {
+        let path: PathBuf = dev_dir.join(format!("{cpu}/msr"));
+        let file = File::open(&path).with_context(|| format!("Could not open {path:?}. {PERMISSION_ADVICE}"))?;
+        Ok(Self { file })
+    }
+
+    /// Reads a register.
+    ///
+    /// In the device file, the offset of a register is its address, and reading 8 bytes at this offset
+    /// gives the value of the register.
+    fn read(&self, register: u64) -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        self.file.read_exact_at(&mut buf, register)?;
+        Ok(u64::from_ne_bytes(buf))
+    }
+
+    fn is_supported(&self, register: u64) -> bool {
+        matches!(self.read(register), Ok(value) if value != 0)
+    }
+}
+
+impl MsrDomain {
+    /// The energy of one increment of the counter, in Joules.
+    pub fn energy_unit(&self) -> f64 {
+        0.5f64.powi(self.energy_status_unit.into())
+    }
+
+    fn open(&self, dev_dir: &Path) -> anyhow::Result<OpenedMsrDomain> {
+        let energy_unit = self.energy_unit();
+        Ok(OpenedMsrDomain {
+            msr: Msr::open(dev_dir, self.cpu)?,
+            register: self.register,
+            energy_unit,
+            domain: self.domain,
+            resource: self.resource.clone(),
+            counter: CounterDiff::with_max_value(ENERGY_STATUS_MAX),
+            overflow: OverflowCheck::new((ENERGY_STATUS_MAX + 1) as f64 * energy_unit),
+        })
+    }
+}
+
+impl OpenedMsrDomain {
+    fn read_counter_diff_in_joules(&mut self) -> anyhow::Result<Option<f64>> {
+        let value = self
+            .msr
+            .read(self.register)
+            .with_context(|| format!("failed to read MSR {:#x} for domain {}", self.register, self.domain))?;
+
+        let diff = match self.counter.update(value & ENERGY_STATUS_MAX) {
+            CounterDiffUpdate::FirstTime => return Ok(None),
+            CounterDiffUpdate::Difference(diff) => diff,
+            CounterDiffUpdate::CorrectedDifference(diff) => {
+                log::debug!("Overflow on MSR counter for RAPL domain {}", self.domain);
+                diff
+            }
+        };
+        let joules = diff as f64 * self.energy_unit;
+        self.overflow.check(joules, self.domain);
+        Ok(Some(joules))
+    }
+}
+
+impl MsrProbe {
+    /// creates a new MsrProbe by passing an Alumet metric ID for energy measurement and the domains to read
+    pub fn new(metric: TypedMetricId<f64>, domains: &[MsrDomain], dev_dir: &Path) -> anyhow::Result<MsrProbe> {
+        if domains.is_empty() {
+            return Err(anyhow!("At least one RAPL domain is required for MsrProbe"));
+        }
+
+        // The DRAM domain of some processors has its own unit.
+        let mut units: BTreeMap<(u32, u8), Vec<&str>> = BTreeMap::new();
+        for d in domains {
+            let names = units.entry((d.package, d.energy_status_unit)).or_default();
+            if !names.contains(&d.domain.as_str()) {
+                names.push(d.domain.as_str());
+            }
+        }
+        for ((package, unit), names) in units {
+            log::info!(
+                "RAPL energy_status_unit of package {package}: {unit} (1 increment = {:e} J) for domains {}",
+                0.5f64.powi(unit.into()),
+                names.join(", ")
+            );
+        }
+
+        let opened = domains.iter().map(|d| d.open(dev_dir)).collect::<anyhow::Result<_>>()?;
+        Ok(MsrProbe {
+            metric,
+            domains: opened,
+        })
+    }
+}
+
+impl Source for MsrProbe {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let mut totals = DomainTotals::new();
+        for d in &mut self.domains {
+            if let Some(joules) = d.read_counter_diff_in_joules()? {
+                let consumer = ResourceConsumer::LocalMachine;
+                measurements.push(
+                    MeasurementPoint::new(timestamp, self.metric, d.resource.clone(), consumer, joules)
+                        .with_attr("domain", d.domain.as_str()),
+                );
+                totals.push(d.domain, joules);
+            }
+        }
+        for (domain, total) in totals.iter() {
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    total,
+                )
+                .with_attr("domain", domain.as_str_total()),
+            );
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> anyhow::Result<()> {
+        for d in &mut self.domains {
+            d.counter.reset();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::mocks::{MockCpu, create_mock_cpuinfo, create_mock_msr};
+
+    use tempfile::tempdir;
+
+    /// energy_status_unit = 14 (61µJ), like most Intel processors
+    const INTEL_UNIT: u64 = 0xa0e03;
+    /// energy_status_unit = 16 (15.3µJ), like AMD Zen processors
+    const AMD_UNIT: u64 = 0xa1003;
+
+    #[test]
+    fn test_energy_status_unit() {
+        assert_eq!(energy_status_unit(INTEL_UNIT), 14);
+        assert_eq!(energy_status_unit(AMD_UNIT), 16);
+    }
+
+    #[test]
+    fn test_intel_domains() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        // 2 packages with 2 cpus each, no pp1 register
+        let cpus = [
+            MockCpu::new(0, 0, 0),
+            MockCpu::new(1, 0, 1),
+            MockCpu::new(2, 1, 0),
+            MockCpu::new(3, 1, 1),
+        ];
+        let registers = [
+            (intel::RAPL_POWER_UNIT, INTEL_UNIT),
+            (intel::PKG_ENERGY_STATUS, 1000),
+            (intel::DRAM_ENERGY_STATUS, 100),
+            (intel::PP0_ENERGY_STATUS, 500),
+            (intel::PLATFORM_ENERGY_STATUS, 5000),
+        ];
+        let (dev_dir, sysfs_dir) = create_mock_msr(tmp.path(), &cpus, &registers)?;
+
+        // Kaby Lake: the DRAM domain uses the unit of the power unit register
+        let cpuinfo = create_mock_cpuinfo(tmp.path(), 6, 0x9e)?;
+
+        let domains = all_msr_domains_from_path(&dev_dir, &sysfs_dir, &cpuinfo)?;
+        let summary: Vec<_> = domains.iter().map(|d| (d.domain, d.resource.clone(), d.cpu)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (RaplDomainType::Package, Resource::CpuPackage { id: 0 }, 0),
+                (RaplDomainType::PP0, Resource::CpuPackage { id: 0 }, 0),
+                (RaplDomainType::Dram, Resource::Dram { pkg_id: 0 }, 0),
+                (RaplDomainType::Platform, Resource::LocalMachine, 0),
+                (RaplDomainType::Package, Resource::CpuPackage { id: 1 }, 2),
+                (RaplDomainType::PP0, Resource::CpuPackage { id: 1 }, 2),
+                (RaplDomainType::Dram, Resource::Dram { pkg_id: 1 }, 2),
+            ]
+        );
+        assert!(domains.iter().all(|d| d.energy_status_unit == 14));
+        Ok(())
+    }
+
+    #[test]
+    fn test_intel_server_dram_unit() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let cpus = [MockCpu::new(0, 0, 0)];
+        let registers = [
+            (intel::RAPL_POWER_UNIT, INTEL_UNIT),
+            (intel::PKG_ENERGY_STATUS, 1000),
+            (intel::DRAM_ENERGY_STATUS, 100),
+        ];
+        let (dev_dir, sysfs_dir) = create_mock_msr(tmp.path(), &cpus, &registers)?;
+        // Skylake-X: the DRAM domain has a fixed unit of 2^-16 J
+        let cpuinfo = create_mock_cpuinfo(tmp.path(), 6, 0x55)?;
+
+        let domains = all_msr_domains_from_path(&dev_dir, &sysfs_dir, &cpuinfo)?;
+        let units: Vec<_> = domains.iter().map(|d| (d.domain, d.energy_status_unit)).collect();
+        assert_eq!(units, vec![(RaplDomainType::Package, 14), (RaplDomainType::Dram, 16)]);
+
+        // without the model of the processor, the unit of the power unit register is used
+        let domains = all_msr_domains_from_path(&dev_dir, &sysfs_dir, &tmp.path().join("nothing"))?;
+        assert!(domains.iter().all(|d| d.energy_status_unit == 14));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cpu_family_and_model() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let cpuinfo = tmp.path().join("cpuinfo");
+        fs::write(
+            &cpuinfo,
+            "processor\t: 0\nvendor_id\t: GenuineIntel\ncpu family\t: 6\nmodel\t\t: 85\nmodel name\t: Intel(R) Xeon(R) Gold 6130 CPU @ 2.10GHz\n\nprocessor\t: 1\n",
+        )?;
+        assert_eq!(cpu_family_and_model(&cpuinfo)?, (6, 0x55));
+        Ok(())
+    }
+
+    #[test]
+    fn test_amd_domains() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        // 1 package with 2 cores, 2 threads per core
+        let cpus = [
+            MockCpu::new(0, 0, 0),
+            MockCpu::new(1, 0, 1),
+            MockCpu::new(2, 0, 0),
+            MockCpu::new(3, 0, 1),
+        ];
+        // The fake registers overlap (see create_mock_msr): the lowest byte of the core register
+        // is the second byte of the power unit register, which contains the energy_status_unit.
+        let registers = [
+            (amd::RAPL_POWER_UNIT, AMD_UNIT),
+            (amd::CORE_ENERGY_STATUS, AMD_UNIT >> 8),
+            (amd::PKG_ENERGY_STATUS, 1000),
+        ];
+        let (dev_dir, sysfs_dir) = create_mock_msr(tmp.path(), &cpus, &registers)?;
+
+        let cpuinfo = create_mock_cpuinfo(tmp.path(), 0x19, 0x01)?;
+
+        let domains = all_msr_domains_from_path(&dev_dir, &sysfs_dir, &cpuinfo)?;
+        let summary: Vec<_> = domains.iter().map(|d| (d.domain, d.resource.clone(), d.cpu)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (RaplDomainType::Package, Resource::CpuPackage { id: 0 }, 0),
+                (RaplDomainType::PerCore, Resource::CpuCore { id: 0 }, 0),
+                (RaplDomainType::PerCore, Resource::CpuCore { id: 1 }, 1),
+            ]
+        );
+        assert_eq!(domains[0].energy_unit(), 1.0 / 65536.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_rapl() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let (dev_dir, sysfs_dir) = create_mock_msr(tmp.path(), &[MockCpu::new(0, 0, 0)], &[])?;
+        let err = all_msr_domains_from_path(&dev_dir, &sysfs_dir, &tmp.path().join("cpuinfo")).unwrap_err();
+        assert!(err.to_string().contains("RAPL does not seem to be supported"));
+
+        let err = all_msr_domains_from_path(&tmp.path().join("nothing"), &sysfs_dir, &tmp.path().join("cpuinfo"))
+            .unwrap_err();
+        assert!(err.to_string().contains("msr kernel module"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_overflow() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let cpus = [MockCpu::new(0, 0, 0)];
+        let (dev_dir, sysfs_dir) = create_mock_msr(
+            tmp.path(),
+            &cpus,
+            &[
+                (intel::RAPL_POWER_UNIT, INTEL_UNIT),
+                (intel::PKG_ENERGY_STATUS, 0xffff_c000),
+            ],
+        )?;
+        let domains = all_msr_domains_from_path(&dev_dir, &sysfs_dir, &tmp.path().join("cpuinfo"))?;
+        let mut opened = domains[0].open(&dev_dir)?;
+        assert_eq!(opened.read_counter_diff_in_joules()?, None);
+
+        // the reserved bits are ignored, and the overflow is corrected: 0x4000 + 0x4000 increments = 2 J
+        create_mock_msr(tmp.path(), &cpus, &[(intel::PKG_ENERGY_STATUS, 0xabcd_0000_0000_4000)])?;
+        assert_eq!(opened.read_counter_diff_in_joules()?, Some(2.0));
+        assert!(!opened.overflow.check(2.0, RaplDomainType::Package));
+
+        // the counter has advanced by more than half of its range: an overflow may have been missed
+        create_mock_msr(tmp.path(), &cpus, &[(intel::PKG_ENERGY_STATUS, 0x8000_4001)])?;
+        let joules = opened.read_counter_diff_in_joules()?.unwrap();
+        assert_eq!(joules, 0x8000_0001_u32 as f64 / 16384.0);
+        assert!(opened.overflow.check(joules, RaplDomainType::Package));
+        Ok(())
+    }
+}
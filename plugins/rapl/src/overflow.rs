@@ -0,0 +1,57 @@
+use crate::domains::RaplDomainType;
+
+/// Detects the overflows of an energy counter that cannot be corrected.
+///
+/// [`CounterDiff`](alumet::plugin::util::CounterDiff) corrects one overflow between two measurements,
+/// but it cannot see a second one. When the energy consumed between two measurements gets close to the
+/// range of the counter, an overflow may go unnoticed and the energy would then be underestimated.
+/// This happens at low poll rates: for instance, a 32-bit counter with the usual unit of 61µJ
+/// overflows after about 262kJ, that is 22 minutes at 200W.
+pub struct OverflowCheck {
+    /// The maximum energy that the counter can hold, in Joules.
+    range: f64,
+    warned: bool,
+}
+
+impl OverflowCheck {
+    pub fn new(range: f64) -> Self {
+        Self { range, warned: false }
+    }
+
+    /// Checks the energy consumed since the previous measurement.
+    ///
+    /// Returns `true` if the energy is more than half of the counter range, in which case
+    /// an overflow may have been missed. The first time, a warning is logged.
+    pub fn check(&mut self, joules: f64, domain: RaplDomainType) -> bool {
+        let risky = joules > self.range / 2.0;
+        if risky {
+            let msg = format!(
+                "The energy consumed by the RAPL domain {domain} between two measurements ({joules:.3} J) is more than half of the range of its counter ({:.3} J): some overflows may have been missed. Please decrease the poll_interval.",
+                self.range
+            );
+            if self.warned {
+                log::debug!("{msg}");
+            } else {
+                log::warn!("{msg}");
+                self.warned = true;
+            }
+        }
+        risky
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OverflowCheck;
+    use crate::domains::RaplDomainType;
+
+    #[test]
+    fn test_overflow_check() {
+        let mut check = OverflowCheck::new(1000.0);
+        assert!(!check.check(0.0, RaplDomainType::Package));
+        assert!(!check.check(500.0, RaplDomainType::Package));
+        assert!(check.check(500.1, RaplDomainType::Package));
+        assert!(check.warned);
+        assert!(check.check(999.0, RaplDomainType::Package));
+    }
+}
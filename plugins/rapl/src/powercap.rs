@@ -8,7 +8,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::total::DomainTotals;
+use crate::{overflow::OverflowCheck, total::DomainTotals};
 
 use super::domains::RaplDomainType;
 use alumet::plugin::util::{CounterDiff, CounterDiffUpdate};
@@ -68,6 +68,8 @@ struct OpenedPowerZone {
     resource: Resource,
     /// Overflow-correcting counter, to compute the energy consumption difference.
     counter: CounterDiff,
+    /// Detects the overflows that the counter cannot correct.
+    overflow: OverflowCheck,
 }
 
 /// Powercap probe collects Alumet metrics related to power zones
@@ -221,6 +223,7 @@ impl PowerZone {
             domain: self.domain,
             resource: self.domain.to_resource(socket),
             counter,
+            overflow: OverflowCheck::new(max_energy_uj as f64 * POWERCAP_ENERGY_UNIT),
         })
     }
 
@@ -252,7 +255,11 @@ impl OpenedPowerZone {
     fn read_counter_diff_in_joules(&mut self, self_reading_buf: &mut Vec<u8>) -> anyhow::Result<Option<f64>> {
         // convert to joules and push
         match self.read_counter_diff(self_reading_buf)? {
-            Some(diff) => Ok(Some((diff as f64) * POWERCAP_ENERGY_UNIT)),
+            Some(diff) => {
+                let joules = (diff as f64) * POWERCAP_ENERGY_UNIT;
+                self.overflow.check(joules, self.domain);
+                Ok(Some(joules))
+            }
             None => Ok(None),
         }
     }
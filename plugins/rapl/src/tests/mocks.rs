@@ -1,4 +1,6 @@
-use std::fs::{self, File};
+use std::fs::{self, File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::path::PathBuf;
 use std::{io::Write, path::Path};
 use tempfile::{TempDir, tempdir};
 
@@ -127,3 +129,67 @@ pub fn create_valid_powercap_mock() -> anyhow::Result<TempDir> {
     create_mock_layout(tmp.path(), &entries)?;
     Ok(tmp)
 }
+
+/// A CPU of the mock MSR layout.
+pub struct MockCpu {
+    pub cpu: u32,
+    pub package: u32,
+    pub core: u32,
+}
+
+impl MockCpu {
+    pub fn new(cpu: u32, package: u32, core: u32) -> Self {
+        Self { cpu, package, core }
+    }
+}
+
+/// Creates (or updates) fake MSR device files and the corresponding CPU topology under the given base path.
+///
+/// Every register is written in the `msr` file of every CPU, at the offset given by its address, like in the
+/// real device files. Since the addresses are large (up to `0xc001_029b` on AMD), the files are sparse.
+///
+/// Unlike in the device files, the registers that are less than 8 bytes apart overlap (the power unit, core and
+/// package registers of AMD processors are consecutive). The registers are written in ascending order of address,
+/// therefore reading such a register gives its lowest bytes followed by the bytes of the next registers.
+/// Returns the paths of the fake `/dev/cpu` and `/sys/devices/system/cpu` directories.
+pub fn create_mock_msr(
+    base_path: &Path,
+    cpus: &[MockCpu],
+    registers: &[(u64, u64)],
+) -> std::io::Result<(PathBuf, PathBuf)> {
+    let dev_dir = base_path.join("dev/cpu");
+    let sysfs_dir = base_path.join("sys/devices/system/cpu");
+    for cpu in cpus {
+        let cpu_dir = dev_dir.join(cpu.cpu.to_string());
+        fs::create_dir_all(&cpu_dir)?;
+        let msr = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(cpu_dir.join("msr"))?;
+        let mut registers = registers.to_vec();
+        registers.sort_by_key(|(register, _)| *register);
+        for (register, value) in registers {
+            msr.write_all_at(&value.to_ne_bytes(), register)?;
+        }
+
+        let topology_dir = sysfs_dir.join(format!("cpu{}/topology", cpu.cpu));
+        fs::create_dir_all(&topology_dir)?;
+        fs::write(topology_dir.join("physical_package_id"), format!("{}\n", cpu.package))?;
+        fs::write(topology_dir.join("core_id"), format!("{}\n", cpu.core))?;
+    }
+    Ok((dev_dir, sysfs_dir))
+}
+
+/// Creates a fake `/proc/cpuinfo` with the given processor family and model, under the given base path.
+///
+/// Returns the path of the file.
+pub fn create_mock_cpuinfo(base_path: &Path, family: u32, model: u32) -> std::io::Result<PathBuf> {
+    let path = base_path.join("proc/cpuinfo");
+    fs::create_dir_all(base_path.join("proc"))?;
+    fs::write(
+        &path,
+        format!("processor\t: 0\ncpu family\t: {family}\nmodel\t\t: {model}\n"),
+    )?;
+    Ok(path)
+}
@@ -1,4 +1,4 @@
-use crate::tests::mocks::{Entry, EntryType, create_mock_layout, create_valid_powercap_mock};
+use crate::tests::mocks::{Entry, EntryType, MockCpu, create_mock_layout, create_mock_msr, create_valid_powercap_mock};
 use crate::{Config, RaplPlugin};
 
 use alumet::{
@@ -64,8 +64,11 @@ fn test_runtime_with_powercap() -> anyhow::Result<()> {
         poll_interval: Duration::from_secs(1),
         flush_interval: Duration::from_secs(1),
         no_perf_events: true,
+        use_msr: false,
+        trigger_group: None,
         perf_event_test_path: Path::new("").to_path_buf(),
         powercap_test_path: base_path.clone(),
+        msr_test_path: PathBuf::new(),
     };
     plugins.add_plugin(PluginInfo {
         metadata: PluginMetadata::from_static::<RaplPlugin>(),
@@ -207,8 +210,11 @@ fn test_error_with_powercap() -> anyhow::Result<()> {
         poll_interval: Duration::from_secs(1),
         flush_interval: Duration::from_secs(1),
         no_perf_events: true,
+        use_msr: false,
+        trigger_group: None,
         perf_event_test_path: "/i/do/not/exists".into(),
         powercap_test_path: base_path,
+        msr_test_path: PathBuf::new(),
     };
 
     plugins.add_plugin(PluginInfo {
@@ -234,8 +240,11 @@ fn test_with_powercap_only() -> anyhow::Result<()> {
         poll_interval: Duration::from_secs(1),
         flush_interval: Duration::from_secs(1),
         no_perf_events: false, // disable perf_events to force powercap
+        use_msr: false,
+        trigger_group: None,
         perf_event_test_path: Path::new("").to_path_buf(),
         powercap_test_path: base_path,
+        msr_test_path: PathBuf::new(),
     };
 
     plugins.add_plugin(PluginInfo {
@@ -271,8 +280,11 @@ fn test_powercap_with_no_available_interface() -> anyhow::Result<()> {
         poll_interval: Duration::from_secs(1),
         flush_interval: Duration::from_secs(1),
         no_perf_events: true, // Disable perf_events
+        use_msr: false,
+        trigger_group: None,
         perf_event_test_path: PathBuf::from("/i/do/not/exists"),
         powercap_test_path: base_path, // Powercap empty folder
+        msr_test_path: PathBuf::new(),
     };
 
     plugins.add_plugin(PluginInfo {
@@ -286,3 +298,94 @@ fn test_powercap_with_no_available_interface() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// This test ensures that the MSRs are used when `use_msr` is enabled, and that they are checked against powercap.
+#[test]
+fn test_runtime_with_msr() -> anyhow::Result<()> {
+    let mut plugins = PluginSet::new();
+
+    let powercap = create_valid_powercap_mock()?;
+    let msr = tempdir()?;
+    let msr_path = msr.path().to_owned();
+    let cpus = [MockCpu::new(0, 0, 0), MockCpu::new(1, 0, 1)];
+    let registers = [
+        (0x606, 0xa0e03), // power unit: energy_status_unit = 14
+        (0x611, 1000),    // package
+        (0x639, 500),     // pp0
+        (0x619, 100),     // dram
+    ];
+    create_mock_msr(&msr_path, &cpus, &registers)?;
+
+    let config = Config {
+        poll_interval: Duration::from_secs(1),
+        flush_interval: Duration::from_secs(1),
+        no_perf_events: true,
+        use_msr: true,
+        trigger_group: None,
+        perf_event_test_path: PathBuf::from("/i/do/not/exists"),
+        powercap_test_path: powercap.path().to_owned(),
+        msr_test_path: msr_path.clone(),
+    };
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<RaplPlugin>(),
+        enabled: true,
+        config: Some(config_to_toml_table(&config)),
+    });
+
+    let startup_expectations = StartupExpectations::new()
+        .expect_metric::<f64>("rapl_consumed_energy", Unit::Joule)
+        .expect_source("rapl", "in");
+
+    let runtime_expectations = RuntimeExpectations::new()
+        .test_source(
+            SourceName::from_str("rapl", "in"),
+            || (),
+            |ctx| {
+                assert_eq!(ctx.measurements().len(), 0);
+            },
+        )
+        .test_source(
+            SourceName::from_str("rapl", "in"),
+            || (),
+            move |ctx| {
+                let mut actual_domains: Vec<_> = ctx
+                    .measurements()
+                    .iter()
+                    .map(|m| {
+                        assert_eq!(m.value, WrappedMeasurementValue::F64(0.0));
+                        m.attributes().next().unwrap().1.to_string()
+                    })
+                    .collect();
+                actual_domains.sort();
+                assert_eq!(
+                    actual_domains,
+                    vec!["dram", "dram_total", "package", "package_total", "pp0", "pp0_total"]
+                );
+
+                // consume 1 Joule in the package
+                create_mock_msr(&msr_path, &cpus, &[(0x611, 1000 + 16384)]).unwrap();
+            },
+        )
+        .test_source(
+            SourceName::from_str("rapl", "in"),
+            || (),
+            |ctx| {
+                let package = ctx
+                    .measurements()
+                    .iter()
+                    .find(|m| m.attributes().any(|(_, v)| v.to_string() == "package"))
+                    .expect("there should be a measurement for the package");
+                assert_eq!(package.value, WrappedMeasurementValue::F64(1.0));
+            },
+        );
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(startup_expectations)
+        .with_expectations(runtime_expectations)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+
+    Ok(())
+}
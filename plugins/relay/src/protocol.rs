@@ -17,7 +17,7 @@ use crate::serde_impl;
 /// Version number of the current protocol.
 ///
 /// IMPORTANT: you must increase this number when the protocol changes.
-pub const PROTOCOL_VERSION: u32 = 2;
+pub const PROTOCOL_VERSION: u32 = 3;
 
 /// Maximum size (in bytes) of a message body.
 ///
@@ -112,6 +112,8 @@ pub struct MetricUnit {
 pub enum MetricType {
     F64,
     U64,
+    I64,
+    Histogram,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -339,6 +341,8 @@ impl From<WrappedMeasurementType> for MetricType {
         match value {
             WrappedMeasurementType::F64 => MetricType::F64,
             WrappedMeasurementType::U64 => MetricType::U64,
+            WrappedMeasurementType::I64 => MetricType::I64,
+            WrappedMeasurementType::Histogram => MetricType::Histogram,
         }
     }
 }
@@ -348,6 +352,8 @@ impl From<MetricType> for WrappedMeasurementType {
         match value {
             MetricType::F64 => WrappedMeasurementType::F64,
             MetricType::U64 => WrappedMeasurementType::U64,
+            MetricType::I64 => WrappedMeasurementType::I64,
+            MetricType::Histogram => WrappedMeasurementType::Histogram,
         }
     }
 }
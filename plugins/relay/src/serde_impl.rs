@@ -1,11 +1,11 @@
 use std::time::{Duration, SystemTime};
 
 use alumet::{
-    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    measurement::{AttributeValue, Histogram, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
     metrics::RawMetricId,
     resources::{Resource, ResourceConsumer},
 };
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize, ser::SerializeSeq};
 
 /// A measurement buffer than can be serialized and deserialized. This type is similar to [`std::borrow::Cow`].
@@ -142,12 +142,12 @@ impl<'a> TryFrom<SerializableMeasurementPoint<'a>> for MeasurementPoint {
         let metric = RawMetricId::from_u64(point.metric_id);
         let resource = Resource::parse(point.resource_kind.to_owned(), point.resource_id)?;
         let consumer = ResourceConsumer::parse(point.consumer_kind.to_owned(), point.consumer_id)?;
-        let value: WrappedMeasurementValue = point.value.into();
+        let value = WrappedMeasurementValue::try_from(point.value)?;
         let attributes = point
             .attributes
             .iter()
-            .map(|(k, v)| (k.to_string(), AttributeValue::from(v)))
-            .collect();
+            .map(|(k, v)| Ok((k.to_string(), AttributeValue::try_from(v)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
         Ok(MeasurementPoint::new_untyped(timestamp, metric, resource, consumer, value).with_attr_vec(attributes))
     }
 }
@@ -159,6 +159,12 @@ pub enum TypedValue<'a> {
     Bool(bool),
     Str(&'a str),
     ListU64(Vec<u64>), // TODO optimize
+    I64(i64),
+    Histogram {
+        bounds: Vec<f64>,
+        counts: Vec<u64>,
+        sum: f64,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -172,16 +178,30 @@ impl<'a> From<&'a WrappedMeasurementValue> for TypedValue<'a> {
         match value {
             WrappedMeasurementValue::F64(v) => TypedValue::F64(*v),
             WrappedMeasurementValue::U64(v) => TypedValue::U64(*v),
+            WrappedMeasurementValue::I64(v) => TypedValue::I64(*v),
+            WrappedMeasurementValue::Histogram(h) => TypedValue::Histogram {
+                bounds: h.bounds().to_vec(),
+                counts: h.counts().to_vec(),
+                sum: h.sum(),
+            },
         }
     }
 }
 
-impl<'a> From<TypedValue<'a>> for WrappedMeasurementValue {
-    fn from(value: TypedValue<'a>) -> Self {
+impl<'a> TryFrom<TypedValue<'a>> for WrappedMeasurementValue {
+    type Error = anyhow::Error;
+
+    fn try_from(value: TypedValue<'a>) -> Result<Self, Self::Error> {
         match value {
-            TypedValue::F64(v) => WrappedMeasurementValue::F64(v),
-            TypedValue::U64(v) => WrappedMeasurementValue::U64(v),
-            _ => unreachable!("MeasurementPoint values should never be of this type, got {value:?}"),
+            TypedValue::F64(v) => Ok(WrappedMeasurementValue::F64(v)),
+            TypedValue::U64(v) => Ok(WrappedMeasurementValue::U64(v)),
+            TypedValue::I64(v) => Ok(WrappedMeasurementValue::I64(v)),
+            TypedValue::Histogram { bounds, counts, sum } => Ok(WrappedMeasurementValue::Histogram(
+                Histogram::from_parts(bounds, counts, sum).context("invalid histogram")?,
+            )),
+            _ => Err(anyhow!(
+                "MeasurementPoint values should never be of this type, got {value:?}"
+            )),
         }
     }
 }
@@ -199,14 +219,17 @@ impl<'a> From<&'a AttributeValue> for TypedValue<'a> {
     }
 }
 
-impl<'a> From<&'a TypedValue<'a>> for AttributeValue {
-    fn from(value: &'a TypedValue<'a>) -> Self {
+impl<'a> TryFrom<&'a TypedValue<'a>> for AttributeValue {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &'a TypedValue<'a>) -> Result<Self, Self::Error> {
         match value {
-            TypedValue::F64(v) => AttributeValue::F64(*v),
-            TypedValue::U64(v) => AttributeValue::U64(*v),
-            TypedValue::Bool(v) => AttributeValue::Bool(*v),
-            TypedValue::Str(v) => AttributeValue::String(v.to_string()),
-            TypedValue::ListU64(items) => AttributeValue::ListU64(items.to_owned()),
+            TypedValue::F64(v) => Ok(AttributeValue::F64(*v)),
+            TypedValue::U64(v) => Ok(AttributeValue::U64(*v)),
+            TypedValue::Bool(v) => Ok(AttributeValue::Bool(*v)),
+            TypedValue::Str(v) => Ok(AttributeValue::String(v.to_string())),
+            TypedValue::ListU64(items) => Ok(AttributeValue::ListU64(items.to_owned())),
+            _ => Err(anyhow!("attribute values should never be of this type, got {value:?}")),
         }
     }
 }
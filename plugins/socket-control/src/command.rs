@@ -1,52 +1,98 @@
 //! Command parsing.
 
-use std::str::FromStr;
 use std::time::Duration;
 
+use alumet::metrics::online::MetricReader;
 use alumet::pipeline::control::AnonymousControlHandle;
-use alumet::pipeline::control::handle::DispatchError;
-use alumet::pipeline::control::request::{self, any::AnyAnonymousControlRequest};
-use alumet::pipeline::elements::source::trigger::TriggerSpec;
-use alumet::pipeline::matching::{
-    ElementNamePattern, OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern,
-};
-use alumet::pipeline::naming::ElementKind;
-use alumet::pipeline::naming::parsing::parse_kind;
+use alumet::pipeline::control::request::{self, Action, ElementListFilter, any::AnyAnonymousControlRequest};
+use alumet::pipeline::matching::ElementNamePattern;
+use alumet::pipeline::naming::parsing::parse_pattern;
 
 use anyhow::{Context, anyhow};
 use humantime::parse_duration;
 
+use crate::response::{Format, MetricInfo, Response};
+
 const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub enum Command {
     Control(Vec<AnyAnonymousControlRequest>),
+    List(ElementListFilter),
+    Metrics,
+    Status(ElementListFilter),
+    Stats,
     Shutdown,
 }
 
 impl Command {
-    pub async fn run(self, handle: &AnonymousControlHandle) -> Result<(), DispatchError> {
+    pub async fn run(self, handle: &AnonymousControlHandle, metrics: &MetricReader) -> anyhow::Result<Response> {
         match self {
             Command::Control(messages) => {
                 for msg in messages {
                     handle.dispatch(msg, COMMAND_TIMEOUT).await?;
                 }
-                Ok(())
+                Ok(Response::Ok)
+            }
+            Command::List(filter) => {
+                let mut elements = handle
+                    .send_wait(request::list_elements(filter), COMMAND_TIMEOUT)
+                    .await?;
+                elements.sort_by_key(|e| e.to_string());
+                Ok(Response::Elements(elements))
+            }
+            Command::Metrics => {
+                let registry = metrics.read().await;
+                let mut metrics: Vec<MetricInfo> = registry.iter().map(|(id, m)| MetricInfo::new(id, m)).collect();
+                metrics.sort_by_key(MetricInfo::id);
+                Ok(Response::Metrics(metrics))
+            }
+            Command::Status(filter) => {
+                let mut status = handle
+                    .send_wait(request::element_status(filter), COMMAND_TIMEOUT)
+                    .await?;
+                status.sort_by_key(|s| s.name.to_string());
+                Ok(Response::Status(status))
+            }
+            Command::Stats => {
+                let stats = handle.send_wait(request::pipeline_stats(), COMMAND_TIMEOUT).await?;
+                Ok(Response::Stats(stats))
             }
             Command::Shutdown => {
                 handle.shutdown();
-                Ok(())
+                Ok(Response::Ok)
             }
         }
     }
 }
 
+/// Removes the `--json` flag from a command line and returns the format of the response.
+pub fn parse_format(line: &str) -> (String, Format) {
+    let mut format = Format::Text;
+    let parts: Vec<&str> = line
+        .split_ascii_whitespace()
+        .filter(|part| {
+            if *part == "--json" {
+                format = Format::Json;
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+    (parts.join(" "), format)
+}
+
 /// Parses a command from a string.
 ///
 /// ## Available commands
 ///
 /// - `shutdown` or `stop`: shutdowns the measurement pipeline
 /// - `control <PATTERN> [ARGS...]`: reconfigures a part of the pipeline (see below)
+/// - `list [PATTERN]`: lists the pipeline elements, for instance `list sources`
+/// - `metrics`: lists the registered metrics
+/// - `status [PATTERN]`: gives the state of the pipeline elements, and the poll period and last poll time of the sources
+/// - `stats`: gives the statistics of the pipeline elements and channels
 ///
 /// ### Control arguments
 ///
@@ -65,110 +111,48 @@ impl Command {
 ///
 pub fn parse(command: &str) -> anyhow::Result<Command> {
     fn parse_control_args(pat: ElementNamePattern, args: &[&str]) -> anyhow::Result<Vec<AnyAnonymousControlRequest>> {
-        match args {
-            [] => Err(anyhow!("missing arguments after the selector")),
-            ["pause"] | ["disable"] => match &pat.kind {
-                Some(ElementKind::Source) => Ok(vec![
-                    request::source(SourceNamePattern::try_from(pat).unwrap())
-                        .disable()
-                        .into(),
-                ]),
-                Some(ElementKind::Transform) => Ok(vec![
-                    request::transform(TransformNamePattern::try_from(pat).unwrap())
-                        .disable()
-                        .into(),
-                ]),
-                Some(ElementKind::Output) => Ok(vec![
-                    request::output(OutputNamePattern::try_from(pat).unwrap())
-                        .disable()
-                        .into(),
-                ]),
-                None => {
-                    let for_sources = request::source(SourceNamePattern::try_from(pat.clone()).unwrap())
-                        .disable()
-                        .into();
-                    let for_transforms = request::transform(TransformNamePattern::try_from(pat.clone()).unwrap())
-                        .disable()
-                        .into();
-                    let for_outputs = request::output(OutputNamePattern::try_from(pat).unwrap())
-                        .disable()
-                        .into();
-                    Ok(vec![for_sources, for_transforms, for_outputs])
-                }
-            },
-            ["resume"] | ["enable"] => match &pat.kind {
-                Some(ElementKind::Source) => Ok(vec![
-                    request::source(SourceNamePattern::try_from(pat).unwrap())
-                        .enable()
-                        .into(),
-                ]),
-                Some(ElementKind::Transform) => Ok(vec![
-                    request::transform(TransformNamePattern::try_from(pat).unwrap())
-                        .enable()
-                        .into(),
-                ]),
-                Some(ElementKind::Output) => Ok(vec![
-                    request::output(OutputNamePattern::try_from(pat).unwrap())
-                        .enable()
-                        .into(),
-                ]),
-                None => {
-                    let for_sources = request::source(SourceNamePattern::try_from(pat.clone()).unwrap())
-                        .enable()
-                        .into();
-                    let for_transforms = request::transform(TransformNamePattern::try_from(pat.clone()).unwrap())
-                        .enable()
-                        .into();
-                    let for_outputs = request::output(OutputNamePattern::try_from(pat).unwrap())
-                        .enable()
-                        .into();
-                    Ok(vec![for_sources, for_transforms, for_outputs])
-                }
-            },
-            ["stop"] => match &pat.kind {
-                Some(ElementKind::Source) => Ok(vec![
-                    request::source(SourceNamePattern::try_from(pat).unwrap()).stop().into(),
-                ]),
-                Some(ElementKind::Output) => Ok(vec![
-                    request::output(OutputNamePattern::try_from(pat).unwrap())
-                        .stop(request::RemainingDataStrategy::Write)
-                        .into(),
-                ]),
-                _ => Err(anyhow!(
-                    "invalid control 'stop': it can only be applied to sources and outputs"
-                )),
+        let action = match args {
+            [] => return Err(anyhow!("missing arguments after the selector")),
+            ["pause"] | ["disable"] => Action::Pause,
+            ["resume"] | ["enable"] => Action::Resume,
+            ["stop"] => Action::Stop,
+            ["set-period", period] | ["set-poll-interval", period] => Action::SetPeriod {
+                period: parse_duration(period)?,
             },
-            ["set-period", period] | ["set-poll-interval", period] => match &pat.kind {
-                Some(ElementKind::Source) => {
-                    let poll_interval = parse_duration(period)?;
-                    let spec = TriggerSpec::at_interval(poll_interval);
-                    Ok(vec![
-                        request::source(SourceNamePattern::try_from(pat).unwrap())
-                            .set_trigger(spec)
-                            .into(),
-                    ])
-                }
-                _ => Err(anyhow!(
-                    "invalid control 'set-period': it can only be applied to sources"
-                )),
-            },
-            ["trigger-now"] => match &pat.kind {
-                Some(ElementKind::Source) => Ok(vec![
-                    request::source(SourceNamePattern::try_from(pat).unwrap())
-                        .trigger_now()
-                        .into(),
-                ]),
-                _ => Err(anyhow!(
-                    "invalid control 'trigger-now': it can only be applied to sources"
-                )),
-            },
-            _ => Err(anyhow!("invalid command")),
-        }
+            ["trigger-now"] => Action::TriggerNow,
+            _ => return Err(anyhow!("invalid command")),
+        };
+        Ok(action.requests(&pat)?)
+    }
+
+    fn parse_filter(pat: Option<&&str>) -> anyhow::Result<ElementListFilter> {
+        let pat = match pat {
+            Some(pat) => parse_pattern(pat)?,
+            None => ElementNamePattern::wildcard(),
+        };
+        let filter = match pat.kind {
+            Some(kind) => ElementListFilter::kind(kind),
+            None => ElementListFilter::kind_any(),
+        };
+        Ok(filter.plugin_pat(pat.plugin).name_pat(pat.element))
     }
 
     let parts: Vec<&str> = command.split_ascii_whitespace().collect();
-    match parts[0] {
+    let Some(name) = parts.first() else {
+        return Err(anyhow!("empty command"));
+    };
+    match *name {
         "shutdown" | "stop" => Ok(Command::Shutdown),
+        "list" | "status" if parts.len() > 2 => Err(anyhow!("invalid command '{command}': too many arguments")),
+        "list" => Ok(Command::List(
+            parse_filter(parts.get(1)).with_context(|| format!("invalid command '{command}'"))?,
+        )),
+        "status" => Ok(Command::Status(
+            parse_filter(parts.get(1)).with_context(|| format!("invalid command '{command}'"))?,
+        )),
+        "metrics" if parts.len() == 1 => Ok(Command::Metrics),
+        "stats" if parts.len() == 1 => Ok(Command::Stats),
+        "metrics" | "stats" => Err(anyhow!("invalid command '{command}': too many arguments")),
         "control" => {
             let pat = parts
                 .get(1)
@@ -179,45 +163,23 @@ pub fn parse(command: &str) -> anyhow::Result<Command> {
             Ok(Command::Control(messages))
         }
         _ => Err(anyhow!(
-            "unknown command '{command}'; available commands are 'shutdown' or 'control'"
+            "unknown command '{command}'; available commands are 'shutdown', 'control', 'list', 'metrics', 'status' and 'stats'"
         )),
     }
 }
 
-pub fn parse_pattern(pat: &str) -> anyhow::Result<ElementNamePattern> {
-    let parts: Vec<_> = pat.splitn(3, '/').collect();
-    match parts[..] {
-        [kind, plugin_pat, element_pat] => {
-            let kind = parse_kind(kind).with_context(|| format!("bad kind: '{kind}'"))?;
-            let plugin = StringPattern::from_str(plugin_pat).with_context(|| format!("bad pattern: '{plugin_pat}'"))?;
-            let element =
-                StringPattern::from_str(element_pat).with_context(|| format!("bad pattern: '{element_pat}'"))?;
-            Ok(ElementNamePattern { kind, plugin, element })
-        }
-        [kind] => {
-            let kind = parse_kind(kind).with_context(|| format!("bad kind: '{kind}'"))?;
-            Ok(ElementNamePattern {
-                kind,
-                plugin: StringPattern::Any,
-                element: StringPattern::Any,
-            })
-        }
-        _ => Err(anyhow!("bad pattern, expected kind/plugin/element but got '{pat}'")),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use regex::Regex;
     use std::time::Duration;
 
-    use crate::command::parse_pattern;
-
-    use super::{Command, parse};
+    use super::{Command, parse, parse_format};
+    use crate::response::Format;
     use alumet::pipeline::control::matching::{OutputMatcher, SourceMatcher, TransformMatcher};
-    use alumet::pipeline::control::request::{self, any::AnyAnonymousControlRequest};
+    use alumet::pipeline::control::request::{self, ElementListFilter, any::AnyAnonymousControlRequest};
     use alumet::pipeline::elements::source::trigger::TriggerSpec;
     use alumet::pipeline::matching::{OutputNamePattern, SourceNamePattern, TransformNamePattern};
+    use alumet::pipeline::naming::ElementKind;
 
     #[test]
     fn control_source_exact() {
@@ -373,14 +335,6 @@ mod tests {
         );
     }
 
-    #[test]
-    fn parse_pattern_wrong_pattern() {
-        assert_eq!(
-            parse_pattern("source/without-element").unwrap_err().to_string(),
-            "bad pattern, expected kind/plugin/element but got 'source/without-element'"
-        );
-    }
-
     #[test]
     fn control_common_errors() {
         assert_eq!(
@@ -400,6 +354,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn query_commands() {
+        assert!(matches!(parse("metrics").unwrap(), Command::Metrics));
+        assert!(matches!(parse("stats").unwrap(), Command::Stats));
+        assert_eq!(
+            format!("{:?}", parse("list sources").unwrap()),
+            format!("{:?}", Command::List(ElementListFilter::kind(ElementKind::Source)))
+        );
+        assert_eq!(
+            format!("{:?}", parse("list").unwrap()),
+            format!("{:?}", Command::List(ElementListFilter::kind_any()))
+        );
+        assert_eq!(
+            format!("{:?}", parse("status output/csv/*").unwrap()),
+            format!(
+                "{:?}",
+                Command::Status(ElementListFilter::kind(ElementKind::Output).plugin("csv"))
+            )
+        );
+        assert_eq!(
+            parse("status sources extra").unwrap_err().to_string(),
+            "invalid command 'status sources extra': too many arguments"
+        );
+        assert_eq!(
+            parse("list nothing").unwrap_err().to_string(),
+            "invalid command 'list nothing'"
+        );
+    }
+
+    #[test]
+    fn json_format() {
+        assert_eq!(parse_format("status --json"), (String::from("status"), Format::Json));
+        assert_eq!(
+            parse_format("--json  control  source pause"),
+            (String::from("control source pause"), Format::Json)
+        );
+        assert_eq!(parse_format("stats"), (String::from("stats"), Format::Text));
+        assert_eq!(parse_format("  "), (String::new(), Format::Text));
+    }
+
     fn assert_control_eq(cmd: Command, msg: Vec<AnyAnonymousControlRequest>) {
         let regex_instant = Regex::new(r#"Instant \{ .+ \}"#).expect("regex should be valid");
 
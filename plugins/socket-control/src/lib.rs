@@ -1,4 +1,5 @@
 mod command;
+mod response;
 mod socket;
 
 use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
@@ -42,7 +43,11 @@ impl AlumetPlugin for SocketControlPlugin {
 
     fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
         // Enable remote control via Unix socket.
-        let control = SocketControl::start_new(alumet.pipeline_control(), &self.config.socket_path)?;
+        let control = SocketControl::start_new(
+            alumet.pipeline_control(),
+            alumet.metrics_reader(),
+            &self.config.socket_path,
+        )?;
         self.control = Some(control);
         log::info!("SocketControl enabled.");
         Ok(())
@@ -0,0 +1,350 @@
+//! Responses to the commands.
+//!
+//! In text mode, a response is made of one or more lines and ends with an empty line.
+//! In JSON mode, a response is a single line that contains a JSON object.
+
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+use alumet::metrics::{Metric, RawMetricId};
+use alumet::pipeline::control::request::{ElementState, ElementStatus};
+use alumet::pipeline::health::{ChannelLabel, ElementStats, PipelineStats};
+use alumet::pipeline::naming::ElementName;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// Format of the responses sent back to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum Response {
+    /// The command succeeded and has nothing to return.
+    Ok,
+    Error(String),
+    Elements(Vec<ElementName>),
+    Metrics(Vec<MetricInfo>),
+    Status(Vec<ElementStatus>),
+    Stats(PipelineStats),
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricInfo {
+    id: u64,
+    name: String,
+    description: String,
+    value_type: String,
+    unit: String,
+}
+
+#[derive(Serialize)]
+struct ElementInfo<'a> {
+    kind: String,
+    plugin: &'a str,
+    name: &'a str,
+}
+
+impl Response {
+    /// Renders the response, including the final newline.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => {
+                let mut res = self.to_text();
+                res.push('\n');
+                res
+            }
+            Format::Json => {
+                let mut res = self.to_json().to_string();
+                res.push('\n');
+                res
+            }
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut res = String::new();
+        match self {
+            Response::Ok => res.push_str("ok\n"),
+            Response::Error(msg) => writeln!(res, "error: {msg}").unwrap(),
+            Response::Elements(elements) => {
+                for e in elements {
+                    writeln!(res, "{e}").unwrap();
+                }
+            }
+            Response::Metrics(metrics) => {
+                for m in metrics {
+                    writeln!(
+                        res,
+                        "{}\t{}\t{}\t{}\t{}",
+                        m.id, m.name, m.value_type, m.unit, m.description
+                    )
+                    .unwrap();
+                }
+            }
+            Response::Status(status) => {
+                for s in status {
+                    write!(res, "{}\t{}", s.name, state_str(s.state)).unwrap();
+                    if let Some(period) = s.poll_interval {
+                        write!(res, "\tperiod={}", humantime::format_duration(period)).unwrap();
+                    }
+                    if let Some(t) = s.last_poll {
+                        write!(res, "\tlast_poll={}", format_time(t.into())).unwrap();
+                    }
+                    res.push('\n');
+                }
+            }
+            Response::Stats(stats) => {
+                for (name, s) in &stats.elements {
+                    write!(res, "{name}").unwrap();
+                    for (key, value) in stats_fields(s) {
+                        write!(res, "\t{key}={value}").unwrap();
+                    }
+                    res.push('\n');
+                }
+                for (channel, len) in &stats.channels {
+                    writeln!(res, "channel/{}\tbuffers={len}", channel_str(channel)).unwrap();
+                }
+            }
+        }
+        res
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Response::Ok => json!({ "ok": true }),
+            Response::Error(msg) => json!({ "error": msg }),
+            Response::Elements(elements) => {
+                let elements: Vec<_> = elements.iter().map(ElementInfo::from).collect();
+                json!({ "elements": elements })
+            }
+            Response::Metrics(metrics) => json!({ "metrics": metrics }),
+            Response::Status(status) => {
+                let status: Vec<_> = status
+                    .iter()
+                    .map(|s| {
+                        let mut obj = json!(ElementInfo::from(&s.name));
+                        obj["state"] = json!(state_str(s.state));
+                        obj["period"] = json!(s.poll_interval.map(|p| humantime::format_duration(p).to_string()));
+                        obj["last_poll"] = json!(s.last_poll.map(|t| format_time(t.into())));
+                        obj
+                    })
+                    .collect();
+                json!({ "status": status })
+            }
+            Response::Stats(stats) => {
+                let elements: Vec<_> = stats
+                    .elements
+                    .iter()
+                    .map(|(name, s)| {
+                        let mut obj = json!(ElementInfo::from(name));
+                        for (key, value) in stats_fields(s) {
+                            obj[key] = value.to_json();
+                        }
+                        obj
+                    })
+                    .collect();
+                let channels: Vec<_> = stats
+                    .channels
+                    .iter()
+                    .map(|(channel, len)| json!({ "channel": channel_str(channel), "buffers": len }))
+                    .collect();
+                json!({ "elements": elements, "channels": channels })
+            }
+        }
+    }
+}
+
+impl MetricInfo {
+    pub fn new(id: &RawMetricId, metric: &Metric) -> Self {
+        Self {
+            id: id.as_u64(),
+            name: metric.name.clone(),
+            description: metric.description.clone(),
+            value_type: metric.value_type.to_string(),
+            unit: metric.unit.unique_name(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl<'a> From<&'a ElementName> for ElementInfo<'a> {
+    fn from(value: &'a ElementName) -> Self {
+        Self {
+            kind: value.kind.to_string(),
+            plugin: &value.plugin,
+            name: &value.element,
+        }
+    }
+}
+
+/// A value in the statistics of an element.
+enum StatValue {
+    Count(u64),
+    Time(Duration),
+}
+
+impl StatValue {
+    /// Durations are given in seconds, which is easier to process than a human-readable string.
+    fn to_json(&self) -> Value {
+        match self {
+            StatValue::Count(n) => json!(n),
+            StatValue::Time(d) => json!(d.as_secs_f64()),
+        }
+    }
+}
+
+impl std::fmt::Display for StatValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatValue::Count(n) => write!(f, "{n}"),
+            StatValue::Time(d) => write!(f, "{}", d.as_secs_f64()),
+        }
+    }
+}
+
+fn stats_fields(stats: &ElementStats) -> Vec<(&'static str, StatValue)> {
+    use StatValue::{Count, Time};
+    match *stats {
+        ElementStats::Source {
+            polls,
+            poll_time,
+            overruns,
+        } => vec![
+            ("polls", Count(polls)),
+            ("poll_time", Time(poll_time)),
+            ("overruns", Count(overruns)),
+        ],
+        ElementStats::Transform {
+            applies,
+            apply_time,
+            dropped,
+        } => vec![
+            ("applies", Count(applies)),
+            ("apply_time", Time(apply_time)),
+            ("dropped", Count(dropped)),
+        ],
+        ElementStats::Output {
+            writes,
+            write_time,
+            errors,
+            dropped,
+        } => vec![
+            ("writes", Count(writes)),
+            ("write_time", Time(write_time)),
+            ("errors", Count(errors)),
+            ("dropped", Count(dropped)),
+        ],
+    }
+}
+
+fn state_str(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Running => "running",
+        ElementState::Paused => "paused",
+        ElementState::Stopped => "stopped",
+    }
+}
+
+fn channel_str(channel: &ChannelLabel) -> String {
+    match channel {
+        ChannelLabel::Transform(name) => name.to_string(),
+        other => other.kind().to_owned(),
+    }
+}
+
+fn format_time(t: SystemTime) -> String {
+    humantime::format_rfc3339_micros(t).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::pipeline::control::request::{ElementState, ElementStatus};
+    use alumet::pipeline::health::{ChannelLabel, ElementStats, PipelineStats};
+    use alumet::pipeline::naming::{ElementKind, ElementName};
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+
+    use super::{Format, Response};
+
+    fn source_name() -> ElementName {
+        ElementName {
+            kind: ElementKind::Source,
+            plugin: String::from("rapl"),
+            element: String::from("in"),
+        }
+    }
+
+    #[test]
+    fn ok_and_error() {
+        assert_eq!(Response::Ok.render(Format::Text), "ok\n\n");
+        assert_eq!(Response::Ok.render(Format::Json), "{\"ok\":true}\n");
+        let err = Response::Error(String::from("bad"));
+        assert_eq!(err.render(Format::Text), "error: bad\n\n");
+        assert_eq!(err.render(Format::Json), "{\"error\":\"bad\"}\n");
+    }
+
+    #[test]
+    fn status() {
+        let status = Response::Status(vec![ElementStatus {
+            name: source_name(),
+            state: ElementState::Running,
+            poll_interval: Some(Duration::from_millis(10)),
+            last_poll: Some((UNIX_EPOCH + Duration::from_secs(60)).into()),
+        }]);
+        assert_eq!(
+            status.render(Format::Text),
+            "sources/rapl/in\trunning\tperiod=10ms\tlast_poll=1970-01-01T00:01:00.000000Z\n\n"
+        );
+        assert_eq!(
+            parse_json(status.render(Format::Json)),
+            json!({"status": [{
+                "kind": "source",
+                "plugin": "rapl",
+                "name": "in",
+                "state": "running",
+                "period": "10ms",
+                "last_poll": "1970-01-01T00:01:00.000000Z",
+            }]})
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let stats = Response::Stats(PipelineStats {
+            elements: vec![(
+                source_name(),
+                ElementStats::Source {
+                    polls: 4,
+                    poll_time: Duration::from_millis(2),
+                    overruns: 1,
+                },
+            )],
+            channels: vec![(ChannelLabel::Sources, 3)],
+        });
+        assert_eq!(
+            stats.render(Format::Text),
+            "sources/rapl/in\tpolls=4\tpoll_time=0.002\toverruns=1\nchannel/sources\tbuffers=3\n\n"
+        );
+        assert_eq!(
+            parse_json(stats.render(Format::Json)),
+            json!({
+                "elements": [{"kind": "source", "plugin": "rapl", "name": "in", "polls": 4, "poll_time": 0.002, "overruns": 1}],
+                "channels": [{"channel": "sources", "buffers": 3}],
+            })
+        );
+    }
+
+    fn parse_json(line: String) -> Value {
+        let line = line.strip_suffix('\n').expect("the response should end with a newline");
+        assert!(!line.contains('\n'), "a JSON response should fit on one line");
+        serde_json::from_str(line).unwrap()
+    }
+}
@@ -1,6 +1,9 @@
 use std::{path::Path, time::Duration};
 
-use alumet::pipeline::control::{AnonymousControlHandle, PluginControlHandle};
+use alumet::{
+    metrics::online::MetricReader,
+    pipeline::control::{AnonymousControlHandle, PluginControlHandle},
+};
 use anyhow::Context;
 use tokio::{
     net::{UnixListener, UnixStream, unix::SocketAddr},
@@ -9,7 +12,7 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::command;
+use crate::{command, response::Response};
 
 pub struct SocketControl {
     rt: Runtime,
@@ -19,6 +22,7 @@ pub struct SocketControl {
 impl SocketControl {
     pub fn start_new<P: AsRef<Path>>(
         alumet_handle: PluginControlHandle,
+        metrics: MetricReader,
         socket_path: P,
     ) -> anyhow::Result<SocketControl> {
         // get socket_path as a PathBuf, so that we can send it across threads
@@ -55,12 +59,13 @@ impl SocketControl {
                     new_connection = listener.accept() => {
                         // handle the new connection
                         let alumet_handle = alumet_handle.clone().anonymous();
+                        let metrics = metrics.clone();
                         let rt_handle = rt_handle.clone();
 
                         rt_handle.spawn(async move {
                             match new_connection {
                                 Ok((stream, addr)) => {
-                                    if let Err(e) = handle_socket_connection(stream, addr, &alumet_handle).await {
+                                    if let Err(e) = handle_socket_connection(stream, addr, &alumet_handle, &metrics).await {
                                         log::error!("Error in unix socket processing: {e:#}");
                                     }
                                 },
@@ -92,17 +97,29 @@ async fn handle_socket_connection(
     stream: UnixStream,
     _addr: SocketAddr,
     alumet_handle: &AnonymousControlHandle,
+    metrics: &MetricReader,
 ) -> anyhow::Result<()> {
-    use anyhow::anyhow;
-    use tokio::io::{AsyncBufReadExt, BufStream};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-    let buf = BufStream::new(stream);
-    let mut lines = buf.lines();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
     while let Some(line) = lines.next_line().await? {
-        let cmd = command::parse(&line)?;
-        cmd.run(alumet_handle)
-            .await
-            .map_err(|e| anyhow!("failed to run command {line}: {e}"))?;
+        let (line, format) = command::parse_format(&line);
+        if line.is_empty() {
+            continue;
+        }
+        // Errors are reported to the client, which can send other commands on the same connection.
+        let response = match command::parse(&line) {
+            Ok(cmd) => cmd
+                .run(alumet_handle, metrics)
+                .await
+                .unwrap_or_else(|e| Response::Error(format!("failed to run command '{line}': {e:#}"))),
+            Err(e) => Response::Error(format!("{e:#}")),
+        };
+        if let Response::Error(msg) = &response {
+            log::warn!("{msg}");
+        }
+        writer.write_all(response.render(format).as_bytes()).await?;
     }
     Ok(())
 }
@@ -1,4 +1,8 @@
-use std::{io::Write, os::unix::net::UnixStream, time::Duration};
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
 
 use alumet::{
     agent::{
@@ -8,11 +12,12 @@ use alumet::{
     plugin::{PluginMetadata, rust::serialize_config},
 };
 use plugin_socket_control::{Config, SocketControlPlugin};
+use serde_json::{Value, json};
 
 #[test]
 fn shutdown() {
     // for debugging
-    env_logger::init();
+    let _ = env_logger::try_init();
 
     let tmp = tempfile::tempdir().unwrap();
     std::fs::create_dir_all(&tmp).unwrap();
@@ -49,6 +54,85 @@ fn shutdown() {
         .expect("alumet should stop");
 }
 
+#[test]
+fn query() {
+    let _ = env_logger::try_init();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let socket_file = tmp.path().join("control.sock");
+
+    let plugin_config = serialize_config(Config {
+        socket_path: socket_file.to_str().unwrap().to_owned(),
+    })
+    .unwrap()
+    .0;
+
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<SocketControlPlugin>(),
+        enabled: true,
+        config: Some(plugin_config),
+    });
+
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("alumet should start");
+    std::thread::sleep(Duration::from_millis(100));
+
+    let mut stream = UnixStream::connect(socket_file).expect("I should be able to connect to the socket");
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    // text mode: the response ends with an empty line
+    socket_write_line(&mut stream, "list outputs");
+    assert_eq!(read_response(&mut reader), vec!["outputs/alumet/dummy"]);
+
+    // json mode: the response is a single line
+    socket_write_line(&mut stream, "status outputs --json");
+    let status: Value = serde_json::from_str(&read_line(&mut reader)).unwrap();
+    assert_eq!(
+        status,
+        json!({"status": [{
+            "kind": "output",
+            "plugin": "alumet",
+            "name": "dummy",
+            "state": "running",
+            "period": null,
+            "last_poll": null,
+        }]})
+    );
+
+    // errors do not close the connection
+    socket_write_line(&mut stream, "nonsense --json");
+    assert!(read_line(&mut reader).starts_with(r#"{"error":"unknown command 'nonsense'"#));
+    socket_write_line(&mut stream, "stats --json");
+    assert!(read_line(&mut reader).starts_with('{'));
+
+    socket_write_line(&mut stream, "shutdown");
+    assert_eq!(read_response(&mut reader), vec!["ok"]);
+    agent
+        .wait_for_shutdown(Duration::from_millis(250))
+        .expect("alumet should stop");
+}
+
+fn read_line(reader: &mut impl BufRead) -> String {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .expect("I should be able to read from the socket");
+    line.trim_end_matches('\n').to_owned()
+}
+
+fn read_response(reader: &mut impl BufRead) -> Vec<String> {
+    let mut lines = Vec::new();
+    loop {
+        let line = read_line(reader);
+        if line.is_empty() {
+            return lines;
+        }
+        lines.push(line);
+    }
+}
+
 fn socket_write_line(stream: &mut UnixStream, line: &str) {
     let buf = format!("{line}\n").into_bytes();
     // the newline is important, because the plugin uses read_line() to parse the commands
@@ -0,0 +1,136 @@
+mod transform;
+
+use std::collections::HashMap;
+
+use alumet::{
+    metrics::{RawMetricId, def::MetricId},
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    units::PrefixedUnit,
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use transform::ConversionTransform;
+
+/// Alumet plugin that converts the measurements of some metrics to another unit.
+pub struct UnitConversionPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for UnitConversionPlugin {
+    fn name() -> &'static str {
+        "unit-conversion"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        for c in &config.conversions {
+            c.unit
+                .parse::<PrefixedUnit>()
+                .with_context(|| format!("invalid unit for the conversion of {}", c.metric))?;
+        }
+        Ok(Box::new(UnitConversionPlugin { config }))
+    }
+
+    /// Registers the converted metrics and adds the conversion transform to the pipeline.
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.conversions.is_empty() {
+            log::warn!(
+                "unit-conversion plugin was started but as there's no conversion in its configuration, this will do nothing"
+            );
+            return Ok(());
+        }
+
+        // (original metric name, converted metric id, target unit)
+        let mut targets: Vec<(String, RawMetricId, PrefixedUnit)> = Vec::with_capacity(self.config.conversions.len());
+        for c in &self.config.conversions {
+            let unit: PrefixedUnit = c.unit.parse()?;
+            let name = c.output_name(&unit);
+            let id = alumet.create_metric::<f64>(
+                name.as_str(),
+                unit.clone(),
+                format!("{} converted to {}", c.metric, unit.unique_name()),
+            )?;
+            targets.push((c.metric.clone(), id.untyped_id(), unit));
+        }
+
+        let drop_input = self.config.drop_input;
+        alumet.add_transform_builder("transform", move |ctx| {
+            let mut conversions: HashMap<RawMetricId, _> = HashMap::with_capacity(targets.len());
+            for (metric_name, new_id, unit) in targets {
+                let (id, metric) = ctx
+                    .metric_by_name(&metric_name)
+                    .with_context(|| format!("metric not found: {metric_name}"))?;
+                let conversion = metric
+                    .unit
+                    .conversion_to(&unit)
+                    .with_context(|| format!("cannot convert metric {metric_name}"))?;
+                conversions.insert(id, (new_id, conversion));
+            }
+            Ok(Box::new(ConversionTransform::new(conversions, drop_input)))
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct Config {
+    /// If true, the measurements of the original metrics are replaced by the converted measurements.
+    #[serde(default = "default_drop_input")]
+    drop_input: bool,
+
+    /// The metrics to convert.
+    #[serde(default)]
+    conversions: Vec<ConversionConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ConversionConfig {
+    /// Name of the metric to convert.
+    metric: String,
+    /// Target unit, as specified by the UCUM, for instance "W.h" or "kW".
+    unit: String,
+    /// Name of the converted metric, defaults to `<metric>_<unit>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_metric: Option<String>,
+}
+
+impl ConversionConfig {
+    fn output_name(&self, unit: &PrefixedUnit) -> String {
+        match &self.output_metric {
+            Some(name) => name.to_owned(),
+            None => {
+                // "W.h" becomes "Wh", "[degF]" becomes "degF"
+                let unit: String = unit.unique_name().chars().filter(|c| c.is_alphanumeric()).collect();
+                format!("{}_{unit}", self.metric)
+            }
+        }
+    }
+}
+
+fn default_drop_input() -> bool {
+    true
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            drop_input: default_drop_input(),
+            conversions: Vec::new(),
+        }
+    }
+}
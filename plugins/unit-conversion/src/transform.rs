@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    metrics::RawMetricId,
+    pipeline::{
+        Transform,
+        elements::{error::TransformError, transform::TransformContext},
+    },
+    units::Conversion,
+};
+
+/// Converts the measurements of some metrics to another unit.
+pub struct ConversionTransform {
+    /// For each converted metric, the converted metric and the conversion to apply.
+    conversions: HashMap<RawMetricId, (RawMetricId, Conversion)>,
+    /// If true, the original points are replaced by the converted ones.
+    drop_input: bool,
+}
+
+impl ConversionTransform {
+    pub fn new(conversions: HashMap<RawMetricId, (RawMetricId, Conversion)>, drop_input: bool) -> Self {
+        Self {
+            conversions,
+            drop_input,
+        }
+    }
+}
+
+impl Transform for ConversionTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        let mut converted = MeasurementBuffer::new();
+        for point in measurements.iter_mut() {
+            let Some((new_metric, conversion)) = self.conversions.get(&point.metric) else {
+                continue;
+            };
+            let value = match point.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+                WrappedMeasurementValue::I64(v) => v as f64,
+                // the buckets of a histogram cannot be converted, keep the point as is
+                WrappedMeasurementValue::Histogram(_) => continue,
+            };
+            let value = WrappedMeasurementValue::F64(conversion.apply(value));
+            if self.drop_input {
+                point.metric = *new_metric;
+                point.value = value;
+            } else {
+                let mut new_point = point.clone();
+                new_point.metric = *new_metric;
+                new_point.value = value;
+                converted.push(new_point);
+            }
+        }
+        measurements.merge(&mut converted);
+        Ok(())
+    }
+}
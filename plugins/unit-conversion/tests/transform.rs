@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::RawMetricId,
+    pipeline::naming::TransformName,
+    plugin::PluginMetadata,
+    resources::{Resource, ResourceConsumer},
+    test::RuntimeExpectations,
+    units::{PrefixedUnit, Unit},
+};
+
+use plugin_unit_conversion::UnitConversionPlugin;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+const CONFIG: &str = r#"
+[[conversions]]
+metric = "energy"
+unit = "W.h"
+
+[[conversions]]
+metric = "temperature"
+unit = "[degF]"
+output_metric = "temperature_f"
+"#;
+
+const CONFIG_KEEP_INPUT: &str = r#"
+drop_input = false
+
+[[conversions]]
+metric = "energy"
+unit = "kJ"
+"#;
+
+#[test]
+fn test_conversion() {
+    let transform_name = TransformName::from_str("unit-conversion", "transform");
+    let ts = Timestamp::now();
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<f64>("energy", Unit::Joule)
+        .create_metric::<u64>("temperature", PrefixedUnit::milli(Unit::DegreeCelsius))
+        .create_metric::<u64>("other", Unit::Joule)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let energy = input.metrics().by_name("energy").unwrap().0;
+                let temperature = input.metrics().by_name("temperature").unwrap().0;
+                let other = input.metrics().by_name("other").unwrap().0;
+                let mut buf = MeasurementBuffer::new();
+
+                buf.push(new_point(energy, ts, WrappedMeasurementValue::F64(7200.0)));
+                buf.push(new_point(temperature, ts, WrappedMeasurementValue::U64(100_000)));
+                buf.push(new_point(other, ts, WrappedMeasurementValue::U64(7200)));
+
+                buf
+            },
+            move |output| {
+                let energy_wh = output.metrics().by_name("energy_Wh").unwrap();
+                let temperature_f = output.metrics().by_name("temperature_f").unwrap();
+                let other = output.metrics().by_name("other").unwrap().0;
+                assert_eq!(energy_wh.1.unit, Unit::WattHour.into());
+                assert_eq!(temperature_f.1.unit, Unit::DegreeFahrenheit.into());
+
+                assert_eq!(
+                    output.measurements().to_vec(),
+                    vec![
+                        new_point(energy_wh.0, ts, WrappedMeasurementValue::F64(2.0)),
+                        new_point(temperature_f.0, ts, WrappedMeasurementValue::F64(212.0)),
+                        new_point(other, ts, WrappedMeasurementValue::U64(7200)),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG);
+}
+
+#[test]
+fn test_conversion_keep_input() {
+    let transform_name = TransformName::from_str("unit-conversion", "transform");
+    let ts = Timestamp::now();
+
+    let runtime = RuntimeExpectations::new()
+        .create_metric::<u64>("energy", Unit::Joule)
+        .test_transform(
+            transform_name.clone(),
+            move |input| {
+                let energy = input.metrics().by_name("energy").unwrap().0;
+                let mut buf = MeasurementBuffer::new();
+                buf.push(new_point(energy, ts, WrappedMeasurementValue::U64(1500)));
+                buf
+            },
+            move |output| {
+                let energy = output.metrics().by_name("energy").unwrap().0;
+                let energy_kj = output.metrics().by_name("energy_kiloJ").unwrap().0;
+                assert_eq!(
+                    output.measurements().to_vec(),
+                    vec![
+                        new_point(energy, ts, WrappedMeasurementValue::U64(1500)),
+                        new_point(energy_kj, ts, WrappedMeasurementValue::F64(1.5)),
+                    ]
+                );
+            },
+        );
+
+    run_agent(runtime, CONFIG_KEEP_INPUT);
+}
+
+fn run_agent(runtime: RuntimeExpectations, config: &str) {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<UnitConversionPlugin>(),
+        enabled: true,
+        config: Some(toml::from_str(config).unwrap()),
+    });
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(runtime)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
+fn new_point(metric: RawMetricId, ts: Timestamp, value: WrappedMeasurementValue) -> MeasurementPoint {
+    MeasurementPoint::new_untyped(
+        ts,
+        metric,
+        Resource::LocalMachine,
+        ResourceConsumer::LocalMachine,
+        value,
+    )
+}
@@ -54,5 +54,36 @@ void write_point(void *data, const MeasurementPoint *point) {
             );
         }
         break;
+        case FfiMeasurementValue_I64: {
+            printf("[%lu] on %.*s %.*s by %.*s %.*s, %.*s(id %lu) = %" PRId64 "\n",
+                t.secs,
+                (int)resource_kind.len, resource_kind.ptr,
+                (int)resource_id.len, resource_id.ptr,
+                (int)consumer_kind.len, consumer_kind.ptr,
+                (int)consumer_id.len, consumer_id.ptr,
+                (int)metric.len, metric.ptr,
+                metric_id._0,
+                value.i64
+            );
+        }
+        break;
+        case FfiMeasurementValue_Histogram: {
+            uint64_t count = 0;
+            for (size_t i = 0; i <= value.histogram.n_bounds; i++) {
+                count += value.histogram.counts[i];
+            }
+            printf("[%lu] on %.*s %.*s by %.*s %.*s, %.*s(id %lu) = histogram(count %" PRIu64 ", sum %f)\n",
+                t.secs,
+                (int)resource_kind.len, resource_kind.ptr,
+                (int)resource_id.len, resource_id.ptr,
+                (int)consumer_kind.len, consumer_kind.ptr,
+                (int)consumer_id.len, consumer_id.ptr,
+                (int)metric.len, metric.ptr,
+                metric_id._0,
+                count,
+                value.histogram.sum
+            );
+        }
+        break;
     };
 }
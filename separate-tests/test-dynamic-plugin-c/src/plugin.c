@@ -3,6 +3,7 @@
 #include "alumet.h"
 #include "source.h"
 #include "output.h"
+#include "transform.h"
 
 PLUGIN_API const char *PLUGIN_NAME = "test-dynamic-plugin-c";
 PLUGIN_API const char *PLUGIN_VERSION = "0.1.0";
@@ -41,6 +42,10 @@ PLUGIN_API void plugin_start(PluginStruct *plugin, AlumetPluginStart *alumet) {
     TimeDuration flush_interval = poll_interval;
     alumet_add_source(alumet, source, poll_interval, flush_interval, (SourcePollFn)source_poll, (NullableDropFn)source_drop);
 
+    // create and register the transform
+    AttributeTransform *transform = transform_init(astr_copy(astring_ref(plugin->custom_attribute)));
+    alumet_add_transform(alumet, transform, (TransformApplyFn)transform_apply, (NullableDropFn)transform_drop);
+
     // create and register the output
     StdOutput *output = output_init();
     alumet_add_output(alumet, output, (OutputWriteFn)output_write, (NullableDropFn)output_drop);
@@ -0,0 +1,39 @@
+#include <stdio.h>
+#include "transform.h"
+
+static bool has_custom_attribute(void *data, const MeasurementPoint *point);
+static void mark_point(void *data, MeasurementPoint *point);
+
+/// @brief Creates a new AttributeTransform, which keeps the points that have the custom attribute.
+AttributeTransform *transform_init(AString custom_attribute) {
+    AttributeTransform *transform = malloc(sizeof(AttributeTransform));
+    transform->custom_attribute = custom_attribute;
+    return transform;
+}
+
+void transform_drop(AttributeTransform *transform) {
+    astring_free(transform->custom_attribute);
+    free(transform);
+}
+
+/// @brief Transform.apply(buffer, ctx)
+void transform_apply(AttributeTransform *transform, MeasurementBuffer *buffer, const FfiTransformContext *ctx) {
+    RawMetricId metric;
+    if (!transform_ctx_metric_by_name(ctx, astr("rapl_pkg_consumption"), &metric)) {
+        fprintf(stderr, "metric rapl_pkg_consumption not found\n");
+        return;
+    }
+    mbuffer_retain(buffer, transform, (RetainPointFn)has_custom_attribute);
+    mbuffer_foreach_mut(buffer, NULL, mark_point);
+}
+
+bool has_custom_attribute(void *data, const MeasurementPoint *point) {
+    AttributeTransform *transform = data;
+    FfiAttributeValue value;
+    return mpoint_attr_get(point, astring_ref(transform->custom_attribute), &value)
+        && value.tag == FfiAttributeValue_U64;
+}
+
+void mark_point(void *data, MeasurementPoint *point) {
+    mpoint_attr_bool(point, astr("transformed"), true);
+}
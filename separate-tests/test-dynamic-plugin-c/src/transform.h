@@ -0,0 +1,14 @@
+#ifndef __TRANSFORM_H
+#define __TRANSFORM_H
+
+#include "alumet.h"
+
+typedef struct {
+    AString custom_attribute;
+} AttributeTransform;
+
+AttributeTransform *transform_init(AString custom_attribute);
+void transform_drop(AttributeTransform *transform);
+void transform_apply(AttributeTransform *transform, MeasurementBuffer *buffer, const FfiTransformContext *ctx);
+
+#endif